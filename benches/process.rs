@@ -0,0 +1,241 @@
+//! Throughput of the processing loop, reported as samples per second.
+//!
+//! Run with `cargo bench --bench process`. At 48 kHz, a throughput of 48 Kelem/s is exactly
+//! real time; divide the reported figure by 48 000 for the real-time factor. Add
+//! `--features f64-dsp` to measure the f64 signal path against the default f32 one, or
+//! `--features simd-voices` for the block voice renderer against the scalar one.
+//!
+//! The `process` groups are whole blocks through `process_block` into a stereo port, as
+//! a host without a thread pool would run them: events, voices, effects, limiter and the
+//! copy out. Each voice count is timed at each buffer size with the comb, auto-pan and
+//! reverb all off, then all on.
+//!
+//! The `output-copy` group is `process` at 4096 frames writing out to no port, a mono one
+//! and a stereo one; the differences are what copying the block out costs.
+//!
+//! The `denormal-tail` group shows what `process` saves by flushing denormals: a silent
+//! block through `process_block` once the reverb has rung out from a note-off into the
+//! denormal range, with the flush on and off. Flushed, the tail goes to zero on the first
+//! block; unflushed, it stays where it was, and on x86 every block costs what the audio
+//! thread used to pay.
+
+use std::thread;
+
+use clack_extensions::thread_pool::PluginThreadPoolImpl;
+use clack_plugin::events::io::{EventBuffer, InputEvents, OutputEvents};
+use clack_plugin::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use cave::{
+    CaveAudioProcessor, CaveShared, OutputBuffers, PARAM_AUTO_PAN_DEPTH_ID, PARAM_AUTO_PAN_ON_ID,
+    PARAM_COMB_MIX_ID, PARAM_COMB_ON_ID, PARAM_REVERB_ON_ID,
+};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const BUFFER_SIZES: [usize; 4] = [64, 256, 1024, 4096];
+const VOICE_COUNTS: [u8; 3] = [1, 8, 32];
+const MAX_FRAMES: usize = 4096;
+const TAIL_BLOCK: usize = 512;
+/// Long enough for the reverb at its default decay to settle into the denormal range.
+const TAIL_SECONDS: usize = 30;
+const COPY_FRAMES: usize = 4096;
+
+/// The param changes in `values`, as a host would send them at the start of a block.
+fn param_events(values: &[(u32, f64)]) -> EventBuffer {
+    let mut events = EventBuffer::new();
+    for &(id, value) in values {
+        let id = ClapId::new(id);
+        events.push(&ParamValueEvent::new(0, id, Pckn::match_all(), value, Cookie::empty()));
+    }
+    events
+}
+
+/// Param events switching the comb, auto-pan and reverb all on or all off. On, each is
+/// set to be heard: the comb and auto-pan default to doing nothing.
+fn effect_events(on: bool) -> EventBuffer {
+    let switch = if on { 1.0 } else { 0.0 };
+    param_events(&[
+        (PARAM_COMB_ON_ID, switch),
+        (PARAM_COMB_MIX_ID, 0.5),
+        (PARAM_AUTO_PAN_ON_ID, switch),
+        (PARAM_AUTO_PAN_DEPTH_ID, 1.0),
+        (PARAM_REVERB_ON_ID, switch),
+    ])
+}
+
+fn bench_process(c: &mut Criterion) {
+    let no_events = EventBuffer::new();
+    let no_events = InputEvents::from_buffer(&no_events);
+    let mut output = EventBuffer::new();
+    let (mut left, mut right) = (vec![0.0f32; MAX_FRAMES], vec![0.0f32; MAX_FRAMES]);
+
+    for voices in VOICE_COUNTS {
+        let mut group = c.benchmark_group(format!("process/{voices}-voices"));
+
+        for effects in [false, true] {
+            let name = if effects { "effects-on" } else { "effects-off" };
+            for frames in BUFFER_SIZES {
+                group.throughput(Throughput::Elements(frames as u64));
+
+                // Its own params each time, so one run's settings don't carry into the next.
+                let shared = CaveShared::default();
+                let mut processor = CaveAudioProcessor::new(&shared, SAMPLE_RATE, MAX_FRAMES);
+                for voice in 0..voices {
+                    processor.note_on(48 + voice, 1.0);
+                }
+                let (left, right) = (&mut left[..frames], &mut right[..frames]);
+                let mut block = |input: &InputEvents, output: &mut EventBuffer| {
+                    output.clear();
+                    let outputs = OutputBuffers::F32([Some(&mut *left), Some(&mut *right)]);
+                    let output = &mut OutputEvents::from_buffer(output);
+                    processor.process_block(None, frames as u32, input, output, Some(outputs));
+                    black_box((&*left, &*right));
+                };
+                block(&InputEvents::from_buffer(&effect_events(effects)), &mut output);
+
+                group.bench_function(BenchmarkId::new(name, frames), |b| {
+                    b.iter(|| block(&no_events, &mut output));
+                });
+            }
+        }
+
+        group.finish();
+    }
+}
+
+/// Serial rendering against the thread-pool path at 32 voices. The "host pool" here spawns
+/// scoped threads every block, so it overstates the overhead of a real host's pool.
+fn bench_thread_pool(c: &mut Criterion) {
+    let shared = CaveShared::default();
+    let mut group = c.benchmark_group("thread-pool/32-voices");
+
+    for frames in BUFFER_SIZES {
+        group.throughput(Throughput::Elements(frames as u64));
+
+        let mut processor = CaveAudioProcessor::new(&shared, SAMPLE_RATE, MAX_FRAMES);
+        for voice in 0..32 {
+            processor.note_on(48 + voice, 1.0);
+        }
+        let mut buffer = vec![0.0; frames];
+
+        group.bench_function(BenchmarkId::new("serial", frames), |b| {
+            b.iter(|| {
+                processor.render_serial(&mut buffer);
+                black_box(&buffer);
+            });
+        });
+        group.bench_function(BenchmarkId::new("pooled", frames), |b| {
+            b.iter(|| {
+                let pooled = processor.render_pooled(&mut buffer, |tasks| {
+                    thread::scope(|s| {
+                        for task in 0..tasks {
+                            let shared = &shared;
+                            s.spawn(move || shared.exec(task));
+                        }
+                    });
+                    true
+                });
+                assert!(pooled);
+                black_box(&buffer);
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_output_copy(c: &mut Criterion) {
+    let shared = CaveShared::default();
+    let mut group = c.benchmark_group("output-copy");
+    group.throughput(Throughput::Elements(COPY_FRAMES as u64));
+
+    let input = EventBuffer::new();
+    let input = InputEvents::from_buffer(&input);
+    let mut output = EventBuffer::new();
+    let (mut left, mut right) = (vec![0.0f32; COPY_FRAMES], vec![0.0f32; COPY_FRAMES]);
+
+    for channels in 0..=2 {
+        let mut processor = CaveAudioProcessor::new(&shared, SAMPLE_RATE, COPY_FRAMES);
+        processor.note_on(48, 1.0);
+
+        group.bench_function(BenchmarkId::new("channels", channels), |b| {
+            b.iter(|| {
+                output.clear();
+                let outputs = match channels {
+                    0 => None,
+                    1 => Some(OutputBuffers::F32([Some(&mut left), None])),
+                    _ => Some(OutputBuffers::F32([Some(&mut left), Some(&mut right)])),
+                };
+                let output = &mut OutputEvents::from_buffer(&mut output);
+                processor.process_block(None, COPY_FRAMES as u32, &input, output, outputs);
+                black_box((&left, &right));
+            });
+        });
+    }
+
+    group.finish();
+}
+
+/// A processor whose reverb has rung out from a single note, unflushed, into the denormal
+/// range. Left alone there it doesn't reach zero: the tail sits a step above it for good.
+fn rung_out(shared: &CaveShared) -> CaveAudioProcessor<'_> {
+    let mut processor = CaveAudioProcessor::new(shared, SAMPLE_RATE, TAIL_BLOCK);
+    processor.set_flush_denormals(false);
+    let (reverb_on, none) = (param_events(&[(PARAM_REVERB_ON_ID, 1.0)]), EventBuffer::new());
+    let (mut left, mut right) = (vec![0.0f32; TAIL_BLOCK], vec![0.0f32; TAIL_BLOCK]);
+    let mut output = EventBuffer::new();
+
+    processor.note_on(48, 1.0);
+    for block in 0..TAIL_SECONDS * SAMPLE_RATE as usize / TAIL_BLOCK {
+        if block == 1 {
+            processor.note_off(48);
+        }
+        let input = if block == 0 { &reverb_on } else { &none };
+        let outputs = OutputBuffers::F32([Some(&mut left), Some(&mut right)]);
+        let output = &mut OutputEvents::from_buffer(&mut output);
+        let input = &InputEvents::from_buffer(input);
+        processor.process_block(None, TAIL_BLOCK as u32, input, output, Some(outputs));
+    }
+    let mut samples = left.iter().chain(&right);
+    assert!(samples.clone().all(|s| s.is_subnormal() || *s == 0.0));
+    assert!(samples.any(|s| s.is_subnormal()));
+    processor
+}
+
+fn bench_denormal_tail(c: &mut Criterion) {
+    let mut group = c.benchmark_group("denormal-tail");
+    group.throughput(Throughput::Elements(TAIL_BLOCK as u64));
+
+    let input = EventBuffer::new();
+    let input = InputEvents::from_buffer(&input);
+    let mut output = EventBuffer::new();
+    let (mut left, mut right) = (vec![0.0f32; TAIL_BLOCK], vec![0.0f32; TAIL_BLOCK]);
+
+    for flush in [false, true] {
+        let shared = CaveShared::default();
+        let mut processor = rung_out(&shared);
+        processor.set_flush_denormals(flush);
+
+        let name = if flush { "flushed" } else { "unflushed" };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                output.clear();
+                let outputs = OutputBuffers::F32([Some(&mut left), Some(&mut right)]);
+                let output = &mut OutputEvents::from_buffer(&mut output);
+                processor.process_block(None, TAIL_BLOCK as u32, &input, output, Some(outputs));
+                black_box((&left, &right));
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_process,
+    bench_thread_pool,
+    bench_output_copy,
+    bench_denormal_tail
+);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Sets `CAVE_VERSION` to the crate version, plus the short git hash when built from a
+/// checkout, so the plugin descriptor and the editor's about panel name the same build.
+fn main() {
+    let version = std::env::var("CARGO_PKG_VERSION").unwrap_or_default();
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty());
+
+    match hash {
+        Some(hash) => println!("cargo:rustc-env=CAVE_VERSION={version}+{hash}"),
+        None => println!("cargo:rustc-env=CAVE_VERSION={version}"),
+    }
+    watch_git(Path::new(".git"));
+}
+
+/// Reruns when HEAD moves: on checkout, which rewrites `HEAD`, and on commit, which
+/// rewrites the branch's ref, or `packed-refs` once git has packed it. Cargo takes a path
+/// that doesn't exist as always changed, so only existing ones are named, and outside a
+/// checkout nothing is, which leaves cargo's default of rerunning on any package change.
+fn watch_git(git: &Path) {
+    let Ok(head) = std::fs::read_to_string(git.join("HEAD")) else {
+        return;
+    };
+    let mut watched = vec![git.join("HEAD"), git.join("packed-refs")];
+    if let Some(branch) = head.strip_prefix("ref:").map(str::trim) {
+        let branch = git.join(branch);
+        // A packed branch has no file until the next commit writes one into its directory.
+        if branch.exists() {
+            watched.push(branch);
+        } else {
+            watched.extend(branch.parent().map(Path::to_path_buf));
+        }
+    }
+    for path in watched.iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
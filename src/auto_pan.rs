@@ -0,0 +1,119 @@
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+use crate::lfo::Lfo;
+
+/// Tempo-sync choices for the auto-pan, indexed by the sync param. "Off" uses the free rate.
+pub const AUTO_PAN_SYNC_NAMES: &[&str] = &["Off", "1/1", "1/2", "1/4", "1/8", "1/16"];
+
+/// Quarter-note beats per pan cycle, for each sync choice.
+const SYNC_BEATS: [f32; 6] = [0.0, 4.0, 2.0, 1.0, 0.5, 0.25];
+
+/// The host's transport, for following it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HostTime {
+    /// BPM, when the host reports it.
+    pub tempo: Option<f64>,
+    /// Song position in quarter-note beats at the start of the block, while playing.
+    pub beat: Option<f64>,
+}
+
+/// Auto-pan params for one block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoPanSettings {
+    pub rate: f32, // Hz
+    pub depth: f32,
+    pub shape: usize,
+    pub sync: usize,
+}
+
+impl AutoPanSettings {
+    /// Pan cycles per second. Synced rates follow `tempo` (in BPM), falling back to the free
+    /// rate when the host doesn't report one.
+    pub fn rate_hz(&self, tempo: Option<f64>) -> f32 {
+        match (SYNC_BEATS.get(self.sync), tempo) {
+            (Some(&beats), Some(tempo)) if beats > 0.0 => tempo as f32 / 60.0 / beats,
+            _ => self.rate,
+        }
+    }
+
+    /// Quarter-note beats per pan cycle, when synced.
+    pub fn cycle_beats(&self) -> Option<f32> {
+        SYNC_BEATS.get(self.sync).copied().filter(|&beats| beats > 0.0)
+    }
+}
+
+/// Sweeps the output between the left and right channels with its own LFO.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AutoPan {
+    lfo: Lfo,
+}
+
+impl AutoPan {
+    /// Puts a synced sweep where it belongs at song position `beat`, so it's locked to the
+    /// bar from the start of playback and through loops and jumps. Meant for every block
+    /// while the host plays; in between, the tempo keeps it in place.
+    pub fn align(&mut self, beat: f64, cycle_beats: f32) {
+        self.lfo.set_phase((beat / cycle_beats as f64).rem_euclid(1.0) as f32);
+    }
+
+    /// Writes per-sample channel gains for the next `left.len()` samples. The pan law is
+    /// equal power, scaled so the centre position is unity gain.
+    pub fn gains(
+        &mut self,
+        left: &mut [f32],
+        right: &mut [f32],
+        settings: &AutoPanSettings,
+        rate_hz: f32,
+        sample_rate: f32,
+    ) {
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let pan = self.lfo.value(settings.shape) * settings.depth; // -1.0 (left) to 1.0
+            let angle = (pan + 1.0) * FRAC_PI_4;
+            *l = angle.cos() * SQRT_2;
+            *r = angle.sin() * SQRT_2;
+            self.lfo.advance(rate_hz, 1, sample_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_shape_pans_hard_between_sides() {
+        let settings = AutoPanSettings { rate: 1.0, depth: 1.0, shape: 3, sync: 0 };
+        let mut pan = AutoPan::default();
+        let (mut left, mut right) = ([0.0; 4], [0.0; 4]);
+        pan.gains(&mut left, &mut right, &settings, 1.0, 4.0);
+
+        // The first half cycle is hard right, the second hard left.
+        assert!(left[0].abs() < 1e-6 && (right[0] - SQRT_2).abs() < 1e-6);
+        assert!((left[2] - SQRT_2).abs() < 1e-6 && right[2].abs() < 1e-6);
+    }
+
+    #[test]
+    fn synced_sweep_locks_to_the_song_position() {
+        let settings = AutoPanSettings { rate: 1.0, depth: 1.0, shape: 3, sync: 4 }; // 1/8
+        let cycle = settings.cycle_beats().unwrap();
+        let mut pan = AutoPan::default();
+        let (mut left, mut right) = ([0.0; 1], [0.0; 1]);
+
+        // Half a beat is a whole cycle: 10.25 beats is halfway through one, panned left.
+        pan.align(10.25, cycle);
+        pan.gains(&mut left, &mut right, &settings, 0.0, 1.0);
+        assert!(right[0].abs() < 1e-6);
+        // Looping back, or pre-roll before the song starts, lands on the first half.
+        pan.align(-0.5, cycle);
+        pan.gains(&mut left, &mut right, &settings, 0.0, 1.0);
+        assert!(left[0].abs() < 1e-6);
+        assert_eq!(AutoPanSettings { sync: 0, ..settings }.cycle_beats(), None);
+    }
+
+    #[test]
+    fn synced_rate_follows_tempo() {
+        let settings = AutoPanSettings { rate: 1.0, depth: 1.0, shape: 0, sync: 3 }; // 1/4
+        assert_eq!(settings.rate_hz(Some(120.0)), 2.0);
+        assert_eq!(settings.rate_hz(None), 1.0);
+    }
+}
@@ -0,0 +1,19 @@
+/// Chord shapes selectable with the chord param, as semitone offsets from the played key.
+/// The first entry is "off": every shape includes the root itself.
+pub const CHORD_NAMES: &[&str] = &["Off", "Major", "Minor", "Sus2", "Sus4", "Power", "Major 7", "Minor 7", "Octave"];
+
+const CHORD_INTERVALS: &[&[u8]] = &[
+    &[0],
+    &[0, 4, 7],
+    &[0, 3, 7],
+    &[0, 2, 7],
+    &[0, 5, 7],
+    &[0, 7, 12],
+    &[0, 4, 7, 11],
+    &[0, 3, 7, 10],
+    &[0, 12],
+];
+
+pub fn chord_intervals(chord_type: usize) -> &'static [u8] {
+    CHORD_INTERVALS.get(chord_type).copied().unwrap_or(&[0])
+}
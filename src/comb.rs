@@ -0,0 +1,84 @@
+use crate::denormals::flush_denormal;
+use crate::sample::Sample;
+
+/// Lowest note frequency the comb can track; its delay line is sized for this.
+const MIN_FREQUENCY: f32 = 20.0; // Hz
+
+/// Feedback comb filter: a delay line fed back into itself, which rings at the frequency
+/// whose period matches the delay. Tuned to a voice's note, it turns the oscillator into a
+/// plucked or metallic resonance.
+#[derive(Clone, Default)]
+pub struct CombFilter {
+    /// Empty when the pool was built without a sample rate, which bypasses the filter.
+    buffer: Box<[Sample]>,
+    write: usize,
+}
+
+impl CombFilter {
+    /// Allocates a delay line long enough for [`MIN_FREQUENCY`] at `sample_rate`.
+    pub fn new(sample_rate: f32) -> Self {
+        let len = (sample_rate / MIN_FREQUENCY).ceil() as usize + 2;
+        Self { buffer: vec![0.0; len].into_boxed_slice(), write: 0 }
+    }
+
+    /// Silences the delay line for a new note.
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.write = 0;
+    }
+
+    /// Runs one sample through the comb, tuned to `frequency`, and returns it mixed with
+    /// `input` by `mix`. The resonance is scaled by `1 - feedback` so longer decays don't
+    /// get louder.
+    pub fn process(
+        &mut self,
+        input: Sample,
+        frequency: f32,
+        sample_rate: f32,
+        feedback: f32,
+        mix: f32,
+    ) -> Sample {
+        let len = self.buffer.len();
+        if len < 3 || mix <= 0.0 {
+            return input;
+        }
+
+        let delay = (sample_rate / frequency).clamp(1.0, (len - 2) as f32);
+        let read = self.write as f32 + len as f32 - delay;
+        let (index, frac) = (read as usize, read.fract());
+        let a = self.buffer[index % len];
+        let b = self.buffer[(index + 1) % len];
+        let delayed = a + (b - a) * Sample::from(frac);
+
+        let feedback = Sample::from(feedback);
+        let wet = input + feedback * delayed;
+        self.buffer[self.write] = flush_denormal(wet);
+        self.write = (self.write + 1) % len;
+        input + (wet * (1.0 - feedback) - input) * Sample::from(mix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impulse_repeats_at_the_note_period() {
+        let mut comb = CombFilter::new(1000.0);
+        let out: Vec<Sample> = (0..25)
+            .map(|n| comb.process(if n == 0 { 1.0 } else { 0.0 }, 100.0, 1000.0, 0.5, 1.0))
+            .collect();
+
+        // 100 Hz at 1 kHz is a 10-sample period, each echo half the last.
+        assert_eq!(out[0], 0.5);
+        assert!(out[1..10].iter().all(|&s| s == 0.0));
+        assert!((out[10] - 0.25).abs() < 1e-6);
+        assert!((out[20] - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unallocated_comb_passes_input_through() {
+        let mut comb = CombFilter::default();
+        assert_eq!(comb.process(0.3, 100.0, 1000.0, 0.9, 1.0), 0.3);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::sample::Sample;
+
+/// Whether this target has a control register to flush denormals with. Elsewhere the
+/// recursive parts of the signal path run their state through [`flush_denormal`] instead.
+const HAS_FLUSH_REGISTER: bool = cfg!(any(
+    target_arch = "x86_64",
+    all(target_arch = "x86", target_feature = "sse"),
+    target_arch = "aarch64",
+));
+
+/// Added and taken away again by [`flush_denormal`]: far below anything audible, far above
+/// the denormal range, so anything smaller than it rounds away to zero.
+const DENORMAL_DC: Sample = 1e-18;
+
+/// MXCSR's flush-to-zero and denormals-are-zero bits.
+#[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse")))]
+const MXCSR_FTZ_DAZ: u32 = 1 << 15 | 1 << 6;
+
+/// FPCR's flush-to-zero bit, which on aarch64 covers inputs as well as results.
+#[cfg(target_arch = "aarch64")]
+const FPCR_FZ: u64 = 1 << 24;
+
+/// Treats denormal floats as zero on this thread until dropped, then puts the thread's
+/// float mode back as it was: the host owns the thread and may want it the way it was.
+///
+/// Filter states, comb and string feedback decay into the denormal range once their input
+/// stops, and a comb can sit there indefinitely, rounding each pass back up to the same
+/// denormal. On x86 every operation on one is many times slower, enough to take a block
+/// that has nothing left to say over its deadline.
+pub struct FlushDenormals {
+    saved: u64,
+}
+
+impl FlushDenormals {
+    #[allow(clippy::new_without_default)] // a guard, made for its side effect
+    pub fn new() -> Self {
+        let saved = read_mode();
+        write_mode(flush_mode(saved));
+        Self { saved }
+    }
+}
+
+impl Drop for FlushDenormals {
+    fn drop(&mut self) {
+        write_mode(self.saved);
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse")))]
+fn flush_mode(mode: u64) -> u64 {
+    mode | MXCSR_FTZ_DAZ as u64
+}
+
+#[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse")))]
+fn read_mode() -> u64 {
+    let mut mxcsr = 0u32;
+    // SAFETY: stores the SSE control register into a local.
+    unsafe {
+        std::arch::asm!("stmxcsr [{}]", in(reg) &mut mxcsr, options(nostack, preserves_flags));
+    }
+    mxcsr as u64
+}
+
+#[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse")))]
+fn write_mode(mode: u64) {
+    let mxcsr = mode as u32;
+    // SAFETY: only ever loads a value read from the register, or one with the flush bits
+    // added, which don't unmask any exception.
+    unsafe {
+        std::arch::asm!(
+            "ldmxcsr [{}]",
+            in(reg) &mxcsr,
+            options(nostack, readonly, preserves_flags),
+        );
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn flush_mode(mode: u64) -> u64 {
+    mode | FPCR_FZ
+}
+
+#[cfg(target_arch = "aarch64")]
+fn read_mode() -> u64 {
+    let fpcr: u64;
+    // SAFETY: reads the floating-point control register.
+    unsafe {
+        std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags));
+    }
+    fpcr
+}
+
+#[cfg(target_arch = "aarch64")]
+fn write_mode(mode: u64) {
+    // SAFETY: as for x86, only the value read back or with flush-to-zero added.
+    unsafe {
+        std::arch::asm!("msr fpcr, {}", in(reg) mode, options(nomem, nostack, preserves_flags));
+    }
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    all(target_arch = "x86", target_feature = "sse"),
+    target_arch = "aarch64",
+)))]
+fn flush_mode(mode: u64) -> u64 {
+    mode
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    all(target_arch = "x86", target_feature = "sse"),
+    target_arch = "aarch64",
+)))]
+fn read_mode() -> u64 {
+    0
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    all(target_arch = "x86", target_feature = "sse"),
+    target_arch = "aarch64",
+)))]
+fn write_mode(_mode: u64) {}
+
+/// For a recursive state on its way back round: rounds anything in the denormal range to
+/// zero where [`FlushDenormals`] can't. Costs nothing where it can.
+#[inline(always)]
+pub fn flush_denormal(sample: Sample) -> Sample {
+    if HAS_FLUSH_REGISTER {
+        sample
+    } else {
+        sample + DENORMAL_DC - DENORMAL_DC
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+
+    #[test]
+    fn the_dc_dither_rounds_denormals_away() {
+        let denormal = black_box(Sample::MIN_POSITIVE / 4.0);
+        assert_eq!(denormal + DENORMAL_DC - DENORMAL_DC, 0.0);
+        assert_eq!(0.5 + DENORMAL_DC - DENORMAL_DC, 0.5);
+    }
+
+    #[test]
+    fn flushes_while_held_and_restores_after() {
+        let (denormal, half) = (black_box(f32::MIN_POSITIVE / 4.0), black_box(0.5));
+        let before = read_mode();
+        {
+            let _flush = FlushDenormals::new();
+            if HAS_FLUSH_REGISTER {
+                assert_eq!(black_box(denormal * half), 0.0);
+            }
+        }
+        assert_eq!(read_mode(), before);
+        assert_eq!(black_box(denormal * half), f32::MIN_POSITIVE / 8.0);
+    }
+}
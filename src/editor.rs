@@ -0,0 +1,537 @@
+use baseview::PhySize;
+use clack_plugin::plugin::PluginError;
+use raw_window_handle::RawWindowHandle;
+
+use crate::gui::{scaled, DEFAULT_SIZE, MIN_SIZE};
+
+/// The windowing layer under the editor: baseview in the plugin, a recording fake in tests.
+pub trait WindowLayer {
+    type Window;
+
+    /// Opens a window at `size` and `scale`, embedded in `parent` or floating if it's `None`.
+    fn open(
+        &mut self,
+        parent: Option<RawWindowHandle>,
+        size: PhySize,
+        scale: Option<f64>,
+    ) -> Result<Self::Window, PluginError>;
+    fn close(&mut self, window: Self::Window);
+    /// False once the user has closed a floating window.
+    fn is_alive(&self, window: &Self::Window) -> bool;
+    /// The window's current size, which the user may have changed.
+    fn size(&self, window: &Self::Window) -> PhySize;
+    fn resize(&mut self, window: &mut Self::Window, size: PhySize);
+    fn rescale(&mut self, window: &mut Self::Window, scale: f64);
+}
+
+/// Where the editor is in the CLAP GUI lifecycle.
+enum EditorState<W> {
+    /// Before `create` or after `destroy`, with the size and scale the next window opens at:
+    /// the last window's, so the editor comes back as the user left it.
+    Destroyed { size: PhySize, scale: Option<f64> },
+    /// Created but without a window, either waiting for a parent or hidden. The size and
+    /// scale are applied when the window opens.
+    Created {
+        floating: bool,
+        parent: Option<RawWindowHandle>,
+        pending_size: PhySize,
+        pending_scale: Option<f64>,
+    },
+    Open { floating: bool, parent: Option<RawWindowHandle>, scale: Option<f64>, window: W },
+}
+
+/// Drives the editor window through create, set_parent, show, hide and destroy in whatever
+/// order the host calls them. Hosts disagree: Bitwig sets the scale and parent before show,
+/// Reaper sizes the editor before it has a parent, and some hosts call show first.
+pub struct Editor<L: WindowLayer> {
+    layer: L,
+    state: EditorState<L::Window>,
+    /// Size asked of the host that it hasn't confirmed yet. The window keeps its old size,
+    /// and the layout with it, until the host says yes.
+    requested_size: Option<PhySize>,
+}
+
+impl<L: WindowLayer> Editor<L> {
+    pub fn new(layer: L) -> Self {
+        let state = EditorState::Destroyed { size: DEFAULT_SIZE, scale: None };
+        Self { layer, state, requested_size: None }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(&self.state, EditorState::Open { window, .. } if self.layer.is_alive(window))
+    }
+
+    /// Records the host's chosen configuration. The window opens on set_parent or show.
+    pub fn create(&mut self, floating: bool) {
+        if !matches!(self.state, EditorState::Destroyed { .. }) {
+            eprintln!("[cave-gui] create on a live editor, destroying it first");
+            self.destroy();
+        }
+        let EditorState::Destroyed { size, scale } = self.state else { unreachable!() };
+        self.state = EditorState::Created {
+            floating,
+            parent: None,
+            pending_size: size,
+            pending_scale: scale,
+        };
+    }
+
+    /// Closes any window and forgets the parent. The size and scale are kept for the next
+    /// `create`.
+    pub fn destroy(&mut self) {
+        self.requested_size = None;
+        let (size, scale) = (self.size(), self.current_scale());
+        let state = std::mem::replace(&mut self.state, EditorState::Destroyed { size, scale });
+        if let EditorState::Open { window, .. } = state {
+            self.layer.close(window);
+        }
+    }
+
+    /// Embeds the editor in `parent`, opening it, or reopening it if the parent changed.
+    pub fn set_parent(&mut self, new_parent: RawWindowHandle) -> Result<(), PluginError> {
+        match &self.state {
+            EditorState::Destroyed { .. } => {
+                return Err(PluginError::Message("set_parent called before create"));
+            }
+            EditorState::Created { .. } => {}
+            EditorState::Open { parent, .. } if *parent == Some(new_parent) => {
+                eprintln!("[cave-gui] already open in this parent");
+                return Ok(());
+            }
+            EditorState::Open { .. } => {
+                eprintln!("[cave-gui] parent changed, reopening");
+                self.hide();
+            }
+        }
+        if let EditorState::Created { parent, .. } = &mut self.state {
+            *parent = Some(new_parent);
+        }
+        self.open()
+    }
+
+    /// Opens the window if it can be: embedded editors wait for their parent.
+    pub fn show(&mut self) -> Result<(), PluginError> {
+        match self.state {
+            EditorState::Destroyed { .. } => {
+                Err(PluginError::Message("show called before create"))
+            }
+            EditorState::Created { floating: false, parent: None, .. } => {
+                eprintln!("[cave-gui] show before set_parent, opening once the parent arrives");
+                Ok(())
+            }
+            EditorState::Created { .. } => self.open(),
+            EditorState::Open { .. } => Ok(()),
+        }
+    }
+
+    /// Closes the window but keeps its parent, size and scale for the next show.
+    pub fn hide(&mut self) {
+        self.requested_size = None;
+        let placeholder = EditorState::Destroyed { size: DEFAULT_SIZE, scale: None };
+        let state = std::mem::replace(&mut self.state, placeholder);
+        self.state = match state {
+            EditorState::Open { floating, parent, scale, window } => {
+                let pending_size = self.layer.size(&window);
+                self.layer.close(window);
+                EditorState::Created { floating, parent, pending_size, pending_scale: scale }
+            }
+            state => state,
+        };
+    }
+
+    /// Whether the user closed the floating window since the last call, which the host
+    /// needs to hear about. The editor is hidden from then on.
+    pub fn take_closed_by_user(&mut self) -> bool {
+        let closed = matches!(&self.state, EditorState::Open { window, .. }
+            if !self.layer.is_alive(window));
+        if closed {
+            self.hide();
+        }
+        closed
+    }
+
+    pub fn size(&self) -> PhySize {
+        match &self.state {
+            EditorState::Destroyed { size, .. } => *size,
+            EditorState::Created { pending_size, .. } => *pending_size,
+            EditorState::Open { window, .. } => self.layer.size(window),
+        }
+    }
+
+    fn scale(&self) -> f64 {
+        self.current_scale().unwrap_or(1.0)
+    }
+
+    /// The host's scale factor, if it set one.
+    fn current_scale(&self) -> Option<f64> {
+        match self.state {
+            EditorState::Destroyed { scale, .. } => scale,
+            EditorState::Created { pending_scale, .. } => pending_scale,
+            EditorState::Open { scale, .. } => scale,
+        }
+    }
+
+    /// The closest size to `size` the editor supports. Resizing is free in both directions
+    /// down to [`MIN_SIZE`] at the current scale; the layout just reflows.
+    pub fn adjust_size(&self, size: PhySize) -> PhySize {
+        let min = scaled(MIN_SIZE, self.scale());
+        PhySize::new(size.width.max(min.width), size.height.max(min.height))
+    }
+
+    pub fn set_size(&mut self, size: PhySize) {
+        self.requested_size = None;
+        let size = self.adjust_size(size);
+        match &mut self.state {
+            EditorState::Destroyed { .. } => {}
+            EditorState::Created { pending_size, .. } => *pending_size = size,
+            EditorState::Open { window, .. } => self.layer.resize(window, size),
+        }
+    }
+
+    /// The editor's layout wants `size`. A floating window just takes it. An embedded one
+    /// needs the host to agree: the size to ask it for comes back, and stays requested until
+    /// [`Editor::confirm_resize`] or [`Editor::refuse_resize`].
+    pub fn request_resize(&mut self, size: PhySize) -> Option<PhySize> {
+        let size = self.adjust_size(size);
+        let EditorState::Open { floating, window, .. } = &mut self.state else { return None };
+        if self.layer.size(window) == size {
+            return None;
+        }
+        if *floating {
+            self.layer.resize(window, size);
+            return None;
+        }
+        self.requested_size = Some(size);
+        self.requested_size
+    }
+
+    /// The host took the requested size. It needn't call set_size after, so the window
+    /// resizes itself.
+    pub fn confirm_resize(&mut self) {
+        if let Some(size) = self.requested_size {
+            self.set_size(size);
+        }
+    }
+
+    /// The host kept the old size; the layout scrolls to fit instead.
+    pub fn refuse_resize(&mut self) {
+        self.requested_size = None;
+    }
+
+    /// Adopts the host's scale factor. The size scales along with it so the editor keeps its
+    /// logical size.
+    pub fn set_scale(&mut self, new_scale: f64) {
+        match &mut self.state {
+            EditorState::Destroyed { .. } => {}
+            EditorState::Created { pending_size, pending_scale, .. } => {
+                *pending_size = scaled(*pending_size, new_scale / pending_scale.unwrap_or(1.0));
+                *pending_scale = Some(new_scale);
+            }
+            EditorState::Open { scale, window, .. } => {
+                *scale = Some(new_scale);
+                self.layer.rescale(window, new_scale);
+            }
+        }
+    }
+
+    /// Opens a created editor's window. If that fails the editor stays created, exactly as
+    /// it was, and the error goes back to the host so it can fall back to its own UI; the
+    /// next set_parent or show tries again from scratch.
+    fn open(&mut self) -> Result<(), PluginError> {
+        let EditorState::Created { floating, parent, pending_size, pending_scale } = self.state
+        else {
+            return Ok(());
+        };
+        let embed_in = if floating { None } else { parent };
+        eprintln!("[cave-gui] opening {pending_size:?} at scale {pending_scale:?}");
+        let window = self
+            .layer
+            .open(embed_in, pending_size, pending_scale)
+            .inspect_err(|error| eprintln!("[cave-gui] the window failed to open: {error}"))?;
+        self.state = EditorState::Open { floating, parent, scale: pending_scale, window };
+        Ok(())
+    }
+}
+
+// Hosts that skip `destroy` would otherwise leave the editor's view (an NSView on macOS)
+// attached to their window.
+impl<L: WindowLayer> Drop for Editor<L> {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raw_window_handle::XlibWindowHandle;
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Open { parent: Option<RawWindowHandle>, size: PhySize, scale: Option<f64> },
+        Close(usize),
+        Resize(usize, PhySize),
+        Rescale(usize, f64),
+    }
+
+    struct FakeWindow {
+        id: usize,
+        size: PhySize,
+    }
+
+    /// Records what the editor asks of the windowing layer.
+    #[derive(Default)]
+    struct FakeLayer {
+        calls: Vec<Call>,
+        opened: usize,
+        /// Window the user closed, as a floating window's close button would.
+        closed_by_user: Option<usize>,
+        /// Makes every open fail, as a window system without a display would.
+        broken: bool,
+    }
+
+    impl WindowLayer for FakeLayer {
+        type Window = FakeWindow;
+
+        fn open(
+            &mut self,
+            parent: Option<RawWindowHandle>,
+            size: PhySize,
+            scale: Option<f64>,
+        ) -> Result<FakeWindow, PluginError> {
+            self.calls.push(Call::Open { parent, size, scale });
+            if self.broken {
+                return Err(PluginError::Message("no display"));
+            }
+            self.opened += 1;
+            Ok(FakeWindow { id: self.opened, size })
+        }
+
+        fn close(&mut self, window: FakeWindow) {
+            self.calls.push(Call::Close(window.id));
+        }
+
+        fn is_alive(&self, window: &FakeWindow) -> bool {
+            self.closed_by_user != Some(window.id)
+        }
+
+        fn size(&self, window: &FakeWindow) -> PhySize {
+            window.size
+        }
+
+        fn resize(&mut self, window: &mut FakeWindow, size: PhySize) {
+            window.size = size;
+            self.calls.push(Call::Resize(window.id, size));
+        }
+
+        fn rescale(&mut self, window: &mut FakeWindow, scale: f64) {
+            self.calls.push(Call::Rescale(window.id, scale));
+        }
+    }
+
+    fn parent(window: u64) -> RawWindowHandle {
+        let mut handle = XlibWindowHandle::empty();
+        handle.window = window as _;
+        RawWindowHandle::Xlib(handle)
+    }
+
+    fn calls(editor: &mut Editor<FakeLayer>) -> Vec<Call> {
+        std::mem::take(&mut editor.layer.calls)
+    }
+
+    #[test]
+    fn bitwig_scales_and_parents_before_show() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(false);
+        editor.set_scale(1.5);
+        assert_eq!(editor.size(), PhySize::new(600, 450));
+        editor.set_parent(parent(1)).unwrap();
+        editor.show().unwrap();
+        assert!(editor.is_open());
+        let size = PhySize::new(600, 450);
+        let open = Call::Open { parent: Some(parent(1)), size, scale: Some(1.5) };
+        assert_eq!(calls(&mut editor), [open]);
+
+        editor.hide();
+        editor.show().unwrap();
+        editor.destroy();
+        assert_eq!(
+            calls(&mut editor),
+            [
+                Call::Close(1),
+                Call::Open { parent: Some(parent(1)), size, scale: Some(1.5) },
+                Call::Close(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn reaper_sizes_before_the_parent_arrives() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(false);
+        editor.set_size(PhySize::new(800, 600));
+        editor.show().unwrap();
+        assert!(!editor.is_open());
+        editor.set_parent(parent(1)).unwrap();
+        editor.set_size(PhySize::new(900, 700));
+        editor.set_parent(parent(2)).unwrap();
+        assert_eq!(
+            calls(&mut editor),
+            [
+                Call::Open { parent: Some(parent(1)), size: PhySize::new(800, 600), scale: None },
+                Call::Resize(1, PhySize::new(900, 700)),
+                Call::Close(1),
+                Call::Open { parent: Some(parent(2)), size: PhySize::new(900, 700), scale: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn clap_host_rescales_an_open_editor() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(false);
+        assert_eq!(editor.size(), DEFAULT_SIZE);
+        editor.set_parent(parent(1)).unwrap();
+        editor.show().unwrap();
+        editor.set_scale(2.0);
+        assert_eq!(editor.adjust_size(PhySize::new(0, 0)), PhySize::new(640, 480));
+        editor.hide();
+        editor.destroy();
+        assert_eq!(
+            calls(&mut editor),
+            [
+                Call::Open { parent: Some(parent(1)), size: DEFAULT_SIZE, scale: None },
+                Call::Rescale(1, 2.0),
+                Call::Close(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn destroy_forgets_the_parent_but_reopens_at_the_last_size_and_scale() {
+        let mut editor = Editor::new(FakeLayer::default());
+        assert!(editor.set_parent(parent(1)).is_err());
+        editor.create(false);
+        editor.set_scale(2.0);
+        editor.set_parent(parent(1)).unwrap();
+        let size = PhySize::new(1000, 700);
+        editor.set_size(size);
+        editor.destroy();
+        assert!(editor.set_parent(parent(1)).is_err());
+
+        editor.create(false);
+        assert_eq!(editor.size(), size);
+        editor.show().unwrap();
+        assert!(!editor.is_open());
+        calls(&mut editor);
+        editor.set_parent(parent(2)).unwrap();
+        assert_eq!(
+            calls(&mut editor),
+            [Call::Open { parent: Some(parent(2)), size, scale: Some(2.0) }]
+        );
+    }
+
+    #[test]
+    fn floating_window_closed_by_user_reopens_on_show() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(true);
+        editor.show().unwrap();
+        assert!(!editor.take_closed_by_user());
+        editor.layer.closed_by_user = Some(1);
+        assert!(!editor.is_open());
+        assert!(editor.take_closed_by_user());
+        assert!(!editor.take_closed_by_user());
+        editor.show().unwrap();
+        assert!(editor.is_open());
+        assert_eq!(
+            calls(&mut editor),
+            [
+                Call::Open { parent: None, size: DEFAULT_SIZE, scale: None },
+                Call::Close(1),
+                Call::Open { parent: None, size: DEFAULT_SIZE, scale: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_opens_are_reported_every_time_and_leave_nothing_behind() {
+        let mut editor = Editor::new(FakeLayer { broken: true, ..Default::default() });
+        editor.create(false);
+        editor.set_size(PhySize::new(800, 600));
+        assert!(editor.set_parent(parent(1)).is_err());
+        assert!(editor.show().is_err());
+        assert!(!editor.is_open());
+        editor.hide();
+
+        // Once the window system recovers the retry opens normally.
+        editor.layer.broken = false;
+        editor.set_parent(parent(2)).unwrap();
+        assert!(editor.is_open());
+        editor.destroy();
+        let size = PhySize::new(800, 600);
+        let open = |parent| Call::Open { parent: Some(parent), size, scale: None };
+        assert_eq!(
+            calls(&mut editor),
+            [open(parent(1)), open(parent(1)), open(parent(2)), Call::Close(1)]
+        );
+    }
+
+    #[test]
+    fn bitwig_accepts_a_resize_request_without_calling_set_size() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(false);
+        editor.set_parent(parent(1)).unwrap();
+        calls(&mut editor);
+
+        let size = PhySize::new(400, 420);
+        assert_eq!(editor.request_resize(size), Some(size));
+        assert_eq!(editor.size(), DEFAULT_SIZE, "not before the host agrees");
+        editor.confirm_resize();
+        assert_eq!(editor.requested_size, None);
+        assert_eq!(editor.size(), size);
+        assert_eq!(calls(&mut editor), [Call::Resize(1, size)]);
+        assert_eq!(editor.request_resize(size), None, "already that size");
+    }
+
+    #[test]
+    fn reaper_refusing_a_resize_leaves_the_window_alone() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(false);
+        editor.set_parent(parent(1)).unwrap();
+        calls(&mut editor);
+
+        assert!(editor.request_resize(PhySize::new(400, 420)).is_some());
+        editor.refuse_resize();
+        editor.confirm_resize();
+        assert_eq!(editor.size(), DEFAULT_SIZE);
+        assert_eq!(calls(&mut editor), []);
+
+        // A set_size from the host answers any request still open.
+        editor.request_resize(PhySize::new(400, 420));
+        editor.set_size(PhySize::new(500, 400));
+        assert_eq!(editor.requested_size, None);
+    }
+
+    #[test]
+    fn floating_windows_resize_without_asking() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(true);
+        editor.show().unwrap();
+        calls(&mut editor);
+
+        assert_eq!(editor.request_resize(PhySize::new(400, 420)), None);
+        assert_eq!(calls(&mut editor), [Call::Resize(1, PhySize::new(400, 420))]);
+        assert_eq!(editor.requested_size, None);
+    }
+
+    #[test]
+    fn fractional_scales_keep_the_logical_size() {
+        let mut editor = Editor::new(FakeLayer::default());
+        editor.create(false);
+        editor.set_scale(1.25);
+        assert_eq!(editor.size(), PhySize::new(500, 375));
+        editor.set_scale(1.5);
+        assert_eq!(editor.size(), PhySize::new(600, 450));
+        assert_eq!(editor.adjust_size(PhySize::new(0, 0)), PhySize::new(480, 360));
+    }
+}
@@ -0,0 +1,759 @@
+use std::sync::Arc;
+
+use crate::auto_pan::{AutoPan, HostTime};
+use crate::chord::chord_intervals;
+use crate::mod_matrix::Modulation;
+use crate::oversampling::{decimation_latency, Decimator};
+use crate::params::{Params, PARAM_CUTOFF_ID, PARAM_GAIN_ID};
+use crate::reverb::Reverb;
+use crate::sample::Sample;
+use crate::smoother::Smoother;
+use crate::split::zone_transpositions;
+use crate::thread_pool::{VoiceTasks, PARALLEL_MIN_VOICES, RENDER_TASKS, TASK_SIGNALS};
+use crate::voice::{RenderParams, VoicePool, VoiceSettings, STEAL_FADE};
+
+/// The synth without the plugin around it: voices, modulation, the master chain and
+/// auto-pan, driven by notes and param values. The plugin's
+/// [`CaveAudioProcessor`](crate::CaveAudioProcessor) translates CLAP events into calls on
+/// one of these; anything else can drive it directly.
+///
+/// Renders mono, in [`Sample`]s: `f32` unless built with the `f64-dsp` feature. Voices
+/// panned by key and the reverb also leave a side signal, see [`side`](Self::side).
+///
+/// The FX mix crossfades the voices as they'd sound with no effects at all, no comb,
+/// reverb or auto-pan, with what comes out of them. The engine keeps that dry signal
+/// beside the effects' until the output is put together, see [`fx_dry`](Self::fx_dry).
+pub struct CaveEngine {
+    params: Arc<Params>,
+    voices: VoicePool,
+    modulation: Modulation,
+    auto_pan: AutoPan,
+    /// Whether the combs ran last block, to clear them when they're switched off.
+    comb_on: bool,
+    reverb: Reverb,
+    /// Likewise for the reverb, whose tail also keeps the side signal going.
+    reverb_on: bool,
+    /// The FX mix the last block rendered with. At 0 the effects are skipped.
+    fx_mix: f32,
+    /// Whether the voices render their dry signal themselves, without their combs, this
+    /// block. Otherwise it's their mix as it comes, before the master effects.
+    dry_voices: bool,
+    /// The dry mix and side signal, at the engine's rate and sized for the largest block.
+    dry_buffers: [Vec<Sample>; 2],
+    /// Level of the whole output while a panic fades it out along with the voices, after
+    /// which the effects' tails are cleared. `None` outside a panic.
+    panic_fade: Option<f32>,
+    /// Semitones of pitch modulation the mod matrix gave the last block.
+    pitch_mod: f32,
+    /// The master gain factor, glided per sample, and the cutoff, per block.
+    gain: Smoother,
+    cutoff: Smoother,
+    /// Left then right auto-pan gains, sized for the largest block.
+    pan_gains: Vec<f32>,
+    /// The voices' mix scaled by their pans, for the block just rendered.
+    side_buffer: Vec<Sample>,
+    /// Each of the [`TASK_SIGNALS`] per pool task, sized for the largest block.
+    task_buffers: Vec<Sample>,
+    /// How many times the sample rate the voices run at, fixed for the engine's lifetime:
+    /// changing it changes the latency, which the host only takes between activations.
+    oversampling: usize,
+    /// The voices' mix and side signal at the oversampled rate, then the dry pair, and the
+    /// decimators that bring each down to the engine's. Empty and unused without
+    /// oversampling.
+    oversampled: [Vec<Sample>; TASK_SIGNALS],
+    decimators: [Decimator; TASK_SIGNALS],
+    sample_rate: f32, // Hz
+}
+
+/// Auto-pan's left and right gains for a block.
+pub type PanGains<'a> = (&'a [f32], &'a [f32]);
+
+/// The voices with no effects on them, for a block the FX mix is partway through: see
+/// [`CaveEngine::fx_dry`]. Gain and all, like the effects' output it's crossfaded with.
+#[derive(Clone, Copy)]
+pub struct FxDry<'a> {
+    /// The FX mix, from 0.0 (all dry) to 1.0.
+    pub amount: Sample,
+    pub mix: &'a [Sample],
+    /// Taken off the left channel and added to the right, like [`CaveEngine::side`].
+    pub side: &'a [Sample],
+}
+
+impl FxDry<'_> {
+    /// One sample of the FX mix: `dry` crossfaded with `wet` by the amount.
+    pub fn crossfade(&self, dry: Sample, wet: Sample) -> Sample {
+        dry + (wet - dry) * self.amount
+    }
+}
+
+impl CaveEngine {
+    /// An engine with every param at its default. Blocks may be up to `max_frames` long.
+    pub fn new(sample_rate: f32, max_frames: usize) -> Self {
+        Self::with_params(Arc::new(Params::default()), sample_rate, max_frames)
+    }
+
+    /// An engine reading `params`, which the plugin shares with the host and the editor.
+    /// The oversampling param is read here, once.
+    pub(crate) fn with_params(params: Arc<Params>, sample_rate: f32, max_frames: usize) -> Self {
+        let oversampling = params.oversampling();
+        let oversampled_frames = if oversampling > 1 { max_frames * oversampling } else { 0 };
+        Self {
+            gain: Smoother::new(PARAM_GAIN_ID, params.gain_factor()),
+            cutoff: Smoother::new(PARAM_CUTOFF_ID, params.cutoff()),
+            reverb: Reverb::new(sample_rate, params.reverb().predelay),
+            params,
+            voices: VoicePool::new(sample_rate * oversampling as f32),
+            modulation: Modulation::default(),
+            auto_pan: AutoPan::default(),
+            comb_on: true,
+            reverb_on: false,
+            fx_mix: 1.0,
+            dry_voices: false,
+            dry_buffers: [vec![0.0; max_frames], vec![0.0; max_frames]],
+            panic_fade: None,
+            pitch_mod: 0.0,
+            pan_gains: vec![0.0; max_frames * 2],
+            side_buffer: vec![0.0; max_frames],
+            task_buffers: vec![0.0; max_frames * oversampling * RENDER_TASKS * TASK_SIGNALS],
+            oversampling,
+            oversampled: [(); TASK_SIGNALS].map(|_| vec![0.0; oversampled_frames]),
+            decimators: [(); TASK_SIGNALS].map(|_| Decimator::new(oversampling, max_frames)),
+            sample_rate,
+        }
+    }
+
+    /// Sets param `id`, one of the ids the plugin gives the host, to `value` in the param's
+    /// own units. Unknown ids are ignored.
+    pub fn set_param(&mut self, id: u32, value: f32) {
+        self.params.set_value(id, value);
+    }
+
+    /// Starts a voice for `key` in each keyboard zone it falls in, plus one per extra chord
+    /// tone when chord mode is on. `velocity` runs from 0.0 to 1.0. Returns whether a
+    /// sounding voice had to be stolen.
+    pub fn note_on(&mut self, key: u8, velocity: f32) -> bool {
+        self.apply_voice_limit();
+        let params = &self.params;
+        self.modulation.note_on(params);
+        let settings = VoiceSettings {
+            velocity: velocity.clamp(0.0, 1.0),
+            waveform: params.waveform(),
+            pitch_env_amount: params.pitch_env_amount(),
+            pitch_env_decay: params.pitch_env_decay(),
+            amp_env: params.amp_env(),
+            key_to_pan: params.key_to_pan(),
+            unison: params.unison_voices(),
+            unison_detune: params.unison_detune(),
+        };
+        let zones = zone_transpositions(
+            params.split_mode(),
+            params.split_point(),
+            params.lower_octave(),
+            params.upper_octave(),
+            key,
+        );
+
+        let mut stolen = false;
+        for transpose in zones.into_iter().flatten() {
+            for &interval in chord_intervals(params.chord_type()) {
+                let note = key as i32 + transpose + interval as i32;
+                if (0..=127).contains(&note) {
+                    stolen |= self.voices.note_on(key, note as u8, settings);
+                }
+            }
+        }
+        stolen
+    }
+
+    /// Releases every voice `key` started, chord tones included.
+    pub fn note_off(&mut self, key: u8) {
+        self.voices.note_off(key);
+    }
+
+    /// Releases every voice, whatever key started it.
+    pub fn all_notes_off(&mut self) {
+        self.voices.release_all();
+    }
+
+    /// Stops every voice within a few milliseconds, release tails and all: the way out of
+    /// a stuck note. The effects fade out with them, and then their tails, a frozen reverb's
+    /// too, are cleared. Unlike [`reset`](Self::reset) it fades rather than cuts, so it's
+    /// safe mid-block while sounding.
+    pub fn panic(&mut self) {
+        self.voices.fade_out_all();
+        self.panic_fade = Some(self.panic_fade.unwrap_or(1.0));
+    }
+
+    /// Silences everything at once for a host reset: voices cut off, filters and delay
+    /// lines emptied, LFOs and auto-pan back to the top. The next block starts from silence,
+    /// as on a new engine; the params stay as they are.
+    pub fn reset(&mut self) {
+        self.voices.reset();
+        self.modulation.reset();
+        self.clear_effects();
+        self.panic_fade = None;
+        self.pitch_mod = 0.0;
+        self.gain.set_target(self.params.gain_factor(), self.sample_rate);
+        self.gain.snap();
+        self.cutoff.set_target(self.params.cutoff(), self.sample_rate);
+        self.cutoff.snap();
+    }
+
+    /// One block of the mono mix into `buffer`, overwriting whatever was there.
+    pub fn render(&mut self, buffer: &mut [Sample]) {
+        let render = self.advance_modulation(buffer.len());
+        self.render_voices(buffer, 0, &render);
+        self.master_chain(buffer, 0);
+        self.mix_dry(buffer);
+    }
+
+    /// Follows the max voices param, releasing voices over a lowered limit. Returns whether
+    /// the limit changed.
+    pub(crate) fn apply_voice_limit(&mut self) -> bool {
+        self.voices.set_limit(self.params.max_voices())
+    }
+
+    /// Hands `f` each sounding key whose modulated tuning, in semitones, has moved since
+    /// the last call, for the host to show as a note expression.
+    pub(crate) fn tuning_changes(&mut self, f: impl FnMut(u8, f32)) {
+        self.voices.tuning_changes(self.pitch_mod, f);
+    }
+
+    /// Longest block every buffer has room for.
+    pub(crate) fn max_frames(&self) -> usize {
+        let task_frames =
+            self.task_buffers.len() / (TASK_SIGNALS * RENDER_TASKS * self.oversampling);
+        let mut frames = (self.pan_gains.len() / 2).min(self.side_buffer.len()).min(task_frames);
+        for buffer in &self.dry_buffers {
+            frames = frames.min(buffer.len());
+        }
+        if self.oversampling > 1 {
+            for buffer in &self.oversampled {
+                frames = frames.min(buffer.len() / self.oversampling);
+            }
+        }
+        frames
+    }
+
+    /// How many times the sample rate the voices run at, as the oversampling param was when
+    /// the engine was built.
+    pub(crate) fn oversampling(&self) -> usize {
+        self.oversampling
+    }
+
+    /// Samples the output runs behind the notes: the oversampling decimators' delay.
+    pub fn latency(&self) -> u32 {
+        decimation_latency(self.oversampling)
+    }
+
+    /// Voices sounding, released ones still in their tails included.
+    pub fn active_voices(&self) -> usize {
+        self.voices.active_count()
+    }
+
+    pub(crate) fn held_keys(&self) -> impl Iterator<Item = u8> + '_ {
+        self.voices.held_keys()
+    }
+
+    /// Runs the LFOs and mod matrix for one block of `frames`.
+    pub(crate) fn advance_modulation(&mut self, frames: usize) -> RenderParams {
+        let params = &self.params;
+        let mods = self.modulation.advance(params, frames, self.sample_rate);
+        // Fully dry skips the effects altogether.
+        self.fx_mix = params.fx_mix();
+        let comb_on = params.comb_on() && self.fx_mix > 0.0;
+        if self.comb_on && !comb_on {
+            self.voices.clear_combs();
+        }
+        self.comb_on = comb_on;
+        let comb_mix = if comb_on { params.comb_mix() } else { 0.0 };
+        // Only a comb that's running and partly heard needs the voices rendered without it.
+        let dry_voices = self.fx_partway() && comb_mix > 0.0;
+        if dry_voices && !self.dry_voices {
+            // The dry pair was the mix and side until now, so it picks up their decimators.
+            let [mix, side, dry_mix, dry_side] = &mut self.decimators;
+            dry_mix.follow(mix);
+            dry_side.follow(side);
+        }
+        self.dry_voices = dry_voices;
+        self.pitch_mod = mods.pitch;
+        self.cutoff.set_target(params.cutoff(), self.sample_rate);
+        RenderParams {
+            sample_rate: self.sample_rate * self.oversampling as f32,
+            amp: mods.gain_factor(),
+            pitch_ratio: mods.pitch_ratio(),
+            // A zero mix skips the comb entirely rather than running it transparent.
+            comb_mix,
+            comb_feedback: params.comb_feedback(),
+            pluck_tone: params.pluck_tone(),
+            noise_color: params.noise_color(),
+            cutoff: self.cutoff.skip(frames),
+            resonance: params.resonance(),
+            vel_to_cutoff: params.vel_to_cutoff(),
+        }
+    }
+
+    /// Sums the voices into `buffer`, and their side signal into the engine's own buffer,
+    /// overwriting whatever was there, along with their dry signal when they render it.
+    /// `buffer` is the part of the block from frame `at`, where its side signal goes too: a
+    /// block can be rendered in pieces, between events.
+    pub(crate) fn render_voices(
+        &mut self,
+        buffer: &mut [Sample],
+        at: usize,
+        render: &RenderParams,
+    ) {
+        if self.oversampling == 1 {
+            let frames = at..at + buffer.len();
+            let side = &mut self.side_buffer[frames.clone()];
+            let [dry_mix, dry_side] = &mut self.dry_buffers;
+            let dry = (&mut dry_mix[frames.clone()], &mut dry_side[frames]);
+            self.voices.render_with_dry(buffer, side, self.dry_voices.then_some(dry), render);
+            return;
+        }
+        let frames = buffer.len() * self.oversampling;
+        let [mix, side, dry_mix, dry_side] = &mut self.oversampled;
+        let dry = self.dry_voices.then(|| (&mut dry_mix[..frames], &mut dry_side[..frames]));
+        self.voices.render_with_dry(&mut mix[..frames], &mut side[..frames], dry, render);
+        self.decimate(buffer, at);
+    }
+
+    /// Brings the voices' oversampled mix down into `buffer`, and their side signal, and
+    /// their dry pair when they rendered it, into the engine's own buffers from frame `at`.
+    fn decimate(&mut self, buffer: &mut [Sample], at: usize) {
+        let frames = at..at + buffer.len();
+        let [dry_mix, dry_side] = &mut self.dry_buffers;
+        let outputs = [
+            buffer,
+            &mut self.side_buffer[frames.clone()],
+            &mut dry_mix[frames.clone()],
+            &mut dry_side[frames],
+        ];
+        let signals = if self.dry_voices { TASK_SIGNALS } else { 2 };
+        let stages = self.decimators.iter_mut().zip(&self.oversampled).zip(outputs);
+        for ((decimator, oversampled), output) in stages.take(signals) {
+            decimator.process(&oversampled[..output.len() * self.oversampling], output);
+        }
+    }
+
+    /// [`render_voices`](Self::render_voices) through `exec`, which must run every task
+    /// index it's given through `tasks` before returning true. Returns false, leaving
+    /// `buffer` alone, when there are too few voices to bother or `exec` refused.
+    pub(crate) fn render_voices_pooled(
+        &mut self,
+        buffer: &mut [Sample],
+        at: usize,
+        render: RenderParams,
+        tasks: &VoiceTasks,
+        exec: impl FnOnce(u32) -> bool,
+    ) -> bool {
+        if self.voices.active_count() < PARALLEL_MIN_VOICES {
+            return false;
+        }
+
+        // Clamped as `run` clamps it, so summing never reads past a task's buffer.
+        let stride = self.task_buffers.len() / (TASK_SIGNALS * RENDER_TASKS);
+        let frames = (buffer.len() * self.oversampling).min(stride);
+        let voices = self.voices.voices_mut();
+        let ran = tasks.run(voices, &mut self.task_buffers, frames, self.dry_voices, render, exec);
+        if !ran {
+            return false;
+        }
+
+        let sums: [&mut [Sample]; TASK_SIGNALS] = if self.oversampling == 1 {
+            let [dry_mix, dry_side] = &mut self.dry_buffers;
+            [
+                &mut *buffer,
+                &mut self.side_buffer[at..at + frames],
+                &mut dry_mix[at..at + frames],
+                &mut dry_side[at..at + frames],
+            ]
+        } else {
+            self.oversampled.each_mut().map(|oversampled| &mut oversampled[..frames])
+        };
+        let signals = if self.dry_voices { TASK_SIGNALS } else { 2 };
+        let task_signals = self.task_buffers.chunks_exact(stride * RENDER_TASKS);
+        for (sum, tasks) in sums.into_iter().zip(task_signals).take(signals) {
+            sum.fill(0.0);
+            for task_buffer in tasks.chunks_exact(stride) {
+                for (out, sample) in sum.iter_mut().zip(&task_buffer[..frames]) {
+                    *out += sample;
+                }
+            }
+        }
+        if self.oversampling > 1 {
+            self.decimate(buffer, at);
+        }
+        true
+    }
+
+    /// The effects that run once on the mix rather than per voice, then the master gain.
+    /// New master effects go in front of the gain. `at` is as for
+    /// [`render_voices`](Self::render_voices).
+    ///
+    /// The gain, and a panic's fade, go on the dry signal too: they aren't effects, so the
+    /// FX mix doesn't leave them out.
+    pub(crate) fn master_chain(&mut self, buffer: &mut [Sample], at: usize) {
+        self.gain.set_target(self.params.gain_factor(), self.sample_rate);
+        let frames = at..at + buffer.len();
+        let side = &mut self.side_buffer[frames.clone()];
+        let [dry_mix, dry_side] = &mut self.dry_buffers;
+        let (dry_mix, dry_side) = (&mut dry_mix[frames.clone()], &mut dry_side[frames]);
+        if !self.dry_voices {
+            // Otherwise it's the mix as the voices left it: the combs are out of it, or
+            // it's fully wet and the dry goes unheard.
+            dry_mix.copy_from_slice(buffer);
+            dry_side.copy_from_slice(side);
+        }
+
+        let reverb_on = self.params.reverb_on() && self.fx_mix > 0.0;
+        if self.reverb_on && !reverb_on {
+            self.reverb.clear();
+        }
+        self.reverb_on = reverb_on;
+        if reverb_on {
+            // Back as mid and side, like the voices' pan: the mono sum takes the middle.
+            let settings = self.params.reverb();
+            for (sample, side) in buffer.iter_mut().zip(side.iter_mut()) {
+                let (left, right) = self.reverb.process(*sample, &settings);
+                *sample += (left + right) * 0.5;
+                *side += (right - left) * 0.5;
+            }
+        }
+
+        let signals = buffer.iter_mut().zip(side.iter_mut());
+        let dry = dry_mix.iter_mut().zip(dry_side.iter_mut());
+        for ((sample, side), (dry_mix, dry_side)) in signals.zip(dry) {
+            let gain = Sample::from(self.gain.next_value());
+            *sample *= gain;
+            *side *= gain;
+            *dry_mix *= gain;
+            *dry_side *= gain;
+        }
+
+        // A panic fades the output over [`STEAL_FADE`] as the voices go, then clears what
+        // the effects still hold.
+        let Some(mut level) = self.panic_fade else { return };
+        let step = 1.0 / (STEAL_FADE * self.sample_rate);
+        let signals = buffer.iter_mut().zip(side);
+        let dry = dry_mix.iter_mut().zip(dry_side);
+        for ((sample, side), (dry_mix, dry_side)) in signals.zip(dry) {
+            let fade = Sample::from(level);
+            *sample *= fade;
+            *side *= fade;
+            *dry_mix *= fade;
+            *dry_side *= fade;
+            level = (level - step).max(0.0);
+        }
+        if level > 0.0 {
+            self.panic_fade = Some(level);
+            return;
+        }
+        self.panic_fade = None;
+        self.clear_effects();
+    }
+
+    /// Empties everything that could still ring once the voices are gone, for a reset or
+    /// at the end of a panic: the combs, the reverb, frozen or not, and the decimators, with
+    /// the auto-pan back to the top of its sweep.
+    fn clear_effects(&mut self) {
+        self.voices.clear_combs();
+        self.reverb.clear();
+        self.decimators.iter_mut().for_each(Decimator::clear);
+        self.auto_pan = AutoPan::default();
+    }
+
+    /// The last block's side signal, the first `frames` of it, or `None` when every voice
+    /// is centred and the reverb is off. Stereo outputs take it off the mix on the left and
+    /// add it on the right, which keeps the mono sum as it was.
+    pub fn side(&self, frames: usize) -> Option<&[Sample]> {
+        (self.voices.any_panned() || self.reverb_on).then(|| &self.side_buffer[..frames])
+    }
+
+    /// What the stereo output needs for a block of `frames`: the [`side`](Self::side)
+    /// signal, the auto-pan gains unless the FX mix is fully dry (see
+    /// [`auto_pan_gains`](Self::auto_pan_gains)), and the [`fx_dry`](Self::fx_dry) signal.
+    pub fn stereo_stage(
+        &mut self,
+        frames: usize,
+        time: HostTime,
+    ) -> (Option<&[Sample]>, Option<PanGains<'_>>, Option<FxDry<'_>>) {
+        let auto_pan = self.fx_mix > 0.0 && self.auto_pan_gains(frames, time).is_some();
+        let (left, right) = self.pan_gains.split_at(self.pan_gains.len() / 2);
+        let gains = auto_pan.then(|| (&left[..frames], &right[..frames]));
+        (self.side(frames), gains, self.fx_dry(frames))
+    }
+
+    /// The dry signal for the first `frames` of the last block, as the FX mix crossfades it
+    /// with the effects' output. `None` unless the mix is partway: fully dry, the effects
+    /// were skipped and the output is the dry signal already, and fully wet it's unheard.
+    pub fn fx_dry(&self, frames: usize) -> Option<FxDry<'_>> {
+        let [mix, side] = &self.dry_buffers;
+        self.fx_partway().then(|| FxDry {
+            amount: Sample::from(self.fx_mix),
+            mix: &mix[..frames],
+            side: &side[..frames],
+        })
+    }
+
+    /// The FX mix on the mono mix: crossfades `buffer`, the last block as the effects left
+    /// it, with the dry signal.
+    pub fn mix_dry(&self, buffer: &mut [Sample]) {
+        let Some(dry) = self.fx_dry(buffer.len()) else { return };
+        for (sample, &dry_sample) in buffer.iter_mut().zip(dry.mix) {
+            *sample = dry.crossfade(dry_sample, *sample);
+        }
+    }
+
+    fn fx_partway(&self) -> bool {
+        self.fx_mix > 0.0 && self.fx_mix < 1.0
+    }
+
+    /// Left and right gains for the next `frames` samples of auto-pan, or `None` when it's
+    /// off and the output should be left alone. A synced sweep follows the host's tempo and
+    /// song position.
+    pub fn auto_pan_gains(&mut self, frames: usize, time: HostTime) -> Option<PanGains<'_>> {
+        if !self.params.auto_pan_on() {
+            // Switched back on, it starts its sweep from the top.
+            self.auto_pan = AutoPan::default();
+            return None;
+        }
+        let settings = self.params.auto_pan();
+        if settings.depth <= 0.0 {
+            return None;
+        }
+        let half = self.pan_gains.len() / 2;
+        let (left, right) = self.pan_gains.split_at_mut(half);
+        let (left, right) = (&mut left[..frames], &mut right[..frames]);
+        let synced = time.tempo.and(settings.cycle_beats());
+        if let (Some(beat), Some(cycle)) = (time.beat, synced) {
+            self.auto_pan.align(beat, cycle);
+        }
+        let rate = settings.rate_hz(time.tempo);
+        self.auto_pan.gains(left, right, &settings, rate, self.sample_rate);
+        Some((left, right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{PARAM_CHORD_TYPE_ID, PARAM_GAIN_ID, PARAM_SPLIT_MODE_ID};
+    use crate::params::{PARAM_COMB_FEEDBACK_ID, PARAM_COMB_MIX_ID};
+    use crate::params::{PARAM_KEY_TO_PAN_ID, PARAM_OVERSAMPLING_ID, PARAM_UPPER_OCTAVE_ID};
+    use crate::params::{PARAM_REVERB_FREEZE_ID, PARAM_REVERB_MIX_ID, PARAM_REVERB_ON_ID};
+    use crate::pitch::note_freq;
+    use crate::A4_NOTE;
+    use std::f64::consts::TAU;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn engine() -> CaveEngine {
+        CaveEngine::new(SAMPLE_RATE, BLOCK_SIZE)
+    }
+
+    fn render_block(engine: &mut CaveEngine) -> Vec<Sample> {
+        let mut buffer = vec![0.0; BLOCK_SIZE];
+        engine.render(&mut buffer);
+        buffer
+    }
+
+    fn peak(buffer: &[Sample]) -> Sample {
+        buffer.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    /// Share of `buffer`'s power away from the harmonics of `frequency`: aliasing, which
+    /// folds a square wave's harmonics past Nyquist back down in between them.
+    fn inharmonic_share(buffer: &[Sample], frequency: f32) -> f64 {
+        let len = buffer.len();
+        let turn = |i: usize| TAU * i as f64 / len as f64;
+        let (sin, cos): (Vec<f64>, Vec<f64>) = (0..len).map(|i| turn(i).sin_cos()).unzip();
+        // Hann-windowed, so each harmonic stays within a few bins of its own.
+        let windowed: Vec<f64> =
+            buffer.iter().zip(&cos).map(|(&s, c)| f64::from(s) * (0.5 - 0.5 * c)).collect();
+        let bin_hz = f64::from(SAMPLE_RATE) / len as f64;
+        let (mut total, mut inharmonic) = (0.0, 0.0);
+        for bin in 1..len / 2 {
+            let (re, im) = windowed.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, s)| {
+                let t = bin * i % len;
+                (re + s * cos[t], im + s * sin[t])
+            });
+            let power = re * re + im * im;
+            let harmonic = bin as f64 * bin_hz / f64::from(frequency);
+            let bins_off = (harmonic - harmonic.round()).abs() * f64::from(frequency) / bin_hz;
+            total += power;
+            if harmonic.round() < 1.0 || bins_off > 3.0 {
+                inharmonic += power;
+            }
+        }
+        inharmonic / total
+    }
+
+    #[test]
+    fn chord_mode_starts_a_voice_per_chord_tone() {
+        let mut engine = engine();
+        engine.set_param(PARAM_CHORD_TYPE_ID, 1.0); // Major
+
+        engine.note_on(60, 1.0);
+        assert_eq!(engine.voices.held_count(), 3);
+
+        engine.note_off(60);
+        assert_eq!(engine.voices.held_count(), 0);
+    }
+
+    #[test]
+    fn layer_mode_plays_both_zones_and_split_mode_one() {
+        let mut engine = engine();
+        engine.set_param(PARAM_SPLIT_MODE_ID, 2.0); // Layer
+        engine.set_param(PARAM_UPPER_OCTAVE_ID, 1.0);
+
+        engine.note_on(60, 1.0);
+        assert_eq!(engine.voices.held_count(), 2);
+        engine.note_off(60);
+
+        engine.set_param(PARAM_SPLIT_MODE_ID, 1.0); // Split
+        engine.note_on(60, 1.0);
+        assert_eq!(engine.voices.held_count(), 1);
+    }
+
+    #[test]
+    fn oversampling_keeps_a_high_note_from_aliasing() {
+        const NOTE: u8 = 96; // C7, 2093 Hz
+        let share = |oversampling: f32| {
+            let params = Arc::new(Params::default());
+            params.set_value(PARAM_OVERSAMPLING_ID, oversampling);
+            let mut engine = CaveEngine::with_params(params, SAMPLE_RATE, BLOCK_SIZE);
+            engine.note_on(NOTE, 1.0);
+            // Past the attack, and the decimators' delay.
+            render_block(&mut engine);
+            let buffer: Vec<Sample> = (0..8).flat_map(|_| render_block(&mut engine)).collect();
+            inharmonic_share(&buffer, note_freq(NOTE))
+        };
+        let (off, four_times) = (share(0.0), share(2.0));
+        assert!(four_times < off / 8.0, "aliasing at Off {off}, at 4x {four_times}");
+    }
+
+    #[test]
+    fn silent_without_notes() {
+        assert_eq!(peak(&render_block(&mut engine())), 0.0);
+    }
+
+    #[test]
+    fn note_on_produces_sound_and_note_off_silences_it() {
+        let mut engine = engine();
+
+        engine.note_on(A4_NOTE, 1.0);
+        assert!(peak(&render_block(&mut engine)) > 0.01);
+
+        engine.note_off(A4_NOTE);
+        // Let any release tail run out before checking for silence.
+        for _ in 0..(SAMPLE_RATE as usize / BLOCK_SIZE) {
+            render_block(&mut engine);
+        }
+        assert_eq!(peak(&render_block(&mut engine)), 0.0);
+    }
+
+    #[test]
+    fn panic_silences_a_frozen_reverb_tail() {
+        let mut engine = engine();
+        engine.set_param(PARAM_REVERB_ON_ID, 1.0);
+        engine.set_param(PARAM_REVERB_MIX_ID, 1.0);
+        engine.note_on(A4_NOTE, 1.0);
+        for _ in 0..20 {
+            render_block(&mut engine);
+        }
+        engine.set_param(PARAM_REVERB_FREEZE_ID, 1.0);
+        engine.note_off(A4_NOTE);
+        // Long past the note's release: what's left is the frozen tail.
+        for _ in 0..(SAMPLE_RATE as usize / BLOCK_SIZE) {
+            render_block(&mut engine);
+        }
+        assert!(peak(&render_block(&mut engine)) > 1e-3);
+
+        engine.panic();
+        // Gone within the first few milliseconds, and it stays gone with freeze still on.
+        let fade = render_block(&mut engine);
+        assert_eq!(peak(&fade[BLOCK_SIZE / 2..]), 0.0);
+        assert_eq!(peak(&render_block(&mut engine)), 0.0);
+    }
+
+    #[test]
+    fn after_a_panic_the_next_note_plays_as_after_a_reset() {
+        let [mut panicked, mut reset] = [(); 2].map(|_| {
+            let params = Arc::new(Params::default());
+            params.set_value(PARAM_OVERSAMPLING_ID, 1.0); // 2x
+            params.set_value(PARAM_REVERB_ON_ID, 1.0);
+            params.set_value(PARAM_REVERB_MIX_ID, 1.0);
+            params.set_value(PARAM_COMB_MIX_ID, 1.0);
+            params.set_value(PARAM_COMB_FEEDBACK_ID, 0.99);
+            CaveEngine::with_params(params, SAMPLE_RATE, BLOCK_SIZE)
+        });
+        for engine in [&mut panicked, &mut reset] {
+            engine.note_on(A4_NOTE, 1.0);
+            for _ in 0..4 {
+                render_block(engine);
+            }
+        }
+        panicked.panic();
+        render_block(&mut panicked);
+        reset.reset();
+
+        // No reverb tail or decimator history left over from the first note.
+        for engine in [&mut panicked, &mut reset] {
+            engine.note_on(A4_NOTE + 12, 1.0);
+        }
+        assert_eq!(render_block(&mut panicked), render_block(&mut reset));
+    }
+
+    #[test]
+    fn master_gain_applies_once_to_the_mix() {
+        let (mut voices, mut mixed) = (engine(), engine());
+        for engine in [&mut voices, &mut mixed] {
+            engine.set_param(PARAM_GAIN_ID, 0.5);
+            engine.note_on(A4_NOTE, 1.0);
+        }
+
+        let mut expected = vec![0.0; BLOCK_SIZE];
+        let render = voices.advance_modulation(BLOCK_SIZE);
+        voices.render_voices(&mut expected, 0, &render);
+        expected.iter_mut().for_each(|s| *s *= 0.5);
+        assert_eq!(render_block(&mut mixed), expected);
+    }
+
+    #[test]
+    fn master_gain_glides_to_a_new_level() {
+        let (mut turned_down, mut reference) = (engine(), engine());
+        for engine in [&mut turned_down, &mut reference] {
+            engine.note_on(A4_NOTE, 1.0);
+            render_block(engine);
+        }
+
+        turned_down.set_param(PARAM_GAIN_ID, 0.0);
+        let (block, reference) = (render_block(&mut turned_down), render_block(&mut reference));
+        // No sudden drop: each sample's gain is a small step from the last.
+        let gains: Vec<_> = block
+            .iter()
+            .zip(&reference)
+            .filter(|(_, reference)| reference.abs() > 1e-3)
+            .map(|(sample, reference)| sample / reference)
+            .collect();
+        assert!(gains[0] > 0.9);
+        assert!(gains.windows(2).all(|pair| (pair[1] - pair[0]).abs() < 0.01));
+        assert_eq!(peak(&block[BLOCK_SIZE / 2..]), 0.0);
+    }
+
+    #[test]
+    fn key_to_pan_only_leaves_a_side_signal_when_on() {
+        let mut engine = engine();
+        engine.note_on(84, 1.0);
+        render_block(&mut engine);
+        assert!(engine.side(BLOCK_SIZE).is_none());
+
+        engine.set_param(PARAM_KEY_TO_PAN_ID, 1.0);
+        engine.note_on(36, 1.0);
+        render_block(&mut engine);
+        let side = engine.side(BLOCK_SIZE).unwrap();
+        assert!(peak(side) > 0.0);
+    }
+}
@@ -0,0 +1,212 @@
+/// Envelope modes, indexed by the envelope mode param.
+pub const ENV_MODE_NAMES: &[&str] = &["ADSR", "Gate"];
+
+pub const ENV_MODE_GATE: usize = 1;
+
+/// Where an [`Envelope`] is in its run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Stage {
+    #[default]
+    Idle,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Segment times in seconds, plus the sustain level (0.0 to 1.0).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EnvelopeSettings {
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+    /// Runs attack, hold and decay to the end regardless of note-off.
+    pub one_shot: bool,
+    /// Goes back to the attack at the end of the decay while the note is held, so the
+    /// envelope cycles like an LFO.
+    pub looping: bool,
+}
+
+impl EnvelopeSettings {
+    pub fn adsr(attack: f32, decay: f32, sustain: f32, release: f32) -> Self {
+        Self { attack, decay, sustain, release, ..Self::default() }
+    }
+
+    /// Attack-hold-decay with no sustain, for plucks and one-shot percussion.
+    pub fn gate(attack: f32, hold: f32, decay: f32) -> Self {
+        Self { attack, hold, decay, one_shot: true, ..Self::default() }
+    }
+
+    pub fn looped(self, looping: bool) -> Self {
+        Self { looping, ..self }
+    }
+}
+
+/// Linear-segment envelope generator, stepped once per sample.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Envelope {
+    stage: Stage,
+    level: f32,
+    /// Seconds left in the hold stage.
+    hold_left: f32,
+    settings: EnvelopeSettings,
+}
+
+impl Envelope {
+    /// Restarts the envelope from the top of the attack, keeping the current level so
+    /// retriggering a sounding voice doesn't click.
+    pub fn trigger(&mut self, settings: EnvelopeSettings) {
+        self.settings = settings;
+        self.stage = Stage::Attack;
+    }
+
+    /// Starts the release and stops any looping. One-shot envelopes finish their current
+    /// cycle instead.
+    pub fn release(&mut self) {
+        self.settings.looping = false;
+        if self.stage != Stage::Idle && !self.settings.one_shot {
+            self.stage = Stage::Release;
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    #[cfg(test)]
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// The level the last [`Envelope::next`] returned.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// Advances one sample and returns the new level.
+    pub fn next(&mut self, sample_rate: f32) -> f32 {
+        let EnvelopeSettings { attack, hold, decay, release, .. } = self.settings;
+        let sustain = if self.settings.one_shot { 0.0 } else { self.settings.sustain };
+
+        match self.stage {
+            Stage::Idle => self.level = 0.0,
+            Stage::Attack => {
+                self.level += step(1.0, attack, sample_rate);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.hold_left = hold;
+                    self.stage = if hold > 0.0 { Stage::Hold } else { Stage::Decay };
+                }
+            }
+            Stage::Hold => {
+                self.hold_left -= 1.0 / sample_rate;
+                if self.hold_left <= 0.0 {
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= step(1.0 - sustain, decay, sample_rate);
+                if self.level <= sustain {
+                    self.level = sustain;
+                    // The attack picks up from the sustain level, so the loop has no step.
+                    self.stage = if self.settings.looping {
+                        Stage::Attack
+                    } else if sustain > 0.0 {
+                        Stage::Sustain
+                    } else {
+                        Stage::Idle
+                    };
+                }
+            }
+            Stage::Sustain => self.level = sustain,
+            Stage::Release => {
+                self.level -= step(1.0, release, sample_rate);
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+        self.level
+    }
+}
+
+/// Per-sample change that covers `distance` in `seconds`; zero-length segments jump.
+fn step(distance: f32, seconds: f32, sample_rate: f32) -> f32 {
+    if seconds <= 0.0 {
+        f32::INFINITY
+    } else {
+        distance / (seconds * sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    fn run(env: &mut Envelope, samples: usize) -> f32 {
+        (0..samples).fold(0.0, |_, _| env.next(SAMPLE_RATE))
+    }
+
+    #[test]
+    fn decays_to_idle_without_sustain() {
+        let mut env = Envelope::default();
+        env.trigger(EnvelopeSettings::adsr(0.0, 0.1, 0.0, 0.0));
+
+        assert_eq!(env.next(SAMPLE_RATE), 1.0);
+        run(&mut env, 101);
+        assert!(env.is_idle());
+        assert_eq!(env.next(SAMPLE_RATE), 0.0);
+    }
+
+    #[test]
+    fn adsr_holds_sustain_until_release() {
+        let mut env = Envelope::default();
+        env.trigger(EnvelopeSettings::adsr(0.01, 0.01, 0.5, 0.01));
+
+        assert_eq!(run(&mut env, 1000), 0.5);
+        env.release();
+        run(&mut env, 11);
+        assert!(env.is_idle());
+    }
+
+    #[test]
+    fn gate_ignores_release_and_runs_its_course() {
+        let mut env = Envelope::default();
+        env.trigger(EnvelopeSettings::gate(0.0, 0.05, 0.05));
+
+        run(&mut env, 10);
+        env.release();
+        // Still holding at full level well after the note-off.
+        assert_eq!(run(&mut env, 30), 1.0);
+        run(&mut env, 80);
+        assert!(env.is_idle());
+    }
+
+    #[test]
+    fn looping_cycles_without_jumps_until_release() {
+        let mut env = Envelope::default();
+        env.trigger(EnvelopeSettings::adsr(0.01, 0.01, 0.25, 0.01).looped(true));
+
+        let mut last = env.next(SAMPLE_RATE);
+        let mut peaks = 0;
+        for _ in 0..200 {
+            let level = env.next(SAMPLE_RATE);
+            assert!((level - last).abs() <= 0.1 + 1e-6);
+            if level == 1.0 {
+                peaks += 1;
+            }
+            last = level;
+        }
+        assert!(peaks > 5);
+
+        env.release();
+        run(&mut env, 11);
+        assert!(env.is_idle());
+    }
+}
@@ -0,0 +1,95 @@
+use std::f32::consts::PI;
+
+use crate::denormals::flush_denormal;
+use crate::sample::Sample;
+
+/// Cutoff range of the filter. At the top, with no resonance, it's left out altogether.
+pub const MIN_CUTOFF: f32 = 20.0; // Hz
+pub const MAX_CUTOFF: f32 = 20_000.0; // Hz
+/// Keeps the cutoff clear of Nyquist, where the filter stops behaving.
+const MAX_CUTOFF_RATIO: f32 = 0.45;
+/// Damping at full resonance: close to self-oscillation without getting there.
+const MIN_DAMPING: f32 = 0.05;
+
+/// Coefficients for one cutoff and resonance, worked out once a block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterCoefficients {
+    a1: f32,
+    a2: f32,
+    a3: f32,
+}
+
+impl FilterCoefficients {
+    /// `resonance` runs from 0.0 (a gentle 12 dB/octave slope) to 1.0 (a sharp peak at the
+    /// cutoff).
+    pub fn new(cutoff: f32, resonance: f32, sample_rate: f32) -> Self {
+        let cutoff = cutoff.clamp(MIN_CUTOFF, sample_rate * MAX_CUTOFF_RATIO);
+        let g = (PI * cutoff / sample_rate).tan();
+        let k = 2.0 - (2.0 - MIN_DAMPING) * resonance.clamp(0.0, 1.0);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        Self { a1, a2, a3: g * a2 }
+    }
+
+    /// Coefficients for the params, or `None` when the filter is fully open and should be
+    /// skipped.
+    pub fn for_params(cutoff: f32, resonance: f32, sample_rate: f32) -> Option<Self> {
+        (cutoff < MAX_CUTOFF || resonance > 0.0).then(|| Self::new(cutoff, resonance, sample_rate))
+    }
+}
+
+/// Resonant lowpass: a state-variable filter in its trapezoidal form, which stays stable
+/// while the cutoff moves.
+#[derive(Clone, Default)]
+pub struct LowpassFilter {
+    ic1: Sample,
+    ic2: Sample,
+}
+
+impl LowpassFilter {
+    /// Forgets the last note's state.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn process(&mut self, input: Sample, coefficients: &FilterCoefficients) -> Sample {
+        let a1 = Sample::from(coefficients.a1);
+        let a2 = Sample::from(coefficients.a2);
+        let a3 = Sample::from(coefficients.a3);
+        let v3 = input - self.ic2;
+        let v1 = a1 * self.ic1 + a2 * v3;
+        let v2 = self.ic2 + a2 * self.ic1 + a3 * v3;
+        self.ic1 = flush_denormal(2.0 * v1 - self.ic1);
+        self.ic2 = flush_denormal(2.0 * v2 - self.ic2);
+        v2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Peak output level for a unit sine at `frequency`, once the filter has settled.
+    fn gain_at(frequency: f32, coefficients: &FilterCoefficients) -> Sample {
+        let mut filter = LowpassFilter::default();
+        let sine = |n: usize| Sample::from((2.0 * PI * frequency * n as f32 / 48_000.0).sin());
+        let output = (0..9600).map(|n| filter.process(sine(n), coefficients).abs());
+        output.skip(4800).fold(0.0, Sample::max)
+    }
+
+    #[test]
+    fn passes_the_lows_and_cuts_the_highs() {
+        let coefficients = FilterCoefficients::new(1000.0, 0.0, 48_000.0);
+        assert!((gain_at(100.0, &coefficients) - 1.0).abs() < 0.01);
+        // Two octaves and a bit up, 12 dB/octave takes it down past -24 dB.
+        assert!(gain_at(5000.0, &coefficients) < 0.06);
+    }
+
+    #[test]
+    fn resonance_peaks_at_the_cutoff() {
+        let flat = FilterCoefficients::new(1000.0, 0.0, 48_000.0);
+        let resonant = FilterCoefficients::new(1000.0, 1.0, 48_000.0);
+        assert!(gain_at(1000.0, &resonant) > 10.0 * gain_at(1000.0, &flat));
+        assert_eq!(FilterCoefficients::for_params(MAX_CUTOFF, 0.0, 48_000.0), None);
+    }
+}
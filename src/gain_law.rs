@@ -0,0 +1,65 @@
+/// Curves from the gain param's 0.0 to 1.0 to an amplitude, indexed by the gain law param.
+pub const GAIN_LAW_NAMES: &[&str] = &["Linear", "Decibels", "Squared"];
+
+const GAIN_LAW_DECIBELS: usize = 1;
+const GAIN_LAW_SQUARED: usize = 2;
+
+/// Range of the decibel law: the bottom of the fader, short of silence, is this far down.
+const DECIBEL_RANGE: f32 = 60.0;
+
+/// The amplitude `gain` stands for under `law`. Every law maps 0.0 to silence and 1.0 to
+/// unity; they differ in how much of the travel goes to quiet levels. The param itself
+/// stays linear, so automation lanes read the same whichever law is chosen.
+pub fn gain_amplitude(law: usize, gain: f32) -> f32 {
+    let gain = gain.clamp(0.0, 1.0);
+    match law {
+        GAIN_LAW_DECIBELS if gain > 0.0 => 10.0f32.powf((gain - 1.0) * DECIBEL_RANGE / 20.0),
+        GAIN_LAW_DECIBELS => 0.0,
+        GAIN_LAW_SQUARED => gain * gain,
+        _ => gain,
+    }
+}
+
+/// The gain param that stands for `amplitude` under `law`: [`gain_amplitude`] the other
+/// way, clamped to the param's 0.0 to 1.0.
+pub fn gain_param(law: usize, amplitude: f32) -> f32 {
+    let amplitude = amplitude.max(0.0);
+    let gain = match law {
+        GAIN_LAW_DECIBELS if amplitude > 0.0 => 1.0 + 20.0 * amplitude.log10() / DECIBEL_RANGE,
+        GAIN_LAW_DECIBELS => 0.0,
+        GAIN_LAW_SQUARED => amplitude.sqrt(),
+        _ => amplitude,
+    };
+    gain.clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_law_runs_from_silence_to_unity() {
+        for law in 0..GAIN_LAW_NAMES.len() {
+            assert_eq!(gain_amplitude(law, 0.0), 0.0, "{}", GAIN_LAW_NAMES[law]);
+            assert!((gain_amplitude(law, 1.0) - 1.0).abs() < 1e-6, "{}", GAIN_LAW_NAMES[law]);
+        }
+    }
+
+    #[test]
+    fn decibel_law_is_even_in_decibels() {
+        let db = |gain: f32| 20.0 * gain_amplitude(GAIN_LAW_DECIBELS, gain).log10();
+        assert!((db(0.5) + 30.0).abs() < 1e-3);
+        assert!((db(0.75) + 15.0).abs() < 1e-3);
+        assert_eq!(gain_amplitude(GAIN_LAW_SQUARED, 0.5), 0.25);
+    }
+
+    #[test]
+    fn gain_param_undoes_every_law() {
+        for law in 0..GAIN_LAW_NAMES.len() {
+            for gain in [0.1, 0.5, 0.9] {
+                let back = gain_param(law, gain_amplitude(law, gain));
+                assert!((back - gain).abs() < 1e-5, "{} at {gain}", GAIN_LAW_NAMES[law]);
+            }
+        }
+    }
+}
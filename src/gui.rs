@@ -1,117 +1,1690 @@
-use std::sync::Arc;
-use std::sync::atomic::Ordering;
+use std::panic::AssertUnwindSafe;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use atomic_float::AtomicF32;
-use baseview::{Size, WindowHandle, WindowOpenOptions, WindowScalePolicy};
+use baseview::{PhySize, Size, WindowHandle, WindowOpenOptions, WindowScalePolicy};
 use clack_plugin::plugin::PluginError;
 use egui_baseview::{EguiWindow, GraphicsConfig, Queue};
-use egui_baseview::egui::{self, Context, Slider};
+use egui_baseview::egui::{self, Context};
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
-use crate::params::Params as CaveParams;
+mod keyboard;
+mod meter;
+mod spectrum;
+mod widgets;
 
-pub struct CaveGui {
-    pub parent: Option<RawWindowHandle>,
-    handle: Option<WindowHandle>,
+use crate::editor::WindowLayer;
+use crate::envelope::ENV_MODE_GATE;
+use crate::lfo::NUM_LFOS;
+use crate::load::ProcessLoad;
+use crate::meter::MeterReader;
+use crate::midi_activity::MidiActivity;
+use crate::midi_learn::MidiLearn;
+use crate::mod_matrix::MOD_SLOTS;
+use crate::note_queue::NoteQueue;
+use crate::param_indication::{AutomationState, ParamIndication, SharedIndications};
+use crate::patch;
+use crate::params::{
+    note_name, param_desc, ModRange, ParamDesc, Params as CaveParams, PARAM_ATTACK_ID,
+    PARAM_AUTO_PAN_DEPTH_ID, PARAM_AUTO_PAN_ON_ID, PARAM_AUTO_PAN_RATE_ID, PARAM_AUTO_PAN_SHAPE_ID,
+    PARAM_AUTO_PAN_SYNC_ID, PARAM_CHORD_TYPE_ID, PARAM_COMB_FEEDBACK_ID, PARAM_COMB_MIX_ID,
+    PARAM_COMB_ON_ID, PARAM_CUTOFF_ID, PARAM_DECAY_ID, PARAM_ENV_LOOP_ID, PARAM_ENV_MODE_ID,
+    PARAM_FX_MIX_ID, PARAM_GAIN_ID, PARAM_GAIN_LAW_ID, PARAM_HOLD_ID, PARAM_KEY_TO_PAN_ID,
+    PARAM_LFO_DELAY_ID, PARAM_LIMITER_ON_ID, PARAM_LFO_DEPTH_IDS, PARAM_LFO_RATE_IDS,
+    PARAM_LFO_RETRIGGER_IDS, PARAM_LFO_SHAPE_IDS, PARAM_LOWER_OCTAVE_ID, PARAM_MAX_VOICES_ID,
+    PARAM_MOD_AMOUNT_IDS, PARAM_MOD_DEST_IDS, PARAM_MOD_SOURCE_IDS, PARAM_NOISE_COLOR_ID,
+    PARAM_OVERSAMPLING_ID, PARAM_PITCH_ENV_AMOUNT_ID, PARAM_PITCH_ENV_DECAY_ID, PARAM_PLUCK_TONE_ID,
+    PARAM_RELEASE_ID, PARAM_RESONANCE_ID, PARAM_REVERB_DAMPING_ID, PARAM_REVERB_DECAY_ID,
+    PARAM_REVERB_FREEZE_ID, PARAM_REVERB_MIX_ID, PARAM_REVERB_ON_ID, PARAM_REVERB_PREDELAY_ID,
+    PARAM_SPLIT_MODE_ID, PARAM_SPLIT_POINT_ID, PARAM_SUSTAIN_ID, PARAM_UNISON_DETUNE_ID,
+    PARAM_UNISON_VOICES_ID, PARAM_UPPER_OCTAVE_ID, PARAM_VEL_TO_CUTOFF_ID, PARAM_WAVEFORM_ID,
+};
+use crate::scope::{trigger, ScopeBuffer, SCOPE_LEN};
+use crate::vis::VisChannel;
+use crate::track_info::SharedTrackInfo;
+use crate::transport::{SharedTransport, Transport};
+use crate::voice::{WAVEFORM_NOISE, WAVEFORM_PLUCK};
+use keyboard::Keyboard;
+use meter::Meter;
+use spectrum::Spectrum;
+use widgets::{readout, typed_value, Axis, Knob, StepSlider, XyPad};
+
+/// How long the header keeps warning after a voice was stolen.
+const VOICE_STEAL_WARNING: Duration = Duration::from_secs(2);
+
+/// Seconds the MIDI light takes to fade after an event.
+const MIDI_FLASH: f32 = 0.3;
+const MIDI_LED_RADIUS: f32 = 5.0;
+
+/// Redraw rate for the meter, scope and spectrum while they move; a setting in the
+/// header, editor-local like the keyboard octave.
+const ANIMATION_FPS: u32 = 30;
+const ANIMATION_FPS_RANGE: std::ops::RangeInclusive<u32> = 10..=60;
+/// How often an idle editor looks for changes itself, when the host gave us no timer to
+/// wake it with.
+const IDLE_POLL: Duration = Duration::from_millis(250);
+
+/// How long the about panel's redraw rate averages over.
+const REDRAW_WINDOW: Duration = Duration::from_secs(1);
+
+/// Seconds the header's DSP load readout takes to settle.
+const LOAD_SMOOTHING: f32 = 1.0;
+
+/// How long opening a floating editor waits for its window before telling the host it
+/// failed.
+const FLOATING_OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Samples across the oscilloscope, about 20 ms at 48 kHz.
+const SCOPE_WINDOW: usize = 1024;
+const SCOPE_HEIGHT: f32 = 80.0;
+
+/// Seconds the pointer rests on a control before its tooltip shows.
+const TOOLTIP_DELAY: f32 = 0.4;
+
+/// How long an inline entry stays red after a value it couldn't read.
+const INVALID_FLASH: Duration = Duration::from_millis(400);
+const INLINE_ENTRY_WIDTH: f32 = 80.0;
+const VALUE_BOX_WIDTH: f32 = 64.0;
+
+/// Something the editor needs the main thread to do on its behalf.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuiRequest {
+    /// Pop up the host's context menu for a param, at window-relative physical pixels.
+    ContextMenu { param_id: u32, x: i32, y: i32 },
+    /// The note thru toggle changed, so the note output port should appear or go away.
+    NotePortsChanged,
+    /// The layout wants the window at this size, in host units.
+    Resize(PhySize),
+}
+
+/// The current activation's audio settings, for the about panel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioInfo {
+    pub sample_rate: f64, // Hz
+    pub max_frames: u32,
+}
+
+/// Messages between the editor thread and the plugin's main thread. Neither side is
+/// real-time, so plain mutexes are fine here.
+#[derive(Default)]
+pub struct GuiBridge {
+    requests: Mutex<Vec<GuiRequest>>,
+    /// Set by the main thread when right-clicks should open the host's context menu.
+    pub host_menu: AtomicBool,
+    /// Param the host asked us to open a value entry for, via our own host-menu item.
+    value_entry: Mutex<Option<u32>>,
+    /// Armed by the split "Learn" button; the audio thread takes it on the next note-on.
+    pub split_learn: AtomicBool,
+    /// Set by the Panic button; the audio thread takes it at the start of the next block.
+    pub panic: AtomicBool,
+    /// CC bindings, armed from a control's right-click menu; the audio thread applies them.
+    pub midi_learn: MidiLearn,
+    /// Note and CC events from the host, for the header's MIDI light.
+    pub midi_activity: MidiActivity,
+    /// The host's transport as the audio thread last saw it, for the header.
+    pub transport: SharedTransport,
+    /// Whether the editor wants played notes echoed on a note output port.
+    pub note_thru: AtomicBool,
+    /// When the audio thread last had to steal a voice, for the header warning.
+    last_voice_steal: Mutex<Option<Instant>>,
+    /// Set by the main thread while its timer calls [`GuiBridge::wake_editor`].
+    pub editor_wakes: AtomicBool,
+    /// The open editor's context, to wake it from the main thread.
+    context: Mutex<Option<Context>>,
+    /// Param generation the editor last drew.
+    drawn_params: AtomicU32,
+    /// MIDI event count the editor last drew.
+    drawn_midi: AtomicU32,
+    /// Set while the editor is redrawing on its own for the meter, scope or spectrum.
+    animating: AtomicBool,
+    /// While activated, for the about panel.
+    audio_config: Mutex<Option<AudioInfo>>,
+    /// Windowing API and kind of window the host asked for, while the GUI exists.
+    gui_backend: Mutex<Option<String>>,
+    /// Version that saved the state the host last loaded, if it has loaded one.
+    loaded_state: Mutex<Option<String>>,
+    /// Name of the patch last loaded, by the host or pasted.
+    patch: Mutex<Option<String>>,
+}
+
+impl GuiBridge {
+    pub fn push(&self, request: GuiRequest) {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(request);
+        }
+    }
+
+    pub fn take_requests(&self) -> Vec<GuiRequest> {
+        self.requests.lock().map(|mut r| std::mem::take(&mut *r)).unwrap_or_default()
+    }
+
+    pub fn request_value_entry(&self, param_id: u32) {
+        if let Ok(mut entry) = self.value_entry.lock() {
+            *entry = Some(param_id);
+        }
+    }
+
+    pub fn voice_stolen(&self) {
+        if let Ok(mut at) = self.last_voice_steal.lock() {
+            *at = Some(Instant::now());
+        }
+        self.request_repaint();
+    }
+
+    /// Redraws the editor, if one is open, for something that changed behind its back.
+    pub fn request_repaint(&self) {
+        if let Some(ctx) = self.context.lock().ok().and_then(|ctx| ctx.clone()) {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Main thread, on a timer. The editor only redraws on input, or on its own while
+    /// something animates, so this wakes it for params the host moved, for MIDI coming in
+    /// and for sound reaching a settled meter.
+    pub fn wake_editor(&self, params: &CaveParams, meter: &Mutex<MeterReader>) {
+        let params_moved = params.generation() != self.drawn_params.load(Ordering::Relaxed);
+        let midi = self.midi_activity.events() != self.drawn_midi.load(Ordering::Relaxed);
+        let sound = !self.animating.load(Ordering::Relaxed)
+            && meter.lock().is_ok_and(|meter| meter.has_unread());
+        if params_moved || midi || sound {
+            self.request_repaint();
+        }
+    }
+
+    pub fn set_audio_config(&self, config: Option<AudioInfo>) {
+        if let Ok(mut audio_config) = self.audio_config.lock() {
+            *audio_config = config;
+        }
+    }
+
+    pub fn set_gui_backend(&self, backend: Option<String>) {
+        if let Ok(mut gui_backend) = self.gui_backend.lock() {
+            *gui_backend = backend;
+        }
+    }
+
+    /// What to quote in a bug report: the build, and how the host is running it.
+    pub fn set_loaded_state(&self, version: Option<String>) {
+        if let Ok(mut loaded_state) = self.loaded_state.lock() {
+            *loaded_state = version;
+        }
+    }
+
+    /// Records the patch just applied to `params`, or that it had no name. Edits count
+    /// from here.
+    pub fn set_patch_name(&self, name: Option<String>, params: &CaveParams) {
+        if let Ok(mut patch) = self.patch.lock() {
+            *patch = name;
+        }
+        params.clear_edited();
+        self.request_repaint();
+    }
+
+    pub fn patch_name(&self) -> Option<String> {
+        self.patch.lock().ok()?.clone()
+    }
+
+    /// The loaded patch's name, with an asterisk once the user has edited a param since.
+    pub fn patch_title(&self, params: &CaveParams) -> Option<String> {
+        let patch = self.patch.lock().ok()?;
+        let edited = if params.is_edited() { "*" } else { "" };
+        Some(format!("{}{edited}", patch.as_ref()?))
+    }
+
+    /// "Cave — <patch name>" once a patch is loaded, for floating windows.
+    fn window_title(&self, params: &CaveParams) -> String {
+        match self.patch_title(params) {
+            Some(patch) => format!("{} — {patch}", crate::PLUGIN_NAME),
+            None => crate::PLUGIN_NAME.to_string(),
+        }
+    }
+
+    fn about_text(&self) -> String {
+        let audio = match self.audio_config.lock().ok().and_then(|config| *config) {
+            Some(config) => {
+                format!("{} Hz, blocks up to {} frames", config.sample_rate, config.max_frames)
+            }
+            None => "not active".to_string(),
+        };
+        let backend = self.gui_backend.lock().ok().and_then(|backend| backend.clone());
+        let state = match self.loaded_state.lock().ok().and_then(|state| state.clone()) {
+            Some(version) => format!("saved by {version}"),
+            None => "defaults".to_string(),
+        };
+        format!(
+            "{} {} by {}\nAudio: {audio}\nGUI: {}\nState: {state}",
+            crate::PLUGIN_NAME,
+            crate::VERSION,
+            crate::VENDOR,
+            backend.as_deref().unwrap_or("unknown"),
+        )
+    }
+
+    fn set_context(&self, ctx: Option<Context>) {
+        if let Ok(mut context) = self.context.lock() {
+            *context = ctx;
+        }
+    }
+
+    fn recent_voice_steal(&self) -> bool {
+        let at = self.last_voice_steal.lock().ok().and_then(|at| *at);
+        at.is_some_and(|at| at.elapsed() < VOICE_STEAL_WARNING)
+    }
+
+    fn take_value_entry(&self) -> Option<u32> {
+        self.value_entry.lock().ok()?.take()
+    }
+}
+
+/// Frames the editor draws per second, for the about panel, so what an idle editor costs
+/// can be checked in a host: it should sit near zero with nothing playing or moving.
+#[derive(Debug, Clone, Copy)]
+struct RedrawRate {
+    since: Instant,
+    frames: u32,
+    /// Over the last full [`REDRAW_WINDOW`] or more, `None` before the first.
+    per_second: Option<f32>,
 }
 
-impl Default for CaveGui {
-    fn default() -> Self {
+impl RedrawRate {
+    fn new(now: Instant) -> Self {
+        Self { since: now, frames: 0, per_second: None }
+    }
+
+    /// Counts a frame. A gap with no frames at all counts toward the next average, so an
+    /// editor that sleeps for ten seconds reads 0.1.
+    fn frame(&mut self, now: Instant) {
+        self.frames += 1;
+        let elapsed = now.saturating_duration_since(self.since);
+        if elapsed >= REDRAW_WINDOW {
+            self.per_second = Some(self.frames as f32 / elapsed.as_secs_f32());
+            self.since = now;
+            self.frames = 0;
+        }
+    }
+}
+
+/// Everything the editor thread needs from the plugin, cloned into the window on open.
+#[derive(Clone)]
+pub struct GuiState {
+    pub params: Arc<CaveParams>,
+    pub track_info: Arc<SharedTrackInfo>,
+    pub indications: Arc<SharedIndications>,
+    pub bridge: Arc<GuiBridge>,
+    pub notes: Arc<NoteQueue>,
+    pub vis: Arc<VisChannel>,
+    pub load: Arc<ProcessLoad>,
+    // Editor-local state, reset every time the window opens.
+    value_entry: Option<ValueEntry>,
+    /// A text field standing in for a knob or slider, opened by ctrl-clicking either or
+    /// double-clicking a slider.
+    inline_entry: Option<InlineEntry>,
+    spectrum: Spectrum,
+    level_meter: Meter,
+    /// Which of the filter pad's params its right-click menu is for.
+    pad_menu_axis: Axis,
+    /// Octave shift of the on-screen keyboard.
+    keyboard_octave: i8,
+    /// Velocity of notes played on the computer keyboard.
+    typed_velocity: f32,
+    /// Whether the MIDI bindings window is showing.
+    midi_bindings_open: bool,
+    /// Whether the about panel is showing; the header's title and About button toggle it.
+    about_open: bool,
+    patch_paste: Option<PatchPaste>,
+    /// Redraw rate while the meter, scope or spectrum move.
+    animation_fps: u32,
+    /// Scope samples written as of the last frame, to tell whether new ones came in.
+    scope_written: usize,
+    /// The DSP load readout, smoothed over [`LOAD_SMOOTHING`].
+    shown_load: f32,
+    /// The MIDI light's brightness, 1.0 on an event and fading over [`MIDI_FLASH`].
+    midi_flash: f32,
+    /// Set when a knob drew modulation this frame, whose dot keeps moving.
+    modulation_shown: bool,
+    /// Whether knobs have a value box beside them.
+    value_boxes: bool,
+    /// The value box being typed in, and its text.
+    value_box_edit: Option<(u32, String)>,
+    /// Whether the scope and spectrum panels were open last frame.
+    open_panels: Option<[bool; 2]>,
+    /// Their heights in points, as last seen fully open.
+    panel_heights: [f32; 2],
+    redraws: RedrawRate,
+}
+
+impl GuiState {
+    pub fn new(
+        params: Arc<CaveParams>,
+        track_info: Arc<SharedTrackInfo>,
+        indications: Arc<SharedIndications>,
+        bridge: Arc<GuiBridge>,
+        notes: Arc<NoteQueue>,
+        vis: Arc<VisChannel>,
+        load: Arc<ProcessLoad>,
+    ) -> Self {
         Self {
-            parent: None,
-            handle: None,
+            params,
+            track_info,
+            indications,
+            bridge,
+            notes,
+            vis,
+            load,
+            value_entry: None,
+            inline_entry: None,
+            spectrum: Spectrum::default(),
+            level_meter: Meter::default(),
+            pad_menu_axis: Axis::X,
+            keyboard_octave: 0,
+            typed_velocity: keyboard::TYPED_VELOCITY,
+            midi_bindings_open: false,
+            about_open: false,
+            patch_paste: None,
+            animation_fps: ANIMATION_FPS,
+            scope_written: 0,
+            shown_load: 0.0,
+            midi_flash: 0.0,
+            modulation_shown: false,
+            value_boxes: false,
+            value_box_edit: None,
+            open_panels: None,
+            panel_heights: [SCOPE_HEIGHT, spectrum::PANEL_HEIGHT],
+            redraws: RedrawRate::new(Instant::now()),
         }
     }
 }
 
+/// An open "Enter value…" prompt.
+#[derive(Clone)]
+struct ValueEntry {
+    param_id: u32,
+    text: String,
+}
+
+/// A value being typed in place of a control.
+#[derive(Clone)]
+struct InlineEntry {
+    param_id: u32,
+    text: String,
+    /// Set on open and after rejected input, which takes the field's focus away.
+    wants_focus: bool,
+    /// When the last input that didn't parse was entered.
+    invalid_at: Option<Instant>,
+}
+
+/// An open "Paste patch" prompt, with why the last try didn't take.
+#[derive(Clone, Default)]
+struct PatchPaste {
+    text: String,
+    error: Option<String>,
+}
+
+// Sizes are in physical pixels throughout, which is what CLAP hosts on X11 and Windows
+// pass to set_size and expect from get_size; they also tell us the scale via set_scale.
+// macOS hosts work in logical points instead and leave the scale to the OS, so there the
+// sizes are points, the scale stays 1 and baseview follows the backing scale (2x on Retina).
+
+/// Host size units per egui point: physical pixels, or points on macOS.
+fn host_units_per_point(pixels_per_point: f32) -> f32 {
+    if cfg!(target_os = "macos") { 1.0 } else { pixels_per_point }
+}
+
+/// Why `parent` can't host the embedded editor on `os` (as in [`std::env::consts::OS`]),
+/// or `None` if it can.
+fn parent_handle_error(parent: &RawWindowHandle, os: &str) -> Option<&'static str> {
+    match (os, parent) {
+        ("linux", RawWindowHandle::Xlib(handle)) if handle.window == 0 => {
+            Some("X11 parent handle has no window")
+        }
+        ("linux", RawWindowHandle::Xcb(handle)) if handle.window == 0 => {
+            Some("X11 parent handle has no window")
+        }
+        ("linux", RawWindowHandle::Xlib(_) | RawWindowHandle::Xcb(_)) => None,
+        ("linux", RawWindowHandle::Wayland(_)) => {
+            Some("Got Wayland parent handle; embedded editor not supported in this build")
+        }
+        ("macos", RawWindowHandle::AppKit(handle)) if handle.ns_view.is_null() => {
+            Some("AppKit parent handle has no NSView")
+        }
+        // baseview adds the editor as a subview of the host's NSView.
+        ("macos", RawWindowHandle::AppKit(_)) => None,
+        ("windows", RawWindowHandle::Win32(handle)) if handle.hwnd.is_null() => {
+            Some("Win32 parent handle has no HWND")
+        }
+        ("windows", RawWindowHandle::Win32(_)) => None,
+        ("linux" | "macos" | "windows", _) => Some("Unsupported parent window handle type"),
+        _ => None,
+    }
+}
+
+/// Editor size at a scale of 1.
+pub const DEFAULT_SIZE: PhySize = PhySize { width: 400, height: 300 };
+/// Smallest editor size at a scale of 1.
+pub const MIN_SIZE: PhySize = PhySize { width: 320, height: 240 };
+
+pub fn scaled(size: PhySize, factor: f64) -> PhySize {
+    PhySize::new(
+        (size.width as f64 * factor).round() as u32,
+        (size.height as f64 * factor).round() as u32,
+    )
+}
+
+/// An open window's size and scale, shared between the main thread and the editor thread.
+struct WindowMetrics {
+    /// The authoritative size: what the window is, or is about to be once `requested` lands.
+    current: PhySize,
+    /// Size the host set while the window was open, applied by the editor thread.
+    requested: Option<PhySize>,
+    /// The host's scale factor. Until it sends one, baseview uses the system's.
+    scale: Option<f64>,
+}
+
+impl WindowMetrics {
+    fn scale(&self) -> f64 {
+        self.scale.unwrap_or(1.0)
+    }
+}
+
+/// A floating editor, running on its own thread until told to close or closed by the user.
+struct FloatingWindow {
+    close: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+enum WindowKind {
+    Embedded(WindowHandle),
+    Floating(FloatingWindow),
+}
+
+/// An open editor window.
+pub struct BaseviewWindow {
+    kind: WindowKind,
+    metrics: Arc<Mutex<WindowMetrics>>,
+}
+
+/// The host's window, for baseview to embed the editor in.
+struct ParentWindow(RawWindowHandle);
+
+unsafe impl HasRawWindowHandle for ParentWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.0
+    }
+}
+
+/// The editor UI, in baseview windows. [`crate::editor::Editor`] decides when they open.
+pub struct CaveGui {
+    state: GuiState,
+}
+
 impl CaveGui {
-    pub fn is_open(&self) -> bool {
-        self.handle.is_some()
+    pub fn new(state: GuiState) -> Self {
+        Self { state }
+    }
+
+    fn open_embedded(
+        &self,
+        parent: RawWindowHandle,
+        metrics: &Arc<Mutex<WindowMetrics>>,
+    ) -> Result<WindowKind, PluginError> {
+        eprintln!("[cave-gui] parent handle = {:?}", parent);
+
+        // Refuse handles we know won't work for embedded windows so the host gets an
+        // explicit error instead of timing out.
+        if let Some(error) = parent_handle_error(&parent, std::env::consts::OS) {
+            eprintln!("[cave-gui] rejecting parent handle: {error}");
+            return Err(PluginError::Message(error));
+        }
+
+        let settings = self.window_options(metrics);
+        let update = Self::updater(metrics.clone(), None);
+
+        eprintln!("[cave-gui] calling EguiWindow::open_parented(...)");
+
+        // If this returns but Bitwig still says “did not create its window”, then either:
+        // - baseview failed internally without panicking,
+        // - or the parent handle doesn't match what baseview expects at runtime.
+        // baseview panics when the window or its GL context can't be made. That mustn't
+        // unwind into the host, which should hear the editor failed rather than embed an
+        // empty view.
+        let handle = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            EguiWindow::open_parented(
+                &ParentWindow(parent),
+                settings,
+                GraphicsConfig::default(),
+                self.state.clone(),
+                Self::build,
+                update,
+            )
+        }))
+        .map_err(|_| PluginError::Message("The editor window could not be created"))?;
+
+        eprintln!("[cave-gui] open_parented returned, handle is set");
+        Ok(WindowKind::Embedded(handle))
     }
-    pub fn open(&mut self, params: Arc<CaveParams>) -> Result<(), PluginError> {
-        eprintln!("[cave-gui] open() called");
 
-        let Some(parent) = self.parent else {
-            eprintln!("[cave-gui] ERROR: parent is None (set_parent() likely never ran)");
-            return Err(PluginError::Message("No parent window provided"));
+    /// Opens the editor as a top-level window of its own. baseview only runs those
+    /// blocking, so it gets its own thread, and closing asks the editor to shut itself.
+    ///
+    /// Waits for the window to come up, so a failure reaches the host as an error rather
+    /// than as a window that never appears.
+    fn open_floating(
+        &self,
+        metrics: &Arc<Mutex<WindowMetrics>>,
+    ) -> Result<WindowKind, PluginError> {
+        let close = Arc::new(AtomicBool::new(false));
+        let settings = self.window_options(metrics);
+        let update = Self::updater(metrics.clone(), Some(close.clone()));
+        let state = self.state.clone();
+        let (opened, window_opened) = mpsc::channel();
+
+        let thread = std::thread::Builder::new()
+            .name("cave-editor".to_string())
+            .spawn(move || {
+                let build = move |egui_ctx: &Context, queue: &mut Queue, state: &mut GuiState| {
+                    Self::build(egui_ctx, queue, state);
+                    let _ = opened.send(());
+                };
+                // As with embedded windows, baseview panics when it can't make the window.
+                // That ends this thread and drops `opened`, which is all we need to know.
+                let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    EguiWindow::open_blocking(
+                        settings,
+                        GraphicsConfig::default(),
+                        state,
+                        build,
+                        update,
+                    )
+                }));
+                if result.is_err() {
+                    eprintln!("[cave-gui] the floating editor window could not be created");
+                }
+            })
+            .map_err(|_| PluginError::Message("Could not start the editor thread"))?;
+
+        if window_opened.recv_timeout(FLOATING_OPEN_TIMEOUT).is_err() {
+            // Should it turn up after all, it shuts on its first frame.
+            close.store(true, Ordering::Relaxed);
+            return Err(PluginError::Message("The editor window could not be created"));
+        }
+        eprintln!("[cave-gui] floating editor window opened");
+        Ok(WindowKind::Floating(FloatingWindow { close, thread }))
+    }
+
+    /// Runs once as the window opens, before the first update.
+    fn build(egui_ctx: &Context, _queue: &mut Queue, state: &mut GuiState) {
+        egui_ctx.style_mut(|style| style.interaction.tooltip_delay = TOOLTIP_DELAY);
+        state.bridge.set_context(Some(egui_ctx.clone()));
+    }
+
+    /// Window options at the window's initial size and scale. A floating window's title is
+    /// kept up to date by [`updater`](Self::updater) after that.
+    fn window_options(&self, metrics: &Mutex<WindowMetrics>) -> WindowOpenOptions {
+        let (size, scale) = match metrics.lock() {
+            Ok(metrics) => (metrics.current, metrics.scale),
+            Err(_) => (DEFAULT_SIZE, None),
         };
+        // baseview takes the initial size in logical units.
+        let logical = scale.unwrap_or(1.0);
+        WindowOpenOptions {
+            title: self.state.bridge.window_title(&self.state.params),
+            size: Size::new(size.width as f64 / logical, size.height as f64 / logical),
+            scale: scale.map_or(WindowScalePolicy::SystemScaleFactor, WindowScalePolicy::ScaleFactor),
+            gl_config: Some(Default::default()),
+        }
+    }
 
-        eprintln!("[cave-gui] parent handle = {:?}", parent);
+    /// The editor's per-frame update, the same for embedded and floating windows. Floating
+    /// ones pass the flag `close` sets to shut them.
+    fn updater(
+        metrics: Arc<Mutex<WindowMetrics>>,
+        close: Option<Arc<AtomicBool>>,
+    ) -> impl FnMut(&Context, &mut Queue, &mut GuiState) + Send + 'static {
+        // Last size the editor saw itself at, to tell resizes that happen to the window
+        // from frames where a requested resize hasn't landed yet.
+        let mut seen_size = None;
+        // Title a floating window was last given; it opens with the current one.
+        let mut shown_title = None;
+
+        move |egui_ctx: &Context, queue: &mut Queue, state: &mut GuiState| {
+            if close.as_ref().is_some_and(|close| close.load(Ordering::Relaxed)) {
+                queue.close_window();
+                return;
+            }
+            state.redraws.frame(Instant::now());
+            if close.is_some() {
+                let title = state.bridge.window_title(&state.params);
+                if shown_title.as_ref() != Some(&title) {
+                    shown_title = Some(title.clone());
+                    egui_ctx.send_viewport_cmd(egui::ViewportCommand::Title(title));
+                }
+            }
+            // Read before drawing, so a change that lands mid-frame still wakes the next one.
+            let params_generation = state.params.generation();
+            state.bridge.drawn_params.store(params_generation, Ordering::Relaxed);
+            let midi_events = state.bridge.midi_activity.events();
+            let drawn_midi = state.bridge.drawn_midi.swap(midi_events, Ordering::Relaxed);
+            let midi_moved = drawn_midi != midi_events;
 
-        // (Optional but helpful) refuse handle types we know won't work for embedded windows
-        // so Bitwig gets an explicit error instead of timing out.
-        #[cfg(target_os = "linux")]
-        {
-            match parent {
-                RawWindowHandle::Xlib(_) | RawWindowHandle::Xcb(_) => {
-                    eprintln!("[cave-gui] Linux: got X11 handle (good for open_parented)");
+            if let Ok(mut metrics) = metrics.lock() {
+                // Measured before a scale change, which only takes effect next frame.
+                let units_per_point = host_units_per_point(egui_ctx.pixels_per_point());
+                let actual = egui_ctx.viewport_rect().size() * units_per_point;
+                let actual = PhySize::new(actual.x.round() as u32, actual.y.round() as u32);
+                if seen_size.replace(actual) != Some(actual) {
+                    metrics.current = actual;
                 }
-                RawWindowHandle::Wayland(_) => {
-                    eprintln!("[cave-gui] Linux: got WAYLAND handle (embedded UI usually won't work)");
-                    // IMPORTANT: If Bitwig expects embedded, returning Err here will still mean “no GUI”,
-                    // but it makes the failure explicit and prevents false-success.
-                    return Err(PluginError::Message(
-                        "Got Wayland parent handle; embedded editor not supported in this build",
-                    ));
+
+                if let Some(scale) = metrics.scale.map(|scale| scale as f32) {
+                    if egui_ctx.pixels_per_point() != scale {
+                        egui_ctx.set_pixels_per_point(scale);
+                    }
                 }
-                other => {
-                    eprintln!("[cave-gui] Linux: unsupported parent handle: {:?}", other);
-                    return Err(PluginError::Message(
-                        "Unsupported parent window handle type",
-                    ));
+                if let Some(size) = metrics.requested.take() {
+                    // baseview resizes in physical pixels.
+                    let pixels_per_unit = egui_ctx.pixels_per_point() / units_per_point;
+                    queue.resize(scaled(size, pixels_per_unit as f64));
                 }
             }
+
+            let track = state.track_info.lock().ok().and_then(|info| info.clone());
+            let track_color = track.as_ref().and_then(|info| info.color);
+
+            let mut watching = false;
+            let mut frame = egui::Frame::central_panel(&egui_ctx.style());
+            if let Some(color) = track_color {
+                frame = frame.fill(Self::track_tint(frame.fill, color));
+            }
+
+            egui::TopBottomPanel::bottom("keyboard").show(egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    keyboard::octave_controls(ui, &mut state.keyboard_octave, &state.notes);
+                    ui.separator();
+                    keyboard::velocity_control(ui, &mut state.typed_velocity);
+                });
+                let octave = state.keyboard_octave;
+                ui.add(Keyboard::new(&state.notes, octave, state.typed_velocity));
+            });
+
+            egui::CentralPanel::default().frame(frame).show(egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let title = match state.bridge.patch_title(&state.params) {
+                        Some(_) => state.bridge.window_title(&state.params),
+                        None => "Cave Synth".to_string(),
+                    };
+                    let title = egui::Label::new(egui::RichText::new(title).heading());
+                    if ui.add(title.sense(egui::Sense::click())).on_hover_text("About").clicked() {
+                        state.about_open = !state.about_open;
+                    }
+                    Self::load_readout(ui, state);
+                    Self::midi_light(ui, state, midi_moved);
+                    Self::transport_readout(ui, &state.bridge.transport.load());
+                    if state.bridge.recent_voice_steal() {
+                        ui.colored_label(ui.visuals().warn_fg_color, "Voice pool full");
+                    }
+                    if let Some(name) = track.as_ref().and_then(|info| info.name.as_deref()) {
+                        Self::track_label(ui, name, track_color);
+                    }
+                    if ui.button("Copy patch").clicked() {
+                        let name = state.bridge.patch_name();
+                        ui.ctx().copy_text(patch::to_text(&state.params, name.as_deref()));
+                    }
+                    if ui.button("Paste patch").clicked() {
+                        state.patch_paste = Some(PatchPaste::default());
+                    }
+                    if ui.button("Panic").on_hover_text("Silence every voice at once").clicked() {
+                        state.bridge.panic.store(true, Ordering::Relaxed);
+                    }
+                    ui.toggle_value(&mut state.midi_bindings_open, "MIDI");
+                    ui.toggle_value(&mut state.value_boxes, "Values")
+                        .on_hover_text("Type exact values beside the knobs");
+                    ui.toggle_value(&mut state.about_open, "About");
+                    ui.separator();
+                    if let Ok(mut meter) = state.vis.meter.lock() {
+                        state.level_meter.show(ui, &mut meter);
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut state.animation_fps)
+                            .range(ANIMATION_FPS_RANGE)
+                            .suffix(" fps"),
+                    )
+                    .on_hover_text("How often the meter, scope and spectrum redraw");
+                });
+                // With both collapsed, the audio thread stops feeding them too.
+                let scope = egui::CollapsingHeader::new("Scope")
+                    .show(ui, |ui| Self::scope_view(ui, &state.vis.scope));
+                let spectrum = egui::CollapsingHeader::new("Spectrum")
+                    .show(ui, |ui| state.spectrum.show(ui, &state.vis.scope));
+                watching = scope.body_returned.is_some() || spectrum.body_returned.is_some();
+                state.vis.scope.set_watching(watching);
+                let size = seen_size.unwrap_or(DEFAULT_SIZE);
+                Self::fit_panels(ui.ctx(), state, [&scope, &spectrum], size);
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let master = [PARAM_GAIN_ID, PARAM_GAIN_LAW_ID, PARAM_LIMITER_ON_ID];
+                    Self::control_row(ui, state, &master);
+                    Self::param_control(ui, state, PARAM_CHORD_TYPE_ID);
+                    Self::param_control(ui, state, PARAM_MAX_VOICES_ID);
+                    Self::param_control(ui, state, PARAM_OVERSAMPLING_ID);
+                    Self::param_control(ui, state, PARAM_KEY_TO_PAN_ID);
+                    Self::param_control(ui, state, PARAM_WAVEFORM_ID);
+                    let pitch_env = [PARAM_PITCH_ENV_AMOUNT_ID, PARAM_PITCH_ENV_DECAY_ID];
+                    let oscillator = match state.params.waveform() {
+                        WAVEFORM_PLUCK => Some(PARAM_PLUCK_TONE_ID),
+                        WAVEFORM_NOISE => Some(PARAM_NOISE_COLOR_ID),
+                        _ => None,
+                    };
+                    if let Some(id) = oscillator {
+                        Self::control_row(ui, state, &[id, pitch_env[0], pitch_env[1]]);
+                    } else {
+                        // Unison only thickens the square wave.
+                        let unison = [PARAM_UNISON_VOICES_ID, PARAM_UNISON_DETUNE_ID];
+                        let row = [unison[0], unison[1], pitch_env[0], pitch_env[1]];
+                        Self::control_row(ui, state, &row);
+                    }
+                    egui::CollapsingHeader::new("Filter").show(ui, |ui| {
+                        Self::filter_pad(ui, state);
+                        Self::param_control(ui, state, PARAM_VEL_TO_CUTOFF_ID);
+                    });
+                    ui.separator();
+                    Self::envelope_controls(ui, state);
+                    ui.separator();
+                    Self::param_control(ui, state, PARAM_SPLIT_MODE_ID);
+                    ui.horizontal(|ui| {
+                        Self::param_control(ui, state, PARAM_SPLIT_POINT_ID);
+                        Self::split_learn_button(ui, &state.bridge);
+                    });
+                    Self::param_control(ui, state, PARAM_LOWER_OCTAVE_ID);
+                    Self::param_control(ui, state, PARAM_UPPER_OCTAVE_ID);
+                    Self::note_thru_toggle(ui, &state.bridge);
+                    ui.separator();
+                    Self::modulation_controls(ui, state);
+                    Self::effect_controls(ui, state);
+                });
+            });
+
+            if let Some(param_id) = state.bridge.take_value_entry() {
+                Self::open_value_entry(state, param_id);
+            }
+            Self::value_entry_window(egui_ctx, state);
+            Self::midi_bindings_window(egui_ctx, state);
+            Self::about_window(egui_ctx, state);
+            Self::patch_paste_window(egui_ctx, state);
+            Self::schedule_repaint(egui_ctx, state, watching);
         }
+    }
 
-        let settings = WindowOpenOptions {
-            title: "Cave".to_string(),
-            size: Size::new(400.0, 300.0),
-            scale: WindowScalePolicy::SystemScaleFactor,
-            gl_config: Some(Default::default()),
+    /// Asks for the next frame only when something on screen will move without input:
+    /// the meter falling or the scope and spectrum taking in new samples, at the animation
+    /// rate, and the voice steal warning going away. Otherwise the editor sleeps until
+    /// input or [`GuiBridge::wake_editor`].
+    fn schedule_repaint(egui_ctx: &Context, state: &mut GuiState, watching: bool) {
+        let written = state.vis.scope.written();
+        let scope_moved = std::mem::replace(&mut state.scope_written, written) != written;
+        let scope_moved = watching && scope_moved;
+        // Playing, the transport readout moves on its own.
+        let playing = state.bridge.transport.load().playing;
+        let modulation_shown = std::mem::take(&mut state.modulation_shown);
+        let busy = state.load.voices() > 0 || playing || modulation_shown;
+        let flashing = state.midi_flash > 0.0;
+        let animating = scope_moved || busy || flashing || !state.level_meter.is_settled();
+        state.bridge.animating.store(animating, Ordering::Relaxed);
+        if animating {
+            egui_ctx.request_repaint_after(Duration::from_secs(1) / state.animation_fps.max(1));
+        }
+        if state.bridge.recent_voice_steal() {
+            egui_ctx.request_repaint_after(VOICE_STEAL_WARNING);
+        }
+        if !state.bridge.editor_wakes.load(Ordering::Relaxed) {
+            egui_ctx.request_repaint_after(IDLE_POLL);
+        }
+    }
+
+    /// Flashes as notes and CCs come in from the host, with the last note played beside it.
+    fn midi_light(ui: &mut egui::Ui, state: &mut GuiState, event: bool) {
+        let fade = ui.input(|i| i.stable_dt) / MIDI_FLASH;
+        state.midi_flash = if event { 1.0 } else { (state.midi_flash - fade).max(0.0) };
+
+        let size = egui::Vec2::splat(MIDI_LED_RADIUS * 2.0);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let lit = ui.visuals().selection.bg_fill;
+        let fill = ui.visuals().extreme_bg_color.lerp_to_gamma(lit, state.midi_flash);
+        ui.painter().circle(rect.center(), MIDI_LED_RADIUS, fill, ui.visuals().window_stroke);
+        response.on_hover_text("MIDI in: flashes for each note or CC from the host");
+
+        let last = state.bridge.midi_activity.last_note();
+        let last = last.map_or("—".to_string(), |(key, velocity)| {
+            format!("{} {velocity}", note_name(key as i32))
+        });
+        ui.weak(last).on_hover_text("Last note played, and its velocity");
+    }
+
+    /// The host's tempo and time signature, and the bar and beat while it plays.
+    fn transport_readout(ui: &mut egui::Ui, transport: &Transport) {
+        let tempo = transport.tempo.map_or("— BPM".to_string(), |tempo| format!("{tempo:.1} BPM"));
+        let (numerator, denominator) = transport.time_signature;
+        let mut text = format!("{tempo} {numerator}/{denominator}");
+        if let (true, Some((bar, beat))) = (transport.playing, transport.bar_and_beat()) {
+            let state = if transport.recording { "●" } else { "▶" };
+            text += &format!(" {state} {bar}.{beat}");
+        }
+        ui.weak(text).on_hover_text("Host tempo and time signature, and the bar and beat playing");
+    }
+
+    /// Voices sounding and the audio thread's load, which is smoothed since it jumps about
+    /// from block to block.
+    fn load_readout(ui: &mut egui::Ui, state: &mut GuiState) {
+        let dt = ui.input(|i| i.stable_dt);
+        let follow = 1.0 - (-dt / LOAD_SMOOTHING).exp();
+        state.shown_load += (state.load.load() - state.shown_load) * follow;
+        let voices = state.load.voices();
+        ui.label(format!("{voices} voices, {:.0}% DSP", state.shown_load * 100.0))
+            .on_hover_text("Voices sounding, and the share of each block's time spent on it");
+    }
+
+    /// Asks for more room as the scope or spectrum opens and gives it back as it closes.
+    /// If the host won't resize, the controls below them scroll instead.
+    fn fit_panels(
+        ctx: &Context,
+        state: &mut GuiState,
+        panels: [&egui::CollapsingResponse<()>; 2],
+        size: PhySize,
+    ) {
+        let spacing = ctx.style().spacing.item_spacing.y;
+        for (height, panel) in state.panel_heights.iter_mut().zip(panels) {
+            if let Some(body) = panel.body_response.as_ref().filter(|_| panel.openness >= 1.0) {
+                *height = body.rect.height() + spacing;
+            }
+        }
+        let open = panels.map(|panel| panel.openness > 0.0);
+        let Some(was_open) = state.open_panels.replace(open) else { return };
+
+        let mut grow = 0.0;
+        for ((now, before), height) in open.into_iter().zip(was_open).zip(state.panel_heights) {
+            if now != before {
+                grow += if now { height } else { -height };
+            }
+        }
+        if grow != 0.0 {
+            let grow = grow * host_units_per_point(ctx.pixels_per_point());
+            let height = (size.height as f32 + grow).round().max(0.0) as u32;
+            state.bridge.push(GuiRequest::Resize(PhySize::new(size.width, height)));
+        }
+    }
+
+    /// The output waveform, lined up on a rising zero crossing.
+    fn scope_view(ui: &mut egui::Ui, scope: &ScopeBuffer) {
+        let mut samples = vec![0.0; SCOPE_LEN];
+        scope.read(&mut samples);
+        let start = trigger(&samples, SCOPE_WINDOW);
+        let shown = &samples[start..start + SCOPE_WINDOW];
+
+        let size = egui::vec2(ui.available_width(), SCOPE_HEIGHT);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let step = rect.width() / (SCOPE_WINDOW - 1) as f32;
+        let points = shown
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let y = rect.center().y - s.clamp(-1.0, 1.0) * rect.height() / 2.0;
+                egui::pos2(rect.left() + i as f32 * step, y)
+            })
+            .collect();
+        painter.line(points, egui::Stroke::new(1.5, ui.visuals().selection.bg_fill));
+    }
+
+    /// Blends a bit of the host's track color into the panel background.
+    fn track_tint(base: egui::Color32, [r, g, b]: [u8; 3]) -> egui::Color32 {
+        base.lerp_to_gamma(egui::Color32::from_rgb(r, g, b), 0.2)
+    }
+
+    /// Track name next to a swatch of the track color, right-aligned in the header.
+    fn track_label(ui: &mut egui::Ui, name: &str, color: Option<[u8; 3]>) {
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(name);
+            if let Some([r, g, b]) = color {
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+                ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+            }
+        });
+    }
+
+    /// One row per parameter: host indication marker, then a slider or a drop-down.
+    fn param_control(ui: &mut egui::Ui, state: &mut GuiState, id: u32) {
+        let params = state.params.clone();
+        let (Some(desc), Some(property)) = (param_desc(id), params.atomic(id)) else { return };
+
+        if state.inline_entry.as_ref().is_some_and(|entry| entry.param_id == id) {
+            ui.horizontal(|ui| {
+                Self::indicator(ui, state.indications.get(id));
+                Self::inline_entry(ui, state, desc);
+            });
+            return;
+        }
+
+        let response = ui
+            .horizontal(|ui| {
+                Self::indicator(ui, state.indications.get(id));
+                let response = if desc.is_toggle() {
+                    Self::toggle(ui, property, desc.name)
+                } else if !desc.labels.is_empty() {
+                    Self::choice(ui, property, desc)
+                } else if desc.is_stepped() {
+                    Self::slider(ui, property, desc)
+                } else {
+                    let modulation = params.modulation(id);
+                    state.modulation_shown |= modulation.is_some();
+                    let response = Self::knob(ui, property, desc, modulation);
+                    if state.value_boxes {
+                        Self::value_box(ui, &mut state.value_box_edit, &params, desc);
+                    }
+                    response
+                };
+                Self::midi_badge(ui, &state.bridge.midi_learn, id, &response);
+                response
+            })
+            .inner;
+        let response = Self::param_tooltip(response, &params, &[desc]);
+        // A drag is one gesture from grab to release. Anything else (a click, a key, a
+        // wheel notch) is a gesture of its own.
+        let dragging = response.dragged() || response.drag_stopped();
+        if response.drag_started() {
+            params.begin_gesture(id);
+        }
+        if response.changed() {
+            if dragging {
+                params.mark_changed(id);
+            } else {
+                params.begin_gesture(id);
+                params.mark_changed(id);
+                params.end_gesture(id);
+            }
+        }
+        if response.drag_stopped() {
+            params.end_gesture(id);
+        }
+        // Knobs and sliders take a typed value too; the rest only have a few to pick from.
+        // A knob's double-click resets it, so only ctrl-click types into one.
+        let typable = !desc.is_toggle() && desc.labels.is_empty();
+        let command_click = response.clicked() && ui.input(|i| i.modifiers.command);
+        let double_click = response.double_clicked() && desc.is_stepped();
+        if typable && (double_click || command_click) {
+            let text = readout(desc, property.load(Ordering::Relaxed));
+            let entry = InlineEntry { param_id: id, text, wants_focus: true, invalid_at: None };
+            state.inline_entry = Some(entry);
+        }
+        Self::param_menu(state, &response, id);
+    }
+
+    /// The open inline entry's text field. Enter commits the value as one gesture, escape
+    /// or clicking away cancels, and input that doesn't parse flashes the field and keeps
+    /// the old value.
+    fn inline_entry(ui: &mut egui::Ui, state: &mut GuiState, desc: &ParamDesc) {
+        let Some(entry) = state.inline_entry.as_mut() else { return };
+
+        let mut edit =
+            egui::TextEdit::singleline(&mut entry.text).desired_width(INLINE_ENTRY_WIDTH);
+        let flash = entry.invalid_at.and_then(|at| INVALID_FLASH.checked_sub(at.elapsed()));
+        if let Some(remaining) = flash {
+            edit = edit.background_color(ui.visuals().error_fg_color.gamma_multiply(0.4));
+            ui.ctx().request_repaint_after(remaining);
+        }
+        let response = ui.add(edit);
+        ui.label(desc.name);
+        if std::mem::take(&mut entry.wants_focus) {
+            response.request_focus();
+        }
+        if !response.lost_focus() {
+            return;
+        }
+
+        if !ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            state.inline_entry = None;
+        } else if let Some(value) = typed_value(desc, &entry.text) {
+            state.params.change_as_gesture(desc.id, value);
+            state.inline_entry = None;
+        } else {
+            entry.invalid_at = Some(Instant::now());
+            entry.wants_focus = true;
+        }
+    }
+
+    /// Text field beside a knob. It shows the value, knob moves included, while it isn't
+    /// being typed in. Enter or clicking away sets what was typed as one gesture, escape
+    /// drops it, and text that doesn't parse leaves the value alone.
+    fn value_box(
+        ui: &mut egui::Ui,
+        edit: &mut Option<(u32, String)>,
+        params: &CaveParams,
+        desc: &'static ParamDesc,
+    ) {
+        let mut text = match edit.take() {
+            Some((id, text)) if id == desc.id => text,
+            other => {
+                *edit = other;
+                readout(desc, params.value(desc.id).unwrap_or_default())
+            }
         };
+        let field = egui::TextEdit::singleline(&mut text).desired_width(VALUE_BOX_WIDTH);
+        let response = ui.add(field);
+        if response.has_focus() {
+            *edit = Some((desc.id, text));
+        } else if response.lost_focus() && !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            if let Some(value) = typed_value(desc, &text) {
+                params.change_as_gesture(desc.id, value);
+            }
+        }
+    }
 
-        eprintln!("[cave-gui] calling EguiWindow::open_parented(...)");
+    /// Name, description, value, default and range of each param a control sets, shown on
+    /// hovering it, except while anything is being dragged, where it would sit over what's
+    /// being adjusted.
+    fn param_tooltip(
+        response: egui::Response,
+        params: &CaveParams,
+        descs: &[&ParamDesc],
+    ) -> egui::Response {
+        if response.ctx.dragged_id().is_some() {
+            return response;
+        }
+        response.on_hover_ui(|ui| {
+            for (i, desc) in descs.iter().enumerate() {
+                if i > 0 {
+                    ui.separator();
+                }
+                ui.strong(desc.name);
+                ui.label(desc.description);
+                if let Some(value) = params.value(desc.id) {
+                    ui.label(format!("Value: {}", readout(desc, value)));
+                }
+                ui.weak(format!("Default: {}", readout(desc, desc.default as f32)));
+                // A choice's options are in its drop-down.
+                if desc.labels.is_empty() {
+                    let min = readout(desc, desc.min as f32);
+                    let max = readout(desc, desc.max as f32);
+                    ui.weak(format!("Range: {min} to {max}"));
+                }
+            }
+        })
+    }
 
-        // If this returns but Bitwig still says “did not create its window”, then either:
-        // - baseview failed internally without panicking,
-        // - or the parent handle doesn't match what baseview expects at runtime.
-        self.handle = Some(EguiWindow::open_parented(
-            self,
-            settings,
-            GraphicsConfig::default(),
-            params,
-            |_egui_ctx: &Context, _queue: &mut Queue, _state: &mut Arc<CaveParams>| {},
-            |egui_ctx: &Context, _queue: &mut Queue, state: &mut Arc<CaveParams>| {
-                egui::CentralPanel::default().show(egui_ctx, |ui| {
-                    ui.heading("Cave Synth");
-                    Self::slider(ui, &state.gain, "Gain");
+    /// Right-click menu for the param `id` on `response`'s control.
+    fn param_menu(state: &mut GuiState, response: &egui::Response, id: u32) {
+        let Some(desc) = param_desc(id) else { return };
+
+        // Hosts that can pop up their own menu get the right-click (our entries are added
+        // to it through the context-menu extension); otherwise we show ours in egui.
+        if state.bridge.host_menu.load(Ordering::Relaxed) {
+            if response.secondary_clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let ppp = response.ctx.pixels_per_point();
+                    state.bridge.push(GuiRequest::ContextMenu {
+                        param_id: id,
+                        x: (pos.x * ppp) as i32,
+                        y: (pos.y * ppp) as i32,
+                    });
+                }
+            }
+        } else {
+            response.context_menu(|ui| {
+                if ui.button("Reset to default").clicked() {
+                    state.params.change_as_gesture(id, desc.default as f32);
+                    ui.close();
+                }
+                if ui.button("Enter value…").clicked() {
+                    Self::open_value_entry(state, id);
+                    ui.close();
+                }
+                ui.separator();
+                let midi_learn = &state.bridge.midi_learn;
+                if midi_learn.learning() == Some(id) {
+                    if ui.button("Cancel MIDI Learn").clicked() {
+                        midi_learn.cancel_learn();
+                        ui.close();
+                    }
+                } else if ui.button("MIDI Learn").clicked() {
+                    midi_learn.learn(id);
+                    ui.close();
+                }
+                if let Some(cc) = midi_learn.cc_for(id) {
+                    if ui.button(format!("Forget CC {cc}")).clicked() {
+                        midi_learn.unbind(id);
+                        ui.close();
+                    }
+                }
+            });
+        }
+    }
+
+    /// Cutoff across, resonance up. A drag is one gesture on both params, so the host
+    /// records the sweep as a single edit.
+    fn filter_pad(ui: &mut egui::Ui, state: &mut GuiState) {
+        let ids = [PARAM_CUTOFF_ID, PARAM_RESONANCE_ID];
+        let (Some(x_desc), Some(y_desc)) = (param_desc(ids[0]), param_desc(ids[1])) else { return };
+        let params = state.params.clone();
+        let (old_cutoff, old_resonance) = (params.cutoff(), params.resonance());
+        let (mut cutoff, mut resonance) = (old_cutoff, old_resonance);
+
+        let response = ui.add(XyPad::new(&mut cutoff, x_desc, &mut resonance, y_desc));
+        let response = Self::param_tooltip(response, &params, &[x_desc, y_desc]);
+        // The pad only changes its values under a primary drag or a double-click. A drag is
+        // one gesture from grab to release; a double-click is a gesture of its own.
+        let dragging = response.dragged_by(egui::PointerButton::Primary)
+            || response.drag_stopped_by(egui::PointerButton::Primary);
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            ids.iter().for_each(|&id| params.begin_gesture(id));
+        }
+        if response.changed() {
+            if !dragging {
+                ids.iter().for_each(|&id| params.begin_gesture(id));
+            }
+            if cutoff != old_cutoff {
+                params.change(PARAM_CUTOFF_ID, cutoff);
+            }
+            if resonance != old_resonance {
+                params.change(PARAM_RESONANCE_ID, resonance);
+            }
+            if !dragging {
+                ids.iter().for_each(|&id| params.end_gesture(id));
+            }
+        }
+        if response.drag_stopped_by(egui::PointerButton::Primary) {
+            ids.iter().for_each(|&id| params.end_gesture(id));
+        }
+
+        if response.secondary_clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                state.pad_menu_axis = XyPad::nearer_axis(response.rect, pos);
+            }
+        }
+        let id = match state.pad_menu_axis {
+            Axis::X => PARAM_CUTOFF_ID,
+            Axis::Y => PARAM_RESONANCE_ID,
+        };
+        Self::param_menu(state, &response, id);
+    }
+
+    fn open_value_entry(state: &mut GuiState, param_id: u32) {
+        let (Some(desc), Some(value)) = (param_desc(param_id), state.params.value(param_id)) else { return };
+        state.value_entry = Some(ValueEntry { param_id, text: readout(desc, value) });
+    }
+
+    /// Small prompt for typing an exact value, parsed the same way as the host's text entry.
+    fn value_entry_window(ctx: &Context, state: &mut GuiState) {
+        let Some(entry) = state.value_entry.as_mut() else { return };
+        let Some(desc) = param_desc(entry.param_id) else { return };
+
+        let mut done = false;
+        egui::Window::new(format!("Enter value: {}", desc.name))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let edit = ui.text_edit_singleline(&mut entry.text);
+                edit.request_focus();
+                if edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    if let Some(value) = typed_value(desc, &entry.text) {
+                        state.params.change_as_gesture(entry.param_id, value);
+                    }
+                    done = true;
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    done = true;
+                }
+            });
+
+        if done {
+            state.value_entry = None;
+        }
+    }
+
+    /// Takes a patch pasted into its text box. Every param in it is reported to the host as
+    /// a change, as if set by hand; nothing changes unless the whole patch parses.
+    fn patch_paste_window(ctx: &Context, state: &mut GuiState) {
+        let Some(paste) = state.patch_paste.as_mut() else { return };
+
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new("Paste patch")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Paste a copied patch here:");
+                let edit = egui::TextEdit::multiline(&mut paste.text).desired_rows(6).code_editor();
+                ui.add(edit).request_focus();
+                if let Some(error) = &paste.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                if ui.button("Apply").clicked() {
+                    match patch::from_text(&paste.text) {
+                        Ok(values) => {
+                            for (id, value) in values {
+                                state.params.change_as_gesture(id, value);
+                            }
+                            let name = patch::name(&paste.text).unwrap_or("Pasted patch");
+                            state.bridge.set_patch_name(Some(name.to_string()), &state.params);
+                            done = true;
+                        }
+                        Err(error) => paste.error = Some(error.to_string()),
+                    }
+                }
+            });
+
+        if done || !open {
+            state.patch_paste = None;
+        }
+    }
+
+    /// Outlines a control waiting for its CC, or shows the CC it's bound to.
+    fn midi_badge(ui: &mut egui::Ui, midi_learn: &MidiLearn, id: u32, control: &egui::Response) {
+        if midi_learn.learning() == Some(id) {
+            let stroke = egui::Stroke::new(1.5, ui.visuals().warn_fg_color);
+            let rect = control.rect.expand(2.0);
+            ui.painter().rect_stroke(rect, 3.0, stroke, egui::StrokeKind::Outside);
+            ui.label(egui::RichText::new("Move a control…").small().color(stroke.color));
+        } else if let Some(cc) = midi_learn.cc_for(id) {
+            ui.label(egui::RichText::new(format!("CC {cc}")).small().weak())
+                .on_hover_text("MIDI CC bound to this control. Right-click to forget it.");
+        }
+    }
+
+    /// Version and build, and the audio and GUI setup, with a button to copy them all.
+    fn about_window(ctx: &Context, state: &mut GuiState) {
+        if !state.about_open {
+            return;
+        }
+        let text = state.bridge.about_text();
+        let redraws = state.redraws.per_second;
+        egui::Window::new("About Cave")
+            .open(&mut state.about_open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for line in text.lines() {
+                    ui.label(line);
+                }
+                ui.label(redraws.map_or("Redraws: counting".into(), |rate| {
+                    format!("Redraws: {rate:.1} per second")
+                }));
+                ui.hyperlink(crate::URL);
+                if ui.button("Copy").on_hover_text("For bug reports").clicked() {
+                    ui.ctx().copy_text(text.clone());
+                }
+            });
+    }
+
+    /// Every CC binding, each with a button to forget it, plus the last CC received so
+    /// it's easy to tell what a controller sends.
+    fn midi_bindings_window(ctx: &Context, state: &mut GuiState) {
+        let midi_learn = &state.bridge.midi_learn;
+        egui::Window::new("MIDI bindings")
+            .open(&mut state.midi_bindings_open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let last = midi_learn.last_cc().map_or("none".into(), |cc| format!("CC {cc}"));
+                ui.label(format!("Last received: {last}"));
+                if let Some(desc) = midi_learn.learning().and_then(param_desc) {
+                    ui.horizontal(|ui| {
+                        let warn = ui.visuals().warn_fg_color;
+                        ui.colored_label(warn, format!("Learning {}", desc.name));
+                        if ui.small_button("Cancel").clicked() {
+                            midi_learn.cancel_learn();
+                        }
+                    });
+                }
+                ui.separator();
+
+                let mut any = false;
+                egui::Grid::new("midi_bindings").striped(true).show(ui, |ui| {
+                    let bindings = midi_learn.bindings();
+                    for (cc, desc) in bindings.filter_map(|(cc, id)| Some((cc, param_desc(id)?))) {
+                        any = true;
+                        ui.label(format!("CC {cc}"));
+                        ui.label(desc.name);
+                        if ui.small_button("Forget").clicked() {
+                            midi_learn.unbind(desc.id);
+                        }
+                        ui.end_row();
+                    }
                 });
-            },
-        ));
+                if !any {
+                    ui.weak("No bindings yet: right-click a control and pick MIDI Learn.");
+                } else if ui.button("Clear all").clicked() {
+                    midi_learn.clear();
+                }
+            });
+    }
 
-        eprintln!("[cave-gui] open_parented returned, handle is set");
-        Ok(())
+    /// Dot in the host's mapping color, ring in the automation color. An overridden
+    /// automation lane gets a hollow ring so it stands out from one that's playing back.
+    fn indicator(ui: &mut egui::Ui, indication: ParamIndication) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+        let center = rect.center();
+        let painter = ui.painter();
+
+        if indication.automation != AutomationState::None {
+            let [r, g, b] = indication.automation_color.unwrap_or([0xe0, 0x6c, 0x3c]);
+            let color = egui::Color32::from_rgb(r, g, b);
+            let stroke = match indication.automation {
+                AutomationState::Overriding => egui::Stroke::new(1.0, color.gamma_multiply(0.5)),
+                _ => egui::Stroke::new(2.0, color),
+            };
+            painter.circle_stroke(center, 5.0, stroke);
+        }
+
+        if let Some([r, g, b]) = indication.mapping_color {
+            painter.circle_filled(center, 3.0, egui::Color32::from_rgb(r, g, b));
+        }
     }
 
-    pub fn close(&mut self) {
-        eprintln!("[cave-gui] close() called");
-        if let Some(handle) = self.handle.as_mut() {
-            handle.close();
+    /// Integer params: voice counts, octaves, the split point.
+    fn slider(ui: &mut egui::Ui, property: &AtomicF32, desc: &'static ParamDesc) -> egui::Response {
+        let mut value = property.load(Ordering::Relaxed);
+        let response = ui.add(StepSlider::new(&mut value, desc));
+        if response.changed() {
+            property.store(value, Ordering::Relaxed);
         }
-        self.handle = None;
+        response
     }
 
-    fn slider(ui: &mut egui::Ui, property: &AtomicF32, name: &str) {
+    fn knob(
+        ui: &mut egui::Ui,
+        property: &AtomicF32,
+        desc: &'static ParamDesc,
+        modulation: Option<ModRange>,
+    ) -> egui::Response {
         let mut value = property.load(Ordering::Relaxed);
-        if ui.add(Slider::new(&mut value, 0.0..=1.0).text(name)).changed() {
+        let response = ui.add(Knob::new(&mut value, desc).modulation(modulation));
+        if response.changed() {
             property.store(value, Ordering::Relaxed);
         }
+        response
+    }
+
+    /// Controls side by side, wrapping when the editor is narrow. For runs of knobs.
+    fn control_row(ui: &mut egui::Ui, state: &mut GuiState, ids: &[u32]) {
+        ui.horizontal_wrapped(|ui| {
+            for &id in ids {
+                Self::param_control(ui, state, id);
+            }
+        });
+    }
+
+    /// Amp envelope; gate mode has a hold time instead of sustain and release.
+    fn envelope_controls(ui: &mut egui::Ui, state: &mut GuiState) {
+        Self::param_control(ui, state, PARAM_ENV_MODE_ID);
+        let ids: &[u32] = if state.params.env_mode() == ENV_MODE_GATE {
+            &[PARAM_ATTACK_ID, PARAM_HOLD_ID, PARAM_DECAY_ID]
+        } else {
+            &[PARAM_ATTACK_ID, PARAM_DECAY_ID, PARAM_SUSTAIN_ID, PARAM_RELEASE_ID]
+        };
+        Self::control_row(ui, state, ids);
+        Self::param_control(ui, state, PARAM_ENV_LOOP_ID);
+    }
+
+    /// The LFOs, then one row per mod matrix slot, each under a collapsible header.
+    fn modulation_controls(ui: &mut egui::Ui, state: &mut GuiState) {
+        for lfo in 0..NUM_LFOS {
+            egui::CollapsingHeader::new(format!("LFO {}", lfo + 1)).show(ui, |ui| {
+                Self::control_row(ui, state, &[PARAM_LFO_RATE_IDS[lfo], PARAM_LFO_DEPTH_IDS[lfo]]);
+                Self::param_control(ui, state, PARAM_LFO_SHAPE_IDS[lfo]);
+                Self::param_control(ui, state, PARAM_LFO_RETRIGGER_IDS[lfo]);
+            });
+        }
+        Self::param_control(ui, state, PARAM_LFO_DELAY_ID);
+        egui::CollapsingHeader::new("Mod Matrix").show(ui, |ui| {
+            for slot in 0..MOD_SLOTS {
+                ui.horizontal(|ui| {
+                    Self::param_control(ui, state, PARAM_MOD_SOURCE_IDS[slot]);
+                    Self::param_control(ui, state, PARAM_MOD_DEST_IDS[slot]);
+                    Self::param_control(ui, state, PARAM_MOD_AMOUNT_IDS[slot]);
+                });
+            }
+        });
+    }
+
+    fn effect_controls(ui: &mut egui::Ui, state: &mut GuiState) {
+        Self::param_control(ui, state, PARAM_FX_MIX_ID);
+        egui::CollapsingHeader::new("Auto-Pan").show(ui, |ui| {
+            Self::param_control(ui, state, PARAM_AUTO_PAN_ON_ID);
+            ui.add_enabled_ui(state.params.auto_pan_on(), |ui| {
+                Self::param_control(ui, state, PARAM_AUTO_PAN_SYNC_ID);
+                Self::param_control(ui, state, PARAM_AUTO_PAN_SHAPE_ID);
+                if state.params.auto_pan().sync == 0 {
+                    let ids = [PARAM_AUTO_PAN_DEPTH_ID, PARAM_AUTO_PAN_RATE_ID];
+                    Self::control_row(ui, state, &ids);
+                } else {
+                    Self::param_control(ui, state, PARAM_AUTO_PAN_DEPTH_ID);
+                }
+            });
+        });
+        egui::CollapsingHeader::new("Comb").show(ui, |ui| {
+            Self::param_control(ui, state, PARAM_COMB_ON_ID);
+            ui.add_enabled_ui(state.params.comb_on(), |ui| {
+                Self::control_row(ui, state, &[PARAM_COMB_MIX_ID, PARAM_COMB_FEEDBACK_ID]);
+            });
+        });
+        egui::CollapsingHeader::new("Reverb").show(ui, |ui| {
+            Self::param_control(ui, state, PARAM_REVERB_ON_ID);
+            ui.add_enabled_ui(state.params.reverb_on(), |ui| {
+                let ids = [
+                    PARAM_REVERB_MIX_ID,
+                    PARAM_REVERB_PREDELAY_ID,
+                    PARAM_REVERB_DECAY_ID,
+                    PARAM_REVERB_DAMPING_ID,
+                ];
+                Self::control_row(ui, state, &ids);
+                Self::param_control(ui, state, PARAM_REVERB_FREEZE_ID);
+            });
+        });
+    }
+
+    /// Not a param: it changes the note port layout, which needs the host's cooperation.
+    /// The saved state keeps it alongside the MIDI-learn bindings.
+    fn note_thru_toggle(ui: &mut egui::Ui, bridge: &GuiBridge) {
+        let mut enabled = bridge.note_thru.load(Ordering::Relaxed);
+        if ui.checkbox(&mut enabled, "Note thru").changed() {
+            bridge.note_thru.store(enabled, Ordering::Relaxed);
+            bridge.push(GuiRequest::NotePortsChanged);
+        }
+    }
+
+    /// While armed, the next key played on the keyboard becomes the split point.
+    fn split_learn_button(ui: &mut egui::Ui, bridge: &GuiBridge) {
+        let mut armed = bridge.split_learn.load(Ordering::Relaxed);
+        if ui.toggle_value(&mut armed, "Learn").changed() {
+            bridge.split_learn.store(armed, Ordering::Relaxed);
+        }
+    }
+
+    /// Checkbox for an Off/On param.
+    fn toggle(ui: &mut egui::Ui, property: &AtomicF32, name: &str) -> egui::Response {
+        let mut on = property.load(Ordering::Relaxed) >= 0.5;
+        let response = ui.checkbox(&mut on, name);
+        if response.changed() {
+            property.store(if on { 1.0 } else { 0.0 }, Ordering::Relaxed);
+        }
+        response
+    }
+
+    /// Drop-down for a stepped param whose value indexes into its labels.
+    fn choice(ui: &mut egui::Ui, property: &AtomicF32, desc: &ParamDesc) -> egui::Response {
+        let value = property.load(Ordering::Relaxed);
+        let mut index = value.round() as usize;
+
+        let response = egui::ComboBox::from_label(desc.name)
+            .selected_text(readout(desc, value))
+            .show_index(ui, &mut index, desc.labels.len(), |i| readout(desc, i as f32));
+        if response.changed() {
+            property.store(index as f32, Ordering::Relaxed);
+        }
+        response
     }
 }
 
-unsafe impl HasRawWindowHandle for CaveGui {
-    fn raw_window_handle(&self) -> RawWindowHandle {
-        // If Bitwig never called set_parent(), this will panic (useful: you'll see it in logs).
-        self.parent.expect("Parent window not set")
+impl WindowLayer for CaveGui {
+    type Window = BaseviewWindow;
+
+    fn open(
+        &mut self,
+        parent: Option<RawWindowHandle>,
+        size: PhySize,
+        scale: Option<f64>,
+    ) -> Result<BaseviewWindow, PluginError> {
+        let metrics = WindowMetrics { current: size, requested: None, scale };
+        let metrics = Arc::new(Mutex::new(metrics));
+        let kind = match parent {
+            Some(parent) => self.open_embedded(parent, &metrics)?,
+            None => self.open_floating(&metrics)?,
+        };
+        Ok(BaseviewWindow { kind, metrics })
+    }
+
+    fn close(&mut self, window: BaseviewWindow) {
+        eprintln!("[cave-gui] closing the window");
+        // The window won't see the mouse-up now.
+        self.state.notes.release_all();
+        self.state.vis.scope.set_watching(false);
+        match window.kind {
+            WindowKind::Embedded(mut handle) => handle.close(),
+            // Not joined: the thread winds down on the editor's next frame, which an idle
+            // editor needs waking for.
+            WindowKind::Floating(floating) => {
+                floating.close.store(true, Ordering::Relaxed);
+                self.state.bridge.request_repaint();
+            }
+        }
+        self.state.bridge.set_context(None);
+    }
+
+    fn is_alive(&self, window: &BaseviewWindow) -> bool {
+        match &window.kind {
+            WindowKind::Embedded(_) => true,
+            WindowKind::Floating(floating) => !floating.thread.is_finished(),
+        }
+    }
+
+    fn size(&self, window: &BaseviewWindow) -> PhySize {
+        window.metrics.lock().map_or(DEFAULT_SIZE, |metrics| metrics.current)
+    }
+
+    fn resize(&mut self, window: &mut BaseviewWindow, size: PhySize) {
+        if let Ok(mut metrics) = window.metrics.lock() {
+            metrics.current = size;
+            metrics.requested = Some(size);
+        }
+    }
+
+    /// Scales the size along with the scale, and the editor lays itself out again.
+    fn rescale(&mut self, window: &mut BaseviewWindow, scale: f64) {
+        if let Ok(mut metrics) = window.metrics.lock() {
+            let size = scaled(metrics.current, scale / metrics.scale());
+            metrics.scale = Some(scale);
+            metrics.current = size;
+            metrics.requested = Some(size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raw_window_handle::{
+        AppKitWindowHandle, WaylandWindowHandle, Win32WindowHandle, XlibWindowHandle,
+    };
+
+    fn appkit(ns_view: *mut std::ffi::c_void) -> RawWindowHandle {
+        let mut handle = AppKitWindowHandle::empty();
+        handle.ns_view = ns_view;
+        RawWindowHandle::AppKit(handle)
+    }
+
+    fn win32(hwnd: *mut std::ffi::c_void) -> RawWindowHandle {
+        let mut handle = Win32WindowHandle::empty();
+        handle.hwnd = hwnd;
+        RawWindowHandle::Win32(handle)
+    }
+
+    fn xlib(window: u64) -> RawWindowHandle {
+        let mut handle = XlibWindowHandle::empty();
+        handle.window = window as _;
+        RawWindowHandle::Xlib(handle)
+    }
+
+    #[test]
+    fn macos_accepts_only_appkit_parents_with_a_view() {
+        let view = std::ptr::NonNull::<u8>::dangling().as_ptr().cast();
+        assert_eq!(parent_handle_error(&appkit(view), "macos"), None);
+        assert!(parent_handle_error(&appkit(std::ptr::null_mut()), "macos").is_some());
+        assert!(parent_handle_error(&xlib(1), "macos").is_some());
+    }
+
+    #[test]
+    fn linux_accepts_only_x11_parents_with_a_window() {
+        assert_eq!(parent_handle_error(&xlib(1), "linux"), None);
+        assert!(parent_handle_error(&xlib(0), "linux").is_some());
+        let wayland = RawWindowHandle::Wayland(WaylandWindowHandle::empty());
+        assert!(parent_handle_error(&wayland, "linux").is_some());
+        let view = std::ptr::NonNull::<u8>::dangling().as_ptr().cast();
+        assert!(parent_handle_error(&appkit(view), "linux").is_some());
+    }
+
+    #[test]
+    fn windows_accepts_only_win32_parents_with_a_window() {
+        let hwnd = std::ptr::NonNull::<u8>::dangling().as_ptr().cast();
+        assert_eq!(parent_handle_error(&win32(hwnd), "windows"), None);
+        assert!(parent_handle_error(&win32(std::ptr::null_mut()), "windows").is_some());
+        assert!(parent_handle_error(&xlib(1), "windows").is_some());
+    }
+
+    #[test]
+    fn patch_title_is_starred_once_the_user_edits_a_param() {
+        let (bridge, params) = (GuiBridge::default(), CaveParams::default());
+        assert_eq!(bridge.window_title(&params), "Cave");
+
+        params.change(crate::params::PARAM_GAIN_ID, 0.5);
+        bridge.set_patch_name(Some("Deep Bass".into()), &params);
+        assert_eq!(bridge.window_title(&params), "Cave — Deep Bass");
+        // Loading and automation aren't edits.
+        params.set_value(crate::params::PARAM_GAIN_ID, 0.75);
+        assert_eq!(bridge.patch_title(&params).as_deref(), Some("Deep Bass"));
+        params.change(crate::params::PARAM_GAIN_ID, 0.25);
+        assert_eq!(bridge.patch_title(&params).as_deref(), Some("Deep Bass*"));
+        // Loading the next patch starts it clean.
+        bridge.set_patch_name(Some("Pad".into()), &params);
+        assert_eq!(bridge.patch_title(&params).as_deref(), Some("Pad"));
+
+        bridge.set_patch_name(None, &params);
+        assert_eq!(bridge.patch_title(&params), None);
+    }
+
+    #[test]
+    fn about_panel_follows_each_activation() {
+        let bridge = GuiBridge::default();
+        assert!(bridge.about_text().contains("Audio: not active"));
+
+        bridge.set_audio_config(Some(AudioInfo { sample_rate: 44_100.0, max_frames: 512 }));
+        assert!(bridge.about_text().contains("Audio: 44100 Hz, blocks up to 512 frames"));
+        bridge.set_audio_config(None);
+        bridge.set_audio_config(Some(AudioInfo { sample_rate: 96_000.0, max_frames: 64 }));
+        assert!(bridge.about_text().contains("Audio: 96000 Hz, blocks up to 64 frames"));
+    }
+
+    #[test]
+    fn redraw_rate_counts_the_gaps_an_idle_editor_sleeps_through() {
+        let start = Instant::now();
+        let mut redraws = RedrawRate::new(start);
+        for frame in 1..=30 {
+            redraws.frame(start + Duration::from_millis(frame * 1000 / 30));
+        }
+        assert_eq!(redraws.per_second, Some(30.0));
+
+        redraws.frame(start + Duration::from_secs(11));
+        assert_eq!(redraws.per_second, Some(0.1));
     }
 }
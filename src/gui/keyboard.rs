@@ -0,0 +1,266 @@
+use std::ops::RangeInclusive;
+
+use egui_baseview::egui::{self, Color32, Key, Pos2, Rect, Response, Sense, Stroke, Ui, Widget};
+
+use crate::note_queue::NoteQueue;
+use crate::params::note_name;
+
+/// Lowest and highest keys on the strip before the octave shift: C2 to C7.
+const LOWEST: u8 = 36;
+const HIGHEST: u8 = 96;
+/// Octave shifts that keep the strip within MIDI's range: C-1–C4 up to C4–C9.
+pub const OCTAVE_SHIFTS: RangeInclusive<i8> = -3..=2;
+const HEIGHT: f32 = 56.0;
+/// Black keys' size relative to the white keys.
+const BLACK_WIDTH: f32 = 0.6;
+const BLACK_HEIGHT: f32 = 0.6;
+/// Velocity at the very top of a key; it rises to full at the front edge.
+const MIN_VELOCITY: f32 = 0.1;
+/// Computer keys have no depth to play softer with, so they play at a set velocity.
+pub const TYPED_VELOCITY: f32 = 0.8;
+/// Computer keys that shift the octave down and up, below the row that plays.
+const OCTAVE_DOWN_KEY: Key = Key::Z;
+const OCTAVE_UP_KEY: Key = Key::X;
+/// The computer keyboard layout DAWs share: the home row's letters are the white keys from
+/// C4 up to E5, with the black keys on the row above. Before the octave shift.
+const TYPED_KEYS: [(Key, u8); 17] = [
+    (Key::A, 60),
+    (Key::W, 61),
+    (Key::S, 62),
+    (Key::E, 63),
+    (Key::D, 64),
+    (Key::F, 65),
+    (Key::T, 66),
+    (Key::G, 67),
+    (Key::Y, 68),
+    (Key::H, 69),
+    (Key::U, 70),
+    (Key::J, 71),
+    (Key::K, 72),
+    (Key::O, 73),
+    (Key::L, 74),
+    (Key::P, 75),
+    (Key::Semicolon, 76),
+];
+
+/// Clickable piano strip, also played from the computer keyboard. Notes go through the
+/// [`NoteQueue`] to the audio thread, and keys the voices are playing, from the keyboard or
+/// anywhere else, light up. `octave` shifts every key it plays, see [`OCTAVE_SHIFTS`], and
+/// computer keys play at `typed_velocity`.
+pub struct Keyboard<'a> {
+    notes: &'a NoteQueue,
+    octave: i8,
+    typed_velocity: f32,
+}
+
+impl<'a> Keyboard<'a> {
+    pub fn new(notes: &'a NoteQueue, octave: i8, typed_velocity: f32) -> Self {
+        Self { notes, octave, typed_velocity }
+    }
+
+    /// The note `key` on the unshifted strip plays.
+    fn shifted(&self, key: u8) -> u8 {
+        shift(key, self.octave)
+    }
+
+    /// Plays and releases notes for computer keys, unless a text field has the keyboard.
+    /// Held modifiers leave the keys to shortcuts, and auto-repeat is ignored. Releases
+    /// always go through, and the window losing focus lets go of every typed key, since
+    /// their key-ups would go elsewhere and leave the notes stuck.
+    fn play_typed(&self, ui: &Ui) {
+        let text_has_keyboard = ui.ctx().wants_keyboard_input();
+        ui.input(|input| {
+            let blurred = |event: &egui::Event| matches!(event, egui::Event::WindowFocused(false));
+            if !input.focused || input.events.iter().any(blurred) {
+                self.notes.release_all_typed();
+                return;
+            }
+            for event in &input.events {
+                let egui::Event::Key { key, pressed, repeat: false, modifiers, .. } = *event
+                else {
+                    continue;
+                };
+                let Some(&(_, note)) = TYPED_KEYS.iter().find(|(typed, _)| *typed == key) else {
+                    continue;
+                };
+                let note = self.shifted(note);
+                if !pressed {
+                    self.notes.release_typed(note);
+                } else if modifiers.is_none() && !text_has_keyboard {
+                    self.notes.press_typed(note, self.typed_velocity);
+                }
+            }
+        });
+    }
+}
+
+/// Octave down and up buttons around the range the strip plays, also worked by the Z and X
+/// keys. Shifting lets go of every held key first, so none is left stuck at the old octave.
+pub fn octave_controls(ui: &mut Ui, octave: &mut i8, notes: &NoteQueue) {
+    let old = *octave;
+    let typed = |key| {
+        !ui.ctx().wants_keyboard_input()
+            && ui.input(|i| i.events.iter().any(|event| is_plain_press(event, key)))
+    };
+    let can_go_down = old > *OCTAVE_SHIFTS.start();
+    let down = ui.add_enabled(can_go_down, egui::Button::new("−"));
+    if down.on_hover_text("Octave down (Z)").clicked() || (can_go_down && typed(OCTAVE_DOWN_KEY)) {
+        *octave -= 1;
+    }
+    ui.label(range_label(*octave));
+    let can_go_up = old < *OCTAVE_SHIFTS.end();
+    let up = ui.add_enabled(can_go_up, egui::Button::new("+"));
+    if up.on_hover_text("Octave up (X)").clicked() || (can_go_up && typed(OCTAVE_UP_KEY)) {
+        *octave += 1;
+    }
+    if *octave != old {
+        notes.release_all();
+    }
+}
+
+/// Velocity the computer keys play at.
+pub fn velocity_control(ui: &mut Ui, velocity: &mut f32) {
+    ui.label("Velocity");
+    let drag = egui::DragValue::new(velocity).range(MIN_VELOCITY..=1.0).speed(0.01);
+    ui.add(drag.fixed_decimals(2)).on_hover_text("Velocity of computer keyboard notes");
+}
+
+/// A first press of `key` with no modifiers held.
+fn is_plain_press(event: &egui::Event, key: Key) -> bool {
+    matches!(
+        *event,
+        egui::Event::Key { key: k, pressed: true, repeat: false, modifiers, .. }
+            if k == key && modifiers.is_none()
+    )
+}
+
+fn shift(key: u8, octave: i8) -> u8 {
+    (key as i16 + 12 * octave as i16) as u8
+}
+
+/// The notes the strip plays at `octave`, as in "C2–C7".
+fn range_label(octave: i8) -> String {
+    let note = |key: u8| note_name(shift(key, octave) as i32);
+    format!("{}–{}", note(LOWEST), note(HIGHEST))
+}
+
+impl Widget for Keyboard<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let size = egui::vec2(ui.available_width(), HEIGHT);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+        self.play_typed(ui);
+
+        // Dragging across the strip plays each key in turn. The last one stays down while
+        // the pointer is off the strip, until the button comes up wherever it is.
+        if response.is_pointer_button_down_on() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let key = key_at(rect, pos);
+                if let Some(key) = key.filter(|&key| self.notes.held() != Some(self.shifted(key))) {
+                    self.notes.press(self.shifted(key), velocity(key_rect(rect, key), pos));
+                }
+            }
+        } else if self.notes.held().is_some() {
+            self.notes.release();
+        }
+
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter();
+            let pressed = ui.visuals().selection.bg_fill;
+            let outline = Stroke::new(1.0, Color32::from_gray(90));
+            let keys = LOWEST..=HIGHEST;
+            let (black, white): (Vec<u8>, Vec<u8>) = keys.partition(|&key| is_black(key));
+            for key in white.into_iter().chain(black) {
+                let base = Color32::from_gray(if is_black(key) { 30 } else { 235 });
+                let note = self.shifted(key);
+                let fill = if self.notes.held() == Some(note) || self.notes.is_typed(note) {
+                    pressed
+                } else if self.notes.is_sounding(note) {
+                    base.lerp_to_gamma(pressed, 0.6)
+                } else {
+                    base
+                };
+                let key_rect = key_rect(rect, key);
+                painter.rect_filled(key_rect, 2.0, fill);
+                painter.rect_stroke(key_rect, 2.0, outline, egui::StrokeKind::Inside);
+            }
+        }
+
+        response
+    }
+}
+
+fn is_black(key: u8) -> bool {
+    matches!(key % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+/// White keys below `key` on the strip.
+fn white_index(key: u8) -> usize {
+    (LOWEST..key).filter(|&k| !is_black(k)).count()
+}
+
+/// Where `key` sits on a strip filling `rect`. Black keys straddle the line between their
+/// white neighbours.
+fn key_rect(rect: Rect, key: u8) -> Rect {
+    let white_width = rect.width() / white_index(HIGHEST + 1) as f32;
+    let x = rect.left() + white_index(key) as f32 * white_width;
+    if is_black(key) {
+        let width = white_width * BLACK_WIDTH;
+        let size = egui::vec2(width, rect.height() * BLACK_HEIGHT);
+        Rect::from_min_size(egui::pos2(x - width / 2.0, rect.top()), size)
+    } else {
+        Rect::from_min_size(egui::pos2(x, rect.top()), egui::vec2(white_width, rect.height()))
+    }
+}
+
+/// The key under `pos`. Black keys sit on top of the white ones.
+fn key_at(rect: Rect, pos: Pos2) -> Option<u8> {
+    let mut keys = (LOWEST..=HIGHEST).filter(|&key| key_rect(rect, key).contains(pos));
+    let (black, white) = (keys.clone().find(|&key| is_black(key)), keys.next());
+    black.or(white)
+}
+
+/// Softest at the top of the key, loudest at its front edge.
+fn velocity(key_rect: Rect, pos: Pos2) -> f32 {
+    let depth = ((pos.y - key_rect.top()) / key_rect.height()).clamp(0.0, 1.0);
+    MIN_VELOCITY + (1.0 - MIN_VELOCITY) * depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip() -> Rect {
+        // Ten points per white key.
+        Rect::from_min_size(Pos2::ZERO, egui::vec2(white_index(HIGHEST + 1) as f32 * 10.0, 50.0))
+    }
+
+    #[test]
+    fn black_keys_win_where_they_overlap_white_ones() {
+        let rect = strip();
+        // The line between C and D, near the top, is C#; further down it's D.
+        assert_eq!(key_at(rect, egui::pos2(10.0, 5.0)), Some(LOWEST + 1));
+        assert_eq!(key_at(rect, egui::pos2(10.5, 45.0)), Some(LOWEST + 2));
+        assert_eq!(key_at(rect, egui::pos2(-1.0, 5.0)), None);
+    }
+
+    #[test]
+    fn velocity_rises_towards_the_front_of_the_key() {
+        let key = key_rect(strip(), LOWEST);
+        assert_eq!(velocity(key, key.center_top()), MIN_VELOCITY);
+        assert_eq!(velocity(key, key.center_bottom()), 1.0);
+    }
+
+    #[test]
+    fn octave_keys_play_no_note() {
+        for key in [OCTAVE_DOWN_KEY, OCTAVE_UP_KEY] {
+            assert!(TYPED_KEYS.iter().all(|&(typed, _)| typed != key), "{key:?}");
+        }
+    }
+
+    #[test]
+    fn octave_shifts_stay_within_midi() {
+        assert_eq!(range_label(0), "C2–C7");
+        assert_eq!(shift(LOWEST, *OCTAVE_SHIFTS.start()), 0);
+        assert!(shift(HIGHEST, *OCTAVE_SHIFTS.end()) <= 127);
+    }
+}
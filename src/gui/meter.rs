@@ -0,0 +1,161 @@
+use egui_baseview::egui::{self, Color32, Rect, Sense, Stroke, Ui};
+
+use crate::meter::{MeterLevels, MeterReader, METER_CHANNELS};
+
+/// Bottom of the scale; anything quieter reads as silence.
+const MIN_DB: f32 = -60.0;
+/// How fast the bars fall once the level drops.
+const FALL_DB_PER_SECOND: f32 = 24.0;
+/// How long the peak-hold line stays put before falling with the bars.
+const HOLD_SECONDS: f64 = 3.0;
+const BAR_WIDTH: f32 = 140.0;
+const BAR_HEIGHT: f32 = 6.0;
+const CLIP_RADIUS: f32 = 5.0;
+
+/// Stereo level meter: RMS bars over fainter peak bars, a peak-hold line per channel and
+/// a clip light that stays lit until clicked. The levels come from the shared
+/// [`MeterReader`]; the falloff, the hold and the clip light live here, so they restart
+/// with the window. A clip while it was closed still shows when it opens.
+#[derive(Clone)]
+pub struct Meter {
+    channels: [Ballistics; METER_CHANNELS],
+    /// The levels last taken, their peaks spent once shown.
+    levels: MeterLevels,
+    clipped: bool,
+    /// Editor time of the last frame, in seconds.
+    last_frame: Option<f64>,
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Self {
+            channels: [Ballistics::default(); METER_CHANNELS],
+            levels: MeterLevels::default(),
+            clipped: false,
+            last_frame: None,
+        }
+    }
+}
+
+impl Meter {
+    pub fn show(&mut self, ui: &mut Ui, meter: &mut MeterReader) {
+        let now = ui.input(|i| i.time);
+        let elapsed = self.last_frame.replace(now).map_or(0.0, |last| (now - last) as f32);
+        if let Some(levels) = meter.take() {
+            self.levels = levels;
+            self.clipped |= levels.clipped;
+        }
+        for (channel, ballistics) in self.channels.iter_mut().enumerate() {
+            let levels = &mut self.levels;
+            let peak = to_db(std::mem::take(&mut levels.peak[channel]));
+            ballistics.update(peak, to_db(levels.rms[channel]), now, elapsed);
+        }
+
+        ui.horizontal(|ui| {
+            let size = egui::vec2(BAR_WIDTH, BAR_HEIGHT * 2.0 + 2.0);
+            let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+            if ui.is_rect_visible(rect) {
+                for (channel, ballistics) in self.channels.iter().enumerate() {
+                    let top = rect.top() + channel as f32 * (BAR_HEIGHT + 2.0);
+                    let size = egui::vec2(BAR_WIDTH, BAR_HEIGHT);
+                    let bar = Rect::from_min_size(egui::pos2(rect.left(), top), size);
+                    Self::paint_bar(ui, bar, ballistics);
+                }
+            }
+
+            let size = egui::Vec2::splat(CLIP_RADIUS * 2.0);
+            let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+            let fill = if self.clipped { Color32::RED } else { ui.visuals().extreme_bg_color };
+            ui.painter().circle(rect.center(), CLIP_RADIUS, fill, ui.visuals().window_stroke);
+            if response.on_hover_text("Clip: click to reset").clicked() {
+                self.clipped = false;
+            }
+        });
+    }
+
+    /// Whether every bar and hold line has fallen to the floor, so there's nothing left to
+    /// animate until sound comes in.
+    pub fn is_settled(&self) -> bool {
+        self.channels.iter().all(|b| b.peak <= MIN_DB && b.rms <= MIN_DB && b.hold <= MIN_DB)
+    }
+
+    fn paint_bar(ui: &Ui, rect: Rect, ballistics: &Ballistics) {
+        let painter = ui.painter();
+        painter.rect_filled(rect, 1.0, ui.visuals().extreme_bg_color);
+        let x = |db: f32| rect.left() + position(db) * rect.width();
+
+        let color = ui.visuals().selection.bg_fill;
+        let faint = color.gamma_multiply(0.4);
+        let bar = |db: f32| Rect::from_min_max(rect.min, egui::pos2(x(db), rect.bottom()));
+        painter.rect_filled(bar(ballistics.peak), 1.0, faint);
+        painter.rect_filled(bar(ballistics.rms), 1.0, color);
+        if ballistics.hold > MIN_DB {
+            painter.vline(x(ballistics.hold), rect.y_range(), Stroke::new(1.5, color));
+        }
+    }
+}
+
+/// One channel's displayed levels, in dB.
+#[derive(Clone, Copy)]
+struct Ballistics {
+    peak: f32,
+    rms: f32,
+    hold: f32,
+    /// Editor time the hold line was last raised.
+    held_at: f64,
+}
+
+impl Default for Ballistics {
+    fn default() -> Self {
+        Self { peak: MIN_DB, rms: MIN_DB, hold: MIN_DB, held_at: 0.0 }
+    }
+}
+
+impl Ballistics {
+    /// Rises straight to the new levels and falls back at [`FALL_DB_PER_SECOND`]. The hold
+    /// line only starts to fall [`HOLD_SECONDS`] after its last rise.
+    fn update(&mut self, peak: f32, rms: f32, now: f64, elapsed: f32) {
+        let fall = FALL_DB_PER_SECOND * elapsed;
+        self.peak = peak.max(self.peak - fall);
+        self.rms = rms.max(self.rms - fall);
+        if self.peak >= self.hold {
+            self.hold = self.peak;
+            self.held_at = now;
+        } else if now - self.held_at > HOLD_SECONDS {
+            self.hold = self.peak.max(self.hold - fall);
+        }
+    }
+}
+
+fn to_db(gain: f32) -> f32 {
+    (20.0 * gain.log10()).max(MIN_DB)
+}
+
+/// Where `db` sits on the bar, from 0.0 at [`MIN_DB`] to 1.0 at 0 dBFS.
+fn position(db: f32) -> f32 {
+    (1.0 - db / MIN_DB).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hold_line_stays_for_three_seconds_then_falls() {
+        let mut ballistics = Ballistics::default();
+        ballistics.update(-6.0, -12.0, 0.0, 0.0);
+        ballistics.update(MIN_DB, MIN_DB, 1.0, 1.0);
+        assert_eq!(ballistics.peak, -6.0 - FALL_DB_PER_SECOND);
+        assert_eq!(ballistics.hold, -6.0);
+
+        ballistics.update(MIN_DB, MIN_DB, 3.5, 0.5);
+        assert_eq!(ballistics.hold, -6.0 - FALL_DB_PER_SECOND * 0.5);
+    }
+
+    #[test]
+    fn scale_runs_from_the_floor_to_full_scale() {
+        assert_eq!(position(to_db(0.0)), 0.0);
+        assert_eq!(position(to_db(1.0)), 1.0);
+        assert_eq!(position(to_db(2.0)), 1.0);
+    }
+}
@@ -0,0 +1,287 @@
+use std::f32::consts::PI;
+
+use egui_baseview::egui::{self, Align2, Color32, Pos2, Rect, Stroke, Ui};
+
+use crate::scope::ScopeBuffer;
+
+/// FFT sizes on offer: the smaller follows the sound faster, the larger resolves the low
+/// end. Both fit in the [`ScopeBuffer`].
+const SIZES: [usize; 2] = [1024, 4096];
+const HEIGHT: f32 = 120.0;
+/// Rough height with the controls above the plot, until the panel has been drawn.
+pub const PANEL_HEIGHT: f32 = HEIGHT + 24.0;
+/// Range of the plot.
+const MIN_FREQUENCY: f32 = 20.0; // Hz
+const MIN_DB: f32 = -96.0;
+const MAX_DB: f32 = 6.0;
+/// Labelled grid lines.
+const FREQUENCY_LINES: [(f32, &str); 3] = [(100.0, "100"), (1000.0, "1k"), (10_000.0, "10k")];
+const DB_LINES: [f32; 4] = [0.0, -24.0, -48.0, -72.0];
+
+/// How much of the previous frame each bin keeps.
+#[derive(Clone, Copy, PartialEq)]
+enum Averaging {
+    Off,
+    Short,
+    Long,
+}
+
+impl Averaging {
+    const ALL: [Self; 3] = [Self::Off, Self::Short, Self::Long];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Short => "Short",
+            Self::Long => "Long",
+        }
+    }
+
+    fn keep(self) -> f32 {
+        match self {
+            Self::Off => 0.0,
+            Self::Short => 0.6,
+            Self::Long => 0.9,
+        }
+    }
+}
+
+/// Spectrum analyser over the scope's ring buffer. The FFT runs here, on the editor thread,
+/// once a frame; the audio thread only ever writes the samples. The settings and the
+/// averaged and held levels are editor-local.
+#[derive(Clone)]
+pub struct Spectrum {
+    size: usize,
+    averaging: Averaging,
+    peak_hold: bool,
+    /// Level per bin in dB, averaged over frames.
+    levels: Vec<f32>,
+    /// Highest level per bin since peak hold was turned on.
+    peaks: Vec<f32>,
+    // Scratch space for the FFT, kept between frames.
+    re: Vec<f32>,
+    im: Vec<f32>,
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self {
+            size: SIZES[0],
+            averaging: Averaging::Short,
+            peak_hold: false,
+            levels: Vec::new(),
+            peaks: Vec::new(),
+            re: Vec::new(),
+            im: Vec::new(),
+        }
+    }
+}
+
+impl Spectrum {
+    /// The settings row and the plot, analysing the latest samples in `scope`.
+    pub fn show(&mut self, ui: &mut Ui, scope: &ScopeBuffer) {
+        ui.horizontal(|ui| {
+            let size = self.size;
+            egui::ComboBox::from_label("Size")
+                .selected_text(size.to_string())
+                .show_ui(ui, |ui| {
+                    for size in SIZES {
+                        ui.selectable_value(&mut self.size, size, size.to_string());
+                    }
+                });
+            egui::ComboBox::from_label("Averaging")
+                .selected_text(self.averaging.label())
+                .show_ui(ui, |ui| {
+                    for averaging in Averaging::ALL {
+                        ui.selectable_value(&mut self.averaging, averaging, averaging.label());
+                    }
+                });
+            if ui.checkbox(&mut self.peak_hold, "Peak hold").changed() || size != self.size {
+                self.peaks.clear();
+            }
+        });
+
+        self.analyse(scope);
+
+        let size = egui::vec2(ui.available_width(), HEIGHT);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+        if response.double_clicked() {
+            self.peaks.clear();
+        }
+        if ui.is_rect_visible(rect) {
+            self.paint(ui, rect, scope.sample_rate());
+        }
+    }
+
+    fn analyse(&mut self, scope: &ScopeBuffer) {
+        let size = self.size;
+        self.re.resize(size, 0.0);
+        self.im.clear();
+        self.im.resize(size, 0.0);
+        scope.read(&mut self.re);
+        for (i, sample) in self.re.iter_mut().enumerate() {
+            *sample *= hann(i, size);
+        }
+        fft(&mut self.re, &mut self.im);
+
+        // Scaled so a full-scale sine reads 0 dB whatever the size.
+        let gain = 4.0 / size as f32;
+        let bins = size / 2;
+        let fresh = (0..bins).map(|bin| to_db(self.re[bin].hypot(self.im[bin]) * gain));
+        if self.levels.len() != bins {
+            self.levels = fresh.collect();
+        } else {
+            average(&mut self.levels, fresh, self.averaging.keep());
+        }
+
+        if !self.peak_hold {
+            self.peaks.clear();
+        } else if self.peaks.len() != bins {
+            self.peaks = self.levels.clone();
+        } else {
+            for (peak, &level) in self.peaks.iter_mut().zip(&self.levels) {
+                *peak = peak.max(level);
+            }
+        }
+    }
+
+    fn paint(&self, ui: &Ui, rect: Rect, sample_rate: f32) {
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let max_frequency = sample_rate / 2.0;
+        let x = |frequency: f32| {
+            let position = (frequency / MIN_FREQUENCY).ln() / (max_frequency / MIN_FREQUENCY).ln();
+            rect.left() + position * rect.width()
+        };
+        let y = |db: f32| {
+            let position = (db.clamp(MIN_DB, MAX_DB) - MIN_DB) / (MAX_DB - MIN_DB);
+            rect.bottom() - position * rect.height()
+        };
+
+        let grid = Stroke::new(1.0, ui.visuals().widgets.noninteractive.bg_stroke.color);
+        let font = egui::TextStyle::Small.resolve(ui.style());
+        let label = ui.visuals().weak_text_color();
+        for (frequency, text) in FREQUENCY_LINES {
+            if frequency < max_frequency {
+                let x = x(frequency);
+                painter.vline(x, rect.y_range(), grid);
+                let pos = egui::pos2(x + 2.0, rect.bottom());
+                painter.text(pos, Align2::LEFT_BOTTOM, text, font.clone(), label);
+            }
+        }
+        for db in DB_LINES {
+            let y = y(db);
+            painter.hline(rect.x_range(), y, grid);
+            let pos = egui::pos2(rect.left() + 2.0, y);
+            painter.text(pos, Align2::LEFT_TOP, format!("{db} dB"), font.clone(), label);
+        }
+
+        let bin_width = sample_rate / self.size as f32;
+        let line = |levels: &[f32]| -> Vec<Pos2> {
+            levels
+                .iter()
+                .enumerate()
+                .map(|(bin, &db)| (bin as f32 * bin_width, db))
+                .filter(|&(frequency, _)| frequency >= MIN_FREQUENCY)
+                .map(|(frequency, db)| egui::pos2(x(frequency), y(db)))
+                .collect()
+        };
+        let fill = ui.visuals().selection.bg_fill;
+        if !self.peaks.is_empty() {
+            let held = Color32::from_rgba_unmultiplied(fill.r(), fill.g(), fill.b(), 110);
+            painter.line(line(&self.peaks), Stroke::new(1.0, held));
+        }
+        painter.line(line(&self.levels), Stroke::new(1.5, fill));
+    }
+}
+
+/// Hann window coefficient for sample `i` of `len`.
+fn hann(i: usize, len: usize) -> f32 {
+    0.5 - 0.5 * (2.0 * PI * i as f32 / len as f32).cos()
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-6).log10()
+}
+
+/// Blends `fresh` into `levels`, keeping `keep` of the old value.
+fn average(levels: &mut [f32], fresh: impl Iterator<Item = f32>, keep: f32) {
+    for (level, fresh) in levels.iter_mut().zip(fresh) {
+        *level = *level * keep + fresh * (1.0 - keep);
+    }
+}
+
+/// In-place radix-2 FFT. The length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // Bit-reversal permutation.
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let step = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (step * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::Sample;
+
+    #[test]
+    fn fft_puts_a_sine_in_its_bin() {
+        let n = 64;
+        let sine = |i: usize| (2.0 * PI * 5.0 * i as f32 / n as f32).sin();
+        let mut re: Vec<f32> = (0..n).map(sine).collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im);
+
+        let magnitude = |bin: usize| re[bin].hypot(im[bin]);
+        assert!((magnitude(5) - n as f32 / 2.0).abs() < 1e-3);
+        assert!((0..n / 2).filter(|&bin| bin != 5).all(|bin| magnitude(bin) < 1e-3));
+    }
+
+    #[test]
+    fn full_scale_sine_reads_zero_db() {
+        let scope = ScopeBuffer::default();
+        scope.set_watching(true);
+        let (size, bin) = (SIZES[0], 32);
+        let block: Vec<Sample> = (0..size)
+            .map(|i| Sample::from((2.0 * PI * bin as f32 * i as f32 / size as f32).sin()))
+            .collect();
+        scope.write(&block);
+
+        let mut spectrum = Spectrum::default();
+        spectrum.analyse(&scope);
+        assert!(spectrum.levels[bin].abs() < 0.01);
+        assert!(spectrum.levels[bin + 4] < MIN_DB);
+    }
+}
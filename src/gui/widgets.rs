@@ -0,0 +1,485 @@
+use std::f32::consts::PI;
+
+use egui_baseview::egui::{self, Key, Pos2, Rect, Response, Sense, Stroke, Ui, Widget};
+
+use crate::params::{text_value, value_text, ModRange, ParamDesc, Unit};
+
+const DIAMETER: f32 = 40.0;
+const PAD_SIZE: egui::Vec2 = egui::vec2(200.0, 120.0);
+const PUCK_RADIUS: f32 = 6.0;
+/// Angle of the knob's minimum, in screen coordinates (clockwise from 3 o'clock).
+const START_ANGLE: f32 = 0.75 * PI;
+/// The arc runs clockwise from 7:30 round to 4:30.
+const SWEEP: f32 = 1.5 * PI;
+const HANDLE_RADIUS: f32 = 6.0;
+
+// How far controls move for the mouse and keys. Every control reads these, so this is the
+// place to tune them. Distances are along the param's skewed range (see [`position_of`]),
+// so fine control is as fine at the top of a frequency range as at the bottom.
+/// Knob drag, in points, that sweeps the whole range. A slider's is its width.
+const DRAG_RANGE: f32 = 200.0;
+/// Drag and nudge speed while shift is held.
+const FINE: f32 = 0.1;
+/// Drag, in points, per step of a stepped param while command (ctrl) is held.
+const STEP_DRAG: f32 = 16.0;
+/// Share of the range one arrow key press or wheel notch moves a continuous param.
+/// Stepped params move one step instead.
+const NUDGE: f32 = 0.01;
+/// Scroll egui reports for one mouse wheel notch, in points.
+const WHEEL_NOTCH: f32 = 50.0;
+
+/// Rotary control for a continuous param: an arc showing the value, the param's name and
+/// its value text underneath. Drag up or down to turn it, scroll over it, or focus it and
+/// use the arrow keys; shift makes each of those fine. Double-click to reset it. While
+/// something modulates the param, a ring outside the arc shows how far it swings and a dot
+/// where it is now.
+pub struct Knob<'a> {
+    value: &'a mut f32,
+    desc: &'static ParamDesc,
+    modulation: Option<ModRange>,
+}
+
+impl<'a> Knob<'a> {
+    pub fn new(value: &'a mut f32, desc: &'static ParamDesc) -> Self {
+        Self { value, desc, modulation: None }
+    }
+
+    pub fn modulation(self, modulation: Option<ModRange>) -> Self {
+        Self { modulation, ..self }
+    }
+}
+
+impl Widget for Knob<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let desc = self.desc;
+        let font = egui::TextStyle::Body.resolve(ui.style());
+        let text_color = ui.visuals().text_color();
+        let painter = ui.painter();
+        let name = painter.layout_no_wrap(desc.name.to_string(), font.clone(), text_color);
+        let width = name.size().x.max(DIAMETER + 16.0);
+        let row = ui.text_style_height(&egui::TextStyle::Body);
+        let size = egui::vec2(width, DIAMETER + 2.0 * row + 4.0);
+        let (rect, mut response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+
+        let (position, drag) = (position_of(desc, *self.value), -response.drag_delta().y);
+        let moved = adjust(ui, &response, desc, position, drag, DRAG_RANGE);
+        let reset = response.double_clicked().then_some(desc.default as f32);
+        if let Some(value) = reset.or(moved.map(|position| value_at(desc, position))) {
+            if value != *self.value {
+                *self.value = value;
+                response.mark_changed();
+            }
+        }
+        let value = *self.value;
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.style().interact(&response);
+            let painter = ui.painter();
+            let center = egui::pos2(rect.center().x, rect.top() + DIAMETER / 2.0);
+            let radius = DIAMETER / 2.0 - 3.0;
+            let position = position_of(desc, value);
+
+            let track = ui.visuals().widgets.inactive.bg_fill;
+            painter.line(arc(center, radius, 0.0, 1.0), Stroke::new(4.0, track));
+            let fill = ui.visuals().selection.bg_fill;
+            painter.line(arc(center, radius, 0.0, position), Stroke::new(4.0, fill));
+            let pointer = center + angle_vec(position) * (radius - 6.0);
+            painter.line_segment([center, pointer], visuals.fg_stroke);
+            if let Some(ModRange { low, high, value }) = self.modulation {
+                let ring = radius + 4.0;
+                let color = ui.visuals().selection.stroke.color;
+                let swing = arc(center, ring, position_of(desc, low), position_of(desc, high));
+                painter.line(swing, Stroke::new(2.0, color.gamma_multiply(0.5)));
+                let dot = center + angle_vec(position_of(desc, value)) * ring;
+                painter.circle_filled(dot, 2.5, color);
+            }
+            if response.has_focus() {
+                painter.circle_stroke(center, radius + 3.0, ui.visuals().selection.stroke);
+            }
+
+            let text_top = rect.top() + DIAMETER + 2.0;
+            let name_pos = egui::pos2(rect.center().x - name.size().x / 2.0, text_top);
+            painter.galley(name_pos, name, text_color);
+            painter.text(
+                egui::pos2(rect.center().x, text_top + row),
+                egui::Align2::CENTER_TOP,
+                readout(desc, value),
+                font,
+                ui.visuals().weak_text_color(),
+            );
+        }
+
+        response.widget_info(|| {
+            egui::WidgetInfo::slider(ui.is_enabled(), value as f64, desc.name)
+        });
+        response
+    }
+}
+
+/// Horizontal slider for a stepped param, with its value and name to the right. Click or
+/// drag to set it; shift-drag moves it at a tenth of the speed and command-drag one step at
+/// a time. Scrolling over it or the arrow keys step it too.
+pub struct StepSlider<'a> {
+    value: &'a mut f32,
+    desc: &'static ParamDesc,
+}
+
+impl<'a> StepSlider<'a> {
+    pub fn new(value: &'a mut f32, desc: &'static ParamDesc) -> Self {
+        Self { value, desc }
+    }
+}
+
+impl Widget for StepSlider<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let desc = self.desc;
+        let size = egui::vec2(ui.spacing().slider_width, ui.spacing().interact_size.y);
+        let (rect, mut response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+        let track = rect.shrink2(egui::vec2(HANDLE_RADIUS, 0.0));
+
+        let position = position_of(desc, *self.value);
+        let modifiers = ui.input(|i| i.modifiers);
+        let held = response.is_pointer_button_down_on();
+        let pointer = response.interact_pointer_pos().filter(|_| held);
+        let moved = match pointer {
+            // A plain click or drag puts the handle under the pointer; with a modifier held
+            // the drag moves it relative to where it was instead.
+            Some(pos) if !modifiers.shift && !modifiers.command => {
+                let position = ((pos.x - track.left()) / track.width()).clamp(0.0, 1.0);
+                ui.data_mut(|data| data.insert_temp(response.id, position));
+                Some(position)
+            }
+            _ => adjust(ui, &response, desc, position, response.drag_delta().x, track.width()),
+        };
+        if let Some(position) = moved {
+            let value = value_at(desc, position);
+            if value != *self.value {
+                *self.value = value;
+                response.mark_changed();
+            }
+        }
+        let value = *self.value;
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.style().interact(&response);
+            let painter = ui.painter();
+            let y = rect.center().y;
+            let handle = egui::pos2(track.left() + position_of(desc, value) * track.width(), y);
+            let start = egui::pos2(track.left(), y);
+            let fill = ui.visuals().widgets.inactive.bg_fill;
+            painter.line_segment([start, egui::pos2(track.right(), y)], Stroke::new(4.0, fill));
+            painter.line_segment([start, handle], Stroke::new(4.0, ui.visuals().selection.bg_fill));
+            painter.circle(handle, HANDLE_RADIUS, visuals.bg_fill, visuals.fg_stroke);
+            if response.has_focus() {
+                painter.circle_stroke(handle, HANDLE_RADIUS + 3.0, ui.visuals().selection.stroke);
+            }
+        }
+        ui.label(egui::RichText::new(readout(desc, value)).strong());
+        ui.label(desc.name);
+
+        response.widget_info(|| {
+            egui::WidgetInfo::slider(ui.is_enabled(), value as f64, desc.name)
+        });
+        response
+    }
+}
+
+/// Two params on one surface: drag the puck to set both at once (shift-drag to move it
+/// finely), double-click to reset them. X runs logarithmically, for a frequency; Y is
+/// linear. Away from a drag it just shows the values, so it follows automation.
+pub struct XyPad<'a> {
+    x: &'a mut f32,
+    x_desc: &'static ParamDesc,
+    y: &'a mut f32,
+    y_desc: &'static ParamDesc,
+}
+
+/// One of the pad's two params.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+impl<'a> XyPad<'a> {
+    pub fn new(
+        x: &'a mut f32,
+        x_desc: &'static ParamDesc,
+        y: &'a mut f32,
+        y_desc: &'static ParamDesc,
+    ) -> Self {
+        Self { x, x_desc, y, y_desc }
+    }
+
+    /// The axis whose edge of the pad, X's along the bottom or Y's up the left, is nearer
+    /// `pos`.
+    pub fn nearer_axis(rect: Rect, pos: Pos2) -> Axis {
+        if rect.bottom() - pos.y < pos.x - rect.left() { Axis::X } else { Axis::Y }
+    }
+}
+
+impl Widget for XyPad<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, mut response) = ui.allocate_exact_size(PAD_SIZE, Sense::click_and_drag());
+        let (x_desc, y_desc) = (self.x_desc, self.y_desc);
+
+        // Only the primary button moves the puck, and only while it's down on the pad:
+        // otherwise the values are left exactly as they came in, not round-tripped
+        // through the pad's position.
+        let (mut x, mut y) = (*self.x, *self.y);
+        let primary = ui.input(|i| i.pointer.primary_down());
+        if response.double_clicked() {
+            (x, y) = (x_desc.default as f32, y_desc.default as f32);
+        } else if primary && response.is_pointer_button_down_on() {
+            let mut position = egui::vec2(normalize_log(x_desc, x), normalize(y_desc, y));
+            if ui.input(|i| i.modifiers.shift) {
+                let delta = response.drag_delta() / rect.size() * FINE;
+                position += egui::vec2(delta.x, -delta.y);
+            } else if let Some(pos) = response.interact_pointer_pos() {
+                let offset = (pos - rect.left_bottom()) / rect.size();
+                position = egui::vec2(offset.x, -offset.y);
+            }
+            (x, y) = (denormalize_log(x_desc, position.x), denormalize(y_desc, position.y));
+        }
+        if x != *self.x || y != *self.y {
+            (*self.x, *self.y) = (x, y);
+            response.mark_changed();
+        }
+
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter();
+            let visuals = ui.style().interact(&response);
+            painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+            let grid = Stroke::new(1.0, ui.visuals().widgets.noninteractive.bg_stroke.color);
+            for i in 1..4 {
+                let t = i as f32 / 4.0;
+                painter.vline(rect.lerp_inside(egui::vec2(t, 0.0)).x, rect.y_range(), grid);
+                painter.hline(rect.x_range(), rect.lerp_inside(egui::vec2(0.0, t)).y, grid);
+            }
+
+            let puck = egui::vec2(normalize_log(x_desc, x), 1.0 - normalize(y_desc, y));
+            let puck = rect.lerp_inside(puck);
+            let fill = ui.visuals().selection.bg_fill;
+            painter.circle(puck, PUCK_RADIUS, fill, visuals.fg_stroke);
+
+            let font = egui::TextStyle::Small.resolve(ui.style());
+            let color = ui.visuals().weak_text_color();
+            let x_text = format!("{} {}", x_desc.name, readout(x_desc, x));
+            let y_text = format!("{} {}", y_desc.name, readout(y_desc, y));
+            let margin = egui::vec2(3.0, 2.0);
+            let (x_pos, y_pos) = (rect.right_bottom() - margin, rect.left_top() + margin);
+            painter.text(x_pos, egui::Align2::RIGHT_BOTTOM, x_text, font.clone(), color);
+            painter.text(y_pos, egui::Align2::LEFT_TOP, y_text, font, color);
+        }
+
+        response.widget_info(|| {
+            let label = format!("{} / {}", x_desc.name, y_desc.name);
+            egui::WidgetInfo::labeled(egui::WidgetType::Other, ui.is_enabled(), label)
+        });
+        response
+    }
+}
+
+/// Where this frame's drag, arrow keys and mouse wheel leave a control that was at
+/// `position`, or `None` if none of them moved it. `drag` is along the control, in points
+/// towards the maximum, and `drag_range` the drag that sweeps the whole range. A drag is
+/// tracked unrounded across frames, so slow drags of a stepped param still add up.
+fn adjust(
+    ui: &Ui,
+    response: &Response,
+    desc: &ParamDesc,
+    position: f32,
+    drag: f32,
+    drag_range: f32,
+) -> Option<f32> {
+    let modifiers = ui.input(|i| i.modifiers);
+    let fine = if modifiers.shift { FINE } else { 1.0 };
+    // What one key press or wheel notch moves.
+    let step = if desc.is_stepped() && desc.max > desc.min {
+        1.0 / (desc.max - desc.min) as f32
+    } else {
+        NUDGE * fine
+    };
+
+    let mut moved = position;
+    if response.dragged() {
+        if !response.drag_started() {
+            moved = ui.data(|data| data.get_temp(response.id)).unwrap_or(position);
+        }
+        moved += if desc.is_stepped() && modifiers.command {
+            drag / STEP_DRAG * step
+        } else {
+            drag / drag_range * fine
+        };
+        moved = moved.clamp(0.0, 1.0);
+        ui.data_mut(|data| data.insert_temp(response.id, moved));
+    }
+    if response.has_focus() {
+        let filter = egui::EventFilter {
+            horizontal_arrows: true,
+            vertical_arrows: true,
+            ..Default::default()
+        };
+        ui.memory_mut(|memory| memory.set_focus_lock_filter(response.id, filter));
+        let presses = ui.input(|i| {
+            (i.num_presses(Key::ArrowUp) + i.num_presses(Key::ArrowRight)) as f32
+                - (i.num_presses(Key::ArrowDown) + i.num_presses(Key::ArrowLeft)) as f32
+        });
+        moved += presses * step;
+    }
+    if response.hovered() {
+        // egui turns shift-scrolling sideways, hence both axes.
+        let scroll = ui.input(|i| i.raw_scroll_delta);
+        let notches = (scroll.x + scroll.y) / WHEEL_NOTCH;
+        if notches != 0.0 {
+            // Taken, so the scroll area around the control stays put.
+            ui.input_mut(|i| {
+                i.raw_scroll_delta = egui::Vec2::ZERO;
+                i.smooth_scroll_delta = egui::Vec2::ZERO;
+            });
+            moved += notches * step;
+        }
+    }
+    (moved != position).then_some(moved.clamp(0.0, 1.0))
+}
+
+/// A control's value as the editor shows it: exactly the host's text for it.
+pub fn readout(desc: &ParamDesc, value: f32) -> String {
+    value_text(desc.id, value as f64)
+}
+
+/// A typed value, read the same way as text the host hands over.
+pub fn typed_value(desc: &ParamDesc, text: &str) -> Option<f32> {
+    text_value(desc.id, text).map(|value| value as f32)
+}
+
+/// Params measured in frequency are shown and moved on a log scale, so equal movements are
+/// equal intervals. The range must be above zero.
+fn is_log(desc: &ParamDesc) -> bool {
+    desc.unit == Unit::Hertz && desc.min > 0.0
+}
+
+/// Position of `value` along the param's range as a control shows it, from 0.0 to 1.0.
+fn position_of(desc: &ParamDesc, value: f32) -> f32 {
+    if is_log(desc) { normalize_log(desc, value) } else { normalize(desc, value) }
+}
+
+/// Inverse of [`position_of`], rounded to a whole step for stepped params.
+fn value_at(desc: &ParamDesc, position: f32) -> f32 {
+    let value = if is_log(desc) {
+        denormalize_log(desc, position)
+    } else {
+        denormalize(desc, position)
+    };
+    if desc.is_stepped() { value.round() } else { value }
+}
+
+/// Position of `value` within the param's range, from 0.0 to 1.0.
+fn normalize(desc: &ParamDesc, value: f32) -> f32 {
+    let (min, max) = (desc.min as f32, desc.max as f32);
+    if max > min { ((value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 }
+}
+
+/// Inverse of [`normalize`], clamping to the range.
+fn denormalize(desc: &ParamDesc, position: f32) -> f32 {
+    let (min, max) = (desc.min as f32, desc.max as f32);
+    min + position.clamp(0.0, 1.0) * (max - min)
+}
+
+/// [`normalize`] on a log scale, for params measured in frequency. The range must be
+/// above zero.
+fn normalize_log(desc: &ParamDesc, value: f32) -> f32 {
+    let (min, max) = (desc.min as f32, desc.max as f32);
+    ((value.clamp(min, max) / min).ln() / (max / min).ln()).clamp(0.0, 1.0)
+}
+
+fn denormalize_log(desc: &ParamDesc, position: f32) -> f32 {
+    let (min, max) = (desc.min as f32, desc.max as f32);
+    (min * (max / min).powf(position.clamp(0.0, 1.0))).clamp(min, max)
+}
+
+/// Unit vector pointing at `position` on the knob's sweep.
+fn angle_vec(position: f32) -> egui::Vec2 {
+    egui::Vec2::angled(START_ANGLE + position * SWEEP)
+}
+
+/// Points along the sweep from `from` to `to` (both 0.0 to 1.0).
+fn arc(center: Pos2, radius: f32, from: f32, to: f32) -> Vec<Pos2> {
+    const SEGMENTS: usize = 32;
+    (0..=SEGMENTS)
+        .map(|i| from + (to - from) * i as f32 / SEGMENTS as f32)
+        .map(|position| center + angle_vec(position) * radius)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{
+        param_desc, PARAMS, PARAM_CUTOFF_ID, PARAM_GAIN_ID, PARAM_LFO_RATE_IDS,
+        PARAM_MAX_VOICES_ID, PARAM_PITCH_ENV_AMOUNT_ID,
+    };
+
+    #[test]
+    fn readouts_match_the_host_text() {
+        for desc in PARAMS {
+            for value in [desc.min, desc.default, desc.max] {
+                // The host reads values back as the f32 the params store.
+                let value = value as f32;
+                let text = readout(desc, value);
+                assert_eq!(text, desc.format(value as f64), "{}", desc.name);
+                if let Some(label) = desc.label(value as f64) {
+                    assert_eq!(text, label, "{}", desc.name);
+                }
+                let host = desc.parse(&text).map(|value| value as f32);
+                assert_eq!(typed_value(desc, &text), host, "{}: {text:?}", desc.name);
+            }
+        }
+    }
+
+    #[test]
+    fn positions_span_the_param_range() {
+        let desc = param_desc(PARAM_PITCH_ENV_AMOUNT_ID).unwrap();
+        assert_eq!(denormalize(desc, 0.0), desc.min as f32);
+        assert_eq!(denormalize(desc, 1.0), desc.max as f32);
+        assert_eq!(normalize(desc, denormalize(desc, 0.25)), 0.25);
+    }
+
+    #[test]
+    fn pad_x_is_logarithmic() {
+        let desc = param_desc(PARAM_CUTOFF_ID).unwrap();
+        assert_eq!(denormalize_log(desc, 0.0), 20.0);
+        assert!((denormalize_log(desc, 0.5) - 632.5).abs() < 0.1);
+        assert!((normalize_log(desc, 2000.0) - 2.0 / 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn pad_menu_goes_to_the_nearer_edge() {
+        let rect = Rect::from_min_size(Pos2::ZERO, PAD_SIZE);
+        assert_eq!(XyPad::nearer_axis(rect, egui::pos2(100.0, 110.0)), Axis::X);
+        assert_eq!(XyPad::nearer_axis(rect, egui::pos2(10.0, 60.0)), Axis::Y);
+    }
+
+    #[test]
+    fn frequency_controls_move_in_octaves() {
+        let rate = param_desc(PARAM_LFO_RATE_IDS[0]).unwrap();
+        let octave = position_of(rate, 2.0) - position_of(rate, 1.0);
+        assert!((position_of(rate, 16.0) - position_of(rate, 8.0) - octave).abs() < 1e-5);
+        assert!((value_at(rate, position_of(rate, 1.0)) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn stepped_controls_land_on_whole_steps() {
+        let voices = param_desc(PARAM_MAX_VOICES_ID).unwrap();
+        let value = value_at(voices, 0.49);
+        assert_eq!(value, value.round());
+        assert_eq!(value_at(voices, 1.0), voices.max as f32);
+    }
+
+    #[test]
+    fn positions_clamp_to_the_range() {
+        let desc = param_desc(PARAM_GAIN_ID).unwrap();
+        assert_eq!(normalize(desc, 2.0), 1.0);
+        assert_eq!(denormalize(desc, -0.5), desc.min as f32);
+    }
+}
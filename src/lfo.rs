@@ -0,0 +1,39 @@
+use std::f32::consts::TAU;
+
+/// Number of independent LFOs.
+pub const NUM_LFOS: usize = 2;
+
+/// LFO waveforms, indexed by the LFO shape params.
+pub const LFO_SHAPE_NAMES: &[&str] = &["Sine", "Triangle", "Saw", "Square"];
+
+/// Low-frequency oscillator, advanced once per block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lfo {
+    phase: f32, // 0.0 to 1.0
+}
+
+impl Lfo {
+    /// Bipolar output (-1.0 to 1.0) at the current phase.
+    pub fn value(&self, shape: usize) -> f32 {
+        let phase = self.phase;
+        match shape {
+            1 => 1.0 - 4.0 * (phase - 0.5).abs(),
+            2 => 2.0 * phase - 1.0,
+            3 => if phase < 0.5 { 1.0 } else { -1.0 },
+            _ => (phase * TAU).sin(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Jumps to `phase`, from 0.0 to 1.0.
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase.fract();
+    }
+
+    pub fn advance(&mut self, rate: f32, frames: usize, sample_rate: f32) {
+        self.phase = (self.phase + rate * frames as f32 / sample_rate).fract();
+    }
+}
@@ -1,10 +1,51 @@
+mod auto_pan;
+mod chord;
+mod comb;
+mod denormals;
+mod editor;
+mod engine;
+mod envelope;
+mod filter;
+mod gain_law;
 mod gui;
+mod lfo;
+mod limiter;
+mod load;
+mod main_queue;
+mod meter;
+mod midi_activity;
+mod midi_learn;
+mod mod_matrix;
+mod noise;
+mod note_queue;
+mod oversampling;
+mod param_indication;
 mod params;
+mod patch;
+mod pitch;
+mod pluck;
+mod reverb;
+mod sample;
+mod scope;
+mod smoother;
+mod split;
+mod thread_check;
+mod thread_pool;
+mod track_info;
+mod transport;
+mod triple_buffer;
+mod vis;
+mod voice;
 
 use std::ffi::CStr;
+use std::io::{Read, Write};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Instant;
 
+use clack_plugin::events::event_types::{NoteExpressionEvent, NoteExpressionType, TransportEvent};
 use clack_plugin::events::spaces::CoreEventSpace;
+use clack_plugin::events::UnknownEvent;
 use clack_plugin::prelude::*;
 use clack_plugin::{
     clack_export_entry,
@@ -14,142 +55,962 @@ use clack_plugin::{
         Plugin, PluginAudioProcessor, PluginDescriptor, PluginError, PluginMainThread, PluginShared,
     },
     process::{Audio, Events, PluginAudioConfiguration, Process, ProcessStatus},
+    process::audio::{PairedChannels, SampleType},
+    stream::{InputStream, OutputStream},
 };
 
 // Extension imports
 use clack_extensions::audio_ports::{
-    AudioPortFlags, AudioPortInfo, AudioPortInfoWriter, AudioPortType, PluginAudioPorts,
-    PluginAudioPortsImpl,
+    AudioPortFlags, AudioPortInfo, AudioPortInfoWriter, AudioPortType, HostAudioPorts,
+    PluginAudioPorts, PluginAudioPortsImpl, RescanType,
 };
 use clack_extensions::note_ports::{
-    PluginNotePorts, NotePortInfo, NotePortInfoWriter, PluginNotePortsImpl, NoteDialect
+    HostNotePorts, NotePortRescanFlags, PluginNotePorts, NotePortInfo, NotePortInfoWriter,
+    PluginNotePortsImpl, NoteDialect, NoteDialects
 };
-use clack_extensions::gui::{GuiApiType, GuiConfiguration, GuiSize, PluginGui, PluginGuiImpl, Window};
+use clack_extensions::context_menu::{
+    ContextMenuBuilder, ContextMenuEntry, ContextMenuItem, ContextMenuTarget, HostContextMenu,
+    PluginContextMenu, PluginContextMenuImpl,
+};
+use clack_extensions::gui::{
+    AspectRatioStrategy, GuiApiType, GuiConfiguration, GuiResizeHints, GuiSize, HostGui, PluginGui,
+    PluginGuiImpl, Window,
+};
+use clack_extensions::latency::{HostLatency, PluginLatency, PluginLatencyImpl};
 use clack_extensions::params::{
-    ParamDisplayWriter, ParamInfo, ParamInfoFlags, ParamInfoWriter, PluginAudioProcessorParams,
-    PluginMainThreadParams, PluginParams,
+    HostParams, ParamDisplayWriter, ParamInfo, ParamInfoFlags, ParamInfoWriter,
+    ParamRescanFlags, PluginAudioProcessorParams, PluginMainThreadParams, PluginParams,
+};
+use clack_extensions::param_indication::{
+    ParamIndicationAutomation, PluginParamIndication, PluginParamIndicationImpl,
+};
+use clack_extensions::remote_controls::{
+    HostRemoteControls, PluginRemoteControls, PluginRemoteControlsImpl, RemoteControlsPage,
+    RemoteControlsPageWriter,
+};
+use clack_extensions::state::{PluginState, PluginStateImpl};
+use clack_extensions::thread_pool::{HostThreadPool, PluginThreadPool, PluginThreadPoolImpl};
+use clack_extensions::timer::{HostTimer, PluginTimer, PluginTimerImpl, TimerId};
+use clack_extensions::track_info::{HostTrackInfo, PluginTrackInfo, PluginTrackInfoImpl};
+use clack_extensions::voice_info::{
+    HostVoiceInfo, PluginVoiceInfo, PluginVoiceInfoImpl, VoiceInfo, VoiceInfoFlags,
 };
 
+use clack_plugin::utils::Color;
+use baseview::PhySize;
 use raw_window_handle::HasRawWindowHandle;
 
-use crate::gui::CaveGui;
-use crate::params::{Params as CaveParams, PARAM_GAIN_ID};
+use crate::denormals::FlushDenormals;
+use crate::editor::Editor;
+pub use crate::engine::CaveEngine;
+pub use crate::params::{
+    PARAM_AUTO_PAN_DEPTH_ID, PARAM_AUTO_PAN_ON_ID, PARAM_COMB_MIX_ID, PARAM_COMB_ON_ID,
+    PARAM_REVERB_ON_ID,
+};
+use crate::gui::{AudioInfo, CaveGui, GuiBridge, GuiRequest, GuiState};
+use crate::param_indication::{AutomationState, SharedIndications};
+use crate::params::{
+    param_desc, remote_pages, text_value, value_text, ParamChange, Params as CaveParams, PARAMS,
+    PARAM_SPLIT_POINT_ID,
+};
+use crate::thread_check::ThreadCheck;
+use crate::thread_pool::VoiceTasks;
+use crate::track_info::{SharedTrackInfo, TrackInfo};
+use crate::transport::Transport;
+use crate::main_queue::{MainQueue, MainThreadMessage};
+use crate::limiter::Limiter;
+use crate::load::ProcessLoad;
+use crate::meter::{BlockLevels, MeterWriter};
+use crate::midi_learn::{MIDI_ALL_SOUND_OFF, MIDI_CONTROL_CHANGE};
+use crate::note_queue::{GuiNote, NoteQueue};
+use crate::sample::{FromSample, Sample};
+use crate::vis::VisChannel;
+use crate::voice::MAX_VOICES;
 
 pub struct Cave;
 
+/// The crate version, plus the git hash it was built from when there was one. The host
+/// gets it in the descriptor and the editor shows it in its about panel.
+pub const VERSION: &str = env!("CAVE_VERSION");
+pub const PLUGIN_ID: &str = "com.razboy.cave";
+pub const PLUGIN_NAME: &str = "Cave";
+pub const VENDOR: &str = "razboy";
+pub const URL: &str = "https://github.com/BugsAplenty/cave";
+
 pub struct CaveShared {
     params: Arc<CaveParams>,
+    track_info: Arc<SharedTrackInfo>,
+    indications: Arc<SharedIndications>,
+    gui_bridge: Arc<GuiBridge>,
+    /// Voice rendering handed to the host's thread pool, see `CaveAudioProcessor::render`.
+    voice_tasks: VoiceTasks,
+    /// Audio-thread events that need main-thread follow-up, drained in `on_main_thread`.
+    main_queue: MainQueue,
+    /// Notes played on the editor's keyboard, drained at the start of each block.
+    gui_notes: Arc<NoteQueue>,
+    /// Recent output and levels for the editor's scope, spectrum and meter.
+    vis: Arc<VisChannel>,
+    /// Voice count and DSP load for the editor's header.
+    load: Arc<ProcessLoad>,
 }
 
 impl Default for CaveShared {
     fn default() -> Self {
         Self {
             params: Arc::new(CaveParams::default()),
+            track_info: Arc::new(SharedTrackInfo::default()),
+            indications: Arc::new(SharedIndications::default()),
+            gui_bridge: Arc::new(GuiBridge::default()),
+            voice_tasks: VoiceTasks::default(),
+            main_queue: MainQueue::default(),
+            gui_notes: Arc::new(NoteQueue::default()),
+            vis: Arc::new(VisChannel::default()),
+            load: Arc::new(ProcessLoad::default()),
         }
     }
 }
 
+impl CaveShared {
+    /// Main thread, while deactivated. Clears what the last audio thread left for the
+    /// editor: the displays and load go quiet, no keys show as sounding, and notes played on
+    /// the editor meanwhile, and a panic, are dropped rather than acted on at activation.
+    fn clear_audio_state(&self) {
+        self.vis.clear_idle();
+        self.load.clear();
+        self.gui_notes.set_sounding(std::iter::empty());
+        self.gui_notes.drain(|_| {});
+        self.gui_bridge.panic.store(false, Ordering::Relaxed);
+        self.gui_bridge.request_repaint();
+    }
+
+    fn gui_state(&self) -> GuiState {
+        GuiState::new(
+            self.params.clone(),
+            self.track_info.clone(),
+            self.indications.clone(),
+            self.gui_bridge.clone(),
+            self.gui_notes.clone(),
+            self.vis.clone(),
+            self.load.clone(),
+        )
+    }
+}
+
 impl<'a> PluginShared<'a> for CaveShared {}
 
 pub struct CaveMainThread<'a> {
     shared: &'a CaveShared,
-    gui: CaveGui,
+    host: HostMainThreadHandle<'a>,
+    thread_check: ThreadCheck<'a>,
+    host_track_info: Option<HostTrackInfo>,
+    host_audio_ports: Option<HostAudioPorts>,
+    track_info: Option<TrackInfo>,
+    /// Output port layout: mono when the host track is mono, stereo otherwise.
+    mono_output: bool,
+    host_note_ports: Option<HostNotePorts>,
+    /// Played notes are echoed on a note output port.
+    note_thru: bool,
+    is_active: bool,
+    host_params: Option<HostParams>,
+    host_timer: Option<HostTimer>,
+    host_context_menu: Option<HostContextMenu>,
+    host_voice_info: Option<HostVoiceInfo>,
+    host_gui: Option<HostGui>,
+    host_latency: Option<HostLatency>,
+    host_remote_controls: Option<HostRemoteControls>,
+    /// Samples of latency last reported to the host: the oversampling decimators' delay.
+    latency: u32,
+    /// Polls the editor's requests while the GUI exists.
+    gui_timer: Option<TimerId>,
+    gui: Editor<CaveGui>,
+}
+
+impl<'a> PluginMainThread<'a, CaveShared> for CaveMainThread<'a> {
+    fn on_main_thread(&mut self) {
+        let bridge = &self.shared.gui_bridge;
+        let mut voice_info_changed = false;
+        let mut oversampling_changed = false;
+        let mut remote_controls_changed = false;
+        self.shared.main_queue.drain(|message| match message {
+            MainThreadMessage::VoiceStolen { key } => {
+                eprintln!("[cave] voice pool exhausted, key {key} stole a voice");
+                bridge.voice_stolen();
+            }
+            MainThreadMessage::VoiceLimitChanged => voice_info_changed = true,
+            MainThreadMessage::OversamplingChanged => oversampling_changed = true,
+            MainThreadMessage::RemoteControlsChanged => remote_controls_changed = true,
+        });
+        if let (true, Some(voice_info)) = (voice_info_changed, self.host_voice_info) {
+            voice_info.changed(&mut self.host);
+        }
+        if remote_controls_changed {
+            self.remote_controls_changed();
+        }
+        // Picked up, and the new latency reported, in the next `activate`.
+        if oversampling_changed && self.is_active {
+            self.host.shared().request_restart();
+        }
+
+        let dropped = self.shared.main_queue.dropped();
+        if dropped > 0 {
+            eprintln!("[cave] {dropped} main-thread messages dropped so far");
+        }
+    }
 }
 
-impl<'a> PluginMainThread<'a, CaveShared> for CaveMainThread<'a> {}
+/// How often the main thread picks up requests from the editor.
+const GUI_TIMER_PERIOD_MS: u32 = 30;
+
+impl<'a> CaveMainThread<'a> {
+    fn handle_gui_request(&mut self, request: GuiRequest) {
+        match request {
+            GuiRequest::ContextMenu { param_id, x, y } => {
+                let Some(menu) = self.host_context_menu else { return };
+                let target = ContextMenuTarget::Param(ClapId::new(param_id));
+                if menu.popup(&mut self.host, target, 0, x, y).is_err() {
+                    eprintln!("[cave-gui] host refused to pop up the context menu");
+                }
+            }
+            GuiRequest::NotePortsChanged => self.apply_note_port_layout(),
+            GuiRequest::Resize(size) => self.request_editor_resize(size),
+        }
+    }
+
+    /// Asks the host for the size the editor's layout wants. Hosts may say no, Reaper for
+    /// some window setups, and then the editor keeps its size and scrolls.
+    fn request_editor_resize(&mut self, size: PhySize) {
+        let Some(size) = self.gui.request_resize(size) else { return };
+        let accepted = self.host_gui.is_some_and(|gui| {
+            gui.request_resize(&self.host.shared(), size.width, size.height).is_ok()
+        });
+        if accepted {
+            self.gui.confirm_resize();
+        } else {
+            eprintln!("[cave-gui] host refused to resize the editor to {size:?}");
+            self.gui.refuse_resize();
+        }
+    }
+
+    /// Brings the note output port in line with the editor's note thru toggle. Ports may
+    /// only change while we're deactivated, so an active plugin asks the host to restart
+    /// it and finishes the job in `deactivate`.
+    fn apply_note_port_layout(&mut self) {
+        let note_thru = self.shared.gui_bridge.note_thru.load(Ordering::Relaxed);
+        if note_thru == self.note_thru {
+            return;
+        }
+        if self.is_active {
+            self.host.shared().request_restart();
+            return;
+        }
+
+        self.note_thru = note_thru;
+        self.rescan_note_ports(NotePortRescanFlags::ALL);
+    }
+
+    fn rescan_note_ports(&mut self, flags: NotePortRescanFlags) {
+        if let Some(note_ports) = self.host_note_ports {
+            note_ports.rescan(&mut self.host, flags);
+        }
+    }
+
+    /// Applies a value changed from the main thread and passes it on to the host.
+    fn set_param_from_main_thread(&mut self, param_id: u32, value: f64) {
+        self.shared.params.change_as_gesture(param_id, value as f32);
+        self.request_param_flush();
+    }
+
+    /// Has the host collect params changed on our side, through `flush` if it isn't
+    /// processing.
+    fn request_param_flush(&self) {
+        if let Some(host_params) = self.host_params {
+            host_params.request_flush(&self.host.shared());
+        }
+    }
+
+    /// Has the host re-read the remote pages, whose controls follow the mod routing.
+    fn remote_controls_changed(&mut self) {
+        if let Some(remote_controls) = self.host_remote_controls {
+            remote_controls.changed(&mut self.host);
+        }
+    }
+
+    /// Re-reads the track info from the host and forwards the bits the GUI cares about.
+    fn refresh_track_info(&mut self) {
+        let Some(ext) = self.host_track_info else { return };
+
+        self.track_info = ext.get(&mut self.host).map(|info| TrackInfo::from_host(&info));
+
+        if let Some(info) = &self.track_info {
+            eprintln!(
+                "[cave] track info: name={:?} color={:?} channels={:?}",
+                info.name, info.color, info.channel_count
+            );
+        }
+
+        if let Ok(mut shared) = self.shared.track_info.lock() {
+            shared.clone_from(&self.track_info);
+        }
+        self.shared.gui_bridge.request_repaint();
+        self.apply_output_layout();
+    }
+
+    /// Brings the output port in line with the track's channel layout, mono or stereo.
+    /// Like the note ports, it may only change while we're deactivated, so an active plugin
+    /// asks the host to restart it and finishes the job in `deactivate`.
+    fn apply_output_layout(&mut self) {
+        let mono = self.track_info.as_ref().is_some_and(TrackInfo::is_mono);
+        if mono == self.mono_output {
+            return;
+        }
+        if self.is_active {
+            self.host.shared().request_restart();
+            return;
+        }
+
+        self.mono_output = mono;
+        if let Some(audio_ports) = self.host_audio_ports {
+            let flags = RescanType::CHANNEL_COUNT | RescanType::PORT_TYPE;
+            audio_ports.rescan(&mut self.host, flags);
+        }
+    }
+}
 
 pub struct CaveAudioProcessor<'a> {
     shared: &'a CaveShared,
-    phase: f32,       // 0.0 to 1.0
-    frequency: f32,   // Hz
+    /// `None` when driven without a host, as in the tests and benchmarks.
+    host: Option<HostAudioProcessorHandle<'a>>,
+    thread_check: ThreadCheck<'a>,
+    host_thread_pool: Option<HostThreadPool>,
+    engine: CaveEngine,
+    /// The mono mix for the block being processed, sized for the largest block at activate.
+    mix_buffer: Vec<Sample>,
+    /// Left and right, spread from the mix once a block for every stereo port to copy out.
+    stereo_buffers: [Vec<Sample>; 2],
+    /// The output's safety net, when the limiter param is on.
+    limiter: Limiter,
+    /// Echo note on/off to the note output port; fixed for the whole activation.
+    note_thru: bool,
+    /// Something went into the main queue this block, so the host should call us back.
+    callback_pending: bool,
+    /// The main thread was asked to have the host restart us; see
+    /// [`follow_oversampling`](Self::follow_oversampling).
+    restart_requested: bool,
+    /// [`CaveParams::mod_routing`] as the main thread last heard it.
+    mod_routing: u32,
+    /// Phase of the diagnostic test tone, or `None` when it's off. See [`TEST_TONE_ENV`].
+    test_tone: Option<f32>,
     sample_rate: f32, // Hz
-    note_on: bool,    // Is key pressed?
+    /// Longest block the host said it would send; every buffer is sized for it up front.
+    max_frames: usize,
+    /// Tempo, song position and the rest, as the host last reported them.
+    transport: Transport,
+    /// Frame of this block the transport was last reported at, so the block's end moves the
+    /// position on only from there.
+    transport_at: u32,
+    /// The meter's writing end, taken from [`VisChannel`] when built and handed back at
+    /// deactivate. `None` for a second processor on the same plugin, as in some tests.
+    meter: Option<MeterWriter>,
+    /// Whether blocks run under [`FlushDenormals`]. Always, but in the benchmark of what
+    /// that saves.
+    flush_denormals: bool,
+}
+
+/// Key a note-on for any key plays: middle C.
+const WILDCARD_KEY: u8 = 60;
+
+// Host-free entry points: `process` translates CLAP events into these, and tests and
+// benchmarks drive them directly.
+impl<'a> CaveAudioProcessor<'a> {
+    /// Everything that hangs on the sample rate or the block size is built here: the
+    /// voices' delay lines and every buffer, sized for `max_frames`, and the engine's rate
+    /// for the envelope, glide and fade steps. `activate` builds a new processor each time,
+    /// so a host reactivating at another rate gets all of it over again.
+    pub fn new(shared: &'a CaveShared, sample_rate: f32, max_frames: usize) -> Self {
+        Self {
+            shared,
+            host: None,
+            thread_check: ThreadCheck::default(),
+            host_thread_pool: None,
+            engine: CaveEngine::with_params(shared.params.clone(), sample_rate, max_frames),
+            mix_buffer: vec![0.0; max_frames],
+            stereo_buffers: [vec![0.0; max_frames], vec![0.0; max_frames]],
+            limiter: Limiter::new(sample_rate),
+            note_thru: false,
+            callback_pending: false,
+            restart_requested: false,
+            mod_routing: shared.params.mod_routing(),
+            test_tone: None,
+            sample_rate,
+            max_frames,
+            transport: Transport::default(),
+            transport_at: 0,
+            meter: shared.vis.take_meter(),
+            flush_denormals: true,
+        }
+    }
+
+    /// Whether every buffer has room for a block of `max_frames`, so `process` never needs
+    /// to allocate.
+    fn is_sized(&self) -> bool {
+        let buffers = self.stereo_buffers.iter().chain([&self.mix_buffer]);
+        buffers.map(Vec::len).chain([self.engine.max_frames()]).all(|len| len >= self.max_frames)
+    }
+
+    /// Starts the engine's voices for `key`, telling the main thread when one was stolen.
+    pub fn note_on(&mut self, key: u8, velocity: f32) {
+        self.apply_voice_limit();
+        if self.engine.note_on(key, velocity) {
+            self.shared.main_queue.push(MainThreadMessage::VoiceStolen { key });
+            self.callback_pending = true;
+        }
+    }
+
+    /// Follows the max voices param. Voices over a lowered limit are released, and the main
+    /// thread is told so it can have the host re-read our voice info.
+    fn apply_voice_limit(&mut self) {
+        if self.engine.apply_voice_limit() {
+            self.shared.main_queue.push(MainThreadMessage::VoiceLimitChanged);
+            self.callback_pending = true;
+        }
+    }
+
+    /// Asks the main thread, once, to have the host restart us when the oversampling param
+    /// no longer matches the engine's. The engine is built with it at activate, since the
+    /// latency it sets may only change then.
+    fn follow_oversampling(&mut self) {
+        let oversampling = self.shared.params.oversampling();
+        if self.restart_requested || oversampling == self.engine.oversampling() {
+            return;
+        }
+        self.shared.main_queue.push(MainThreadMessage::OversamplingChanged);
+        self.callback_pending = true;
+        self.restart_requested = true;
+    }
+
+    /// Tells the main thread when a mod slot gains or loses its source, so the host can
+    /// re-read the remote pages, which only show routed slots' destinations and amounts.
+    fn follow_mod_routing(&mut self) {
+        let routing = self.shared.params.mod_routing();
+        if routing != self.mod_routing {
+            self.mod_routing = routing;
+            self.shared.main_queue.push(MainThreadMessage::RemoteControlsChanged);
+            self.callback_pending = true;
+        }
+    }
+
+    /// Raw MIDI from the note input. Only control changes are used: All Sound Off panics,
+    /// and the rest go to MIDI learn.
+    fn handle_midi(&mut self, [status, number, value]: [u8; 3], output: &mut OutputEvents) {
+        self.shared.gui_bridge.midi_activity.event();
+        if status & 0xf0 != MIDI_CONTROL_CHANGE {
+            return;
+        }
+        if number == MIDI_ALL_SOUND_OFF {
+            self.panic(output);
+        } else {
+            self.shared.gui_bridge.midi_learn.handle_cc(number, value, &self.shared.params);
+        }
+    }
+
+    /// Fades out every voice, for the editor's Panic button or All Sound Off, and lets go
+    /// of every note echoed on the note output port too.
+    fn panic(&mut self, output: &mut OutputEvents) {
+        self.engine.panic();
+        if self.note_thru {
+            let _ = output.try_push(NoteOffEvent::new(0, Pckn::match_all(), 0.0));
+        }
+    }
+
+    /// If the editor armed split learn, moves the split point to `key` and returns the new
+    /// param value so `process` can tell the host.
+    pub fn learn_split_point(&mut self, key: u8) -> Option<f64> {
+        if !self.shared.gui_bridge.split_learn.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+        self.shared.params.set_value(PARAM_SPLIT_POINT_ID, key as f32);
+        Some(key as f64)
+    }
+
+    /// Plays the block's events into `mix`, rendering up to each one's time first so it
+    /// lands on its own sample, then renders the rest of the block.
+    fn play_events(&mut self, mix: &mut [Sample], input: &InputEvents, output: &mut OutputEvents) {
+        let mut rendered = 0;
+        for batch in input.batch() {
+            for event in batch.events() {
+                self.render_until(mix, &mut rendered, event.header().time() as usize);
+                self.handle_event(event, output);
+            }
+        }
+        self.render_until(mix, &mut rendered, mix.len());
+    }
+
+    fn handle_event(&mut self, event: &UnknownEvent, output: &mut OutputEvents) {
+        if let Some(event) = event.as_core_event() {
+            use clack_plugin::events::spaces::CoreEventSpace::*;
+            match event {
+                NoteOn(e) => {
+                    let velocity = e.velocity() as f32;
+                    // Any key: a host's test trigger or a drum lane without a pitch. Plays
+                    // WILDCARD_KEY rather than nothing; split learn leaves it alone, having
+                    // no key to learn. A note-off for any key then releases it along with
+                    // everything else.
+                    let specific = match e.key() {
+                        Match::Specific(key) => Some(key as u8),
+                        Match::All => None,
+                    };
+                    let key = specific.unwrap_or(WILDCARD_KEY);
+                    self.shared.gui_bridge.midi_activity.note_on(key, velocity);
+                    if let Some(value) = specific.and_then(|key| self.learn_split_point(key)) {
+                        let _ = output.try_push(ParamValueEvent::new(
+                            e.header().time(),
+                            ClapId::new(PARAM_SPLIT_POINT_ID),
+                            Pckn::match_all(),
+                            value,
+                            Cookie::empty(),
+                        ));
+                    }
+                    self.note_on(key, velocity);
+                    if self.note_thru {
+                        let _ = output.try_push(e);
+                    }
+                }
+                NoteOff(e) => {
+                    self.shared.gui_bridge.midi_activity.event();
+                    match e.key() {
+                        Match::Specific(key) => self.note_off(key as u8),
+                        Match::All => self.all_notes_off(),
+                    }
+                    if self.note_thru {
+                        let _ = output.try_push(e);
+                    }
+                }
+                ParamValue(e) => self.shared.params.handle_param_value_event(e),
+                ParamMod(e) => self.shared.params.handle_param_mod_event(e),
+                Transport(e) => {
+                    self.transport.update(e);
+                    self.transport_at = e.header().time();
+                }
+                Midi(e) => self.handle_midi(e.data(), output),
+                _ => {}
+            }
+        }
+    }
+
+    /// Plays what the editor's keyboard queued up, echoing it on the note output port so
+    /// the host can record it.
+    fn play_gui_notes(&mut self, output: &mut OutputEvents) {
+        let notes = self.shared.gui_notes.clone();
+        notes.drain(|note| match note {
+            GuiNote::On { key, velocity } => {
+                self.note_on(key, velocity);
+                if self.note_thru {
+                    let pckn = Pckn::new(0u16, 0u16, key as u16, Match::All);
+                    let _ = output.try_push(NoteOnEvent::new(0, pckn, velocity as f64));
+                }
+            }
+            GuiNote::Off { key } => {
+                self.note_off(key);
+                if self.note_thru {
+                    let pckn = Pckn::new(0u16, 0u16, key as u16, Match::All);
+                    let _ = output.try_push(NoteOffEvent::new(0, pckn, 0.0));
+                }
+            }
+        });
+    }
+
+    /// Reports the pitch envelope and mod matrix's pitch modulation to the host as tuning
+    /// note expressions (semitones off each sounding key), stamped at `time`, the block's
+    /// last frame. Tuning is the only expression sent: velocity, pan and the rest aren't
+    /// modulated per voice. Only sent with the note output port, which carries the notes
+    /// the expressions belong to.
+    fn push_tuning_expressions(&mut self, output: &mut OutputEvents, time: u32) {
+        self.engine.tuning_changes(|key, tuning| {
+            let pckn = Pckn::new(0u16, 0u16, key as u16, Match::All);
+            let _ = output.try_push(NoteExpressionEvent::new(
+                time,
+                pckn,
+                NoteExpressionType::Tuning,
+                tuning as f64,
+            ));
+        });
+    }
+
+    /// Fills `buffer` with the diagnostic test tone, at the master gain.
+    pub fn render_test_tone(&mut self, buffer: &mut [Sample]) {
+        let Some(phase) = self.test_tone.as_mut() else { return };
+        let gain = self.shared.params.gain_factor() * TEST_TONE_LEVEL;
+        let step = A4_FREQ / self.sample_rate;
+        for sample in buffer.iter_mut() {
+            *sample = Sample::from((*phase * std::f32::consts::TAU).sin() * gain);
+            *phase = (*phase + step).fract();
+        }
+    }
+
+    /// Releases every voice `key` started, chord tones included.
+    pub fn note_off(&mut self, key: u8) {
+        self.engine.note_off(key);
+    }
+
+    /// Releases every voice, for a note-off that matches any key.
+    pub fn all_notes_off(&mut self) {
+        self.engine.all_notes_off();
+    }
+
+    /// Runs the blocks that follow with or without [`FlushDenormals`], so the benchmarks can
+    /// time a tail decayed into the denormal range as the audio thread would pay for it.
+    #[doc(hidden)]
+    pub fn set_flush_denormals(&mut self, flush: bool) {
+        self.flush_denormals = flush;
+    }
+
+    /// One block of the mono mix, in signal-flow order:
+    ///
+    /// 1. voices: oscillator, then the per-voice comb, then the amp envelope
+    /// 2. summed into `buffer` by [`render`](Self::render)
+    /// 3. master effects, which run once on the mix rather than per voice
+    /// 4. master gain
+    ///
+    /// `process` then spreads the mix over the output channels, panning voices by key and
+    /// auto-panning when stereo, crossfades it all with the dry voices by the FX mix, and
+    /// runs the limiter last of all.
+    ///
+    /// `buffer` is the part of the block from frame `at`: `process` renders up to each
+    /// event's time, so the block comes in pieces.
+    pub fn render_mix(&mut self, buffer: &mut [Sample], at: usize) {
+        self.render_at(buffer, at);
+        self.engine.master_chain(buffer, at);
+    }
+
+    /// Renders the synth voices into `buffer`, overwriting whatever was there. Spreads
+    /// them over the host's thread pool when it offers one.
+    pub fn render(&mut self, buffer: &mut [Sample]) {
+        self.render_at(buffer, 0);
+    }
+
+    fn render_at(&mut self, buffer: &mut [Sample], at: usize) {
+        let render = self.engine.advance_modulation(buffer.len());
+        if let (Some(pool), Some(mut host)) = (self.host_thread_pool, self.host.take()) {
+            let tasks = &self.shared.voice_tasks;
+            let pooled = self.engine.render_voices_pooled(buffer, at, render, tasks, |tasks| {
+                pool.request_exec(&mut host, tasks).is_ok()
+            });
+            self.host = Some(host);
+            if pooled {
+                return;
+            }
+        }
+        self.engine.render_voices(buffer, at, &render);
+    }
+
+    /// [`render`](Self::render) without the thread pool.
+    pub fn render_serial(&mut self, buffer: &mut [Sample]) {
+        let render = self.engine.advance_modulation(buffer.len());
+        self.engine.render_voices(buffer, 0, &render);
+    }
+
+    /// Renders the block in `mix` from frame `*rendered` up to `end`, where an event is
+    /// about to land, and moves `*rendered` on.
+    fn render_until(&mut self, mix: &mut [Sample], rendered: &mut usize, end: usize) {
+        let end = end.min(mix.len());
+        if end <= *rendered {
+            return;
+        }
+        let part = &mut mix[*rendered..end];
+        // The test tone skips the synth and effects to check just the output path.
+        if self.test_tone.is_some() {
+            self.render_test_tone(part);
+        } else {
+            self.render_mix(part, *rendered);
+        }
+        *rendered = end;
+    }
+
+    /// Renders through `exec`, which must run every task index it's given through
+    /// [`CaveShared::exec`](PluginThreadPoolImpl::exec) before returning true. Returns false,
+    /// leaving `buffer` alone, when there are too few voices to bother or `exec` refused.
+    pub fn render_pooled(&mut self, buffer: &mut [Sample], exec: impl FnOnce(u32) -> bool) -> bool {
+        let render = self.engine.advance_modulation(buffer.len());
+        self.engine.render_voices_pooled(buffer, 0, render, &self.shared.voice_tasks, exec)
+    }
+
+    /// Spreads the block's `mix`, as the effects left it, over
+    /// [`stereo_buffers`](Self::stereo_buffers), and auto-pans it. The side signal (see
+    /// [`CaveEngine::side`]) comes off the left and goes onto the right, the dry signal's
+    /// too, which each channel is then crossfaded with by the FX mix (see
+    /// [`CaveEngine::fx_dry`]). Returns false, leaving the buffers alone, when both sides
+    /// would just be the mix.
+    ///
+    /// Once a block, however many stereo ports there are: auto-pan moves on as it runs.
+    fn mix_stereo(&mut self, mix: &[Sample], time: HostTime) -> bool {
+        let (side, pan, dry) = self.engine.stereo_stage(mix.len(), time);
+        if side.is_none() && pan.is_none() {
+            return false;
+        }
+
+        for (index, buffer) in self.stereo_buffers.iter_mut().enumerate() {
+            let side_sign: Sample = if index == 0 { -1.0 } else { 1.0 };
+            let gains = pan.map(|(left, right)| if index == 0 { left } else { right });
+            for (i, (out, &sample)) in buffer.iter_mut().zip(mix).enumerate() {
+                let wet = side.map_or(sample, |side| sample + side[i] * side_sign);
+                let wet = gains.map_or(wet, |gains| wet * Sample::from(gains[i]));
+                *out = match dry {
+                    Some(dry) => dry.crossfade(dry.mix[i] + dry.side[i] * side_sign, wet),
+                    None => wet,
+                };
+            }
+        }
+        true
+    }
 }
 
 impl<'a> PluginAudioProcessor<'a, CaveShared, CaveMainThread<'a>> for CaveAudioProcessor<'a> {
     fn activate(
-        _host: HostAudioProcessorHandle<'a>,
-        _main_thread: &mut CaveMainThread<'a>,
+        host: HostAudioProcessorHandle<'a>,
+        main_thread: &mut CaveMainThread<'a>,
         shared: &'a CaveShared,
         audio_config: PluginAudioConfiguration,
     ) -> Result<Self, PluginError> {
-        Ok(Self {
-            shared,
-            phase: 0.0,
-            frequency: 440.0,
-            sample_rate: audio_config.sample_rate as f32,
-            note_on: false,
-        })
+        main_thread.thread_check.main_thread("activate");
+        shared.clear_audio_state();
+        shared.vis.scope.set_sample_rate(audio_config.sample_rate as f32);
+        shared.gui_bridge.set_audio_config(Some(AudioInfo {
+            sample_rate: audio_config.sample_rate,
+            max_frames: audio_config.max_frames_count,
+        }));
+        let max_frames = audio_config.max_frames_count as usize;
+        let processor = Self {
+            thread_check: ThreadCheck::new(host.shared()),
+            host_thread_pool: host.get_extension::<HostThreadPool>(),
+            host: Some(host),
+            note_thru: main_thread.note_thru,
+            test_tone: test_tone_enabled().then_some(0.0),
+            ..Self::new(shared, audio_config.sample_rate as f32, max_frames)
+        };
+        // `new` sizes everything from `max_frames`; this guards that it keeps doing so.
+        debug_assert!(processor.is_sized(), "buffers not sized for the largest block");
+        let latency = processor.engine.latency();
+        if latency != main_thread.latency {
+            main_thread.latency = latency;
+            if let Some(host_latency) = main_thread.host_latency {
+                host_latency.changed(&mut main_thread.host);
+            }
+        }
+        main_thread.is_active = true;
+        Ok(processor)
+    }
+
+    fn deactivate(self, main_thread: &mut CaveMainThread<'a>) {
+        main_thread.thread_check.main_thread("deactivate");
+        main_thread.is_active = false;
+        main_thread.shared.gui_bridge.set_audio_config(None);
+        if let Some(meter) = self.meter {
+            main_thread.shared.vis.return_meter(meter);
+        }
+        main_thread.shared.clear_audio_state();
+        main_thread.apply_note_port_layout();
+        main_thread.apply_output_layout();
+    }
+
+    fn reset(&mut self) {
+        self.thread_check.audio_thread("reset");
+        self.engine.reset();
+        self.limiter.reset();
+        // The host sends its modulation again from scratch after a reset.
+        self.shared.params.clear_host_modulation();
+        if let Some(phase) = &mut self.test_tone {
+            *phase = 0.0;
+        }
+        self.shared.gui_notes.set_sounding(std::iter::empty());
+    }
+
+    fn stop_processing(&mut self) {
+        self.thread_check.audio_thread("stop_processing");
+        // Nothing renders until the next start, so a held note would sit frozen and pick
+        // up where it left off. Let it all go, along with notes the editor queued.
+        self.reset();
+        self.shared.gui_notes.drain(|_| {});
+        self.shared.vis.clear(self.meter.as_mut());
+        self.shared.load.clear();
     }
 
         fn process(
         &mut self,
-        _process: Process,
+        process: Process,
         mut audio: Audio,
         events: Events,
     ) -> Result<ProcessStatus, PluginError> {
-        // ... (Event handling same as above) ...
-        // Copy the event handling code from above block
-        for batch in events.input.batch() {
-            for event in batch.events() {
-                if let Some(event) = event.as_core_event() {
-                    use clack_plugin::events::spaces::CoreEventSpace::*;
-                    match event {
-                        NoteOn(e) => {
-                            if let clack_plugin::events::Match::Specific(key) = e.key() {
-                                self.frequency = midi_to_freq(key as u8);
-                                self.note_on = true;
-                            }
-                        }
-                        NoteOff(e) => {
-                            if let clack_plugin::events::Match::Specific(_) = e.key() {
-                                self.note_on = false;
-                            }
-                        }
-                        ParamValue(e) => self.shared.params.handle_param_value_event(e),
-                        _ => {}
-                    }
-                }
-            }
+        self.thread_check.audio_thread("process");
+        let frames = audio.frames_count();
+        // There's the one output port.
+        let mut port_pair = (&mut audio).into_iter().next();
+        let mut channels = match port_pair.as_mut().map(|pair| pair.channels()).transpose()? {
+            Some(SampleType::F64(channels)) => Some(OutputChannels::F64(channels)),
+            // Hosts offering both get the f32 path, our native one.
+            Some(channels) => channels.into_f32().map(OutputChannels::F32),
+            None => None,
+        };
+        let outputs = channels.as_mut().map(OutputChannels::buffers);
+        self.process_block(process.transport, frames, events.input, events.output, outputs);
+        Ok(ProcessStatus::Continue)
+    }
+}
+
+impl<'a> CaveAudioProcessor<'a> {
+    /// Everything `process` does with a block of `frames`, from the host's `transport`
+    /// and events to the samples in `outputs`, with the host's buffers taken apart into
+    /// plain slices so tests and benchmarks can hand over their own.
+    pub fn process_block(
+        &mut self,
+        transport: Option<&TransportEvent>,
+        frames: u32,
+        input: &InputEvents,
+        output: &mut OutputEvents,
+        mut outputs: Option<OutputBuffers>,
+    ) {
+        let started = Instant::now();
+        let _flush = self.flush_denormals.then(FlushDenormals::new);
+
+        push_param_changes(&self.shared.params, output);
+        if self.shared.gui_bridge.panic.swap(false, Ordering::Relaxed) {
+            self.panic(output);
+        }
+        self.play_gui_notes(output);
+
+        if let Some(transport) = transport {
+            self.transport.update(transport);
         }
+        self.transport_at = 0;
+        let time = self.transport.host_time();
+        self.shared.gui_bridge.transport.publish(&self.transport);
+
+        // A host going over the size it gave at activate gets the rest of the block silent
+        // rather than a panic or an allocation.
+        let frames = frames.min(self.max_frames as u32);
+        // Taken out of `self` for the block so rendering and the stereo stage can borrow
+        // `self` too.
+        let mut mix_buffer = std::mem::take(&mut self.mix_buffer);
+        let mix = &mut mix_buffer[..frames as usize];
+        self.play_events(mix, input, output);
 
-        let gain = self.shared.params.gain();
-        let phase_step = self.frequency / self.sample_rate;
-
-        for mut port_pair in &mut audio {
-            let Some(mut channels) = port_pair.channels()?.into_f32() else { continue };
-            
-            // Get the raw sample count
-            let frame_count = port_pair.frames_count();
-            
-            // We'll generate the synth output into a temporary buffer (scratch space)
-            // so we can copy it to both Left and Right channels identically.
-            // (Allocating a vec in audio thread is bad practice, but for 1024 floats it's "okay" for a toy.
-            //  Real plugins use a pre-allocated buffer in the struct).
-            let mut synth_buffer = vec![0.0; frame_count as usize];
-            
-            // Generate Audio into temp buffer
-            for sample in synth_buffer.iter_mut() {
-                if self.note_on {
-                    self.phase += phase_step;
-                    if self.phase > 1.0 { self.phase -= 1.0; }
-                    let raw = if self.phase < 0.5 { 1.0 } else { -1.0 };
-                    *sample = raw * gain * 0.1;
+        self.apply_voice_limit();
+        self.follow_oversampling();
+        self.follow_mod_routing();
+        self.shared.gui_notes.set_sounding(self.engine.held_keys());
+
+        if let Some(outputs) = &mut outputs {
+            // The test tone goes out as it is, on every channel.
+            let stereo = self.test_tone.is_none() && outputs.is_stereo();
+            let wide = stereo && self.mix_stereo(mix, time);
+            if self.test_tone.is_none() {
+                // After the stereo image, which spreads the mix as the effects left it.
+                self.engine.mix_dry(mix);
+            }
+            if self.shared.params.limiter_on() {
+                if wide {
+                    let [left, right] = &mut self.stereo_buffers;
+                    self.limiter.process(&mut left[..mix.len()], &mut right[..mix.len()]);
                 } else {
-                    *sample = 0.0;
+                    self.limiter.process_mono(mix);
                 }
             }
+            let (left, right) = if wide {
+                let [left, right] = &self.stereo_buffers;
+                (&left[..mix.len()], &right[..mix.len()])
+            } else {
+                (&*mix, &*mix)
+            };
+            let levels = outputs.write(left, right);
+            self.shared.vis.write(self.meter.as_mut(), mix, &levels);
+        }
 
-            // Copy temp buffer to all output channels
-            for channel_pair in channels.iter_mut() {
-                if let ChannelPair::OutputOnly(out_buf) = channel_pair {
-                    // Optimized copy
-                    out_buf.copy_from_slice(&synth_buffer);
-                }
+        self.mix_buffer = mix_buffer;
+        if self.note_thru {
+            self.push_tuning_expressions(output, frames.saturating_sub(1));
+        }
+
+        if std::mem::take(&mut self.callback_pending) {
+            if let Some(host) = &self.host {
+                host.shared().request_callback();
             }
         }
 
-        Ok(ProcessStatus::Continue)
+        self.transport.advance(frames.saturating_sub(self.transport_at), self.sample_rate);
+        let voices = self.engine.active_voices();
+        self.shared.load.update(voices, started.elapsed(), frames, self.sample_rate);
+    }
+}
+
+/// An output port's buffers, in whichever sample size the host gave us.
+enum OutputChannels<'a> {
+    F32(PairedChannels<'a, f32>),
+    F64(PairedChannels<'a, f64>),
+}
+
+impl OutputChannels<'_> {
+    fn buffers(&mut self) -> OutputBuffers<'_> {
+        match self {
+            Self::F32(channels) => OutputBuffers::F32(output_slices(channels)),
+            Self::F64(channels) => OutputBuffers::F64(output_slices(channels)),
+        }
+    }
+}
+
+/// The first two channels of a port, the left and the right, or the one of a mono port.
+fn output_slices<'a, S>(channels: &'a mut PairedChannels<'_, S>) -> [Option<&'a mut [S]>; 2] {
+    let mut slices = [None, None];
+    for (slice, channel_pair) in slices.iter_mut().zip(channels.iter_mut()) {
+        if let ChannelPair::OutputOnly(out_buf) = channel_pair {
+            *slice = Some(out_buf);
+        }
+    }
+    slices
+}
+
+/// The output port's channels as plain slices, in the port's sample size. The mix is
+/// converted from [`Sample`] as it's written out.
+pub enum OutputBuffers<'a> {
+    F32([Option<&'a mut [f32]>; 2]),
+    F64([Option<&'a mut [f64]>; 2]),
+}
+
+impl OutputBuffers<'_> {
+    fn is_stereo(&self) -> bool {
+        match self {
+            Self::F32([_, right]) => right.is_some(),
+            Self::F64([_, right]) => right.is_some(),
+        }
+    }
+
+    /// Copies the block out: `left` to the first channel and `right` to the second, in the
+    /// port's sample type. Returns the levels written, for the meter.
+    fn write(&mut self, left: &[Sample], right: &[Sample]) -> BlockLevels {
+        let mut levels = BlockLevels::default();
+        match self {
+            Self::F32(channels) => write_channels(channels, left, right, &mut levels),
+            Self::F64(channels) => write_channels(channels, left, right, &mut levels),
+        }
+        levels
+    }
+}
+
+fn write_channels<S: Copy + FromSample>(
+    channels: &mut [Option<&mut [S]>; 2],
+    left: &[Sample],
+    right: &[Sample],
+    levels: &mut BlockLevels,
+) {
+    for (index, out_buf) in channels.iter_mut().enumerate() {
+        let Some(out_buf) = out_buf else { continue };
+        let source = if index == 0 { left } else { right };
+        for (out, &sample) in out_buf.iter_mut().zip(source) {
+            levels.add(index, sample);
+            *out = S::from_sample(sample);
+        }
+        // Past the end of the block, when it ran longer than the buffers.
+        for out in out_buf.iter_mut().skip(source.len()) {
+            *out = S::from_sample(0.0);
+        }
     }
 }
 
@@ -162,24 +1023,45 @@ impl Plugin for Cave {
         builder
             .register::<PluginAudioPorts>()
             .register::<PluginParams>()
+            .register::<PluginState>()
             .register::<PluginGui>()
-            .register::<PluginNotePorts>();
+            .register::<PluginNotePorts>()
+            .register::<PluginTrackInfo>()
+            .register::<PluginRemoteControls>()
+            .register::<PluginParamIndication>()
+            .register::<PluginContextMenu>()
+            .register::<PluginTimer>()
+            .register::<PluginThreadPool>()
+            .register::<PluginVoiceInfo>()
+            .register::<PluginLatency>();
     }
 }
 
 impl<'a> PluginNotePortsImpl for CaveMainThread<'a> {
     fn count(&mut self, is_input: bool) -> u32 {
-        if is_input { 1 } else { 0 }
+        if is_input { 1 } else { self.note_thru as u32 }
     }
 
     fn get(&mut self, index: u32, is_input: bool, writer: &mut NotePortInfoWriter) {
-        if !is_input || index != 0 { return; }
+        if index != 0 { return; }
 
+        let (id, name): (u32, &[u8]) = if is_input {
+            (0, b"MIDI Input")
+        } else if self.note_thru {
+            (1, b"Note Thru")
+        } else {
+            return;
+        };
         writer.set(&NotePortInfo {
-            id: ClapId::new(0),
-            name: b"MIDI Input",
+            id: ClapId::new(id),
+            name,
             preferred_dialect: Some(NoteDialect::Clap),
-            supported_dialects: NoteDialect::Clap.into(),
+            // MIDI too, for the CCs that MIDI learn binds.
+            supported_dialects: if is_input {
+                NoteDialects::CLAP | NoteDialects::MIDI
+            } else {
+                NoteDialect::Clap.into()
+            },
         });
     }
 }
@@ -187,8 +1069,10 @@ impl<'a> PluginNotePortsImpl for CaveMainThread<'a> {
 impl DefaultPluginFactory for Cave {
     fn get_descriptor() -> PluginDescriptor {
         use clack_plugin::plugin::features::*;
-        PluginDescriptor::new("com.razboy.cave", "Cave")
-            .with_vendor("razboy")
+        PluginDescriptor::new(PLUGIN_ID, PLUGIN_NAME)
+            .with_vendor(VENDOR)
+            .with_url(URL)
+            .with_version(VERSION)
             .with_features([INSTRUMENT, SYNTHESIZER, STEREO])
     }
 
@@ -197,13 +1081,45 @@ impl DefaultPluginFactory for Cave {
     }
 
     fn new_main_thread<'a>(
-        _host: HostMainThreadHandle<'a>,
+        host: HostMainThreadHandle<'a>,
         shared: &'a Self::Shared<'a>,
     ) -> Result<Self::MainThread<'a>, PluginError> {
-        Ok(CaveMainThread {
+        let host_track_info = host.get_extension::<HostTrackInfo>();
+        let host_audio_ports = host.get_extension::<HostAudioPorts>();
+        let host_params = host.get_extension::<HostParams>();
+        let host_timer = host.get_extension::<HostTimer>();
+        let host_context_menu = host.get_extension::<HostContextMenu>();
+        let host_note_ports = host.get_extension::<HostNotePorts>();
+        let host_voice_info = host.get_extension::<HostVoiceInfo>();
+        let host_gui = host.get_extension::<HostGui>();
+        let host_latency = host.get_extension::<HostLatency>();
+        let host_remote_controls = host.get_extension::<HostRemoteControls>();
+
+        let mut main_thread = CaveMainThread {
             shared,
-            gui: CaveGui::default(),
-        })
+            thread_check: ThreadCheck::new(host.shared()),
+            host,
+            host_track_info,
+            host_audio_ports,
+            track_info: None,
+            mono_output: false,
+            host_note_ports,
+            note_thru: false,
+            is_active: false,
+            host_params,
+            host_timer,
+            host_context_menu,
+            host_voice_info,
+            host_gui,
+            host_latency,
+            host_remote_controls,
+            latency: 0,
+            gui_timer: None,
+            gui: Editor::new(CaveGui::new(shared.gui_state())),
+        };
+        main_thread.refresh_track_info();
+
+        Ok(main_thread)
     }
 }
 
@@ -215,157 +1131,1090 @@ impl<'a> PluginAudioPortsImpl for CaveMainThread<'a> {
     fn get(&mut self, index: u32, is_input: bool, writer: &mut AudioPortInfoWriter) {
         if is_input || index != 0 { return; }
 
+        let (channel_count, port_type) = if self.mono_output {
+            (1, AudioPortType::MONO)
+        } else {
+            (2, AudioPortType::STEREO)
+        };
+
         writer.set(&AudioPortInfo {
             id: ClapId::new(0),
             name: b"Output",
-            channel_count: 2,
-            flags: AudioPortFlags::IS_MAIN,
-            port_type: Some(AudioPortType::STEREO),
+            channel_count,
+            // The mix is converted to whichever sample size the host picks; see `OutputChannels`.
+            flags: AudioPortFlags::IS_MAIN | AudioPortFlags::SUPPORTS_64BITS,
+            port_type: Some(port_type),
             in_place_pair: None,
         });
     }
 }
 
+// ---- Latency ----
+impl<'a> PluginLatencyImpl for CaveMainThread<'a> {
+    fn get(&mut self) -> u32 {
+        self.latency
+    }
+}
+
+// ---- Track info ----
+impl<'a> PluginTrackInfoImpl for CaveMainThread<'a> {
+    fn changed(&mut self) {
+        self.refresh_track_info();
+    }
+}
+
 // ---- Params ----
 impl<'a> PluginMainThreadParams for CaveMainThread<'a> {
-    fn count(&mut self) -> u32 { 1 }
+    fn count(&mut self) -> u32 { PARAMS.len() as u32 }
 
     fn get_info(&mut self, param_index: u32, info: &mut ParamInfoWriter) {
-        if param_index != 0 { return; }
+        self.thread_check.main_thread("params.get_info");
+        let Some(desc) = PARAMS.get(param_index as usize) else { return };
+
+        let mut flags = ParamInfoFlags::IS_AUTOMATABLE;
+        if desc.is_stepped() {
+            flags |= ParamInfoFlags::IS_STEPPED;
+        }
+        if desc.modulatable {
+            flags |= ParamInfoFlags::IS_MODULATABLE;
+        }
 
         info.set(&ParamInfo {
-            id: ClapId::new(PARAM_GAIN_ID),
-            flags: ParamInfoFlags::IS_AUTOMATABLE,
+            id: ClapId::new(desc.id),
+            flags,
             cookie: Default::default(),
-            name: b"Gain",
-            module: b"",
-            min_value: 0.0,
-            max_value: 1.0,
-            default_value: 0.5,
+            name: desc.name.as_bytes(),
+            module: desc.module.as_bytes(),
+            min_value: desc.min,
+            max_value: desc.max,
+            default_value: desc.default,
         });
     }
 
     fn get_value(&mut self, param_id: ClapId) -> Option<f64> {
-        match param_id.into() {
-            PARAM_GAIN_ID => Some(self.shared.params.gain() as f64),
-            _ => None,
-        }
+        self.shared.params.value(param_id.into()).map(|v| v as f64)
     }
 
     fn value_to_text(
         &mut self,
-        _param_id: ClapId,
+        param_id: ClapId,
         value: f64,
         writer: &mut ParamDisplayWriter,
     ) -> std::fmt::Result {
         use std::fmt::Write;
-        write!(writer, "{:.3}", value)
+        write!(writer, "{}", value_text(param_id.into(), value))
     }
 
-    fn text_to_value(&mut self, _param_id: ClapId, text: &CStr) -> Option<f64> {
-        text.to_str().ok()?.parse::<f64>().ok()
+    fn text_to_value(&mut self, param_id: ClapId, text: &CStr) -> Option<f64> {
+        text_value(param_id.into(), text.to_str().ok()?)
     }
 
-    fn flush(&mut self, input: &InputEvents, _output: &mut OutputEvents) {
-        for event in input {
-            if let Some(CoreEventSpace::ParamValue(ev)) = event.as_core_event() {
-                self.shared.params.handle_param_value_event(ev);
-            }
-        }
+    fn flush(&mut self, input: &InputEvents, output: &mut OutputEvents) {
+        take_param_events(&self.shared.params, input);
+        push_param_changes(&self.shared.params, output);
     }
 }
 
 impl<'a> PluginAudioProcessorParams for CaveAudioProcessor<'a> {
-    fn flush(&mut self, input: &InputEvents, _output: &mut OutputEvents) {
-        for event in input {
-            if let Some(CoreEventSpace::ParamValue(ev)) = event.as_core_event() {
-                self.shared.params.handle_param_value_event(ev);
+    fn flush(&mut self, input: &InputEvents, output: &mut OutputEvents) {
+        self.thread_check.audio_thread("params.flush");
+        take_param_events(&self.shared.params, input);
+        push_param_changes(&self.shared.params, output);
+    }
+}
+
+/// The host's value changes and modulation, for a `flush` outside `process`.
+fn take_param_events(params: &CaveParams, input: &InputEvents) {
+    for event in input {
+        match event.as_core_event() {
+            Some(CoreEventSpace::ParamValue(event)) => params.handle_param_value_event(event),
+            Some(CoreEventSpace::ParamMod(event)) => params.handle_param_mod_event(event),
+            _ => {}
+        }
+    }
+}
+
+/// Tells the host about values changed on our side, from `flush` or the start of `process`,
+/// whichever the host runs first after [`CaveMainThread::request_param_flush`].
+fn push_param_changes(params: &CaveParams, output: &mut OutputEvents) {
+    params.take_changes(|id, change| {
+        let param_id = ClapId::new(id);
+        let pushed = match change {
+            ParamChange::GestureBegin => output.try_push(ParamGestureBeginEvent::new(0, param_id)),
+            ParamChange::Value(value) => output.try_push(ParamValueEvent::new(
+                0,
+                param_id,
+                Pckn::match_all(),
+                value as f64,
+                Cookie::empty(),
+            )),
+            ParamChange::GestureEnd => output.try_push(ParamGestureEndEvent::new(0, param_id)),
+        };
+        if pushed.is_err() {
+            // Try again next time rather than leave the host out of date.
+            params.requeue(id, change);
+        }
+    });
+}
+
+// ---- Remote controls ----
+impl<'a> PluginRemoteControlsImpl for CaveMainThread<'a> {
+    fn count(&mut self) -> u32 {
+        remote_pages().count() as u32
+    }
+
+    fn get(&mut self, page_index: u32, writer: &mut RemoteControlsPageWriter) {
+        let Some(page) = remote_pages().nth(page_index as usize) else { return };
+
+        let mut param_ids = [None; 8];
+        for (slot, id) in param_ids.iter_mut().zip(page.controls(&self.shared.params)) {
+            *slot = id.map(ClapId::new);
+        }
+
+        writer.set(&RemoteControlsPage {
+            section_name: b"Cave",
+            page_id: ClapId::new(page.id),
+            page_name: page.name.as_bytes(),
+            param_ids,
+            is_for_preset: false,
+        });
+    }
+}
+
+// ---- Param indication ----
+impl<'a> PluginParamIndicationImpl for CaveMainThread<'a> {
+    fn set_mapping(
+        &mut self,
+        param_id: ClapId,
+        has_mapping: bool,
+        color: Option<Color>,
+        _label: Option<&CStr>,
+        _description: Option<&CStr>,
+    ) {
+        // Hosts may map without suggesting a color; fall back to a neutral one.
+        let color = has_mapping.then(|| color.map_or([0x9a, 0x9a, 0x9a], rgb));
+        self.shared.indications.update(param_id.into(), |ind| ind.mapping_color = color);
+        self.shared.gui_bridge.request_repaint();
+    }
+
+    fn set_automation(
+        &mut self,
+        param_id: ClapId,
+        automation_state: ParamIndicationAutomation,
+        color: Option<Color>,
+    ) {
+        let automation = match automation_state {
+            ParamIndicationAutomation::None => AutomationState::None,
+            ParamIndicationAutomation::Present => AutomationState::Present,
+            ParamIndicationAutomation::Playing => AutomationState::Playing,
+            ParamIndicationAutomation::Recording => AutomationState::Recording,
+            ParamIndicationAutomation::Overriding => AutomationState::Overriding,
+        };
+        self.shared.indications.update(param_id.into(), |ind| {
+            ind.automation = automation;
+            ind.automation_color = color.map(rgb);
+        });
+        self.shared.gui_bridge.request_repaint();
+    }
+}
+
+fn rgb(color: Color) -> [u8; 3] {
+    [color.red, color.green, color.blue]
+}
+
+// ---- Context menu ----
+const ACTION_RESET_TO_DEFAULT: u32 = 0;
+const ACTION_ENTER_VALUE: u32 = 1;
+const ACTION_MIDI_LEARN: u32 = 2;
+const ACTION_FORGET_CC: u32 = 3;
+
+impl<'a> PluginContextMenuImpl for CaveMainThread<'a> {
+    fn populate(
+        &mut self,
+        target: ContextMenuTarget,
+        builder: &mut ContextMenuBuilder,
+    ) -> Result<(), PluginError> {
+        let ContextMenuTarget::Param(param_id) = target else { return Ok(()) };
+        let midi_learn = &self.shared.gui_bridge.midi_learn;
+
+        builder.add_item(ContextMenuItem::Separator)?;
+        builder.add_item(ContextMenuItem::Entry(ContextMenuEntry {
+            label: c"Reset to default",
+            is_enabled: true,
+            action_id: ClapId::new(ACTION_RESET_TO_DEFAULT),
+        }))?;
+        builder.add_item(ContextMenuItem::Entry(ContextMenuEntry {
+            label: c"Enter value…",
+            is_enabled: self.gui.is_open(),
+            action_id: ClapId::new(ACTION_ENTER_VALUE),
+        }))?;
+        builder.add_item(ContextMenuItem::Entry(ContextMenuEntry {
+            label: c"MIDI Learn",
+            is_enabled: midi_learn.learning() != Some(param_id.into()),
+            action_id: ClapId::new(ACTION_MIDI_LEARN),
+        }))?;
+        builder.add_item(ContextMenuItem::Entry(ContextMenuEntry {
+            label: c"Forget MIDI CC",
+            is_enabled: midi_learn.cc_for(param_id.into()).is_some(),
+            action_id: ClapId::new(ACTION_FORGET_CC),
+        }))
+    }
+
+    fn perform(&mut self, target: ContextMenuTarget, action_id: ClapId) -> Result<(), PluginError> {
+        let ContextMenuTarget::Param(param_id) = target else { return Ok(()) };
+        let desc = param_desc(param_id.into()).ok_or(PluginError::Message("Unknown param"))?;
+
+        match action_id.into() {
+            ACTION_RESET_TO_DEFAULT => self.set_param_from_main_thread(desc.id, desc.default),
+            ACTION_ENTER_VALUE => self.shared.gui_bridge.request_value_entry(desc.id),
+            ACTION_MIDI_LEARN => self.shared.gui_bridge.midi_learn.learn(desc.id),
+            ACTION_FORGET_CC => self.shared.gui_bridge.midi_learn.unbind(desc.id),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// ---- State ----
+/// The state is the patch text, whose version line says which build wrote it. Params are
+/// keyed by id, with a fallback to the name for patches from before the ids were written,
+/// so older states load whatever still exists without a migration.
+impl<'a> PluginStateImpl for CaveMainThread<'a> {
+    fn save(&mut self, output: &mut OutputStream) -> Result<(), PluginError> {
+        self.thread_check.main_thread("state.save");
+        let bridge = &self.shared.gui_bridge;
+        let name = bridge.patch_name();
+        let note_thru = bridge.note_thru.load(Ordering::Relaxed);
+        let state =
+            patch::to_state(&self.shared.params, name.as_deref(), &bridge.midi_learn, note_thru);
+        let error = PluginError::Message("Could not write the state");
+        output.write_all(state.as_bytes()).map_err(|_| error)
+    }
+
+    fn load(&mut self, input: &mut InputStream) -> Result<(), PluginError> {
+        self.thread_check.main_thread("state.load");
+        let mut text = String::new();
+        input
+            .read_to_string(&mut text)
+            .map_err(|_| PluginError::Message("Could not read the state"))?;
+
+        let version = patch::version(&text).unwrap_or("an unknown version");
+        if version != VERSION {
+            eprintln!("[cave] loading state saved by {version}");
+        }
+        let values = patch::from_text(&text).map_err(|_| PluginError::Message("Not a Cave state"))?;
+        self.shared.gui_bridge.set_loaded_state(Some(version.to_string()));
+        for (id, value) in values {
+            self.shared.params.set_value(id, value);
+        }
+        let midi_learn = &self.shared.gui_bridge.midi_learn;
+        midi_learn.clear();
+        for (cc, param_id) in patch::bindings(&text) {
+            midi_learn.bind(cc, param_id);
+        }
+        let name = patch::name(&text).map(str::to_string);
+        self.shared.gui_bridge.set_patch_name(name, &self.shared.params);
+        self.shared.gui_bridge.note_thru.store(patch::note_thru(&text), Ordering::Relaxed);
+        self.apply_note_port_layout();
+        if let Some(host_params) = self.host_params {
+            host_params.rescan(&mut self.host, ParamRescanFlags::VALUES);
+        }
+        self.remote_controls_changed();
+        Ok(())
+    }
+}
+
+// ---- Voice info ----
+impl<'a> PluginVoiceInfoImpl for CaveMainThread<'a> {
+    fn get(&mut self) -> Option<VoiceInfo> {
+        self.thread_check.main_thread("voice_info.get");
+        Some(VoiceInfo {
+            voice_count: self.shared.params.max_voices() as u32,
+            voice_capacity: MAX_VOICES as u32,
+            flags: VoiceInfoFlags::empty(),
+        })
+    }
+}
+
+// ---- Thread pool ----
+impl PluginThreadPoolImpl for CaveShared {
+    fn exec(&self, task_index: u32) {
+        self.voice_tasks.exec(task_index);
+    }
+}
+
+// ---- Timer ----
+impl<'a> PluginTimerImpl for CaveMainThread<'a> {
+    fn on_timer(&mut self, timer_id: TimerId) {
+        if Some(timer_id) != self.gui_timer { return; }
+
+        for request in self.shared.gui_bridge.take_requests() {
+            self.handle_gui_request(request);
+        }
+        if self.shared.params.has_changes() {
+            self.request_param_flush();
+        }
+        self.shared.gui_bridge.wake_editor(&self.shared.params, &self.shared.vis.meter);
+
+        if self.gui.take_closed_by_user() {
+            if let Some(gui) = self.host_gui {
+                gui.closed(&self.host.shared(), false);
             }
         }
     }
 }
 
 // ---- GUI ----
+/// Windowing API name for the editor's about panel.
+fn gui_api_name(api: &GuiApiType) -> &'static str {
+    let known = [
+        (GuiApiType::X11, "X11"),
+        (GuiApiType::WAYLAND, "Wayland"),
+        (GuiApiType::COCOA, "Cocoa"),
+        (GuiApiType::WIN32, "Win32"),
+    ];
+    known.into_iter().find(|(known, _)| known == api).map_or("unknown", |(_, name)| name)
+}
+
 impl<'a> PluginGuiImpl for CaveMainThread<'a> {
     fn is_api_supported(&mut self, cfg: GuiConfiguration) -> bool {
+        self.thread_check.main_thread("gui.is_api_supported");
         #[cfg(target_os = "linux")]
-        { cfg.api_type == GuiApiType::X11 && !cfg.is_floating }
+        { cfg.api_type == GuiApiType::X11 }
 
-        #[cfg(not(target_os = "linux"))]
-        {
-            let default = GuiApiType::default_for_current_platform().unwrap_or(GuiApiType::Win32);
-            cfg.api_type == default && !cfg.is_floating
-        }
+        // Floating editors run on a thread of their own, which Cocoa doesn't allow.
+        #[cfg(target_os = "macos")]
+        { cfg.api_type == GuiApiType::COCOA && !cfg.is_floating }
+
+        #[cfg(target_os = "windows")]
+        { cfg.api_type == GuiApiType::WIN32 }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+        { let _ = cfg; false }
     }
 
     fn get_preferred_api(&mut self) -> Option<GuiConfiguration> {
+        self.thread_check.main_thread("gui.get_preferred_api");
         #[cfg(target_os = "linux")]
         { Some(GuiConfiguration { api_type: GuiApiType::X11, is_floating: false }) }
 
-        #[cfg(not(target_os = "linux"))]
-        { Some(GuiConfiguration { api_type: GuiApiType::default_for_current_platform()?, is_floating: false }) }
+        #[cfg(target_os = "macos")]
+        { Some(GuiConfiguration { api_type: GuiApiType::COCOA, is_floating: false }) }
+
+        #[cfg(target_os = "windows")]
+        { Some(GuiConfiguration { api_type: GuiApiType::WIN32, is_floating: false }) }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+        { None }
     }
 
     fn create(&mut self, cfg: GuiConfiguration) -> Result<(), PluginError> {
+        self.thread_check.main_thread("gui.create");
         eprintln!("[cave-gui] create: {:?}", cfg);
+        self.gui.create(cfg.is_floating);
+        let window = if cfg.is_floating { "floating" } else { "embedded" };
+        let backend = format!("{}, {window}", gui_api_name(&cfg.api_type));
+        self.shared.gui_bridge.set_gui_backend(Some(backend));
+
+        if self.gui_timer.is_none() {
+            if let Some(timer) = self.host_timer {
+                self.gui_timer = timer.register_timer(&mut self.host, GUI_TIMER_PERIOD_MS).ok();
+            }
+        }
+
+        let editor_wakes = self.gui_timer.is_some();
+        self.shared.gui_bridge.editor_wakes.store(editor_wakes, Ordering::Relaxed);
+        // Without the timer we'd never see the editor's popup requests.
+        let host_menu = self.gui_timer.is_some()
+            && self.host_context_menu.is_some_and(|menu| menu.can_popup(&mut self.host));
+        self.shared.gui_bridge.host_menu.store(host_menu, Ordering::Relaxed);
         Ok(())
     }
 
     fn destroy(&mut self) {
+        self.thread_check.main_thread("gui.destroy");
         eprintln!("[cave-gui] destroy");
-        self.gui.close();
+        self.gui.destroy();
+        self.shared.gui_bridge.set_gui_backend(None);
+
+        if let (Some(timer), Some(id)) = (self.host_timer, self.gui_timer.take()) {
+            let _ = timer.unregister_timer(&mut self.host, id);
+        }
     }
 
     fn set_scale(&mut self, scale: f64) -> Result<(), PluginError> {
+        self.thread_check.main_thread("gui.set_scale");
         eprintln!("[cave-gui] set_scale: {}", scale);
+        // Cocoa sizes are logical points and the OS handles the backing scale.
+        if cfg!(target_os = "macos") {
+            return Err(PluginError::Message("GUI scale is handled by the OS on macOS"));
+        }
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(PluginError::Message("Invalid GUI scale"));
+        }
+        // Windows hosts often send fractional factors (1.25, 1.5) from per-monitor DPI. We
+        // take them as given rather than asking Windows ourselves, since the host's DPI
+        // awareness decides what its window is actually scaled by.
+
+        self.gui.set_scale(scale);
         Ok(())
     }
 
     fn get_size(&mut self) -> Option<GuiSize> {
-        Some(GuiSize { width: 400, height: 300 })
+        self.thread_check.main_thread("gui.get_size");
+        let size = self.gui.size();
+        Some(GuiSize { width: size.width, height: size.height })
+    }
+
+    fn can_resize(&mut self) -> bool {
+        self.thread_check.main_thread("gui.can_resize");
+        true
+    }
+
+    fn get_resize_hints(&mut self) -> Option<GuiResizeHints> {
+        self.thread_check.main_thread("gui.get_resize_hints");
+        Some(GuiResizeHints {
+            can_resize_horizontally: true,
+            can_resize_vertically: true,
+            strategy: AspectRatioStrategy::Disregard,
+        })
+    }
+
+    fn adjust_size(&mut self, size: GuiSize) -> Option<GuiSize> {
+        self.thread_check.main_thread("gui.adjust_size");
+        let size = self.gui.adjust_size(PhySize::new(size.width, size.height));
+        Some(GuiSize { width: size.width, height: size.height })
     }
 
     fn set_size(&mut self, size: GuiSize) -> Result<(), PluginError> {
+        self.thread_check.main_thread("gui.set_size");
         eprintln!("[cave-gui] set_size: {:?}", size);
+        self.gui.set_size(PhySize::new(size.width, size.height));
         Ok(())
     }
 
     fn set_parent(&mut self, window: Window) -> Result<(), PluginError> {
+        self.thread_check.main_thread("gui.set_parent");
         let h = window.raw_window_handle();
         eprintln!("[cave-gui] set_parent: {:?}", h);
-        self.gui.parent = Some(h);
-
-        if self.gui.is_open() {
-            eprintln!("[cave-gui] already open, skip open()");
-            return Ok(());
-        }
-
-        eprintln!("[cave-gui] opening GUI from set_parent()");
-        self.gui.open(self.shared.params.clone())
+        // Bitwig embeds the editor here rather than waiting for show.
+        self.gui.set_parent(h)
     }
 
     fn set_transient(&mut self, _window: Window) -> Result<(), PluginError> {
+        self.thread_check.main_thread("gui.set_transient");
+        // baseview has no way to give a top-level window a transient parent on any platform,
+        // so the floating editor just stays a window of its own.
+        eprintln!("[cave-gui] set_transient: not supported by baseview, ignoring");
         Ok(())
     }
 
     fn show(&mut self) -> Result<(), PluginError> {
+        self.thread_check.main_thread("gui.show");
         eprintln!("[cave-gui] show");
-        if !self.gui.is_open() {
-            self.gui.open(self.shared.params.clone())?;
-        }
-        Ok(())
+        self.gui.show()
     }
 
     fn hide(&mut self) -> Result<(), PluginError> {
+        self.thread_check.main_thread("gui.hide");
         eprintln!("[cave-gui] hide");
-        self.gui.close();
+        self.gui.hide();
         Ok(())
     }
 }
 
-// MIDI Note to Frequency Helper
-fn midi_to_freq(note: u8) -> f32 {
-    440.0 * 2.0f32.powf((note as f32 - 69.0) / 12.0)
+/// Set (to anything but "0") to replace the synth's output with a steady A4 sine, for
+/// checking audio routing without MIDI. Read when the plugin activates.
+const TEST_TONE_ENV: &str = "CAVE_TEST_TONE";
+/// Test tone level before the master gain: -12 dBFS.
+const TEST_TONE_LEVEL: f32 = 0.25;
+
+fn test_tone_enabled() -> bool {
+    let enabled = std::env::var(TEST_TONE_ENV).is_ok_and(|value| value != "0");
+    if enabled {
+        eprintln!("[cave] {TEST_TONE_ENV} set, outputting a 440 Hz test tone");
+    }
+    enabled
+}
+
+// Tuning reference: A4 is MIDI note 69 at 440 Hz.
+pub(crate) const A4_NOTE: u8 = 69;
+pub(crate) const A4_FREQ: f32 = 440.0;
+
+// MIDI Note to Frequency Helper: the reference the audio thread's tables in `pitch` are
+// held to.
+#[cfg(test)]
+pub(crate) fn midi_to_freq(note: u8) -> f32 {
+    A4_FREQ * 2.0f32.powf((note as f32 - A4_NOTE as f32) / 12.0)
 }
 
 clack_export_entry!(SinglePluginEntry<Cave>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    use crate::auto_pan::HostTime;
+    use crate::thread_pool::PARALLEL_MIN_VOICES;
+
+    const EPSILON: f32 = 1e-4;
+    const SAMPLE_RATE: f32 = 48_000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    // The harness drives a processor without a host: notes go in through the same entry
+    // points `process` uses, audio comes out of `render`.
+    fn processor(shared: &CaveShared) -> CaveAudioProcessor<'_> {
+        CaveAudioProcessor::new(shared, SAMPLE_RATE, BLOCK_SIZE)
+    }
+
+    fn render_block(processor: &mut CaveAudioProcessor) -> Vec<Sample> {
+        let mut buffer = vec![0.0; BLOCK_SIZE];
+        processor.render(&mut buffer);
+        buffer
+    }
+
+    fn peak(buffer: &[Sample]) -> Sample {
+        buffer.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    /// The system allocator, counting each thread's allocations so a test can hold the
+    /// audio path to none.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // SAFETY: everything goes straight on to the system allocator.
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // Gone while the thread shuts down, when nothing of ours runs anyway.
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// How many times `f` allocated on this thread.
+    fn allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    #[test]
+    fn split_learn_takes_the_next_key() {
+        let shared = CaveShared::default();
+        let mut processor = processor(&shared);
+        assert_eq!(processor.learn_split_point(48), None);
+
+        shared.gui_bridge.split_learn.store(true, Ordering::Relaxed);
+        assert_eq!(processor.learn_split_point(48), Some(48.0));
+        assert_eq!(shared.params.split_point(), 48);
+        assert_eq!(processor.learn_split_point(50), None);
+    }
+
+    #[test]
+    fn buffers_fit_the_largest_block() {
+        const MAX_FRAMES: usize = 4096;
+        let shared = CaveShared::default();
+        shared.params.set_value(params::PARAM_AUTO_PAN_DEPTH_ID, 1.0);
+        let mut processor = CaveAudioProcessor::new(&shared, SAMPLE_RATE, MAX_FRAMES);
+        assert!(processor.is_sized());
+        for key in 48..48 + PARALLEL_MIN_VOICES as u8 {
+            processor.note_on(key, 1.0);
+        }
+
+        let mut buffer = vec![0.0; MAX_FRAMES];
+        let ran = processor.render_pooled(&mut buffer, |tasks| {
+            (0..tasks).for_each(|task| shared.exec(task));
+            true
+        });
+        assert!(ran);
+        processor.render_mix(&mut buffer, 0);
+        let (_, pan, _) = processor.engine.stereo_stage(MAX_FRAMES, HostTime::default());
+        assert_eq!(pan.map(|(left, _)| left.len()), Some(MAX_FRAMES));
+    }
+
+    #[test]
+    fn silent_until_the_first_note_and_the_same_after_every_activate() {
+        let shared = CaveShared::default();
+        // Everything with state of its own, in the path.
+        shared.params.set_value(params::PARAM_COMB_MIX_ID, 1.0);
+        shared.params.set_value(params::PARAM_RESONANCE_ID, 1.0);
+        shared.params.set_value(params::PARAM_AUTO_PAN_DEPTH_ID, 1.0);
+        let first_note = |processor: &mut CaveAudioProcessor| {
+            processor.note_on(A4_NOTE, 1.0);
+            let mut buffer = vec![0.0; BLOCK_SIZE];
+            processor.render_mix(&mut buffer, 0);
+            buffer
+        };
+
+        let heard = first_note(&mut processor(&shared));
+        assert!(peak(&heard) > 0.0);
+
+        let mut idle = processor(&shared);
+        let mut buffer = vec![1.0; BLOCK_SIZE];
+        for _ in 0..4 {
+            idle.render_mix(&mut buffer, 0);
+            assert!(buffer.iter().all(|&s| s == 0.0));
+            let (side, ..) = idle.engine.stereo_stage(BLOCK_SIZE, HostTime::default());
+            assert_eq!(side, None);
+        }
+
+        // Reactivated, it plays its first note exactly as the last activation did.
+        assert_eq!(first_note(&mut processor(&shared)), heard);
+    }
+
+    #[test]
+    fn a_centred_mix_goes_out_as_it_is() {
+        let stereo = |key_to_pan: f32| {
+            let shared = CaveShared::default();
+            shared.params.set_value(params::PARAM_KEY_TO_PAN_ID, key_to_pan);
+            let mut processor = processor(&shared);
+            processor.note_on(36, 1.0);
+            let mut mix = vec![0.0; BLOCK_SIZE];
+            processor.render_mix(&mut mix, 0);
+            let wide = processor.mix_stereo(&mix, HostTime::default());
+            (wide, mix, processor.stereo_buffers.clone())
+        };
+
+        // Nothing off centre: every port takes the mix itself, so mono is untouched.
+        assert!(!stereo(0.0).0);
+
+        let (wide, mix, [left, right]) = stereo(1.0);
+        assert!(wide);
+        assert!(peak(&left) > 0.0);
+        for ((&mix, &left), &right) in mix.iter().zip(&left).zip(&right) {
+            assert!((left + right - 2.0 * mix).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn the_stereo_image_is_the_per_channel_spread_bit_for_bit() {
+        let shared = CaveShared::default();
+        shared.params.set_value(params::PARAM_KEY_TO_PAN_ID, 1.0);
+        shared.params.set_value(params::PARAM_AUTO_PAN_DEPTH_ID, 1.0);
+        shared.params.set_value(params::PARAM_FX_MIX_ID, 0.7);
+        let played = || {
+            let mut processor = processor(&shared);
+            processor.note_on(36, 1.0);
+            processor.note_on(79, 0.8);
+            let mut mix = vec![0.0; BLOCK_SIZE];
+            processor.render_mix(&mut mix, 0);
+            (processor, mix)
+        };
+
+        let (mut processor, mix) = played();
+        assert!(processor.mix_stereo(&mix, HostTime::default()));
+
+        // What each channel was written as before the image was spread once a block: the
+        // side signal off the left and onto the right and auto-panned, crossfaded by the FX
+        // mix with the dry signal spread the same way.
+        let (mut twin, twin_mix) = played();
+        assert_eq!(twin_mix, mix);
+        let (side, pan, dry) = twin.engine.stereo_stage(BLOCK_SIZE, HostTime::default());
+        let (side, (left_gains, right_gains), dry) = (side.unwrap(), pan.unwrap(), dry.unwrap());
+        for (index, gains) in [left_gains, right_gains].into_iter().enumerate() {
+            let side_sign: Sample = if index == 0 { -1.0 } else { 1.0 };
+            let channel: Vec<Sample> = (0..BLOCK_SIZE)
+                .map(|i| {
+                    let wet = (mix[i] + side[i] * side_sign) * Sample::from(gains[i]);
+                    let dry_sample = dry.mix[i] + dry.side[i] * side_sign;
+                    dry_sample + (wet - dry_sample) * dry.amount
+                })
+                .collect();
+            assert_eq!(processor.stereo_buffers[index][..BLOCK_SIZE], channel[..], "{index}");
+        }
+    }
+
+    #[test]
+    fn a_centred_stereo_port_gets_the_mix_bit_for_bit() {
+        use clack_plugin::events::io::EventBuffer;
+
+        let shared = CaveShared::default();
+        shared.params.set_value(params::PARAM_LIMITER_ON_ID, 0.0);
+        let (mut processor, mut reference) = (processor(&shared), processor(&shared));
+        processor.note_on(48, 1.0);
+        reference.note_on(48, 1.0);
+
+        let input = EventBuffer::new();
+        let mut output = EventBuffer::new();
+        let (mut left, mut right) = (vec![0.0f32; BLOCK_SIZE], vec![0.0f32; BLOCK_SIZE]);
+        processor.process_block(
+            None,
+            BLOCK_SIZE as u32,
+            &InputEvents::from_buffer(&input),
+            &mut OutputEvents::from_buffer(&mut output),
+            Some(OutputBuffers::F32([Some(&mut left), Some(&mut right)])),
+        );
+
+        // The same block as `render_mix` leaves it, before any port is written.
+        let mut mix = vec![0.0; BLOCK_SIZE];
+        reference.render_mix(&mut mix, 0);
+        let mix: Vec<f32> = mix.iter().map(|&s| f32::from_sample(s)).collect();
+        assert!(left.iter().any(|&s| s != 0.0));
+        assert_eq!(left, mix);
+        assert_eq!(right, mix);
+    }
+
+    #[test]
+    fn the_fx_mix_crossfades_the_dry_voices_with_every_effect() {
+        use clack_plugin::events::io::EventBuffer;
+
+        // A few blocks of both channels out of `process`, with the comb, reverb and
+        // auto-pan all turned up and the FX mix at `fx_mix`, or with none of them on.
+        let play = |fx_mix: Option<f32>| {
+            let shared = CaveShared::default();
+            shared.params.set_value(params::PARAM_LIMITER_ON_ID, 0.0);
+            shared.params.set_value(params::PARAM_KEY_TO_PAN_ID, 1.0);
+            if let Some(fx_mix) = fx_mix {
+                shared.params.set_value(params::PARAM_FX_MIX_ID, fx_mix);
+                shared.params.set_value(params::PARAM_COMB_MIX_ID, 1.0);
+                shared.params.set_value(params::PARAM_REVERB_ON_ID, 1.0);
+                shared.params.set_value(params::PARAM_REVERB_MIX_ID, 1.0);
+                shared.params.set_value(params::PARAM_AUTO_PAN_DEPTH_ID, 1.0);
+            }
+            let mut processor = processor(&shared);
+            processor.note_on(36, 1.0);
+            processor.note_on(79, 0.8);
+            let input = EventBuffer::new();
+            let mut output = EventBuffer::new();
+            let (mut left, mut right) = (vec![0.0f32; 4 * BLOCK_SIZE], vec![0.0; 4 * BLOCK_SIZE]);
+            let blocks = left.chunks_mut(BLOCK_SIZE).zip(right.chunks_mut(BLOCK_SIZE));
+            for (left, right) in blocks {
+                processor.process_block(
+                    None,
+                    BLOCK_SIZE as u32,
+                    &InputEvents::from_buffer(&input),
+                    &mut OutputEvents::from_buffer(&mut output),
+                    Some(OutputBuffers::F32([Some(left), Some(right)])),
+                );
+            }
+            [left, right]
+        };
+
+        let dry = play(None);
+        assert!(dry[0].iter().any(|&s| s != 0.0));
+        assert_eq!(play(Some(0.0)), dry);
+
+        let wet = play(Some(1.0));
+        assert_ne!(wet, dry);
+        let halfway = play(Some(0.5));
+        let both = dry.iter().flatten().zip(wet.iter().flatten());
+        for (halfway, (dry, wet)) in halfway.iter().flatten().zip(both) {
+            assert!((halfway - (dry + wet) * 0.5).abs() < EPSILON, "{halfway} {dry} {wet}");
+        }
+    }
+
+    #[test]
+    fn notes_land_on_their_own_sample() {
+        use clack_plugin::events::io::EventBuffer;
+        const NOTE_AT: u32 = 300;
+
+        let shared = CaveShared::default();
+        let mut processor = processor(&shared);
+        let mut input = EventBuffer::new();
+        let pckn = Pckn::new(0u16, 0u16, A4_NOTE as u16, Match::All);
+        input.push(&NoteOnEvent::new(NOTE_AT, pckn, 1.0));
+        let mut output = EventBuffer::new();
+
+        let mut mix = vec![1.0; BLOCK_SIZE];
+        let input = InputEvents::from_buffer(&input);
+        processor.play_events(&mut mix, &input, &mut OutputEvents::from_buffer(&mut output));
+        let (before, after) = mix.split_at(NOTE_AT as usize);
+        assert_eq!(peak(before), 0.0);
+        assert!(peak(&after[..64]) > 0.0);
+    }
+
+    #[test]
+    fn the_audio_path_never_allocates() {
+        use clack_plugin::events::io::EventBuffer;
+
+        let shared = CaveShared::default();
+        // Everything that can run, running: tuning expressions, auto-pan, the thread pool.
+        shared.params.set_value(params::PARAM_PITCH_ENV_AMOUNT_ID, 12.0);
+        shared.params.set_value(params::PARAM_AUTO_PAN_DEPTH_ID, 1.0);
+        let mut processor = processor(&shared);
+        processor.note_thru = true;
+
+        // A chord going down and coming up again over the block, past the pool's threshold.
+        let mut input = EventBuffer::new();
+        let keys = 48..48 + PARALLEL_MIN_VOICES as u16;
+        let chord = keys.map(|key| Pckn::new(0u16, 0u16, key, Match::All));
+        for (n, pckn) in chord.clone().enumerate() {
+            input.push(&NoteOnEvent::new(n as u32 * 16, pckn, 1.0));
+        }
+        for (n, pckn) in chord.enumerate() {
+            input.push(&NoteOffEvent::new(BLOCK_SIZE as u32 / 2 + n as u32 * 16, pckn, 0.0));
+        }
+        let input = InputEvents::from_buffer(&input);
+        let mut output = EventBuffer::new();
+        let (mut left, mut right) = (vec![0.0f32; BLOCK_SIZE], vec![0.0f32; BLOCK_SIZE]);
+        let mut mix = vec![0.0; BLOCK_SIZE];
+
+        // A block as `process` has it, into a stereo f32 port; with no host to lend its
+        // pool, the pooled render goes through the voice tasks here first.
+        let mut block = |processor: &mut CaveAudioProcessor, output: &mut EventBuffer| {
+            output.clear();
+            processor.render_pooled(&mut mix, |tasks| {
+                (0..tasks).for_each(|task| shared.exec(task));
+                true
+            });
+            let outputs = OutputBuffers::F32([Some(&mut left), Some(&mut right)]);
+            let output = &mut OutputEvents::from_buffer(output);
+            processor.process_block(None, BLOCK_SIZE as u32, &input, output, Some(outputs));
+        };
+
+        // The first block sizes the output event buffer, which a host hands over ready.
+        block(&mut processor, &mut output);
+        assert_eq!(allocations(|| block(&mut processor, &mut output)), 0);
+    }
+
+    #[test]
+    fn pitch_and_envelope_times_hold_across_sample_rates() {
+        const ATTACK: f32 = 0.1; // seconds
+        let shared = CaveShared::default();
+        shared.params.set_value(params::PARAM_ATTACK_ID, ATTACK);
+
+        // Each activation builds its processor afresh, the host's rate changing in between.
+        for sample_rate in [44_100.0, 96_000.0] {
+            let mut processor = CaveAudioProcessor::new(&shared, sample_rate, BLOCK_SIZE);
+            processor.note_on(A4_NOTE, 1.0);
+            let mut output = Vec::new();
+            for _ in 0..sample_rate as usize / 2 / BLOCK_SIZE {
+                output.extend(render_block(&mut processor));
+            }
+
+            let seconds = |samples: usize| samples as f32 / sample_rate;
+            let periods = output.windows(2).filter(|pair| pair[0] <= 0.0 && pair[1] > 0.0).count();
+            let frequency = periods as f32 / seconds(output.len());
+            assert!((frequency - A4_FREQ).abs() < 3.0, "{frequency} Hz at {sample_rate}");
+
+            let top = peak(&output);
+            let attack_end = output.iter().position(|s| s.abs() >= top * 0.99).unwrap();
+            let attack = seconds(attack_end);
+            assert!((attack - ATTACK).abs() < 0.005, "{attack} s attack at {sample_rate}");
+        }
+    }
+
+    #[test]
+    fn pooled_render_matches_serial() {
+        let shared = CaveShared::default();
+        let mut serial = processor(&shared);
+        let mut pooled = processor(&shared);
+        for key in 48..48 + PARALLEL_MIN_VOICES as u8 {
+            serial.note_on(key, 1.0);
+            pooled.note_on(key, 1.0);
+        }
+
+        let expected = render_block(&mut serial);
+        let mut buffer = vec![0.0; BLOCK_SIZE];
+        let ran = pooled.render_pooled(&mut buffer, |tasks| {
+            (0..tasks).for_each(|task| shared.exec(task));
+            true
+        });
+
+        assert!(ran);
+        for (a, b) in buffer.iter().zip(&expected) {
+            assert!((a - b).abs() < Sample::from(EPSILON));
+        }
+    }
+
+    #[test]
+    fn params_start_at_their_table_defaults() {
+        let params = CaveParams::default();
+        for desc in PARAMS {
+            assert_eq!(params.value(desc.id), Some(desc.default as f32), "{}", desc.name);
+        }
+    }
+
+    #[test]
+    fn internal_changes_reach_the_host_once_with_the_latest_value() {
+        let params = CaveParams::default();
+        params.set_value(params::PARAM_GAIN_ID, 0.3); // from the host: not echoed
+        params.change(params::PARAM_GAIN_ID, 0.2);
+        params.change(params::PARAM_GAIN_ID, 0.4);
+        params.change(params::PARAM_WAVEFORM_ID, 1.0);
+        assert!(params.has_changes());
+
+        let mut changes = Vec::new();
+        params.take_changes(|id, change| changes.push((id, change)));
+        assert_eq!(
+            changes,
+            [
+                (params::PARAM_GAIN_ID, ParamChange::Value(0.4)),
+                (params::PARAM_WAVEFORM_ID, ParamChange::Value(1.0)),
+            ]
+        );
+        assert!(!params.has_changes());
+    }
+
+    #[test]
+    fn gestures_wrap_the_values_changed_during_them() {
+        let params = CaveParams::default();
+        params.begin_gesture(params::PARAM_CUTOFF_ID);
+        params.change(params::PARAM_CUTOFF_ID, 500.0);
+        params.end_gesture(params::PARAM_CUTOFF_ID);
+
+        let mut changes = Vec::new();
+        params.take_changes(|_, change| changes.push(change));
+        let value = ParamChange::Value(500.0);
+        assert_eq!(changes, [ParamChange::GestureBegin, value, ParamChange::GestureEnd]);
+    }
+
+    #[test]
+    fn disabled_effects_are_skipped() {
+        use crate::params::{PARAM_AUTO_PAN_DEPTH_ID, PARAM_AUTO_PAN_ON_ID};
+        use crate::params::{PARAM_COMB_MIX_ID, PARAM_COMB_ON_ID};
+
+        let (dry, wet) = (CaveShared::default(), CaveShared::default());
+        wet.params.set_value(PARAM_COMB_MIX_ID, 1.0);
+        wet.params.set_value(PARAM_COMB_ON_ID, 0.0);
+        wet.params.set_value(PARAM_AUTO_PAN_DEPTH_ID, 1.0);
+        wet.params.set_value(PARAM_AUTO_PAN_ON_ID, 0.0);
+        let (mut dry, mut wet) = (processor(&dry), processor(&wet));
+        dry.note_on(A4_NOTE, 1.0);
+        wet.note_on(A4_NOTE, 1.0);
+
+        assert_eq!(render_block(&mut wet), render_block(&mut dry));
+        assert!(wet.engine.auto_pan_gains(BLOCK_SIZE, HostTime::default()).is_none());
+    }
+
+    #[test]
+    fn test_tone_follows_the_master_gain() {
+        let shared = CaveShared::default();
+        shared.params.set_value(crate::params::PARAM_GAIN_ID, 0.5);
+        let mut processor = processor(&shared);
+        processor.test_tone = Some(0.0);
+
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize / 100];
+        processor.render_test_tone(&mut buffer);
+        assert!((peak(&buffer) - Sample::from(0.5 * TEST_TONE_LEVEL)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reset_cuts_a_note_off_without_a_tail() {
+        use crate::params::{PARAM_COMB_FEEDBACK_ID, PARAM_COMB_MIX_ID, PARAM_WAVEFORM_ID};
+        use crate::voice::WAVEFORM_PLUCK;
+
+        // A plucked string through a ringing comb, to leave delay lines full.
+        let shared = CaveShared::default();
+        shared.params.set_value(PARAM_WAVEFORM_ID, WAVEFORM_PLUCK as f32);
+        shared.params.set_value(PARAM_COMB_MIX_ID, 1.0);
+        shared.params.set_value(PARAM_COMB_FEEDBACK_ID, 0.9);
+        let mut processor = processor(&shared);
+        processor.note_on(A4_NOTE, 1.0);
+        assert!(peak(&render_block(&mut processor)) > 0.0);
+
+        PluginAudioProcessor::reset(&mut processor);
+        assert_eq!(peak(&render_block(&mut processor)), 0.0);
+        assert_eq!(processor.engine.active_voices(), 0);
+        assert_eq!(processor.engine.held_keys().count(), 0);
+
+        processor.note_on(A4_NOTE, 1.0);
+        assert!(peak(&render_block(&mut processor)) > 0.0);
+    }
+
+    #[test]
+    fn all_sound_off_fades_everything_out() {
+        use clack_plugin::events::io::EventBuffer;
+
+        let shared = CaveShared::default();
+        let mut processor = processor(&shared);
+        for key in [48, 60, 72] {
+            processor.note_on(key, 1.0);
+        }
+        render_block(&mut processor);
+
+        let mut output = EventBuffer::new();
+        let all_sound_off = [MIDI_CONTROL_CHANGE, MIDI_ALL_SOUND_OFF, 0];
+        processor.handle_midi(all_sound_off, &mut OutputEvents::from_buffer(&mut output));
+        let block = render_block(&mut processor);
+        // Faded, not cut: still sounding at first, silent a few milliseconds on.
+        assert!(peak(&block[..16]) > 0.0);
+        assert_eq!(peak(&block[BLOCK_SIZE / 2..]), 0.0);
+        assert_eq!(processor.engine.active_voices(), 0);
+        assert_eq!(processor.engine.held_keys().count(), 0);
+    }
+
+    #[test]
+    fn stopping_lets_go_of_everything_held() {
+        let shared = CaveShared::default();
+        let mut processor = processor(&shared);
+        processor.note_on(A4_NOTE, 1.0);
+        render_block(&mut processor);
+        shared.gui_notes.press(60, 1.0);
+
+        processor.stop_processing();
+        let mut queued = 0;
+        shared.gui_notes.drain(|_| queued += 1);
+        assert_eq!(queued, 0);
+        assert!(!shared.gui_notes.is_sounding(A4_NOTE));
+        assert_eq!(peak(&render_block(&mut processor)), 0.0);
+    }
+
+    #[test]
+    fn reactivation_leaves_the_editor_nothing_stale() {
+        use crate::meter::MeterLevels;
+        use std::time::Duration;
+
+        // An open editor watching a processor that's playing, as `process` would leave it.
+        let shared = CaveShared::default();
+        shared.vis.scope.set_watching(true);
+        let mut first = processor(&shared);
+        first.note_on(A4_NOTE, 1.0);
+        let mix = render_block(&mut first);
+        let mut levels = BlockLevels::default();
+        mix.iter().for_each(|&sample| levels.add(0, sample));
+        shared.vis.write(first.meter.as_mut(), &mix, &levels);
+        shared.load.update(1, Duration::from_millis(1), BLOCK_SIZE as u32, SAMPLE_RATE);
+        shared.gui_notes.set_sounding(first.engine.held_keys());
+        // What deactivate does with it.
+        shared.vis.return_meter(first.meter.take().unwrap());
+        drop(first);
+
+        // Deactivated, with a key played on the editor before the host activates again.
+        shared.clear_audio_state();
+        shared.gui_notes.press(60, 1.0);
+        shared.clear_audio_state();
+
+        assert_eq!(shared.vis.meter.lock().unwrap().take(), Some(MeterLevels::default()));
+        let mut scope = [1.0; 256];
+        shared.vis.scope.read(&mut scope);
+        assert!(scope.iter().all(|&sample| sample == 0.0));
+        assert_eq!((shared.load.voices(), shared.load.load()), (0, 0.0));
+        assert!(!shared.gui_notes.is_sounding(A4_NOTE));
+        let mut queued = 0;
+        shared.gui_notes.drain(|_| queued += 1);
+        assert_eq!(queued, 0);
+
+        let mut second = processor(&shared);
+        second.note_on(A4_NOTE, 1.0);
+        assert!(peak(&render_block(&mut second)) > 0.0);
+    }
+
+    #[test]
+    fn midi_to_freq_matches_reference_pitches() {
+        assert!((midi_to_freq(A4_NOTE) - A4_FREQ).abs() < EPSILON);
+        assert!((midi_to_freq(69) - 440.0).abs() < EPSILON);
+        assert!((midi_to_freq(81) - 880.0).abs() < EPSILON);
+        assert!((midi_to_freq(57) - 220.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn midi_to_freq_doubles_every_octave() {
+        for note in 0..=115u8 {
+            let ratio = midi_to_freq(note + 12) / midi_to_freq(note);
+            assert!((ratio - 2.0).abs() < EPSILON, "note {note}: ratio {ratio}");
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::sample::Sample;
+
+/// Level the limiter holds peaks down to: -1 dBFS. Below it the output is untouched.
+const THRESHOLD: Sample = 0.891;
+/// How quickly the gain comes down for a peak, and goes back up after it.
+const ATTACK: f32 = 0.001; // seconds
+const RELEASE: f32 = 0.1; // seconds
+/// How close to unity the gain has to recover before it snaps there, a step of under a
+/// hundredth of a dB. The output is then bit-for-bit the input again, rather than stuck a
+/// hair quieter where the release's steps fall below the float's resolution.
+const UNITY_SNAP: f32 = 1e-3;
+
+/// The output's safety net, the last thing before the host's buffers. A gain computer
+/// works out, sample by sample, the gain that would bring the louder channel down to
+/// [`THRESHOLD`]; the gain follows it down over [`ATTACK`] and back up over [`RELEASE`], so
+/// a loud chord is turned down smoothly rather than bent out of shape. Both channels share
+/// the gain, so the stereo image holds still. There's no lookahead, and so no latency:
+/// what a transient gets past the attack is clipped at 0 dBFS, so nothing ever goes over.
+/// Below the threshold the gain stays at exactly one and the limiter is transparent.
+pub struct Limiter {
+    gain: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl Limiter {
+    pub fn new(sample_rate: f32) -> Self {
+        let coefficient = |seconds: f32| 1.0 - (-1.0 / (seconds * sample_rate)).exp();
+        Self { gain: 1.0, attack: coefficient(ATTACK), release: coefficient(RELEASE) }
+    }
+
+    /// Limits a stereo block in place.
+    pub fn process(&mut self, left: &mut [Sample], right: &mut [Sample]) {
+        for (left, right) in left.iter_mut().zip(right) {
+            let gain = self.next_gain(left.abs().max(right.abs()));
+            *left = (*left * gain).clamp(-1.0, 1.0);
+            *right = (*right * gain).clamp(-1.0, 1.0);
+        }
+    }
+
+    /// Limits a mono block in place.
+    pub fn process_mono(&mut self, buffer: &mut [Sample]) {
+        for sample in buffer {
+            let gain = self.next_gain(sample.abs());
+            *sample = (*sample * gain).clamp(-1.0, 1.0);
+        }
+    }
+
+    /// Back to unity gain, for a reset.
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+
+    fn next_gain(&mut self, peak: Sample) -> Sample {
+        let target = if peak > THRESHOLD { (THRESHOLD / peak) as f32 } else { 1.0 };
+        let coefficient = if target < self.gain { self.attack } else { self.release };
+        self.gain += (target - self.gain) * coefficient;
+        if 1.0 - self.gain < UNITY_SNAP {
+            self.gain = 1.0;
+        }
+        Sample::from(self.gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    /// A second of a 440 Hz sine at `amplitude`.
+    fn sine(amplitude: f64) -> Vec<Sample> {
+        (0..SAMPLE_RATE as usize)
+            .map(|n| amplitude * (TAU * 440.0 * n as f64 / f64::from(SAMPLE_RATE)).sin())
+            .map(|s| s as Sample)
+            .collect()
+    }
+
+    fn peak(buffer: &[Sample]) -> Sample {
+        buffer.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn transparent_below_the_threshold() {
+        let mut limiter = Limiter::new(SAMPLE_RATE);
+        let input = sine(f64::from(THRESHOLD));
+        let mut output = input.clone();
+        limiter.process_mono(&mut output);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn holds_a_loud_signal_down_without_going_over() {
+        let mut limiter = Limiter::new(SAMPLE_RATE);
+        let mut left = sine(4.0);
+        let mut right: Vec<Sample> = left.iter().map(|s| s * 0.5).collect();
+        limiter.process(&mut left, &mut right);
+        assert!(peak(&left) <= 1.0 && peak(&right) <= 1.0);
+        // Once the gain has come down, peaks sit within a dB of the threshold, what gets
+        // past the attack, and the quieter channel keeps its balance with the louder.
+        let settled = &left[left.len() / 2..];
+        let over = peak(settled) / THRESHOLD;
+        assert!((1.0..1.122).contains(&over), "peaks at {}", peak(settled));
+        let ratio = peak(&right[right.len() / 2..]) / peak(settled);
+        assert!((ratio - 0.5).abs() < 1e-3, "channels at {ratio}");
+    }
+
+    #[test]
+    fn recovers_to_transparent_after_a_peak() {
+        let mut limiter = Limiter::new(SAMPLE_RATE);
+        let mut burst = sine(2.0)[..4800].to_vec();
+        limiter.process_mono(&mut burst);
+        // The gain comes back up smoothly: no step between neighbouring samples of a quiet
+        // signal bigger than the sine's own.
+        let quiet = sine(0.5);
+        let mut recovering = quiet.clone();
+        limiter.process_mono(&mut recovering);
+        let steps = recovering.windows(2).map(|pair| (pair[1] - pair[0]).abs());
+        assert!(steps.fold(0.0, Sample::max) < 0.03);
+        // And after a few release times it's bit-for-bit again.
+        let mut after = quiet.clone();
+        limiter.process_mono(&mut after);
+        assert_eq!(after, quiet);
+    }
+}
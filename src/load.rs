@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use atomic_float::AtomicF32;
+
+/// How busy the audio thread is, for the editor's header: the voices sounding and the
+/// share of each block's real-time budget `process` took. The audio thread stores the
+/// latest block's figures; smoothing them is the editor's business.
+#[derive(Default)]
+pub struct ProcessLoad {
+    voices: AtomicUsize,
+    load: AtomicF32,
+}
+
+impl ProcessLoad {
+    /// Audio thread only. `elapsed` is how long a block of `frames` took to process.
+    pub fn update(&self, voices: usize, elapsed: Duration, frames: u32, sample_rate: f32) {
+        self.voices.store(voices, Ordering::Relaxed);
+        if frames > 0 {
+            let budget = frames as f32 / sample_rate;
+            self.load.store(elapsed.as_secs_f32() / budget, Ordering::Relaxed);
+        }
+    }
+
+    /// Audio thread, or while it isn't running: nothing sounding, nothing taken.
+    pub fn clear(&self) {
+        self.voices.store(0, Ordering::Relaxed);
+        self.load.store(0.0, Ordering::Relaxed);
+    }
+
+    pub fn voices(&self) -> usize {
+        self.voices.load(Ordering::Relaxed)
+    }
+
+    /// Of the last block's budget: 1.0 took as long as the block lasts.
+    pub fn load(&self) -> f32 {
+        self.load.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_is_time_taken_over_block_length() {
+        let load = ProcessLoad::default();
+        load.update(3, Duration::from_micros(250), 480, 48_000.0);
+        assert_eq!(load.voices(), 3);
+        assert!((load.load() - 0.025).abs() < 1e-6);
+
+        // An empty block leaves the last figure standing.
+        load.update(3, Duration::from_micros(250), 0, 48_000.0);
+        assert!((load.load() - 0.025).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// Messages the audio thread leaves for the main thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainThreadMessage {
+    /// A note-on found every voice busy and cut off the oldest one.
+    VoiceStolen { key: u8 },
+    /// The max voices param changed, so the host's voice info is stale.
+    VoiceLimitChanged,
+    /// The oversampling param changed, and with it the latency: the host has to restart us.
+    OversamplingChanged,
+    /// A mod slot gained or lost its source, and with it the controls on the remote pages.
+    RemoteControlsChanged,
+}
+
+const VOICE_LIMIT_CHANGED: u32 = 1 << 8;
+const OVERSAMPLING_CHANGED: u32 = 1 << 9;
+const REMOTE_CONTROLS_CHANGED: u32 = 1 << 10;
+
+impl MainThreadMessage {
+    fn to_bits(self) -> u32 {
+        match self {
+            Self::VoiceStolen { key } => key as u32,
+            Self::VoiceLimitChanged => VOICE_LIMIT_CHANGED,
+            Self::OversamplingChanged => OVERSAMPLING_CHANGED,
+            Self::RemoteControlsChanged => REMOTE_CONTROLS_CHANGED,
+        }
+    }
+
+    fn from_bits(bits: u32) -> Self {
+        match bits {
+            VOICE_LIMIT_CHANGED => Self::VoiceLimitChanged,
+            OVERSAMPLING_CHANGED => Self::OversamplingChanged,
+            REMOTE_CONTROLS_CHANGED => Self::RemoteControlsChanged,
+            _ => Self::VoiceStolen { key: bits as u8 },
+        }
+    }
+}
+
+/// Messages kept before the oldest start getting dropped.
+const CAPACITY: usize = 64;
+
+/// Bounded, lock-free queue from the audio thread to the main thread.
+///
+/// The audio thread never waits: when the main thread falls behind, new messages overwrite
+/// the oldest unread ones, which the reader counts in [`MainQueue::dropped`]. Each slot
+/// holds the message and the write count it was stored at in one atomic word, so the
+/// reader can tell a slot that was overwritten under it. One writer and one reader only.
+pub struct MainQueue {
+    slots: [AtomicU64; CAPACITY],
+    /// Total messages ever written.
+    written: AtomicUsize,
+    /// Total messages the reader has consumed or skipped.
+    read: AtomicUsize,
+    dropped: AtomicU32,
+}
+
+impl Default for MainQueue {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| AtomicU64::new(0)),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            dropped: AtomicU32::new(0),
+        }
+    }
+}
+
+impl MainQueue {
+    /// Audio thread only.
+    pub fn push(&self, message: MainThreadMessage) {
+        let index = self.written.load(Ordering::Relaxed);
+        let word = ((index as u32 as u64) << 32) | message.to_bits() as u64;
+        self.slots[index % CAPACITY].store(word, Ordering::Release);
+        self.written.store(index + 1, Ordering::Release);
+    }
+
+    /// Main thread only. Hands every message still in the queue to `f`, oldest first.
+    pub fn drain(&self, mut f: impl FnMut(MainThreadMessage)) {
+        let written = self.written.load(Ordering::Acquire);
+        let mut read = self.read.load(Ordering::Relaxed);
+
+        if written - read > CAPACITY {
+            self.count_dropped(written - read - CAPACITY);
+            read = written - CAPACITY;
+        }
+
+        while read < written {
+            let word = self.slots[read % CAPACITY].load(Ordering::Acquire);
+            if (word >> 32) as u32 == read as u32 {
+                f(MainThreadMessage::from_bits(word as u32));
+            } else {
+                // Overwritten by a newer message while we were catching up.
+                self.count_dropped(1);
+            }
+            read += 1;
+        }
+        self.read.store(read, Ordering::Relaxed);
+    }
+
+    /// Messages lost to overflow so far.
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn count_dropped(&self, count: usize) {
+        self.dropped.fetch_add(count as u32, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &MainQueue) -> Vec<u8> {
+        let mut keys = Vec::new();
+        queue.drain(|message| {
+            if let MainThreadMessage::VoiceStolen { key } = message {
+                keys.push(key);
+            }
+        });
+        keys
+    }
+
+    #[test]
+    fn overflow_drops_the_oldest_and_counts_them() {
+        let queue = MainQueue::default();
+        for key in 0..CAPACITY as u8 + 10 {
+            queue.push(MainThreadMessage::VoiceStolen { key });
+        }
+
+        let keys = drain(&queue);
+        assert_eq!(keys.len(), CAPACITY);
+        assert_eq!(keys[0], 10);
+        assert_eq!(queue.dropped(), 10);
+        assert!(drain(&queue).is_empty());
+    }
+}
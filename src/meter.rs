@@ -0,0 +1,158 @@
+use crate::sample::{FromSample, Sample};
+use crate::triple_buffer::{triple_buffer, Reader, Writer};
+
+/// Channels the meter shows. Mono output shows the one channel on both.
+pub const METER_CHANNELS: usize = 2;
+
+/// What the meter has to show, as the editor takes it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeterLevels {
+    /// Highest peak per channel since the editor last took the levels, as linear gain.
+    pub peak: [f32; METER_CHANNELS],
+    /// Of the latest block.
+    pub rms: [f32; METER_CHANNELS],
+    /// Whether a sample went over 0 dBFS since the editor last took the levels.
+    pub clipped: bool,
+}
+
+/// Output levels for the editor's meter, as the two ends of a [`triple_buffer`]. The
+/// audio thread folds in each block and publishes; until the editor takes them the peaks
+/// keep rising and a clip stays set, so a peak in a block between two frames, or while
+/// the editor is closed, still shows. Decay, peak hold and the clip light are the
+/// editor's business.
+pub fn level_meter() -> (MeterWriter, MeterReader) {
+    let (writer, reader) = triple_buffer(MeterLevels::default());
+    (MeterWriter { levels: writer }, MeterReader { levels: reader })
+}
+
+/// The audio thread's end of a [`level_meter`].
+pub struct MeterWriter {
+    levels: Writer<MeterLevels>,
+}
+
+/// The editor's end of a [`level_meter`].
+pub struct MeterReader {
+    levels: Reader<MeterLevels>,
+}
+
+impl MeterWriter {
+    pub fn update(&mut self, block: &BlockLevels) {
+        // Mono output fills only the first channel.
+        let measured = if block.len[1] > 0 { METER_CHANNELS } else { 1 };
+        self.levels.publish_with(|levels, taken| {
+            let last = *levels;
+            if taken {
+                levels.peak = [0.0; METER_CHANNELS];
+                levels.clipped = false;
+            }
+            for channel in 0..METER_CHANNELS {
+                let from = channel.min(measured - 1);
+                let peak = f32::from_sample(block.peak[from]);
+                levels.peak[channel] = levels.peak[channel].max(peak);
+                levels.rms[channel] = block.rms(from);
+                levels.clipped |= peak > 1.0;
+            }
+            // Silence after silence is nothing new, so an idle plugin leaves the editor be.
+            !(taken && *levels == last)
+        });
+    }
+
+    /// Publishes silence, dropping any peaks and clip the editor hasn't taken.
+    pub fn clear(&mut self) {
+        self.levels.publish_with(|levels, _| {
+            *levels = MeterLevels::default();
+            true
+        });
+    }
+}
+
+impl MeterReader {
+    /// The levels since the last call, or `None` if nothing changed.
+    pub fn take(&mut self) -> Option<MeterLevels> {
+        self.levels.read()
+    }
+
+    /// Whether there are levels the editor hasn't taken.
+    pub fn has_unread(&self) -> bool {
+        self.levels.has_unread()
+    }
+}
+
+/// One block's levels, gathered as the output buffers are written.
+#[derive(Default)]
+pub struct BlockLevels {
+    peak: [Sample; METER_CHANNELS],
+    sum_squares: [Sample; METER_CHANNELS],
+    len: [usize; METER_CHANNELS],
+}
+
+impl BlockLevels {
+    /// Channels past the meter's are ignored.
+    pub fn add(&mut self, channel: usize, sample: Sample) {
+        if channel < METER_CHANNELS {
+            self.peak[channel] = self.peak[channel].max(sample.abs());
+            self.sum_squares[channel] += sample * sample;
+            self.len[channel] += 1;
+        }
+    }
+
+    fn rms(&self, channel: usize) -> f32 {
+        match self.len[channel] {
+            0 => 0.0,
+            len => f32::from_sample((self.sum_squares[channel] / len as Sample).sqrt()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(channels: &[&[Sample]]) -> BlockLevels {
+        let mut levels = BlockLevels::default();
+        for (channel, samples) in channels.iter().enumerate() {
+            for &sample in *samples {
+                levels.add(channel, sample);
+            }
+        }
+        levels
+    }
+
+    #[test]
+    fn peaks_hold_until_taken_and_mono_fills_both_channels() {
+        let (mut meter, mut reader) = level_meter();
+        meter.update(&block(&[&[0.5, -0.8, 0.5, -0.8]]));
+        meter.update(&block(&[&[0.1]]));
+
+        let levels = reader.take().unwrap();
+        assert_eq!(levels.peak, [0.8, 0.8]);
+        assert_eq!(levels.rms, [0.1, 0.1]);
+        assert_eq!(reader.take(), None);
+
+        meter.update(&block(&[&[0.1]]));
+        assert_eq!(reader.take().unwrap().peak, [0.1, 0.1]);
+    }
+
+    #[test]
+    fn clip_holds_until_taken() {
+        let (mut meter, mut reader) = level_meter();
+        meter.update(&block(&[&[0.5], &[-1.5]]));
+        meter.update(&block(&[&[0.5], &[0.5]]));
+        assert!(reader.take().unwrap().clipped);
+
+        meter.update(&block(&[&[1.0], &[-1.0]]));
+        assert!(!reader.take().unwrap().clipped);
+    }
+
+    #[test]
+    fn silence_is_published_once() {
+        let (mut meter, mut reader) = level_meter();
+        meter.update(&block(&[&[0.5]]));
+        reader.take();
+        meter.update(&block(&[&[0.0]]));
+        assert_eq!(reader.take().unwrap().rms, [0.0, 0.0]);
+
+        meter.update(&block(&[&[0.0]]));
+        assert!(!reader.has_unread());
+    }
+}
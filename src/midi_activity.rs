@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// No note received yet.
+const NONE: u32 = u32::MAX;
+
+/// Incoming note and CC events, for the editor's MIDI light: the first thing to check when
+/// the plugin makes no sound is whether anything is arriving at all.
+///
+/// The audio thread counts events and notes the last note-on; the editor flashes whenever
+/// the count moves. Played from the editor's own keyboard doesn't count.
+pub struct MidiActivity {
+    events: AtomicU32,
+    /// Key in the high byte and MIDI velocity in the low one, or [`NONE`].
+    last_note: AtomicU32,
+}
+
+impl Default for MidiActivity {
+    fn default() -> Self {
+        Self { events: AtomicU32::new(0), last_note: AtomicU32::new(NONE) }
+    }
+}
+
+impl MidiActivity {
+    /// Audio thread. A note-on for `key` at `velocity`, from 0.0 to 1.0.
+    pub fn note_on(&self, key: u8, velocity: f32) {
+        let velocity = (velocity.clamp(0.0, 1.0) * 127.0).round() as u32;
+        self.last_note.store((key as u32) << 8 | velocity, Ordering::Relaxed);
+        self.event();
+    }
+
+    /// Audio thread. Any other note or controller event.
+    pub fn event(&self) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Events so far, wrapping. Only ever compared with an earlier count.
+    pub fn events(&self) -> u32 {
+        self.events.load(Ordering::Relaxed)
+    }
+
+    /// The last note-on's key and MIDI velocity (0 to 127).
+    pub fn last_note(&self) -> Option<(u8, u8)> {
+        let note = self.last_note.load(Ordering::Relaxed);
+        (note != NONE).then_some(((note >> 8) as u8, note as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_events_and_keeps_the_last_note() {
+        let activity = MidiActivity::default();
+        assert_eq!(activity.last_note(), None);
+
+        activity.note_on(60, 1.0);
+        activity.event();
+        activity.note_on(127, 0.5);
+        assert_eq!(activity.events(), 3);
+        assert_eq!(activity.last_note(), Some((127, 64)));
+    }
+}
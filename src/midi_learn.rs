@@ -0,0 +1,151 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::params::{param_desc, Params};
+
+/// Status byte of a control change, less the channel.
+pub const MIDI_CONTROL_CHANGE: u8 = 0xb0;
+/// The channel mode message that silences everything, whatever's held. Never learned.
+pub const MIDI_ALL_SOUND_OFF: u8 = 120;
+/// MIDI CC numbers run from 0 to this, exclusive.
+pub const CC_COUNT: usize = 128;
+/// An empty binding slot, no param being learned or no CC received yet.
+const NONE: u32 = u32::MAX;
+
+/// Which CC moves which param, and the learn mode that sets that up.
+///
+/// The editor arms learning and edits the table; the audio thread binds the next CC it
+/// sees and then applies bound CCs to their params. Every slot is its own atomic, so
+/// neither side ever waits on the other. A param has at most one CC.
+pub struct MidiLearn {
+    /// Param id per CC number, or [`NONE`].
+    bindings: [AtomicU32; CC_COUNT],
+    /// Param waiting for the next CC, or [`NONE`].
+    learning: AtomicU32,
+    /// The last CC number received, bound or not, for the editor to show.
+    last_cc: AtomicU32,
+}
+
+impl Default for MidiLearn {
+    fn default() -> Self {
+        Self {
+            bindings: std::array::from_fn(|_| AtomicU32::new(NONE)),
+            learning: AtomicU32::new(NONE),
+            last_cc: AtomicU32::new(NONE),
+        }
+    }
+}
+
+impl MidiLearn {
+    /// Editor only. The next CC received will move `param_id`.
+    pub fn learn(&self, param_id: u32) {
+        self.learning.store(param_id, Ordering::Relaxed);
+    }
+
+    pub fn cancel_learn(&self) {
+        self.learning.store(NONE, Ordering::Relaxed);
+    }
+
+    /// The param waiting for a CC, if any.
+    pub fn learning(&self) -> Option<u32> {
+        some(self.learning.load(Ordering::Relaxed))
+    }
+
+    pub fn last_cc(&self) -> Option<u8> {
+        some(self.last_cc.load(Ordering::Relaxed)).map(|cc| cc as u8)
+    }
+
+    pub fn cc_for(&self, param_id: u32) -> Option<u8> {
+        let cc = self.bindings.iter().position(|p| p.load(Ordering::Relaxed) == param_id)?;
+        Some(cc as u8)
+    }
+
+    /// Every binding as `(cc, param_id)`, in CC order.
+    pub fn bindings(&self) -> impl Iterator<Item = (u8, u32)> + '_ {
+        self.bindings
+            .iter()
+            .enumerate()
+            .filter_map(|(cc, param)| some(param.load(Ordering::Relaxed)).map(|p| (cc as u8, p)))
+    }
+
+    /// Binds `cc` to `param_id`, freeing whichever CC moved it before: for bindings saved
+    /// with the project.
+    pub fn bind(&self, cc: u8, param_id: u32) {
+        let Some(slot) = self.bindings.get(cc as usize) else { return };
+        self.unbind(param_id);
+        slot.store(param_id, Ordering::Relaxed);
+    }
+
+    /// Frees whichever CC moves `param_id`.
+    pub fn unbind(&self, param_id: u32) {
+        for param in &self.bindings {
+            let _ = param.compare_exchange(param_id, NONE, Ordering::Relaxed, Ordering::Relaxed);
+        }
+    }
+
+    pub fn clear(&self) {
+        for param in &self.bindings {
+            param.store(NONE, Ordering::Relaxed);
+        }
+    }
+
+    /// Audio thread only. Binds `cc` if a param is waiting for one, then moves whatever
+    /// param it's bound to, spreading the CC's 0 to 127 over the param's range. The change
+    /// goes through [`Params::change`], so the host hears about it like any other edit.
+    pub fn handle_cc(&self, cc: u8, value: u8, params: &Params) {
+        let Some(slot) = self.bindings.get(cc as usize) else { return };
+        self.last_cc.store(cc as u32, Ordering::Relaxed);
+        if let Some(param_id) = some(self.learning.swap(NONE, Ordering::Relaxed)) {
+            self.unbind(param_id);
+            slot.store(param_id, Ordering::Relaxed);
+        }
+
+        let Some(desc) = some(slot.load(Ordering::Relaxed)).and_then(param_desc) else { return };
+        let value = desc.min + (desc.max - desc.min) * value.min(127) as f64 / 127.0;
+        let value = if desc.is_stepped() { value.round() } else { value };
+        params.change(desc.id, value as f32);
+    }
+}
+
+fn some(value: u32) -> Option<u32> {
+    (value != NONE).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{ParamChange, PARAM_GAIN_ID, PARAM_MAX_VOICES_ID};
+
+    #[test]
+    fn the_next_cc_binds_and_then_moves_the_param() {
+        let (learn, params) = (MidiLearn::default(), Params::default());
+        learn.learn(PARAM_GAIN_ID);
+        learn.handle_cc(7, 127, &params);
+        assert_eq!(learn.learning(), None);
+        assert_eq!(learn.cc_for(PARAM_GAIN_ID), Some(7));
+        assert_eq!(params.gain(), 1.0);
+
+        learn.handle_cc(7, 0, &params);
+        learn.handle_cc(8, 127, &params);
+        assert_eq!(params.gain(), 0.0);
+        assert_eq!(learn.last_cc(), Some(8));
+
+        let mut changes = Vec::new();
+        params.take_changes(|id, change| changes.push((id, change)));
+        assert_eq!(changes, [(PARAM_GAIN_ID, ParamChange::Value(0.0))]);
+    }
+
+    #[test]
+    fn learning_again_moves_the_binding() {
+        let (learn, params) = (MidiLearn::default(), Params::default());
+        learn.learn(PARAM_MAX_VOICES_ID);
+        learn.handle_cc(1, 0, &params);
+        learn.learn(PARAM_MAX_VOICES_ID);
+        learn.handle_cc(2, 64, &params);
+        assert_eq!(learn.bindings().collect::<Vec<_>>(), [(2, PARAM_MAX_VOICES_ID)]);
+        // Stepped params land on whole values.
+        assert_eq!(params.max_voices() as f32, params.value(PARAM_MAX_VOICES_ID).unwrap());
+
+        learn.unbind(PARAM_MAX_VOICES_ID);
+        assert_eq!(learn.bindings().count(), 0);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::gain_law::gain_param;
+use crate::lfo::{Lfo, NUM_LFOS};
+use crate::params::{param_desc, ModRange, Params, PARAM_GAIN_ID, PARAM_LFO_RATE_IDS};
+use crate::pitch::{exp2, semitones_to_ratio};
+
+/// Routing slots in the mod matrix.
+pub const MOD_SLOTS: usize = 4;
+
+/// Mod sources, indexed by the slot source params. 0 leaves the slot unused.
+pub const MOD_SOURCE_NAMES: &[&str] = &["Off", "LFO 1", "LFO 2"];
+
+/// Mod destinations, indexed by the slot destination params.
+pub const MOD_DEST_NAMES: &[&str] = &["Pitch", "Amp", "LFO 1 Rate", "LFO 2 Rate"];
+
+const DEST_PITCH: usize = 0;
+const DEST_AMP: usize = 1;
+const DEST_LFO_RATE: usize = 2;
+
+/// Semitones of pitch modulation at full amount.
+const PITCH_RANGE: f32 = 12.0;
+/// Octaves of LFO rate modulation at full amount.
+const LFO_RATE_RANGE: f32 = 2.0;
+
+/// Summed modulation for one block, in destination units.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModValues {
+    /// Semitones.
+    pub pitch: f32,
+    /// Added to a gain factor of 1.0.
+    pub amp: f32,
+    /// Octaves, per LFO.
+    pub lfo_rate: [f32; NUM_LFOS],
+}
+
+impl ModValues {
+    fn add(&mut self, dest: usize, value: f32) {
+        match dest {
+            DEST_PITCH => self.pitch += value * PITCH_RANGE,
+            DEST_AMP => self.amp += value,
+            _ => {
+                if let Some(rate) = self.lfo_rate.get_mut(dest - DEST_LFO_RATE) {
+                    *rate += value * LFO_RATE_RANGE;
+                }
+            }
+        }
+    }
+
+    pub fn gain_factor(&self) -> f32 {
+        (1.0 + self.amp).max(0.0)
+    }
+
+    pub fn pitch_ratio(&self) -> f32 {
+        semitones_to_ratio(self.pitch)
+    }
+}
+
+/// The LFOs and the matrix that routes them, run at block rate on the audio thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Modulation {
+    lfos: [Lfo; NUM_LFOS],
+    /// Seconds since the last note-on, for the LFO fade-in.
+    since_note_on: f32,
+    /// Destinations whose modulation the editor is being shown.
+    shown: [bool; MOD_DEST_NAMES.len()],
+}
+
+impl Modulation {
+    /// Restarts the LFO fade-in, and the LFOs set to retrigger; free-running ones carry on.
+    pub fn note_on(&mut self, params: &Params) {
+        self.since_note_on = 0.0;
+        for (i, lfo) in self.lfos.iter_mut().enumerate() {
+            if params.lfo_retrigger(i) {
+                lfo.reset();
+            }
+        }
+    }
+
+    /// Puts every LFO back to the start of its cycle and the fade-in back to nothing played.
+    pub fn reset(&mut self) {
+        self.since_note_on = 0.0;
+        for lfo in &mut self.lfos {
+            lfo.reset();
+        }
+    }
+
+    /// Routes the LFOs' current outputs through the matrix, then moves them on by `frames`.
+    /// LFO rate modulation takes effect from this block on.
+    pub fn advance(&mut self, params: &Params, frames: usize, sample_rate: f32) -> ModValues {
+        let delay = params.lfo_delay();
+        let fade = if delay > 0.0 { (self.since_note_on / delay).min(1.0) } else { 1.0 };
+        self.since_note_on += frames as f32 / sample_rate;
+
+        let sources: [f32; NUM_LFOS] = std::array::from_fn(|i| {
+            self.lfos[i].value(params.lfo_shape(i)) * params.lfo_depth(i) * fade
+        });
+
+        let mut mods = ModValues::default();
+        // How far the routed LFOs can swing each destination either way, for the editor.
+        let mut swing = ModValues::default();
+        for slot in 0..MOD_SLOTS {
+            let (source, dest, amount) = params.mod_slot(slot);
+            let Some(lfo) = source.checked_sub(1).filter(|&lfo| lfo < NUM_LFOS) else { continue };
+            mods.add(dest, sources[lfo] * amount);
+            swing.add(dest, (params.lfo_depth(lfo) * amount).abs());
+        }
+
+        for (i, lfo) in self.lfos.iter_mut().enumerate() {
+            let rate = params.lfo_rate(i) * exp2(mods.lfo_rate[i]);
+            lfo.advance(rate, frames, sample_rate);
+        }
+        self.show(params, &mods, &swing);
+        mods
+    }
+
+    /// Publishes, for the editor, where the matrix has each destination that has a
+    /// control: the amp on the gain knob, and the LFO rates on theirs. Pitch has no control
+    /// to draw on.
+    fn show(&mut self, params: &Params, mods: &ModValues, swing: &ModValues) {
+        let amp = (swing.amp > 0.0).then(|| {
+            let (law, factor) = (params.gain_law(), params.gain_factor());
+            let gain = |amp: f32| gain_param(law, factor * amp.max(0.0));
+            ModRange {
+                low: gain(1.0 - swing.amp),
+                high: gain(1.0 + swing.amp),
+                value: gain(mods.gain_factor()),
+            }
+        });
+        self.publish(params, DEST_AMP, PARAM_GAIN_ID, amp);
+
+        for (i, &id) in PARAM_LFO_RATE_IDS.iter().enumerate() {
+            let octaves = swing.lfo_rate[i];
+            let range = (octaves > 0.0).then(|| {
+                let base = params.lfo_rate(i);
+                swung_range(id, base, octaves, base * exp2(mods.lfo_rate[i]))
+            });
+            self.publish(params, DEST_LFO_RATE + i, id, range);
+        }
+    }
+
+    /// Only when there's modulation to show, or modulation shown to take down.
+    fn publish(&mut self, params: &Params, dest: usize, id: u32, range: Option<ModRange>) {
+        if range.is_some() || self.shown[dest] {
+            params.set_modulation(id, range);
+            self.shown[dest] = range.is_some();
+        }
+    }
+}
+
+/// `base` swung `octaves` either way, and modulated to `value`, within the param's range.
+fn swung_range(id: u32, base: f32, octaves: f32, value: f32) -> ModRange {
+    let (min, max) = param_desc(id).map_or((f32::MIN, f32::MAX), |d| (d.min as f32, d.max as f32));
+    let swing = exp2(octaves);
+    ModRange {
+        low: (base / swing).clamp(min, max),
+        high: (base * swing).clamp(min, max),
+        value: value.clamp(min, max),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{
+        PARAM_LFO_DELAY_ID, PARAM_LFO_DEPTH_IDS, PARAM_LFO_RATE_IDS, PARAM_LFO_RETRIGGER_IDS,
+        PARAM_LFO_SHAPE_IDS, PARAM_MOD_AMOUNT_IDS, PARAM_MOD_DEST_IDS, PARAM_MOD_SOURCE_IDS,
+    };
+
+    #[test]
+    fn lfos_keep_independent_phase_and_cross_modulate_rate() {
+        let params = Params::default();
+        params.set_value(PARAM_LFO_DEPTH_IDS[0], 1.0);
+        params.set_value(PARAM_MOD_SOURCE_IDS[0], 1.0); // LFO 1
+        params.set_value(PARAM_MOD_DEST_IDS[0], 3.0); // LFO 2 rate
+        params.set_value(PARAM_MOD_AMOUNT_IDS[0], 1.0);
+
+        let mut modulation = Modulation::default();
+        // LFO 1 starts at phase 0, so the first block leaves LFO 2's rate alone.
+        assert_eq!(modulation.advance(&params, 100, 1000.0).lfo_rate, [0.0, 0.0]);
+        let mods = modulation.advance(&params, 100, 1000.0);
+
+        assert!(mods.lfo_rate[1] > 0.0);
+        assert_eq!(mods.lfo_rate[0], 0.0);
+        assert_ne!(modulation.lfos[0].value(0), modulation.lfos[1].value(0));
+    }
+
+    #[test]
+    fn rate_modulation_is_shown_only_while_routed() {
+        let params = Params::default();
+        params.set_value(PARAM_LFO_DEPTH_IDS[0], 1.0);
+        params.set_value(PARAM_MOD_SOURCE_IDS[0], 1.0); // LFO 1
+        params.set_value(PARAM_MOD_DEST_IDS[0], 3.0); // LFO 2 rate
+        params.set_value(PARAM_MOD_AMOUNT_IDS[0], -0.5);
+
+        let mut modulation = Modulation::default();
+        modulation.advance(&params, 100, 1000.0);
+        let range = params.modulation(PARAM_LFO_RATE_IDS[1]).unwrap();
+        assert_eq!((range.low, range.high), (0.5, 2.0));
+        assert!(range.low <= range.value && range.value <= range.high);
+        assert_eq!(params.modulation(PARAM_LFO_RATE_IDS[0]), None);
+
+        params.set_value(PARAM_MOD_SOURCE_IDS[0], 0.0);
+        modulation.advance(&params, 100, 1000.0);
+        assert_eq!(params.modulation(PARAM_LFO_RATE_IDS[1]), None);
+    }
+
+    #[test]
+    fn amp_modulation_is_shown_on_the_gain() {
+        let params = Params::default();
+        params.set_value(PARAM_LFO_DEPTH_IDS[1], 1.0);
+        params.set_value(PARAM_MOD_SOURCE_IDS[2], 2.0); // LFO 2
+        params.set_value(PARAM_MOD_DEST_IDS[2], 1.0); // Amp
+        params.set_value(PARAM_MOD_AMOUNT_IDS[2], 0.5);
+
+        let mut modulation = Modulation::default();
+        modulation.advance(&params, 100, 1000.0);
+        let range = params.modulation(PARAM_GAIN_ID).unwrap();
+        let gain = params.gain();
+        assert!(range.low < gain && gain < range.high, "{range:?}");
+        assert!(range.low <= range.value && range.value <= range.high);
+
+        params.set_value(PARAM_MOD_DEST_IDS[2], 0.0); // Pitch, which has no control
+        modulation.advance(&params, 100, 1000.0);
+        assert_eq!(params.modulation(PARAM_GAIN_ID), None);
+    }
+
+    #[test]
+    fn note_on_only_resets_retriggered_lfos() {
+        let params = Params::default();
+        params.set_value(PARAM_LFO_RETRIGGER_IDS[1], 1.0);
+
+        let mut modulation = Modulation::default();
+        modulation.advance(&params, 250, 1000.0);
+        modulation.note_on(&params);
+
+        assert_eq!(modulation.lfos[0].value(0), 1.0); // a quarter cycle in
+        assert_eq!(modulation.lfos[1].value(0), 0.0);
+    }
+
+    #[test]
+    fn lfo_delay_fades_depth_in_after_note_on() {
+        let params = Params::default();
+        params.set_value(PARAM_LFO_DEPTH_IDS[0], 1.0);
+        params.set_value(PARAM_LFO_SHAPE_IDS[0], 3.0); // Square, at full swing for half a cycle
+        params.set_value(PARAM_LFO_RATE_IDS[0], 0.01);
+        params.set_value(PARAM_MOD_SOURCE_IDS[0], 1.0);
+        params.set_value(PARAM_MOD_AMOUNT_IDS[0], 1.0 / PITCH_RANGE);
+        params.set_value(PARAM_LFO_DELAY_ID, 1.0);
+
+        let mut modulation = Modulation::default();
+        modulation.note_on(&params);
+        for expected in [0.0, 0.25, 0.5, 0.75, 1.0, 1.0] {
+            let pitch = modulation.advance(&params, 250, 1000.0).pitch;
+            assert!((pitch - expected).abs() < 1e-5, "{pitch} != {expected}");
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::sample::Sample;
+
+/// Pole of the tilt filter at either end of the color range. Just short of 1.0, which
+/// would be a pure integrator (or differentiator) and run away.
+const MAX_POLE: Sample = 0.98;
+/// Where the noise starts, and what each voice's seed is spread from.
+const SEED: u32 = 0x2545_f491;
+
+/// Noise oscillator with a sweepable color: white noise through a one-pole filter whose
+/// coefficient tilts the spectrum down towards red at one end and up towards blue at the
+/// other. Output is scaled to keep the level steady across the sweep.
+#[derive(Clone)]
+pub struct ColoredNoise {
+    state: u32,
+    /// Tilt filter state.
+    last: Sample,
+}
+
+impl Default for ColoredNoise {
+    fn default() -> Self {
+        Self { state: SEED, last: 0.0 }
+    }
+}
+
+impl ColoredNoise {
+    /// Noise of its own for voice `index`, so voices started together don't play the same
+    /// stream and sum to one louder copy of it.
+    pub fn for_voice(index: usize) -> Self {
+        // Seeds a golden-ratio step apart; xorshift never leaves zero, so that one's out.
+        let step = (index as u32).wrapping_add(1).wrapping_mul(0x9e37_79b9);
+        Self { state: (SEED ^ step).max(1), last: 0.0 }
+    }
+
+    pub fn clear(&mut self) {
+        self.last = 0.0;
+    }
+
+    /// Next output sample. `color` runs from red (-1.0) through pink-ish and white (0.0)
+    /// to blue (1.0).
+    pub fn next(&mut self, color: f32) -> Sample {
+        // A positive pole favours the lows, a negative one the highs.
+        let pole = -Sample::from(color.clamp(-1.0, 1.0)) * MAX_POLE;
+        self.last = Sample::from(white_noise(&mut self.state)) + pole * self.last;
+        // A one-pole's gain on white noise is 1 / sqrt(1 - pole²).
+        self.last * (1.0 - pole * pole).sqrt()
+    }
+}
+
+/// White noise from -1.0 to 1.0 (xorshift32).
+pub fn white_noise(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Energy of the sample-to-sample differences over the energy of the samples: higher
+    /// for brighter noise.
+    fn brightness(color: f32) -> Sample {
+        let mut noise = ColoredNoise::default();
+        let samples: Vec<Sample> = (0..48000).map(|_| noise.next(color)).collect();
+        let energy: Sample = samples.iter().map(|s| s * s).sum();
+        let highs: Sample = samples.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum();
+        highs / energy
+    }
+
+    #[test]
+    fn each_voice_gets_its_own_noise() {
+        let stream = |index| {
+            let mut noise = ColoredNoise::for_voice(index);
+            (0..48000).map(|_| noise.next(0.0)).collect::<Vec<Sample>>()
+        };
+        let streams: Vec<_> = (0..32).map(stream).collect();
+        let dot = |a: &[Sample], b: &[Sample]| -> Sample {
+            a.iter().zip(b).map(|(a, b)| a * b).sum()
+        };
+        for (i, a) in streams.iter().enumerate() {
+            for b in &streams[i + 1..] {
+                let correlation = dot(a, b) / dot(a, a);
+                assert!(correlation.abs() < 0.05, "voices correlate at {correlation}");
+            }
+        }
+    }
+
+    #[test]
+    fn color_tilts_the_spectrum() {
+        let (red, white, blue) = (brightness(-1.0), brightness(0.0), brightness(1.0));
+        assert!(red < 0.5 * white, "red {red} vs white {white}");
+        assert!(blue > 1.5 * white, "blue {blue} vs white {white}");
+    }
+
+    #[test]
+    fn level_holds_across_the_sweep() {
+        let rms = |color: f32| {
+            let mut noise = ColoredNoise::default();
+            let energy: Sample = (0..48000).map(|_| noise.next(color).powi(2)).sum();
+            (energy / 48000.0).sqrt()
+        };
+        let white = rms(0.0);
+        for color in [-1.0, -0.5, 0.5, 1.0] {
+            assert!((rms(color) / white - 1.0).abs() < 0.25, "color {color}");
+        }
+    }
+}
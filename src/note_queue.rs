@@ -0,0 +1,244 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// A note played on the editor's keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuiNote {
+    /// `velocity` runs from 0.0 to 1.0.
+    On { key: u8, velocity: f32 },
+    Off { key: u8 },
+}
+
+const NOTE_ON: u32 = 1 << 8;
+
+impl GuiNote {
+    fn to_bits(self) -> u32 {
+        match self {
+            Self::On { key, velocity } => {
+                let velocity = (velocity.clamp(0.0, 1.0) * 127.0).round() as u32;
+                NOTE_ON | velocity << 16 | key as u32
+            }
+            Self::Off { key } => key as u32,
+        }
+    }
+
+    fn from_bits(bits: u32) -> Self {
+        let key = bits as u8;
+        if bits & NOTE_ON != 0 {
+            Self::On { key, velocity: (bits >> 16) as u8 as f32 / 127.0 }
+        } else {
+            Self::Off { key }
+        }
+    }
+}
+
+/// Notes kept before new ones get refused.
+const CAPACITY: usize = 64;
+/// [`NoteQueue::held`] when no key is down.
+const NO_KEY: u32 = u32::MAX;
+
+/// Bounded, lock-free queue from the editor to the audio thread, which drains it at the
+/// start of each block.
+///
+/// Unlike [`MainQueue`](crate::main_queue::MainQueue) a full queue refuses new notes
+/// rather than overwriting old ones, since a lost note-off would leave a voice stuck.
+/// The editor doesn't mind: it's only a mouse. One writer and one reader only.
+pub struct NoteQueue {
+    slots: [AtomicU32; CAPACITY],
+    /// Total notes ever written.
+    written: AtomicUsize,
+    /// Total notes the audio thread has consumed.
+    read: AtomicUsize,
+    /// The key the editor holds down, so closing the editor can let go of it.
+    held: AtomicU32,
+    /// One bit per key held down on the computer keyboard, for the same reason.
+    typed: [AtomicU64; 2],
+    /// One bit per key the voices are playing, published by the audio thread each block
+    /// for the editor's keyboard to light up.
+    sounding: [AtomicU64; 2],
+}
+
+impl Default for NoteQueue {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| AtomicU32::new(0)),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            held: AtomicU32::new(NO_KEY),
+            typed: std::array::from_fn(|_| AtomicU64::new(0)),
+            sounding: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl NoteQueue {
+    /// Editor only. Plays `key`, letting go of whichever key was down first.
+    pub fn press(&self, key: u8, velocity: f32) {
+        self.release();
+        if self.push(GuiNote::On { key, velocity }) {
+            self.held.store(key as u32, Ordering::Relaxed);
+        }
+    }
+
+    /// Editor only. Lets go of the held key, if any.
+    pub fn release(&self) {
+        let held = self.held.load(Ordering::Relaxed);
+        if held != NO_KEY && self.push(GuiNote::Off { key: held as u8 }) {
+            self.held.store(NO_KEY, Ordering::Relaxed);
+        }
+    }
+
+    pub fn held(&self) -> Option<u8> {
+        let held = self.held.load(Ordering::Relaxed);
+        (held != NO_KEY).then_some(held as u8)
+    }
+
+    /// Editor only. Plays `key` from the computer keyboard, where any number of keys can be
+    /// down at once. A key that's already down is left alone.
+    pub fn press_typed(&self, key: u8, velocity: f32) {
+        if !self.is_typed(key) && self.push(GuiNote::On { key, velocity }) {
+            let (word, bit) = key_bit(key);
+            self.typed[word].fetch_or(bit, Ordering::Relaxed);
+        }
+    }
+
+    /// Editor only. Lets go of a key held on the computer keyboard.
+    pub fn release_typed(&self, key: u8) {
+        if self.is_typed(key) && self.push(GuiNote::Off { key }) {
+            let (word, bit) = key_bit(key);
+            self.typed[word].fetch_and(!bit, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_typed(&self, key: u8) -> bool {
+        let (word, bit) = key_bit(key);
+        self.typed[word].load(Ordering::Relaxed) & bit != 0
+    }
+
+    /// Editor only. Lets go of every key the editor holds, clicked or typed.
+    pub fn release_all(&self) {
+        self.release();
+        self.release_all_typed();
+    }
+
+    /// Editor only. Lets go of every key held on the computer keyboard.
+    pub fn release_all_typed(&self) {
+        for key in 0..=127 {
+            self.release_typed(key);
+        }
+    }
+
+    fn push(&self, note: GuiNote) -> bool {
+        let index = self.written.load(Ordering::Relaxed);
+        if index - self.read.load(Ordering::Acquire) >= CAPACITY {
+            return false;
+        }
+        self.slots[index % CAPACITY].store(note.to_bits(), Ordering::Relaxed);
+        self.written.store(index + 1, Ordering::Release);
+        true
+    }
+
+    /// Audio thread only. Hands every queued note to `f`, oldest first.
+    pub fn drain(&self, mut f: impl FnMut(GuiNote)) {
+        let written = self.written.load(Ordering::Acquire);
+        let mut read = self.read.load(Ordering::Relaxed);
+        while read < written {
+            f(GuiNote::from_bits(self.slots[read % CAPACITY].load(Ordering::Relaxed)));
+            read += 1;
+        }
+        self.read.store(read, Ordering::Release);
+    }
+
+    /// Audio thread only. Publishes the keys the voices are playing.
+    pub fn set_sounding(&self, keys: impl Iterator<Item = u8>) {
+        let mut bits = [0u64; 2];
+        for key in keys {
+            let (word, bit) = key_bit(key);
+            bits[word] |= bit;
+        }
+        for (slot, bits) in self.sounding.iter().zip(bits) {
+            slot.store(bits, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_sounding(&self, key: u8) -> bool {
+        let (word, bit) = key_bit(key);
+        self.sounding[word].load(Ordering::Relaxed) & bit != 0
+    }
+}
+
+/// Where `key` sits in a two-word key set.
+fn key_bit(key: u8) -> (usize, u64) {
+    ((key as usize / 64) % 2, 1 << (key % 64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &NoteQueue) -> Vec<GuiNote> {
+        let mut notes = Vec::new();
+        queue.drain(|note| notes.push(note));
+        notes
+    }
+
+    #[test]
+    fn pressing_another_key_releases_the_first() {
+        let queue = NoteQueue::default();
+        queue.press(60, 1.0);
+        queue.press(62, 0.0);
+        queue.release();
+        assert_eq!(
+            drain(&queue),
+            [
+                GuiNote::On { key: 60, velocity: 1.0 },
+                GuiNote::Off { key: 60 },
+                GuiNote::On { key: 62, velocity: 0.0 },
+                GuiNote::Off { key: 62 },
+            ]
+        );
+        assert_eq!(queue.held(), None);
+    }
+
+    #[test]
+    fn full_queue_keeps_the_key_held_until_its_note_off_fits() {
+        let queue = NoteQueue::default();
+        for _ in 0..CAPACITY / 2 {
+            queue.press(60, 1.0);
+            queue.release();
+        }
+        queue.press(61, 1.0);
+        assert_eq!(queue.held(), None);
+        queue.release();
+
+        assert_eq!(drain(&queue).len(), CAPACITY);
+        queue.press(62, 1.0);
+        assert_eq!(queue.held(), Some(62));
+    }
+
+    #[test]
+    fn typed_keys_chord_and_all_come_up_together() {
+        let queue = NoteQueue::default();
+        queue.press_typed(60, 1.0);
+        queue.press_typed(64, 1.0);
+        queue.press_typed(60, 1.0); // key repeat
+        queue.press(48, 1.0);
+        queue.release_typed(64);
+        drain(&queue);
+
+        queue.release_all();
+        assert_eq!(drain(&queue), [GuiNote::Off { key: 48 }, GuiNote::Off { key: 60 }]);
+        assert!(!queue.is_typed(60));
+    }
+
+    #[test]
+    fn losing_focus_lets_go_of_typed_keys_only() {
+        let queue = NoteQueue::default();
+        queue.press_typed(60, 1.0);
+        queue.press(48, 1.0);
+        drain(&queue);
+
+        queue.release_all_typed();
+        assert_eq!(drain(&queue), [GuiNote::Off { key: 60 }]);
+        assert_eq!(queue.held(), Some(48));
+    }
+}
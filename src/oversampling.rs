@@ -0,0 +1,177 @@
+use std::f64::consts::PI;
+
+use crate::sample::Sample;
+
+/// Oversampling choices, indexed by the oversampling param.
+pub const OVERSAMPLING_NAMES: &[&str] = &["Off", "2x", "4x"];
+
+/// How many times the sample rate the voices run at for oversampling choice `choice`.
+pub fn oversampling_factor(choice: usize) -> usize {
+    1 << choice.min(OVERSAMPLING_NAMES.len() - 1)
+}
+
+/// Non-zero taps either side of the half-band filter's centre. Every other tap of a
+/// half-band filter is zero, so these are the odd ones, out to `2 * HALF_TAPS - 1`.
+const HALF_TAPS: usize = 12;
+/// The filter's centre tap, in input samples back from the newest: its group delay.
+const CENTRE: usize = 2 * HALF_TAPS;
+const TAPS: usize = 2 * CENTRE + 1;
+
+/// The output delay, in samples at the plugin's rate, of decimating from `factor` times
+/// the rate: each halving delays by its filter's centre at the rate it runs at.
+pub fn decimation_latency(factor: usize) -> u32 {
+    match factor {
+        4 => (CENTRE / 4 + CENTRE / 2) as u32,
+        2 => (CENTRE / 2) as u32,
+        _ => 0,
+    }
+}
+
+/// Halves the sample rate: a windowed-sinc lowpass at a quarter of the input rate, then
+/// every other sample. A half-band filter's odd taps are all that's left to multiply, and
+/// they're symmetric, so it costs a dozen multiplies an output sample.
+#[derive(Clone)]
+struct HalfBand {
+    /// The odd taps, nearest the centre first.
+    coefficients: [Sample; HALF_TAPS],
+    /// The last [`TAPS`] inputs, written twice over so they can always be read as one
+    /// slice, oldest first.
+    history: [Sample; 2 * TAPS],
+    position: usize,
+}
+
+impl HalfBand {
+    fn new() -> Self {
+        // Blackman-windowed, for around 70 dB of stopband from a short filter.
+        let window = |n: usize| {
+            let x = 2.0 * PI * n as f64 / (TAPS - 1) as f64;
+            0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos()
+        };
+        let mut coefficients: [f64; HALF_TAPS] = std::array::from_fn(|i| {
+            let offset = 2 * i + 1;
+            let sinc = (PI * offset as f64 / 2.0).sin() / (PI * offset as f64);
+            sinc * window(CENTRE + offset)
+        });
+        // Scaled so the gain at DC is exactly one: the centre tap gives half of it.
+        let sum: f64 = coefficients.iter().sum();
+        coefficients.iter_mut().for_each(|c| *c *= 0.25 / sum);
+        Self {
+            coefficients: coefficients.map(|c| c as Sample),
+            history: [0.0; 2 * TAPS],
+            position: 0,
+        }
+    }
+
+    fn push(&mut self, input: Sample) {
+        self.position = (self.position + 1) % TAPS;
+        self.history[self.position] = input;
+        self.history[self.position + TAPS] = input;
+    }
+
+    /// Filters into `output`, one sample for every two of `input`. Each output sample is
+    /// taken at the first of its pair, so the delay comes out in whole samples.
+    fn decimate(&mut self, input: &[Sample], output: &mut [Sample]) {
+        for (pair, out) in input.chunks_exact(2).zip(output) {
+            self.push(pair[0]);
+            let window = &self.history[self.position + 1..self.position + 1 + TAPS];
+            let mut sum = window[CENTRE] * 0.5;
+            for (i, coefficient) in self.coefficients.iter().enumerate() {
+                let offset = 2 * i + 1;
+                sum += (window[CENTRE - offset] + window[CENTRE + offset]) * coefficient;
+            }
+            *out = sum;
+            self.push(pair[1]);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.history = [0.0; 2 * TAPS];
+    }
+}
+
+/// Brings voices rendered at 2x or 4x the sample rate back down to it, a halving at a time,
+/// filtering out what's above the plugin's Nyquist first so it doesn't fold back down.
+pub struct Decimator {
+    factor: usize,
+    stages: [HalfBand; 2],
+    /// Between the halvings at 4x, sized for the largest block.
+    halfway: Vec<Sample>,
+}
+
+impl Decimator {
+    /// A decimator from `factor` times the rate, for blocks of up to `max_frames` at the
+    /// plugin's rate.
+    pub fn new(factor: usize, max_frames: usize) -> Self {
+        let halfway = if factor == 4 { vec![0.0; max_frames * 2] } else { Vec::new() };
+        Self { factor, stages: [HalfBand::new(), HalfBand::new()], halfway }
+    }
+
+    /// `output.len()` samples from `factor` times as many of `input`, overwriting `output`.
+    pub fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        let [first, second] = &mut self.stages;
+        match self.factor {
+            4 => {
+                let halfway = &mut self.halfway[..output.len() * 2];
+                first.decimate(input, halfway);
+                second.decimate(halfway, output);
+            }
+            2 => first.decimate(input, output),
+            _ => output.copy_from_slice(&input[..output.len()]),
+        }
+    }
+
+    /// Forgets the filters' history.
+    pub fn clear(&mut self) {
+        self.stages.iter_mut().for_each(HalfBand::clear);
+    }
+
+    /// Picks up where `other` is, history and all, for a signal that's been going through
+    /// `other` until now. Allocates nothing.
+    pub fn follow(&mut self, other: &Decimator) {
+        self.stages.clone_from(&other.stages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `input` brought down from `factor` times the rate.
+    fn decimated(factor: usize, input: &[Sample]) -> Vec<Sample> {
+        let mut decimator = Decimator::new(factor, input.len());
+        let mut output = vec![0.0; input.len() / factor];
+        decimator.process(input, &mut output);
+        output
+    }
+
+    #[test]
+    fn an_impulse_comes_out_after_the_reported_latency() {
+        for factor in [2, 4] {
+            let mut input = vec![0.0; 256 * factor];
+            input[0] = 1.0;
+            let output = decimated(factor, &input);
+            let peak = (0..output.len()).max_by(|&a, &b| output[a].total_cmp(&output[b]));
+            assert_eq!(peak, Some(decimation_latency(factor) as usize), "{factor}x");
+        }
+    }
+
+    #[test]
+    fn passes_the_band_and_stops_what_would_alias() {
+        // Amplitude out, after the filters have filled, of a sine at `cycles` per output
+        // sample.
+        let level = |factor: usize, cycles: f64| {
+            let input: Vec<Sample> = (0..4096 * factor)
+                .map(|n| (2.0 * PI * cycles * n as f64 / factor as f64).sin() as Sample)
+                .collect();
+            let output = decimated(factor, &input);
+            output[256..].iter().fold(0.0, |peak: Sample, s| peak.max(s.abs()))
+        };
+        for factor in [2, 4] {
+            // 10 kHz at 48 kHz, then what would fold down onto 18 kHz.
+            let pass = level(factor, 10.0 / 48.0);
+            let stop = level(factor, 30.0 / 48.0);
+            assert!((pass - 1.0).abs() < 0.01, "{factor}x passes at {pass}");
+            assert!(stop < 1e-3, "{factor}x lets {stop} through");
+        }
+    }
+}
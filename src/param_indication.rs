@@ -0,0 +1,50 @@
+use std::sync::Mutex;
+
+use crate::params::PARAMS;
+
+/// What the host's automation is doing with a parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AutomationState {
+    #[default]
+    None,
+    Present,
+    Playing,
+    Recording,
+    /// The user moved the control and took over from the automation lane.
+    Overriding,
+}
+
+/// Host-provided hints for a single parameter, as reported via param-indication.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ParamIndication {
+    /// Set while a hardware controller (or host macro) is mapped to the parameter.
+    pub mapping_color: Option<[u8; 3]>,
+    pub automation: AutomationState,
+    pub automation_color: Option<[u8; 3]>,
+}
+
+/// Indications for every param, indexed like [`PARAMS`]. Written by the main thread,
+/// read by the editor every frame.
+pub struct SharedIndications(Mutex<Vec<ParamIndication>>);
+
+impl Default for SharedIndications {
+    fn default() -> Self {
+        Self(Mutex::new(vec![ParamIndication::default(); PARAMS.len()]))
+    }
+}
+
+impl SharedIndications {
+    pub fn get(&self, param_id: u32) -> ParamIndication {
+        let Some(index) = PARAMS.iter().position(|desc| desc.id == param_id) else {
+            return ParamIndication::default();
+        };
+        self.0.lock().map(|all| all[index]).unwrap_or_default()
+    }
+
+    pub fn update(&self, param_id: u32, f: impl FnOnce(&mut ParamIndication)) {
+        let Some(index) = PARAMS.iter().position(|desc| desc.id == param_id) else { return };
+        if let Ok(mut all) = self.0.lock() {
+            f(&mut all[index]);
+        }
+    }
+}
@@ -1,35 +1,1320 @@
 use atomic_float::AtomicF32;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
 
-use clack_plugin::events::event_types::ParamValueEvent;
+use clack_plugin::events::event_types::{ParamModEvent, ParamValueEvent};
+use clack_plugin::events::Pckn;
+
+use crate::auto_pan::{AutoPanSettings, AUTO_PAN_SYNC_NAMES};
+use crate::chord::CHORD_NAMES;
+use crate::envelope::{EnvelopeSettings, ENV_MODE_GATE, ENV_MODE_NAMES};
+use crate::filter::{MAX_CUTOFF, MIN_CUTOFF};
+use crate::gain_law::{gain_amplitude, GAIN_LAW_NAMES};
+use crate::lfo::{LFO_SHAPE_NAMES, NUM_LFOS};
+use crate::mod_matrix::{MOD_DEST_NAMES, MOD_SLOTS, MOD_SOURCE_NAMES};
+use crate::oversampling::{oversampling_factor, OVERSAMPLING_NAMES};
+use crate::reverb::{ReverbSettings, MAX_PREDELAY};
+use crate::split::SPLIT_MODE_NAMES;
+use crate::voice::{MAX_UNISON, MAX_VOICES, WAVEFORM_NAMES};
 
 pub const PARAM_GAIN_ID: u32 = 0;
+pub const PARAM_CHORD_TYPE_ID: u32 = 1;
+pub const PARAM_SPLIT_MODE_ID: u32 = 2;
+pub const PARAM_SPLIT_POINT_ID: u32 = 3;
+pub const PARAM_LOWER_OCTAVE_ID: u32 = 4;
+pub const PARAM_UPPER_OCTAVE_ID: u32 = 5;
+pub const PARAM_PITCH_ENV_AMOUNT_ID: u32 = 6;
+pub const PARAM_PITCH_ENV_DECAY_ID: u32 = 7;
+pub const PARAM_ENV_MODE_ID: u32 = 8;
+pub const PARAM_ATTACK_ID: u32 = 9;
+pub const PARAM_HOLD_ID: u32 = 10;
+pub const PARAM_DECAY_ID: u32 = 11;
+pub const PARAM_SUSTAIN_ID: u32 = 12;
+pub const PARAM_RELEASE_ID: u32 = 13;
+pub const PARAM_ENV_LOOP_ID: u32 = 14;
+pub const PARAM_LFO_RATE_IDS: [u32; NUM_LFOS] = [15, 16];
+pub const PARAM_LFO_DEPTH_IDS: [u32; NUM_LFOS] = [17, 18];
+pub const PARAM_LFO_SHAPE_IDS: [u32; NUM_LFOS] = [19, 20];
+pub const PARAM_MOD_SOURCE_IDS: [u32; MOD_SLOTS] = [21, 22, 23, 24];
+pub const PARAM_MOD_DEST_IDS: [u32; MOD_SLOTS] = [25, 26, 27, 28];
+pub const PARAM_MOD_AMOUNT_IDS: [u32; MOD_SLOTS] = [29, 30, 31, 32];
+pub const PARAM_LFO_RETRIGGER_IDS: [u32; NUM_LFOS] = [33, 34];
+pub const PARAM_LFO_DELAY_ID: u32 = 35;
+pub const PARAM_MAX_VOICES_ID: u32 = 36;
+pub const PARAM_AUTO_PAN_RATE_ID: u32 = 37;
+pub const PARAM_AUTO_PAN_DEPTH_ID: u32 = 38;
+pub const PARAM_AUTO_PAN_SHAPE_ID: u32 = 39;
+pub const PARAM_AUTO_PAN_SYNC_ID: u32 = 40;
+pub const PARAM_COMB_MIX_ID: u32 = 41;
+pub const PARAM_COMB_FEEDBACK_ID: u32 = 42;
+pub const PARAM_WAVEFORM_ID: u32 = 43;
+pub const PARAM_PLUCK_TONE_ID: u32 = 44;
+pub const PARAM_FX_MIX_ID: u32 = 45;
+pub const PARAM_AUTO_PAN_ON_ID: u32 = 46;
+pub const PARAM_COMB_ON_ID: u32 = 47;
+pub const PARAM_CUTOFF_ID: u32 = 48;
+pub const PARAM_RESONANCE_ID: u32 = 49;
+pub const PARAM_VEL_TO_CUTOFF_ID: u32 = 50;
+pub const PARAM_NOISE_COLOR_ID: u32 = 51;
+pub const PARAM_KEY_TO_PAN_ID: u32 = 52;
+pub const PARAM_GAIN_LAW_ID: u32 = 53;
+pub const PARAM_LIMITER_ON_ID: u32 = 54;
+pub const PARAM_UNISON_VOICES_ID: u32 = 55;
+pub const PARAM_UNISON_DETUNE_ID: u32 = 56;
+pub const PARAM_REVERB_ON_ID: u32 = 57;
+pub const PARAM_REVERB_MIX_ID: u32 = 58;
+pub const PARAM_REVERB_DECAY_ID: u32 = 59;
+pub const PARAM_REVERB_DAMPING_ID: u32 = 60;
+pub const PARAM_REVERB_PREDELAY_ID: u32 = 61;
+pub const PARAM_REVERB_FREEZE_ID: u32 = 62;
+pub const PARAM_OVERSAMPLING_ID: u32 = 63;
+
+const OFF_ON: &[&str] = &["Off", "On"];
+
+/// How a param's value is shown to (and typed by) the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    None,
+    /// A MIDI note number, shown as a note name ("C4" is 60).
+    Note,
+    /// Signed semitones, shown as "+12.0 st".
+    Semitones,
+    /// A time in seconds, shown in ms below one second.
+    Seconds,
+    Hertz,
+}
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+pub fn note_name(note: i32) -> String {
+    format!("{}{}", NOTE_NAMES[note.rem_euclid(12) as usize], note.div_euclid(12) - 1)
+}
+
+fn parse_note_name(text: &str) -> Option<f64> {
+    let split = text.find(|c: char| c == '-' || c.is_ascii_digit())?;
+    let (name, octave) = text.split_at(split);
+    let pitch = NOTE_NAMES.iter().position(|n| n.eq_ignore_ascii_case(name))? as i32;
+    let octave: i32 = octave.parse().ok()?;
+    Some(((octave + 1) * 12 + pitch) as f64)
+}
+
+/// A plain number or a fraction such as "1/8".
+fn parse_number(text: &str) -> Option<f64> {
+    match text.split_once('/') {
+        Some((num, den)) => {
+            let (num, den) = (num.trim().parse::<f64>().ok()?, den.trim().parse::<f64>().ok()?);
+            (den != 0.0).then_some(num / den)
+        }
+        None => text.parse().ok(),
+    }
+}
+
+/// Static description of a parameter as exposed to the host.
+pub struct ParamDesc {
+    pub id: u32,
+    pub name: &'static str,
+    pub module: &'static str,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+    pub stepped: bool,
+    pub unit: Unit,
+    /// Display names for stepped params, indexed by value. Empty for continuous params.
+    pub labels: &'static [&'static str],
+    /// One line on what the param does, for the editor's tooltips.
+    pub description: &'static str,
+    /// Seconds a change glides over in the engine's [`Smoother`](crate::smoother::Smoother).
+    /// Only params the engine reads through one set it; the rest, stepped params among
+    /// them, jump, and stay at 0.0.
+    pub smoothing: f32,
+    /// Whether the host may modulate the param (CLAP's param mod events) on top of its
+    /// value. Only params the engine reads every block take it.
+    pub modulatable: bool,
+}
+
+impl ParamDesc {
+    const fn new(id: u32, name: &'static str, min: f64, max: f64, default: f64) -> Self {
+        Self {
+            id,
+            name,
+            module: "",
+            min,
+            max,
+            default,
+            stepped: false,
+            unit: Unit::None,
+            labels: &[],
+            description: "",
+            smoothing: 0.0,
+            modulatable: false,
+        }
+    }
+
+    /// A stepped param with one named value per label, starting at 0.
+    const fn choice(id: u32, name: &'static str, labels: &'static [&'static str], default: f64) -> Self {
+        Self { labels, ..Self::integer(id, name, 0.0, (labels.len() - 1) as f64, default) }
+    }
+
+    /// A stepped param over whole numbers.
+    const fn integer(id: u32, name: &'static str, min: f64, max: f64, default: f64) -> Self {
+        Self { stepped: true, ..Self::new(id, name, min, max, default) }
+    }
+
+    const fn with_unit(self, unit: Unit) -> Self {
+        Self { unit, ..self }
+    }
+
+    const fn with_description(self, description: &'static str) -> Self {
+        Self { description, ..self }
+    }
+
+    const fn with_smoothing(self, smoothing: f32) -> Self {
+        Self { smoothing, ..self }
+    }
+
+    const fn modulatable(self) -> Self {
+        Self { modulatable: true, ..self }
+    }
+
+    pub fn is_stepped(&self) -> bool {
+        self.stepped
+    }
+
+    /// An Off/On switch, shown as a toggle.
+    pub fn is_toggle(&self) -> bool {
+        self.labels == OFF_ON
+    }
+
+    pub fn label(&self, value: f64) -> Option<&'static str> {
+        self.labels.get(value.round() as usize).copied()
+    }
+
+    /// Display text for `value`; shared by the host's value_to_text and the editor.
+    pub fn format(&self, value: f64) -> String {
+        if let Some(label) = self.label(value) {
+            return label.to_string();
+        }
+        match self.unit {
+            Unit::Note => note_name(value.round() as i32),
+            Unit::Semitones => format!("{:+.1} st", value),
+            Unit::Seconds if value < 1.0 => format!("{:.0} ms", value * 1000.0),
+            Unit::Seconds => format!("{:.2} s", value),
+            Unit::Hertz => format!("{:.2} Hz", value),
+            Unit::None if self.stepped => format!("{}", value.round()),
+            Unit::None => format!("{:.3}", value),
+        }
+    }
+
+    /// Inverse of [`ParamDesc::format`]. Accepts a value label, a note name for note
+    /// params, a number with or without its unit suffix, or a plain number. Numbers may
+    /// be written as fractions ("1/8").
+    pub fn parse(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        if let Some(index) = self.labels.iter().position(|l| l.eq_ignore_ascii_case(text)) {
+            return Some(index as f64);
+        }
+        let value = match self.unit {
+            Unit::Note => parse_note_name(text).or_else(|| parse_number(text))?,
+            Unit::Semitones => parse_number(text.trim_end_matches("st").trim())?,
+            Unit::Seconds => match text.strip_suffix("ms") {
+                Some(ms) => parse_number(ms.trim())? / 1000.0,
+                None => parse_number(text.trim_end_matches('s').trim())?,
+            },
+            Unit::Hertz => parse_number(text.trim_end_matches("Hz").trim())?,
+            Unit::None => parse_number(text)?,
+        };
+        let value = if self.stepped { value.round() } else { value };
+        Some(value.clamp(self.min, self.max))
+    }
+}
+
+/// Every parameter the plugin exposes, in host-facing index order. The defaults here are
+/// both what the host is told and what [`Params::default`] starts from.
+pub const PARAMS: &[ParamDesc] = &[
+    ParamDesc::new(PARAM_GAIN_ID, "Gain", 0.0, 1.0, 0.5)
+        .with_smoothing(0.005)
+        .modulatable()
+        .with_description("Output level of the whole synth, after the effects."),
+    ParamDesc::choice(PARAM_GAIN_LAW_ID, "Gain Law", GAIN_LAW_NAMES, 0.0)
+        .with_description("How the gain fader's travel maps to loudness."),
+    ParamDesc::choice(PARAM_LIMITER_ON_ID, "Limiter", OFF_ON, 1.0)
+        .with_description("Holds peaks down to -1 dBFS so the output never clips."),
+    ParamDesc::choice(PARAM_CHORD_TYPE_ID, "Chord", CHORD_NAMES, 0.0)
+        .with_description("Plays a chord built on each key instead of a single note."),
+    ParamDesc::integer(PARAM_MAX_VOICES_ID, "Max Voices", 1.0, MAX_VOICES as f64, MAX_VOICES as f64)
+        .with_description("How many voices may sound at once; past this the oldest is stolen."),
+    ParamDesc::choice(PARAM_OVERSAMPLING_ID, "Oversampling", OVERSAMPLING_NAMES, 0.0)
+        .with_description("Renders voices at a higher rate against aliasing. Applies on restart."),
+    ParamDesc::new(PARAM_KEY_TO_PAN_ID, "Key to Pan", -1.0, 1.0, 0.0)
+        .with_description("Spreads notes left to right by pitch; negative reverses it."),
+    ParamDesc::choice(PARAM_SPLIT_MODE_ID, "Split Mode", SPLIT_MODE_NAMES, 0.0)
+        .with_description("Whole keyboard, two split zones, or both zones layered."),
+    ParamDesc::integer(PARAM_SPLIT_POINT_ID, "Split Point", 0.0, 127.0, 60.0)
+        .with_unit(Unit::Note)
+        .with_description("Lowest key of the upper zone when the keyboard is split."),
+    ParamDesc::integer(PARAM_LOWER_OCTAVE_ID, "Lower Octave", -3.0, 3.0, 0.0)
+        .with_description("Octave shift of the lower zone."),
+    ParamDesc::integer(PARAM_UPPER_OCTAVE_ID, "Upper Octave", -3.0, 3.0, 0.0)
+        .with_description("Octave shift of the upper zone."),
+    ParamDesc::choice(PARAM_WAVEFORM_ID, "Waveform", WAVEFORM_NAMES, 0.0)
+        .with_description("The oscillator every voice plays."),
+    ParamDesc::integer(PARAM_UNISON_VOICES_ID, "Unison", 1.0, MAX_UNISON as f64, 1.0)
+        .with_description("Detuned copies of the square wave per note, spread left to right."),
+    ParamDesc::new(PARAM_UNISON_DETUNE_ID, "Unison Detune", 0.0, 1.0, 0.2)
+        .with_unit(Unit::Semitones)
+        .with_description("How far the outermost unison copies are tuned either side of the note."),
+    ParamDesc::new(PARAM_PLUCK_TONE_ID, "Pluck Tone", 0.0, 1.0, 0.5)
+        .modulatable()
+        .with_description("How bright the plucked string stays; darker strings die away sooner."),
+    ParamDesc::new(PARAM_NOISE_COLOR_ID, "Noise Color", -1.0, 1.0, 0.0)
+        .modulatable()
+        .with_description("Tilts the noise from red (-1) through white (0) to blue (+1)."),
+    ParamDesc::new(PARAM_PITCH_ENV_AMOUNT_ID, "Pitch Env Amount", -48.0, 48.0, 0.0)
+        .with_unit(Unit::Semitones)
+        .with_description("How far each note's pitch starts from where it settles."),
+    ParamDesc::new(PARAM_PITCH_ENV_DECAY_ID, "Pitch Env Decay", 0.001, 2.0, 0.1)
+        .with_unit(Unit::Seconds)
+        .with_description("How long the pitch sweep takes to reach the played pitch."),
+    ParamDesc::new(PARAM_CUTOFF_ID, "Cutoff", MIN_CUTOFF as f64, MAX_CUTOFF as f64, MAX_CUTOFF as f64)
+        .with_unit(Unit::Hertz)
+        .with_smoothing(0.05)
+        .modulatable()
+        .with_description("Frequency above which the lowpass filter cuts the sound."),
+    ParamDesc::new(PARAM_RESONANCE_ID, "Resonance", 0.0, 1.0, 0.0)
+        .modulatable()
+        .with_description("Emphasis at the filter cutoff; high values ring."),
+    ParamDesc::new(PARAM_VEL_TO_CUTOFF_ID, "Velocity to Cutoff", 0.0, 60.0, 0.0)
+        .with_unit(Unit::Semitones)
+        .modulatable()
+        .with_description("How far a full-velocity note opens the filter above the cutoff."),
+    ParamDesc::choice(PARAM_ENV_MODE_ID, "Env Mode", ENV_MODE_NAMES, 0.0)
+        .with_description("ADSR follows the key; Gate runs attack, hold and decay regardless."),
+    ParamDesc::new(PARAM_ATTACK_ID, "Attack", 0.0, 5.0, 0.005)
+        .with_unit(Unit::Seconds)
+        .with_description("Time for a note to rise to full level."),
+    ParamDesc::new(PARAM_HOLD_ID, "Hold", 0.0, 5.0, 0.1)
+        .with_unit(Unit::Seconds)
+        .with_description("Time a gate-mode note stays at full level before decaying."),
+    ParamDesc::new(PARAM_DECAY_ID, "Decay", 0.0, 5.0, 0.2)
+        .with_unit(Unit::Seconds)
+        .with_description("Time for a note to fall from full level to the sustain level."),
+    ParamDesc::new(PARAM_SUSTAIN_ID, "Sustain", 0.0, 1.0, 1.0)
+        .with_description("Level a note holds at while its key is down."),
+    ParamDesc::new(PARAM_RELEASE_ID, "Release", 0.0, 5.0, 0.05)
+        .with_unit(Unit::Seconds)
+        .with_description("Time for a note to fade out after its key goes up."),
+    ParamDesc::choice(PARAM_ENV_LOOP_ID, "Env Loop", OFF_ON, 0.0)
+        .with_description("Restarts the amp envelope whenever it ends, while the key is down."),
+    ParamDesc::new(PARAM_LFO_RATE_IDS[0], "LFO 1 Rate", 0.01, 20.0, 1.0)
+        .with_unit(Unit::Hertz)
+        .modulatable()
+        .with_description("Speed of LFO 1."),
+    ParamDesc::new(PARAM_LFO_DEPTH_IDS[0], "LFO 1 Depth", 0.0, 1.0, 0.0)
+        .modulatable()
+        .with_description("How strongly LFO 1 modulates its destinations."),
+    ParamDesc::choice(PARAM_LFO_SHAPE_IDS[0], "LFO 1 Shape", LFO_SHAPE_NAMES, 0.0)
+        .with_description("Waveform of LFO 1."),
+    ParamDesc::choice(PARAM_LFO_RETRIGGER_IDS[0], "LFO 1 Retrigger", OFF_ON, 0.0)
+        .with_description("Restarts LFO 1 on every note instead of letting it run freely."),
+    ParamDesc::new(PARAM_LFO_RATE_IDS[1], "LFO 2 Rate", 0.01, 20.0, 1.0)
+        .with_unit(Unit::Hertz)
+        .modulatable()
+        .with_description("Speed of LFO 2."),
+    ParamDesc::new(PARAM_LFO_DEPTH_IDS[1], "LFO 2 Depth", 0.0, 1.0, 0.0)
+        .modulatable()
+        .with_description("How strongly LFO 2 modulates its destinations."),
+    ParamDesc::choice(PARAM_LFO_SHAPE_IDS[1], "LFO 2 Shape", LFO_SHAPE_NAMES, 0.0)
+        .with_description("Waveform of LFO 2."),
+    ParamDesc::choice(PARAM_LFO_RETRIGGER_IDS[1], "LFO 2 Retrigger", OFF_ON, 0.0)
+        .with_description("Restarts LFO 2 on every note instead of letting it run freely."),
+    ParamDesc::new(PARAM_LFO_DELAY_ID, "LFO Delay", 0.0, 5.0, 0.0)
+        .with_unit(Unit::Seconds)
+        .with_description("Time the LFOs take to fade in after a note starts."),
+    ParamDesc::choice(PARAM_MOD_SOURCE_IDS[0], "Mod 1 Source", MOD_SOURCE_NAMES, 0.0)
+        .with_description("What mod slot 1 takes its modulation from."),
+    ParamDesc::choice(PARAM_MOD_DEST_IDS[0], "Mod 1 Destination", MOD_DEST_NAMES, 0.0)
+        .with_description("What mod slot 1 modulates."),
+    ParamDesc::new(PARAM_MOD_AMOUNT_IDS[0], "Mod 1 Amount", -1.0, 1.0, 0.0)
+        .with_description("How strongly, and in which direction, mod slot 1 modulates."),
+    ParamDesc::choice(PARAM_MOD_SOURCE_IDS[1], "Mod 2 Source", MOD_SOURCE_NAMES, 0.0)
+        .with_description("What mod slot 2 takes its modulation from."),
+    ParamDesc::choice(PARAM_MOD_DEST_IDS[1], "Mod 2 Destination", MOD_DEST_NAMES, 0.0)
+        .with_description("What mod slot 2 modulates."),
+    ParamDesc::new(PARAM_MOD_AMOUNT_IDS[1], "Mod 2 Amount", -1.0, 1.0, 0.0)
+        .with_description("How strongly, and in which direction, mod slot 2 modulates."),
+    ParamDesc::choice(PARAM_MOD_SOURCE_IDS[2], "Mod 3 Source", MOD_SOURCE_NAMES, 0.0)
+        .with_description("What mod slot 3 takes its modulation from."),
+    ParamDesc::choice(PARAM_MOD_DEST_IDS[2], "Mod 3 Destination", MOD_DEST_NAMES, 0.0)
+        .with_description("What mod slot 3 modulates."),
+    ParamDesc::new(PARAM_MOD_AMOUNT_IDS[2], "Mod 3 Amount", -1.0, 1.0, 0.0)
+        .with_description("How strongly, and in which direction, mod slot 3 modulates."),
+    ParamDesc::choice(PARAM_MOD_SOURCE_IDS[3], "Mod 4 Source", MOD_SOURCE_NAMES, 0.0)
+        .with_description("What mod slot 4 takes its modulation from."),
+    ParamDesc::choice(PARAM_MOD_DEST_IDS[3], "Mod 4 Destination", MOD_DEST_NAMES, 0.0)
+        .with_description("What mod slot 4 modulates."),
+    ParamDesc::new(PARAM_MOD_AMOUNT_IDS[3], "Mod 4 Amount", -1.0, 1.0, 0.0)
+        .with_description("How strongly, and in which direction, mod slot 4 modulates."),
+    ParamDesc::new(PARAM_FX_MIX_ID, "FX Mix", 0.0, 1.0, 1.0)
+        .modulatable()
+        .with_description("Balance between the dry sound and the effects."),
+    ParamDesc::choice(PARAM_AUTO_PAN_ON_ID, "Auto-Pan", OFF_ON, 1.0)
+        .with_description("Sweeps the output between the left and right channels."),
+    ParamDesc::new(PARAM_AUTO_PAN_RATE_ID, "Auto-Pan Rate", 0.01, 20.0, 1.0)
+        .with_unit(Unit::Hertz)
+        .modulatable()
+        .with_description("Speed of the auto-pan sweep when it isn't synced to the tempo."),
+    ParamDesc::new(PARAM_AUTO_PAN_DEPTH_ID, "Auto-Pan Depth", 0.0, 1.0, 0.0)
+        .modulatable()
+        .with_description("How far the auto-pan sweeps towards each side."),
+    ParamDesc::choice(PARAM_AUTO_PAN_SHAPE_ID, "Auto-Pan Shape", LFO_SHAPE_NAMES, 0.0)
+        .with_description("Waveform of the auto-pan sweep."),
+    ParamDesc::choice(PARAM_AUTO_PAN_SYNC_ID, "Auto-Pan Sync", AUTO_PAN_SYNC_NAMES, 0.0)
+        .with_description("Locks the auto-pan sweep to the host tempo."),
+    ParamDesc::choice(PARAM_COMB_ON_ID, "Comb", OFF_ON, 1.0)
+        .with_description("A comb filter tuned to each note, for metallic and resonant tones."),
+    ParamDesc::new(PARAM_COMB_MIX_ID, "Comb Mix", 0.0, 1.0, 0.0)
+        .modulatable()
+        .with_description("How much of the comb-filtered sound is heard."),
+    ParamDesc::new(PARAM_COMB_FEEDBACK_ID, "Comb Feedback", 0.0, 0.99, 0.9)
+        .modulatable()
+        .with_description("How long the comb filter rings."),
+    ParamDesc::choice(PARAM_REVERB_ON_ID, "Reverb", OFF_ON, 0.0)
+        .with_description("A stereo reverb on the whole mix, for a sense of space."),
+    ParamDesc::new(PARAM_REVERB_MIX_ID, "Reverb Mix", 0.0, 1.0, 0.3)
+        .modulatable()
+        .with_description("How much reverb is added to the dry sound."),
+    ParamDesc::new(PARAM_REVERB_DECAY_ID, "Reverb Decay", 0.0, 1.0, 0.5)
+        .modulatable()
+        .with_description("How long the reverb's tail lasts, from a small room to a cavern."),
+    ParamDesc::new(PARAM_REVERB_DAMPING_ID, "Reverb Damping", 0.0, 1.0, 0.5)
+        .modulatable()
+        .with_description("How quickly the highs fade from the reverb's tail."),
+    ParamDesc::new(PARAM_REVERB_PREDELAY_ID, "Reverb Predelay", 0.0, MAX_PREDELAY as f64, 0.02)
+        .with_unit(Unit::Seconds)
+        .with_smoothing(0.1)
+        .modulatable()
+        .with_description("Time before the first echoes come back off the cave's walls."),
+    ParamDesc::choice(PARAM_REVERB_FREEZE_ID, "Reverb Freeze", OFF_ON, 0.0)
+        .with_description("Holds the reverb's tail indefinitely and lets nothing more in."),
+];
+
+pub fn param_desc(id: u32) -> Option<&'static ParamDesc> {
+    PARAMS.iter().find(|desc| desc.id == id)
+}
+
+/// `value` as text, the way the host shows it through `value_to_text`. The editor's
+/// readouts go through here too, so the two never disagree.
+pub fn value_text(id: u32, value: f64) -> String {
+    match param_desc(id) {
+        Some(desc) => desc.format(value),
+        None => format!("{value:.3}"),
+    }
+}
+
+/// Typed text as a value, for the host's `text_to_value` and the editor's value entry.
+pub fn text_value(id: u32, text: &str) -> Option<f64> {
+    param_desc(id)?.parse(text)
+}
+
+/// A page of up to eight controls for hardware controllers and host macro panels.
+pub struct RemotePage {
+    pub id: u32,
+    pub name: &'static str,
+    pub params: &'static [u32],
+}
+
+/// A position left empty on a remote page: CLAP's invalid id, which no param has.
+pub const NO_CONTROL: u32 = u32::MAX;
+
+impl RemotePage {
+    /// The page's controls as the params stand, a position each, `None` for an empty one.
+    /// A mod slot with no source only shows its source: its destination and amount do
+    /// nothing until it has one, so they come and go with the routing,
+    /// [`Params::mod_routing`], though nothing else on the page moves.
+    pub fn controls<'a>(&'a self, params: &'a Params) -> impl Iterator<Item = Option<u32>> + 'a {
+        let unrouted = move |id: u32| {
+            let mut slot_params = PARAM_MOD_DEST_IDS.iter().chain(&PARAM_MOD_AMOUNT_IDS);
+            let slot = slot_params.position(|&param| param == id).map(|i| i % MOD_SLOTS);
+            slot.is_some_and(|slot| params.mod_slot(slot).0 == 0)
+        };
+        let shown = move |id: u32| param_desc(id).is_some() && !unrouted(id);
+        self.params.iter().map(move |&id| shown(id).then_some(id))
+    }
+}
+
+/// Curated remote-control pages. Ids are stable: hosts remember them per project.
+/// Params that aren't in [`PARAMS`] leave their position empty, and pages left empty
+/// aren't published.
+pub const REMOTE_PAGES: &[RemotePage] = &[
+    RemotePage {
+        id: 0,
+        name: "Main",
+        params: &[
+            PARAM_GAIN_ID,
+            PARAM_CUTOFF_ID,
+            PARAM_RESONANCE_ID,
+            PARAM_ATTACK_ID,
+            PARAM_RELEASE_ID,
+            PARAM_LFO_DEPTH_IDS[0],
+            // The comb is our delay line.
+            PARAM_COMB_MIX_ID,
+            PARAM_REVERB_MIX_ID,
+        ],
+    },
+    RemotePage {
+        id: 10,
+        name: "Setup",
+        params: &[
+            PARAM_GAIN_LAW_ID,
+            PARAM_LIMITER_ON_ID,
+            PARAM_CHORD_TYPE_ID,
+            PARAM_MAX_VOICES_ID,
+            PARAM_KEY_TO_PAN_ID,
+            PARAM_UNISON_VOICES_ID,
+            PARAM_UNISON_DETUNE_ID,
+        ],
+    },
+    RemotePage {
+        id: 1,
+        name: "Oscillator",
+        params: &[
+            PARAM_WAVEFORM_ID,
+            PARAM_PLUCK_TONE_ID,
+            PARAM_NOISE_COLOR_ID,
+            PARAM_PITCH_ENV_AMOUNT_ID,
+            PARAM_PITCH_ENV_DECAY_ID,
+            PARAM_CUTOFF_ID,
+            PARAM_RESONANCE_ID,
+            PARAM_VEL_TO_CUTOFF_ID,
+        ],
+    },
+    RemotePage {
+        id: 2,
+        name: "FX",
+        params: &[
+            PARAM_FX_MIX_ID,
+            PARAM_AUTO_PAN_ON_ID,
+            PARAM_AUTO_PAN_RATE_ID,
+            PARAM_AUTO_PAN_DEPTH_ID,
+            PARAM_AUTO_PAN_SHAPE_ID,
+            PARAM_AUTO_PAN_SYNC_ID,
+        ],
+    },
+    RemotePage {
+        id: 9,
+        name: "Comb",
+        params: &[PARAM_COMB_ON_ID, PARAM_COMB_MIX_ID, PARAM_COMB_FEEDBACK_ID],
+    },
+    RemotePage {
+        id: 7,
+        name: "Reverb",
+        params: &[
+            PARAM_REVERB_ON_ID,
+            PARAM_REVERB_MIX_ID,
+            PARAM_REVERB_DECAY_ID,
+            PARAM_REVERB_DAMPING_ID,
+            PARAM_REVERB_PREDELAY_ID,
+            PARAM_REVERB_FREEZE_ID,
+        ],
+    },
+    RemotePage {
+        id: 4,
+        name: "Envelope",
+        params: &[
+            PARAM_ENV_MODE_ID,
+            PARAM_ATTACK_ID,
+            PARAM_HOLD_ID,
+            PARAM_DECAY_ID,
+            PARAM_SUSTAIN_ID,
+            PARAM_RELEASE_ID,
+            PARAM_ENV_LOOP_ID,
+        ],
+    },
+    RemotePage {
+        id: 5,
+        name: "LFO 1",
+        params: &[
+            PARAM_LFO_RATE_IDS[0],
+            PARAM_LFO_DEPTH_IDS[0],
+            PARAM_LFO_SHAPE_IDS[0],
+            PARAM_LFO_RETRIGGER_IDS[0],
+            PARAM_LFO_DELAY_ID,
+        ],
+    },
+    RemotePage {
+        id: 8,
+        name: "LFO 2",
+        params: &[
+            PARAM_LFO_RATE_IDS[1],
+            PARAM_LFO_DEPTH_IDS[1],
+            PARAM_LFO_SHAPE_IDS[1],
+            PARAM_LFO_RETRIGGER_IDS[1],
+        ],
+    },
+    // A row of four a slot, the last left empty, which lines up on controllers with
+    // their knobs in rows of four.
+    RemotePage {
+        id: 6,
+        name: "Mod Slots 1-2",
+        params: &[
+            PARAM_MOD_SOURCE_IDS[0],
+            PARAM_MOD_DEST_IDS[0],
+            PARAM_MOD_AMOUNT_IDS[0],
+            NO_CONTROL,
+            PARAM_MOD_SOURCE_IDS[1],
+            PARAM_MOD_DEST_IDS[1],
+            PARAM_MOD_AMOUNT_IDS[1],
+            NO_CONTROL,
+        ],
+    },
+    RemotePage {
+        id: 11,
+        name: "Mod Slots 3-4",
+        params: &[
+            PARAM_MOD_SOURCE_IDS[2],
+            PARAM_MOD_DEST_IDS[2],
+            PARAM_MOD_AMOUNT_IDS[2],
+            NO_CONTROL,
+            PARAM_MOD_SOURCE_IDS[3],
+            PARAM_MOD_DEST_IDS[3],
+            PARAM_MOD_AMOUNT_IDS[3],
+            NO_CONTROL,
+        ],
+    },
+    RemotePage {
+        id: 3,
+        name: "Keyboard",
+        params: &[PARAM_SPLIT_MODE_ID, PARAM_SPLIT_POINT_ID, PARAM_LOWER_OCTAVE_ID, PARAM_UPPER_OCTAVE_ID],
+    },
+];
+
+pub fn remote_pages() -> impl Iterator<Item = &'static RemotePage> {
+    REMOTE_PAGES
+        .iter()
+        .filter(|page| page.params.iter().any(|&id| param_desc(id).is_some()))
+}
+
+/// Something the host needs to hear about a param changed on our side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamChange {
+    GestureBegin,
+    Value(f32),
+    GestureEnd,
+}
+
+const GESTURE_BEGIN: u8 = 1;
+const GESTURE_END: u8 = 2;
+
+fn param_index(id: u32) -> Option<usize> {
+    PARAMS.iter().position(|desc| desc.id == id)
+}
 
 pub struct Params {
     pub gain: AtomicF32,
+    pub gain_law: AtomicF32,
+    pub limiter_on: AtomicF32,
+    pub chord_type: AtomicF32,
+    pub max_voices: AtomicF32,
+    pub oversampling: AtomicF32,
+    pub key_to_pan: AtomicF32,
+    pub split_mode: AtomicF32,
+    pub split_point: AtomicF32,
+    pub lower_octave: AtomicF32,
+    pub upper_octave: AtomicF32,
+    pub pitch_env_amount: AtomicF32,
+    pub pitch_env_decay: AtomicF32,
+    pub cutoff: AtomicF32,
+    pub resonance: AtomicF32,
+    pub vel_to_cutoff: AtomicF32,
+    pub env_mode: AtomicF32,
+    pub attack: AtomicF32,
+    pub hold: AtomicF32,
+    pub decay: AtomicF32,
+    pub sustain: AtomicF32,
+    pub release: AtomicF32,
+    pub env_loop: AtomicF32,
+    pub lfo_rate: [AtomicF32; NUM_LFOS],
+    pub lfo_depth: [AtomicF32; NUM_LFOS],
+    pub lfo_shape: [AtomicF32; NUM_LFOS],
+    pub lfo_retrigger: [AtomicF32; NUM_LFOS],
+    pub lfo_delay: AtomicF32,
+    pub mod_source: [AtomicF32; MOD_SLOTS],
+    pub mod_dest: [AtomicF32; MOD_SLOTS],
+    pub mod_amount: [AtomicF32; MOD_SLOTS],
+    pub fx_mix: AtomicF32,
+    pub auto_pan_on: AtomicF32,
+    pub auto_pan_rate: AtomicF32,
+    pub auto_pan_depth: AtomicF32,
+    pub auto_pan_shape: AtomicF32,
+    pub auto_pan_sync: AtomicF32,
+    pub comb_on: AtomicF32,
+    pub comb_mix: AtomicF32,
+    pub comb_feedback: AtomicF32,
+    pub waveform: AtomicF32,
+    pub pluck_tone: AtomicF32,
+    pub noise_color: AtomicF32,
+    pub unison_voices: AtomicF32,
+    pub unison_detune: AtomicF32,
+    pub reverb_on: AtomicF32,
+    pub reverb_mix: AtomicF32,
+    pub reverb_decay: AtomicF32,
+    pub reverb_damping: AtomicF32,
+    pub reverb_predelay: AtomicF32,
+    pub reverb_freeze: AtomicF32,
+    /// Per entry in [`PARAMS`]: changed on our side since the host was last told.
+    changed: [AtomicBool; PARAMS.len()],
+    /// Per entry in [`PARAMS`]: gesture begins and ends the host hasn't been told about.
+    gestures: [AtomicU8; PARAMS.len()],
+    /// Counts value changes from any side, so the editor can tell when it's out of date.
+    generation: AtomicU32,
+    /// Set by edits on our side, see [`Params::is_edited`].
+    edited: AtomicBool,
+    /// Per entry in [`PARAMS`]: modulation for the editor to draw on the param's control.
+    modulation: [ModIndicator; PARAMS.len()],
+    /// Per entry in [`PARAMS`]: the host's modulation, in the param's units, added to the
+    /// value wherever the engine reads it. Only [modulatable](ParamDesc::modulatable)
+    /// params get any.
+    host_modulation: [AtomicF32; PARAMS.len()],
+}
+
+/// How far modulation swings a param, and where it has it right now, in the param's units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModRange {
+    pub low: f32,
+    pub high: f32,
+    pub value: f32,
+}
+
+/// One param's [`ModRange`], published by the audio thread once a block. The fields may be
+/// a block apart from each other, which only ever shows as a slightly stale dot.
+#[derive(Default)]
+struct ModIndicator {
+    active: AtomicBool,
+    low: AtomicF32,
+    high: AtomicF32,
+    value: AtomicF32,
+}
+
+/// An atomic holding the param's default from [`PARAMS`], the one place defaults live.
+fn default_atomic(id: u32) -> AtomicF32 {
+    AtomicF32::new(param_desc(id).map_or(0.0, |desc| desc.default as f32))
+}
+
+fn default_atomics<const N: usize>(ids: &[u32; N]) -> [AtomicF32; N] {
+    std::array::from_fn(|i| default_atomic(ids[i]))
+}
+
+/// The atomic in `atomics` whose param ID sits at the same index in `ids`.
+fn indexed_atomic<'a>(ids: &[u32], atomics: &'a [AtomicF32], id: u32) -> Option<&'a AtomicF32> {
+    ids.iter().position(|&i| i == id).map(|i| &atomics[i])
 }
 
 impl Default for Params {
     fn default() -> Self {
         Self {
-            gain: AtomicF32::new(1.0),
+            gain: default_atomic(PARAM_GAIN_ID),
+            gain_law: default_atomic(PARAM_GAIN_LAW_ID),
+            limiter_on: default_atomic(PARAM_LIMITER_ON_ID),
+            chord_type: default_atomic(PARAM_CHORD_TYPE_ID),
+            max_voices: default_atomic(PARAM_MAX_VOICES_ID),
+            oversampling: default_atomic(PARAM_OVERSAMPLING_ID),
+            key_to_pan: default_atomic(PARAM_KEY_TO_PAN_ID),
+            split_mode: default_atomic(PARAM_SPLIT_MODE_ID),
+            split_point: default_atomic(PARAM_SPLIT_POINT_ID),
+            lower_octave: default_atomic(PARAM_LOWER_OCTAVE_ID),
+            upper_octave: default_atomic(PARAM_UPPER_OCTAVE_ID),
+            pitch_env_amount: default_atomic(PARAM_PITCH_ENV_AMOUNT_ID),
+            pitch_env_decay: default_atomic(PARAM_PITCH_ENV_DECAY_ID),
+            cutoff: default_atomic(PARAM_CUTOFF_ID),
+            resonance: default_atomic(PARAM_RESONANCE_ID),
+            vel_to_cutoff: default_atomic(PARAM_VEL_TO_CUTOFF_ID),
+            env_mode: default_atomic(PARAM_ENV_MODE_ID),
+            attack: default_atomic(PARAM_ATTACK_ID),
+            hold: default_atomic(PARAM_HOLD_ID),
+            decay: default_atomic(PARAM_DECAY_ID),
+            sustain: default_atomic(PARAM_SUSTAIN_ID),
+            release: default_atomic(PARAM_RELEASE_ID),
+            env_loop: default_atomic(PARAM_ENV_LOOP_ID),
+            lfo_rate: default_atomics(&PARAM_LFO_RATE_IDS),
+            lfo_depth: default_atomics(&PARAM_LFO_DEPTH_IDS),
+            lfo_shape: default_atomics(&PARAM_LFO_SHAPE_IDS),
+            lfo_retrigger: default_atomics(&PARAM_LFO_RETRIGGER_IDS),
+            lfo_delay: default_atomic(PARAM_LFO_DELAY_ID),
+            mod_source: default_atomics(&PARAM_MOD_SOURCE_IDS),
+            mod_dest: default_atomics(&PARAM_MOD_DEST_IDS),
+            mod_amount: default_atomics(&PARAM_MOD_AMOUNT_IDS),
+            fx_mix: default_atomic(PARAM_FX_MIX_ID),
+            auto_pan_on: default_atomic(PARAM_AUTO_PAN_ON_ID),
+            auto_pan_rate: default_atomic(PARAM_AUTO_PAN_RATE_ID),
+            auto_pan_depth: default_atomic(PARAM_AUTO_PAN_DEPTH_ID),
+            auto_pan_shape: default_atomic(PARAM_AUTO_PAN_SHAPE_ID),
+            auto_pan_sync: default_atomic(PARAM_AUTO_PAN_SYNC_ID),
+            comb_on: default_atomic(PARAM_COMB_ON_ID),
+            comb_mix: default_atomic(PARAM_COMB_MIX_ID),
+            comb_feedback: default_atomic(PARAM_COMB_FEEDBACK_ID),
+            waveform: default_atomic(PARAM_WAVEFORM_ID),
+            pluck_tone: default_atomic(PARAM_PLUCK_TONE_ID),
+            noise_color: default_atomic(PARAM_NOISE_COLOR_ID),
+            unison_voices: default_atomic(PARAM_UNISON_VOICES_ID),
+            unison_detune: default_atomic(PARAM_UNISON_DETUNE_ID),
+            reverb_on: default_atomic(PARAM_REVERB_ON_ID),
+            reverb_mix: default_atomic(PARAM_REVERB_MIX_ID),
+            reverb_decay: default_atomic(PARAM_REVERB_DECAY_ID),
+            reverb_damping: default_atomic(PARAM_REVERB_DAMPING_ID),
+            reverb_predelay: default_atomic(PARAM_REVERB_PREDELAY_ID),
+            reverb_freeze: default_atomic(PARAM_REVERB_FREEZE_ID),
+            changed: std::array::from_fn(|_| AtomicBool::new(false)),
+            gestures: std::array::from_fn(|_| AtomicU8::new(0)),
+            generation: AtomicU32::new(0),
+            edited: AtomicBool::new(false),
+            modulation: std::array::from_fn(|_| ModIndicator::default()),
+            host_modulation: std::array::from_fn(|_| AtomicF32::new(0.0)),
         }
     }
 }
 
 impl Params {
+    /// The gain param as stored, 0.0 to 1.0 whatever the law.
     pub fn gain(&self) -> f32 {
         self.gain.load(Ordering::Relaxed)
     }
 
-    pub fn set_gain(&self, v: f32) {
-        self.gain.store(v, Ordering::Relaxed);
+    pub fn gain_law(&self) -> usize {
+        self.gain_law.load(Ordering::Relaxed).round() as usize
+    }
+
+    /// The master gain as an amplitude, through the gain law.
+    pub fn gain_factor(&self) -> f32 {
+        gain_amplitude(self.gain_law(), self.modulated(PARAM_GAIN_ID, &self.gain))
+    }
+
+    pub fn chord_type(&self) -> usize {
+        self.chord_type.load(Ordering::Relaxed).round() as usize
+    }
+
+    /// How many voices may sound at once, 1 to [`MAX_VOICES`].
+    pub fn max_voices(&self) -> usize {
+        (self.max_voices.load(Ordering::Relaxed).round() as usize).clamp(1, MAX_VOICES)
+    }
+
+    /// How many times the sample rate the voices run at: 1, 2 or 4.
+    pub fn oversampling(&self) -> usize {
+        oversampling_factor(self.oversampling.load(Ordering::Relaxed).round() as usize)
+    }
+
+    /// How far voices spread across the stereo field by note, -1.0 to 1.0. Positive puts
+    /// low notes on the left.
+    pub fn key_to_pan(&self) -> f32 {
+        self.key_to_pan.load(Ordering::Relaxed)
+    }
+
+    pub fn split_mode(&self) -> usize {
+        self.split_mode.load(Ordering::Relaxed).round() as usize
+    }
+
+    pub fn split_point(&self) -> u8 {
+        self.split_point.load(Ordering::Relaxed).round().clamp(0.0, 127.0) as u8
+    }
+
+    pub fn lower_octave(&self) -> i32 {
+        self.lower_octave.load(Ordering::Relaxed).round() as i32
+    }
+
+    pub fn upper_octave(&self) -> i32 {
+        self.upper_octave.load(Ordering::Relaxed).round() as i32
+    }
+
+    pub fn waveform(&self) -> usize {
+        self.waveform.load(Ordering::Relaxed).round() as usize
+    }
+
+    pub fn pluck_tone(&self) -> f32 {
+        self.modulated(PARAM_PLUCK_TONE_ID, &self.pluck_tone)
+    }
+
+    /// Tilt of the noise oscillator, from red (-1.0) through white (0.0) to blue (1.0).
+    pub fn noise_color(&self) -> f32 {
+        self.modulated(PARAM_NOISE_COLOR_ID, &self.noise_color)
+    }
+
+    /// Copies of the square wave each note plays, 1 to [`MAX_UNISON`].
+    pub fn unison_voices(&self) -> usize {
+        (self.unison_voices.load(Ordering::Relaxed).round() as usize).clamp(1, MAX_UNISON)
+    }
+
+    /// Semitones the outermost unison copies sit either side of the note.
+    pub fn unison_detune(&self) -> f32 {
+        self.unison_detune.load(Ordering::Relaxed)
+    }
+
+    pub fn pitch_env_amount(&self) -> f32 {
+        self.pitch_env_amount.load(Ordering::Relaxed)
+    }
+
+    pub fn pitch_env_decay(&self) -> f32 {
+        self.pitch_env_decay.load(Ordering::Relaxed)
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.modulated(PARAM_CUTOFF_ID, &self.cutoff)
+    }
+
+    pub fn resonance(&self) -> f32 {
+        self.modulated(PARAM_RESONANCE_ID, &self.resonance)
+    }
+
+    /// How far a full-velocity note opens the cutoff, in semitones.
+    pub fn vel_to_cutoff(&self) -> f32 {
+        self.modulated(PARAM_VEL_TO_CUTOFF_ID, &self.vel_to_cutoff)
+    }
+
+    pub fn env_mode(&self) -> usize {
+        self.env_mode.load(Ordering::Relaxed).round() as usize
+    }
+
+    /// Amp envelope settings for a new voice, in whichever mode is selected.
+    pub fn amp_env(&self) -> EnvelopeSettings {
+        let load = |p: &AtomicF32| p.load(Ordering::Relaxed);
+        let settings = if self.env_mode() == ENV_MODE_GATE {
+            EnvelopeSettings::gate(load(&self.attack), load(&self.hold), load(&self.decay))
+        } else {
+            EnvelopeSettings::adsr(load(&self.attack), load(&self.decay), load(&self.sustain), load(&self.release))
+        };
+        settings.looped(load(&self.env_loop) >= 0.5)
+    }
+
+    pub fn lfo_rate(&self, lfo: usize) -> f32 {
+        self.modulated(PARAM_LFO_RATE_IDS[lfo], &self.lfo_rate[lfo])
+    }
+
+    pub fn lfo_depth(&self, lfo: usize) -> f32 {
+        self.modulated(PARAM_LFO_DEPTH_IDS[lfo], &self.lfo_depth[lfo])
+    }
+
+    pub fn lfo_shape(&self, lfo: usize) -> usize {
+        self.lfo_shape[lfo].load(Ordering::Relaxed).round() as usize
+    }
+
+    /// Whether the LFO restarts its cycle on every note-on rather than running freely.
+    pub fn lfo_retrigger(&self, lfo: usize) -> bool {
+        self.lfo_retrigger[lfo].load(Ordering::Relaxed) >= 0.5
+    }
+
+    /// Seconds the LFOs take to fade in after a note-on.
+    pub fn lfo_delay(&self) -> f32 {
+        self.lfo_delay.load(Ordering::Relaxed)
+    }
+
+    /// Source index, destination index and amount of one mod matrix slot.
+    pub fn mod_slot(&self, slot: usize) -> (usize, usize, f32) {
+        (
+            self.mod_source[slot].load(Ordering::Relaxed).round() as usize,
+            self.mod_dest[slot].load(Ordering::Relaxed).round() as usize,
+            self.mod_amount[slot].load(Ordering::Relaxed),
+        )
+    }
+
+    /// Which mod slots have a source, a bit a slot. The remote pages follow it.
+    pub fn mod_routing(&self) -> u32 {
+        let routed = (0..MOD_SLOTS).filter(|&slot| self.mod_slot(slot).0 != 0);
+        routed.fold(0, |bits, slot| bits | 1 << slot)
+    }
+
+    /// Dry (0.0) to wet (1.0) balance across the master effects.
+    pub fn fx_mix(&self) -> f32 {
+        self.modulated(PARAM_FX_MIX_ID, &self.fx_mix)
+    }
+
+    /// Off skips the auto-pan altogether.
+    pub fn auto_pan_on(&self) -> bool {
+        self.auto_pan_on.load(Ordering::Relaxed) >= 0.5
+    }
+
+    pub fn auto_pan(&self) -> AutoPanSettings {
+        AutoPanSettings {
+            rate: self.modulated(PARAM_AUTO_PAN_RATE_ID, &self.auto_pan_rate),
+            depth: self.modulated(PARAM_AUTO_PAN_DEPTH_ID, &self.auto_pan_depth),
+            shape: self.auto_pan_shape.load(Ordering::Relaxed).round() as usize,
+            sync: self.auto_pan_sync.load(Ordering::Relaxed).round() as usize,
+        }
+    }
+
+    /// Whether the output goes through the limiter on its way out.
+    pub fn limiter_on(&self) -> bool {
+        self.limiter_on.load(Ordering::Relaxed) >= 0.5
+    }
+
+    /// Off skips the per-voice combs altogether.
+    pub fn comb_on(&self) -> bool {
+        self.comb_on.load(Ordering::Relaxed) >= 0.5
+    }
+
+    pub fn comb_mix(&self) -> f32 {
+        self.modulated(PARAM_COMB_MIX_ID, &self.comb_mix)
+    }
+
+    pub fn comb_feedback(&self) -> f32 {
+        self.modulated(PARAM_COMB_FEEDBACK_ID, &self.comb_feedback)
+    }
+
+    /// Off skips the reverb altogether.
+    pub fn reverb_on(&self) -> bool {
+        self.reverb_on.load(Ordering::Relaxed) >= 0.5
+    }
+
+    pub fn reverb(&self) -> ReverbSettings {
+        ReverbSettings {
+            mix: self.modulated(PARAM_REVERB_MIX_ID, &self.reverb_mix),
+            decay: self.modulated(PARAM_REVERB_DECAY_ID, &self.reverb_decay),
+            damping: self.modulated(PARAM_REVERB_DAMPING_ID, &self.reverb_damping),
+            predelay: self.modulated(PARAM_REVERB_PREDELAY_ID, &self.reverb_predelay),
+            freeze: self.reverb_freeze.load(Ordering::Relaxed) >= 0.5,
+        }
+    }
+
+    pub fn atomic(&self, id: u32) -> Option<&AtomicF32> {
+        match id {
+            PARAM_GAIN_ID => Some(&self.gain),
+            PARAM_GAIN_LAW_ID => Some(&self.gain_law),
+            PARAM_LIMITER_ON_ID => Some(&self.limiter_on),
+            PARAM_CHORD_TYPE_ID => Some(&self.chord_type),
+            PARAM_MAX_VOICES_ID => Some(&self.max_voices),
+            PARAM_OVERSAMPLING_ID => Some(&self.oversampling),
+            PARAM_KEY_TO_PAN_ID => Some(&self.key_to_pan),
+            PARAM_SPLIT_MODE_ID => Some(&self.split_mode),
+            PARAM_SPLIT_POINT_ID => Some(&self.split_point),
+            PARAM_LOWER_OCTAVE_ID => Some(&self.lower_octave),
+            PARAM_UPPER_OCTAVE_ID => Some(&self.upper_octave),
+            PARAM_PITCH_ENV_AMOUNT_ID => Some(&self.pitch_env_amount),
+            PARAM_PITCH_ENV_DECAY_ID => Some(&self.pitch_env_decay),
+            PARAM_CUTOFF_ID => Some(&self.cutoff),
+            PARAM_RESONANCE_ID => Some(&self.resonance),
+            PARAM_VEL_TO_CUTOFF_ID => Some(&self.vel_to_cutoff),
+            PARAM_ENV_MODE_ID => Some(&self.env_mode),
+            PARAM_ATTACK_ID => Some(&self.attack),
+            PARAM_HOLD_ID => Some(&self.hold),
+            PARAM_DECAY_ID => Some(&self.decay),
+            PARAM_SUSTAIN_ID => Some(&self.sustain),
+            PARAM_RELEASE_ID => Some(&self.release),
+            PARAM_ENV_LOOP_ID => Some(&self.env_loop),
+            PARAM_LFO_DELAY_ID => Some(&self.lfo_delay),
+            PARAM_FX_MIX_ID => Some(&self.fx_mix),
+            PARAM_AUTO_PAN_ON_ID => Some(&self.auto_pan_on),
+            PARAM_AUTO_PAN_RATE_ID => Some(&self.auto_pan_rate),
+            PARAM_AUTO_PAN_DEPTH_ID => Some(&self.auto_pan_depth),
+            PARAM_AUTO_PAN_SHAPE_ID => Some(&self.auto_pan_shape),
+            PARAM_AUTO_PAN_SYNC_ID => Some(&self.auto_pan_sync),
+            PARAM_COMB_ON_ID => Some(&self.comb_on),
+            PARAM_COMB_MIX_ID => Some(&self.comb_mix),
+            PARAM_COMB_FEEDBACK_ID => Some(&self.comb_feedback),
+            PARAM_WAVEFORM_ID => Some(&self.waveform),
+            PARAM_PLUCK_TONE_ID => Some(&self.pluck_tone),
+            PARAM_NOISE_COLOR_ID => Some(&self.noise_color),
+            PARAM_UNISON_VOICES_ID => Some(&self.unison_voices),
+            PARAM_UNISON_DETUNE_ID => Some(&self.unison_detune),
+            PARAM_REVERB_ON_ID => Some(&self.reverb_on),
+            PARAM_REVERB_MIX_ID => Some(&self.reverb_mix),
+            PARAM_REVERB_DECAY_ID => Some(&self.reverb_decay),
+            PARAM_REVERB_DAMPING_ID => Some(&self.reverb_damping),
+            PARAM_REVERB_PREDELAY_ID => Some(&self.reverb_predelay),
+            PARAM_REVERB_FREEZE_ID => Some(&self.reverb_freeze),
+            _ => indexed_atomic(&PARAM_LFO_RATE_IDS, &self.lfo_rate, id)
+                .or_else(|| indexed_atomic(&PARAM_LFO_DEPTH_IDS, &self.lfo_depth, id))
+                .or_else(|| indexed_atomic(&PARAM_LFO_SHAPE_IDS, &self.lfo_shape, id))
+                .or_else(|| indexed_atomic(&PARAM_LFO_RETRIGGER_IDS, &self.lfo_retrigger, id))
+                .or_else(|| indexed_atomic(&PARAM_MOD_SOURCE_IDS, &self.mod_source, id))
+                .or_else(|| indexed_atomic(&PARAM_MOD_DEST_IDS, &self.mod_dest, id))
+                .or_else(|| indexed_atomic(&PARAM_MOD_AMOUNT_IDS, &self.mod_amount, id)),
+        }
+    }
+
+    pub fn value(&self, id: u32) -> Option<f32> {
+        self.atomic(id).map(|v| v.load(Ordering::Relaxed))
+    }
+
+    pub fn set_value(&self, id: u32, value: f32) {
+        if let Some(atomic) = self.atomic(id) {
+            atomic.store(value, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Goes up on every value change: set here, by the host or by a control storing into
+    /// its atomic and calling [`Params::mark_changed`].
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Whether the user has edited a param here, in the editor or through MIDI learn,
+    /// since [`Params::clear_edited`]. Unlike [`Params::generation`], loading a patch or
+    /// the host's automation doesn't count.
+    pub fn is_edited(&self) -> bool {
+        self.edited.load(Ordering::Relaxed)
+    }
+
+    /// For when a patch or the state has just been loaded.
+    pub fn clear_edited(&self) {
+        self.edited.store(false, Ordering::Relaxed);
+    }
+
+    /// Audio thread. Publishes where modulation has the param this block, or with `None`
+    /// that nothing modulates it any more.
+    pub fn set_modulation(&self, id: u32, range: Option<ModRange>) {
+        let Some(indicator) = param_index(id).map(|index| &self.modulation[index]) else {
+            return;
+        };
+        if let Some(ModRange { low, high, value }) = range {
+            indicator.low.store(low, Ordering::Relaxed);
+            indicator.high.store(high, Ordering::Relaxed);
+            indicator.value.store(value, Ordering::Relaxed);
+        }
+        indicator.active.store(range.is_some(), Ordering::Release);
+    }
+
+    /// Where modulation has the param, if anything modulates it: the mod matrix, as
+    /// published with [`Params::set_modulation`], or else the host.
+    pub fn modulation(&self, id: u32) -> Option<ModRange> {
+        let index = param_index(id)?;
+        let indicator = &self.modulation[index];
+        if indicator.active.load(Ordering::Acquire) {
+            return Some(ModRange {
+                low: indicator.low.load(Ordering::Relaxed),
+                high: indicator.high.load(Ordering::Relaxed),
+                value: indicator.value.load(Ordering::Relaxed),
+            });
+        }
+        let offset = self.host_modulation[index].load(Ordering::Relaxed);
+        (offset != 0.0).then(|| {
+            let value = self.value(id).unwrap_or_default();
+            let modulated = self.atomic(id).map_or(value, |atomic| self.modulated(id, atomic));
+            ModRange { low: value.min(modulated), high: value.max(modulated), value: modulated }
+        })
+    }
+
+    /// The value in `atomic`, param `id`'s, with the host's modulation on top, kept within
+    /// the param's range.
+    fn modulated(&self, id: u32, atomic: &AtomicF32) -> f32 {
+        let value = atomic.load(Ordering::Relaxed);
+        let Some(index) = param_index(id) else { return value };
+        let offset = self.host_modulation[index].load(Ordering::Relaxed);
+        if offset == 0.0 {
+            return value;
+        }
+        let desc = &PARAMS[index];
+        (value + offset).clamp(desc.min as f32, desc.max as f32)
+    }
+
+    /// Sets a value changed on the plugin's side (the editor, a context menu action) and
+    /// queues it for the host, which only hears about it through [`Params::take_changes`].
+    pub fn change(&self, id: u32, value: f32) {
+        self.set_value(id, value);
+        self.mark_changed(id);
+    }
+
+    /// [`Params::change`] for an edit that's over as soon as it's made (a typed value, a
+    /// reset, a pasted patch), wrapped in a gesture of its own so the host records it as
+    /// one.
+    pub fn change_as_gesture(&self, id: u32, value: f32) {
+        self.begin_gesture(id);
+        self.change(id, value);
+        self.end_gesture(id);
+    }
+
+    /// Queues the param's current value for the host. For controls that store into its
+    /// atomic directly.
+    pub fn mark_changed(&self, id: u32) {
+        if let Some(index) = param_index(id) {
+            self.changed[index].store(true, Ordering::Release);
+            self.generation.fetch_add(1, Ordering::Relaxed);
+            self.edited.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Queues a gesture begin for the host: the user has grabbed the param's control, and
+    /// the changes until [`Params::end_gesture`] are one edit.
+    pub fn begin_gesture(&self, id: u32) {
+        self.queue_gesture(id, GESTURE_BEGIN);
+    }
+
+    pub fn end_gesture(&self, id: u32) {
+        self.queue_gesture(id, GESTURE_END);
+    }
+
+    fn queue_gesture(&self, id: u32, gesture: u8) {
+        if let Some(index) = param_index(id) {
+            self.gestures[index].fetch_or(gesture, Ordering::Release);
+        }
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.changed.iter().any(|changed| changed.load(Ordering::Relaxed))
+            || self.gestures.iter().any(|gestures| gestures.load(Ordering::Relaxed) != 0)
+    }
+
+    /// Hands `f` what the host needs to hear about each param changed since the last call:
+    /// a gesture begin, the current value, a gesture end, in that order.
+    pub fn take_changes(&self, mut f: impl FnMut(u32, ParamChange)) {
+        for (index, desc) in PARAMS.iter().enumerate() {
+            let gestures = self.gestures[index].swap(0, Ordering::Acquire);
+            if gestures & GESTURE_BEGIN != 0 {
+                f(desc.id, ParamChange::GestureBegin);
+            }
+            if self.changed[index].swap(false, Ordering::Acquire) {
+                if let Some(value) = self.value(desc.id) {
+                    f(desc.id, ParamChange::Value(value));
+                }
+            }
+            if gestures & GESTURE_END != 0 {
+                f(desc.id, ParamChange::GestureEnd);
+            }
+        }
+    }
+
+    /// Puts back a change the host couldn't take, for the next [`Params::take_changes`].
+    pub fn requeue(&self, id: u32, change: ParamChange) {
+        match change {
+            ParamChange::GestureBegin => self.begin_gesture(id),
+            ParamChange::Value(_) => self.mark_changed(id),
+            ParamChange::GestureEnd => self.end_gesture(id),
+        }
     }
 
     pub fn handle_param_value_event(&self, event: &ParamValueEvent) {
-        match event.param_id().map(|id| id.into()) {
-            Some(PARAM_GAIN_ID) => self.set_gain(event.value() as f32),
-            _ => {}
+        if let Some(id) = event.param_id() {
+            self.set_value(id.into(), event.value() as f32);
+        }
+    }
+
+    /// Takes the host's modulation of a param. Modulation aimed at one note or key isn't
+    /// supported, as voices all read the same params, and is left out.
+    pub fn handle_param_mod_event(&self, event: &ParamModEvent) {
+        let Some(index) = event.param_id().and_then(|id| param_index(id.into())) else {
+            return;
+        };
+        if PARAMS[index].modulatable && event.pckn() == Pckn::match_all() {
+            self.host_modulation[index].store(event.amount() as f32, Ordering::Relaxed);
+        }
+    }
+
+    /// Drops the host's modulation of every param, for when the host stops sending it.
+    pub fn clear_host_modulation(&self) {
+        for offset in &self.host_modulation {
+            offset.store(0.0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_modulation_moves_what_the_engine_reads_and_is_shown() {
+        let params = Params::default();
+        params.set_value(PARAM_CUTOFF_ID, 1000.0);
+        let offset = &params.host_modulation[param_index(PARAM_CUTOFF_ID).unwrap()];
+        offset.store(500.0, Ordering::Relaxed);
+
+        assert_eq!(params.cutoff(), 1500.0);
+        assert_eq!(params.value(PARAM_CUTOFF_ID), Some(1000.0));
+        let shown = ModRange { low: 1000.0, high: 1500.0, value: 1500.0 };
+        assert_eq!(params.modulation(PARAM_CUTOFF_ID), Some(shown));
+
+        offset.store(1e6, Ordering::Relaxed);
+        assert_eq!(params.cutoff(), MAX_CUTOFF);
+
+        params.clear_host_modulation();
+        assert_eq!(params.cutoff(), 1000.0);
+        assert_eq!(params.modulation(PARAM_CUTOFF_ID), None);
+    }
+
+    #[test]
+    fn only_continuous_params_are_modulatable() {
+        assert!(PARAMS.iter().filter(|desc| desc.modulatable).all(|desc| !desc.is_stepped()));
+    }
+
+    #[test]
+    fn a_one_off_change_is_its_own_gesture() {
+        let params = Params::default();
+        params.change_as_gesture(PARAM_GAIN_ID, 0.5);
+        let mut changes = Vec::new();
+        params.take_changes(|id, change| changes.push((id, change)));
+        assert_eq!(
+            changes,
+            [
+                (PARAM_GAIN_ID, ParamChange::GestureBegin),
+                (PARAM_GAIN_ID, ParamChange::Value(0.5)),
+                (PARAM_GAIN_ID, ParamChange::GestureEnd),
+            ]
+        );
+    }
+
+    #[test]
+    fn generation_moves_with_every_change() {
+        let params = Params::default();
+        let start = params.generation();
+        params.set_value(PARAM_GAIN_ID, 0.5);
+        params.mark_changed(PARAM_CUTOFF_ID);
+        assert_eq!(params.generation(), start.wrapping_add(2));
+        // Unknown ids change nothing.
+        params.set_value(u32::MAX, 0.5);
+        assert_eq!(params.generation(), start.wrapping_add(2));
+    }
+
+    #[test]
+    fn parse_reads_what_format_writes() {
+        for desc in PARAMS {
+            let text = desc.format(desc.default);
+            let parsed = desc.parse(&text).unwrap();
+            assert_eq!(desc.format(parsed), text, "{}", desc.name);
+        }
+    }
+
+    #[test]
+    fn parse_takes_units_and_fractions() {
+        let attack = param_desc(PARAM_ATTACK_ID).unwrap();
+        assert_eq!(attack.parse("250 ms"), Some(0.25));
+        assert_eq!(attack.parse("1/8"), Some(0.125));
+        assert_eq!(attack.parse("1/0"), None);
+        let split = param_desc(PARAM_SPLIT_POINT_ID).unwrap();
+        assert_eq!(split.parse("C4"), Some(60.0));
+        let cutoff = param_desc(PARAM_CUTOFF_ID).unwrap();
+        assert_eq!(cutoff.parse("440 Hz"), Some(440.0));
+        assert_eq!(cutoff.parse("loud"), None);
+    }
+
+    #[test]
+    fn mod_slots_keep_their_places_as_the_routing_changes() {
+        let params = Params::default();
+        let pages: Vec<&RemotePage> =
+            REMOTE_PAGES.iter().filter(|page| page.name.starts_with("Mod Slots")).collect();
+        // Every slot, in order, each starting a row of four.
+        let rows = pages.iter().flat_map(|page| page.params.iter().step_by(4));
+        assert_eq!(rows.copied().collect::<Vec<u32>>(), PARAM_MOD_SOURCE_IDS);
+
+        params.set_value(PARAM_MOD_SOURCE_IDS[0], 0.0);
+        params.set_value(PARAM_MOD_SOURCE_IDS[1], 1.0);
+        let controls: Vec<Option<u32>> = pages[0].controls(&params).collect();
+        assert_eq!(
+            controls,
+            [
+                Some(PARAM_MOD_SOURCE_IDS[0]),
+                None,
+                None,
+                None,
+                Some(PARAM_MOD_SOURCE_IDS[1]),
+                Some(PARAM_MOD_DEST_IDS[1]),
+                Some(PARAM_MOD_AMOUNT_IDS[1]),
+                None,
+            ]
+        );
+        assert_eq!(params.mod_routing(), 0b10);
+    }
+
+    #[test]
+    fn remote_pages_fit_eight_controls() {
+        // Controls past the eighth would be dropped without a word.
+        for page in REMOTE_PAGES {
+            assert!(page.params.len() <= 8, "{} has {} controls", page.name, page.params.len());
         }
     }
 }
@@ -0,0 +1,217 @@
+use std::fmt;
+
+use crate::midi_learn::{MidiLearn, CC_COUNT};
+use crate::params::{ParamDesc, Params, PARAMS};
+
+/// First line of every patch, so random clipboard text isn't mistaken for one.
+const HEADER: &str = "# Cave patch";
+/// Second line: the [`crate::VERSION`] that wrote the patch.
+const VERSION_PREFIX: &str = "# version = ";
+/// Optional third line, naming the patch.
+const NAME_PREFIX: &str = "# name = ";
+/// Starts a MIDI-learn binding's line in the saved state: `CC 7 = 0` binds CC 7 to the
+/// param with id 0.
+const CC_PREFIX: &str = "CC ";
+/// In the saved state while notes played on the editor go out on a note port too.
+const NOTE_THRU_LINE: &str = "Note thru = on";
+
+/// Every param as plain text, one `id Name = value` line each, for the clipboard and the
+/// saved state. Loading goes by the id, which never changes; the name is there to read.
+///
+/// Values are written in full (Rust prints the shortest text that parses back to the same
+/// `f32`), so a copy and paste is lossless.
+pub fn to_text(params: &Params, name: Option<&str>) -> String {
+    let mut text = format!("{HEADER}\n{VERSION_PREFIX}{}\n", crate::VERSION);
+    if let Some(name) = name.and_then(|name| name.lines().next()) {
+        text += &format!("{NAME_PREFIX}{}\n", name.trim());
+    }
+    for desc in PARAMS {
+        if let Some(value) = params.value(desc.id) {
+            text += &format!("{} {} = {value}\n", desc.id, desc.name);
+        }
+    }
+    text
+}
+
+/// [`to_text`] with the MIDI-learn bindings and the note thru setting after the params,
+/// for the saved state: they belong to the project, not to a patch on the clipboard.
+pub fn to_state(
+    params: &Params,
+    name: Option<&str>,
+    midi_learn: &MidiLearn,
+    note_thru: bool,
+) -> String {
+    let mut text = to_text(params, name);
+    for (cc, param_id) in midi_learn.bindings() {
+        text += &format!("{CC_PREFIX}{cc} = {param_id}\n");
+    }
+    if note_thru {
+        text += &format!("{NOTE_THRU_LINE}\n");
+    }
+    text
+}
+
+/// Parses [`to_text`]'s output back into `(param_id, value)` pairs. Params are found by
+/// the id leading their line, or, in patches from before the ids were written, by name
+/// regardless of case. Lines naming no param are skipped, so patches from other versions
+/// still load what they can. Values are clamped to their param's range.
+pub fn from_text(text: &str) -> Result<Vec<(u32, f32)>, PatchError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err(PatchError::NotAPatch);
+    }
+
+    let mut values = Vec::new();
+    for (line_number, line) in lines {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let Some(desc) = find_param(key.trim()) else { continue };
+        match value.trim().parse::<f32>() {
+            Ok(number) if number.is_finite() => {
+                values.push((desc.id, number.clamp(desc.min as f32, desc.max as f32)));
+            }
+            _ => return Err(PatchError::BadValue { line: line_number, name: desc.name }),
+        }
+    }
+    Ok(values)
+}
+
+/// The param an `id Name` key, or a bare name, refers to.
+fn find_param(key: &str) -> Option<&'static ParamDesc> {
+    let id = key.split_whitespace().next().and_then(|id| id.parse::<u32>().ok());
+    match id {
+        Some(id) => PARAMS.iter().find(|desc| desc.id == id),
+        None => PARAMS.iter().find(|desc| desc.name.eq_ignore_ascii_case(key)),
+    }
+}
+
+/// The MIDI-learn bindings [`to_state`] wrote, as `(cc, param_id)`. Bindings to CCs or
+/// params that don't exist are skipped.
+pub fn bindings(text: &str) -> Vec<(u8, u32)> {
+    let binding = |line: &str| {
+        let (cc, param_id) = line.strip_prefix(CC_PREFIX)?.split_once('=')?;
+        let cc = cc.trim().parse::<u8>().ok().filter(|&cc| usize::from(cc) < CC_COUNT)?;
+        let param_id = param_id.trim().parse::<u32>().ok()?;
+        PARAMS.iter().any(|desc| desc.id == param_id).then_some((cc, param_id))
+    };
+    text.lines().filter_map(|line| binding(line.trim())).collect()
+}
+
+/// Whether [`to_state`] saved note thru on. States from before it was saved turn it off.
+pub fn note_thru(text: &str) -> bool {
+    text.lines().any(|line| line.trim() == NOTE_THRU_LINE)
+}
+
+/// The version that wrote a patch, for loading older ones differently. `None` for patches
+/// from before the version line.
+pub fn version(text: &str) -> Option<&str> {
+    let line = text.lines().map(str::trim).filter(|line| !line.is_empty()).nth(1)?;
+    line.strip_prefix(VERSION_PREFIX).map(str::trim)
+}
+
+/// The patch's name, from the comment lines at its top.
+pub fn name(text: &str) -> Option<&str> {
+    text.lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with('#'))
+        .find_map(|line| line.strip_prefix(NAME_PREFIX))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /// The text doesn't start with the patch header.
+    NotAPatch,
+    /// A known param with a value that isn't a number. `line` counts from 1.
+    BadValue { line: usize, name: &'static str },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAPatch => write!(f, "Not a Cave patch"),
+            Self::BadValue { line, name } => write!(f, "Line {line}: bad value for {name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{PARAM_CUTOFF_ID, PARAM_GAIN_ID, PARAM_RESONANCE_ID};
+
+    #[test]
+    fn copy_and_paste_is_lossless() {
+        let params = Params::default();
+        params.set_value(PARAM_GAIN_ID, 0.1 + 0.2);
+        params.set_value(PARAM_CUTOFF_ID, 1234.567);
+
+        let pasted = Params::default();
+        for (id, value) in from_text(&to_text(&params, None)).unwrap() {
+            pasted.set_value(id, value);
+        }
+        for desc in PARAMS {
+            assert_eq!(pasted.value(desc.id), params.value(desc.id), "{}", desc.name);
+        }
+    }
+
+    #[test]
+    fn params_load_by_id_whatever_they_were_called() {
+        let text = format!("{HEADER}\n{PARAM_GAIN_ID} Old Gain = 0.25\n{PARAM_CUTOFF_ID} = 500\n");
+        assert_eq!(from_text(&text), Ok(vec![(PARAM_GAIN_ID, 0.25), (PARAM_CUTOFF_ID, 500.0)]));
+    }
+
+    #[test]
+    fn the_state_keeps_the_midi_learn_bindings() {
+        let (params, midi_learn) = (Params::default(), MidiLearn::default());
+        midi_learn.bind(7, PARAM_GAIN_ID);
+        midi_learn.bind(74, PARAM_CUTOFF_ID);
+        let state = to_state(&params, None, &midi_learn, false);
+        assert_eq!(bindings(&state), [(7, PARAM_GAIN_ID), (74, PARAM_CUTOFF_ID)]);
+        // The params still load, and patches off the clipboard bind nothing.
+        assert_eq!(from_text(&state), from_text(&to_text(&params, None)));
+        assert!(bindings(&to_text(&params, None)).is_empty());
+        assert!(bindings(&format!("CC 200 = {PARAM_RESONANCE_ID}\nCC 1 = 9999\n")).is_empty());
+    }
+
+    #[test]
+    fn the_state_keeps_note_thru() {
+        let (params, midi_learn) = (Params::default(), MidiLearn::default());
+        let state = to_state(&params, None, &midi_learn, true);
+        assert!(note_thru(&state));
+        assert_eq!(from_text(&state), from_text(&to_text(&params, None)));
+        assert!(!note_thru(&to_state(&params, None, &midi_learn, false)));
+        assert!(!note_thru(&to_text(&params, None)));
+    }
+
+    #[test]
+    fn patches_carry_the_version_that_wrote_them() {
+        let text = to_text(&Params::default(), None);
+        assert_eq!(version(&text), Some(crate::VERSION));
+        assert_eq!(version(&format!("{HEADER}\nGain = 0.5\n")), None);
+    }
+
+    #[test]
+    fn names_survive_the_round_trip_on_one_line() {
+        let params = Params::default();
+        assert_eq!(name(&to_text(&params, Some(" Deep Bass \nsecond line"))), Some("Deep Bass"));
+        assert_eq!(name(&to_text(&params, None)), None);
+        // Only the comments at the top count.
+        assert_eq!(name(&format!("{HEADER}\nGain = 0.5\n# name = Late\n")), None);
+        assert!(from_text(&to_text(&params, Some("Gain = 1"))).is_ok());
+    }
+
+    #[test]
+    fn unknown_lines_are_skipped_and_bad_values_refused() {
+        let text = format!("{HEADER}\nWobble = 3\ngain = 2\n\n");
+        assert_eq!(from_text(&text), Ok(vec![(PARAM_GAIN_ID, 1.0)]));
+
+        let text = format!("\n{HEADER}\n\nGain = loud\n");
+        assert_eq!(from_text(&text), Err(PatchError::BadValue { line: 4, name: "Gain" }));
+        assert_eq!(from_text("Gain = 0.5"), Err(PatchError::NotAPatch));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{A4_FREQ, A4_NOTE};
+
+/// 2^(k/12) for each semitone k of an octave, to build [`NOTE_FREQS`] from exactly: the
+/// octaves either side are then exact powers of two.
+const SEMITONE_RATIOS: [f32; 12] = [
+    1.0,
+    1.059_463_1,
+    1.122_462,
+    1.189_207_1,
+    1.259_921_1,
+    1.334_839_8,
+    1.414_213_5,
+    1.498_307_1,
+    1.587_401,
+    1.681_792_9,
+    1.781_797_4,
+    1.887_748_6,
+];
+
+/// Every MIDI note's frequency in Hz, equal-tempered from [`A4_FREQ`].
+static NOTE_FREQS: [f32; 128] = note_freqs();
+
+const fn note_freqs() -> [f32; 128] {
+    let mut freqs = [0.0; 128];
+    let mut note = 0;
+    while note < 128 {
+        let from_a4 = note as i32 - A4_NOTE as i32;
+        let (mut octaves, semitone) = (from_a4.div_euclid(12), from_a4.rem_euclid(12));
+        let mut freq = A4_FREQ * SEMITONE_RATIOS[semitone as usize];
+        while octaves > 0 {
+            freq *= 2.0;
+            octaves -= 1;
+        }
+        while octaves < 0 {
+            freq *= 0.5;
+            octaves += 1;
+        }
+        freqs[note] = freq;
+        note += 1;
+    }
+    freqs
+}
+
+/// A MIDI note's frequency in Hz, off the table. Notes past 127 play 127.
+pub fn note_freq(note: u8) -> f32 {
+    NOTE_FREQS[usize::from(note.min(127))]
+}
+
+/// 2^f's Taylor series to the fourth power, ln(2)^k / k!: off by 4e-5 (0.07 cents) at
+/// |f| = 0.5, before rounding.
+const EXP2_SERIES: [f32; 5] = [1.0, 0.693_147_2, 0.240_226_5, 0.055_504_1, 0.009_618_1];
+
+/// 2^x, to within a tenth of a cent: the whole octaves go straight into the float's
+/// exponent, and the rest, within half an octave either way, through a short polynomial.
+/// For pitch ratios on the audio thread, where `powf` costs too much.
+pub fn exp2(x: f32) -> f32 {
+    let x = x.clamp(-126.0, 126.0);
+    let octaves = x.round();
+    let f = x - octaves;
+    let fraction = EXP2_SERIES.iter().rev().fold(0.0, |sum, coefficient| sum * f + coefficient);
+    f32::from_bits(((octaves as i32 + 127) as u32) << 23) * fraction
+}
+
+/// The frequency ratio `semitones` up (or down, negative).
+pub fn semitones_to_ratio(semitones: f32) -> f32 {
+    exp2(semitones / 12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi_to_freq;
+
+    /// How far `freq` is from `reference`, in cents.
+    fn cents(freq: f32, reference: f32) -> f32 {
+        1200.0 * (freq / reference).log2().abs()
+    }
+
+    #[test]
+    fn note_table_matches_powf() {
+        for note in 0..=127 {
+            let off = cents(note_freq(note), midi_to_freq(note));
+            assert!(off < 0.5, "note {note} is {off} cents off");
+        }
+        assert_eq!(note_freq(A4_NOTE), A4_FREQ);
+    }
+
+    #[test]
+    fn fractional_ratios_match_powf() {
+        // Bends, detunes and sweeps, a thousandth of a semitone apart, across the MIDI
+        // range either way.
+        for step in -128_000..=128_000 {
+            let semitones = step as f32 / 1000.0;
+            let off = cents(semitones_to_ratio(semitones), 2.0f32.powf(semitones / 12.0));
+            assert!(off < 0.5, "{semitones} semitones is {off} cents off");
+        }
+    }
+}
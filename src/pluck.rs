@@ -0,0 +1,139 @@
+use crate::denormals::flush_denormal;
+use crate::noise::white_noise;
+use crate::sample::Sample;
+
+/// Lowest note frequency a string can be tuned to; its delay line is sized for this.
+const MIN_FREQUENCY: f32 = 20.0; // Hz
+
+/// Fraction of the string's energy kept per trip round the delay line, before damping.
+const FEEDBACK: Sample = 0.996;
+
+/// Karplus-Strong plucked string: a delay line filled with a noise burst on note-on, read
+/// one note period back and fed back through a lowpass that damps the high partials first.
+/// The period needn't be a whole number of samples: a first-order allpass after the read
+/// tap delays by the fraction left over, at unity gain so high notes keep their partials.
+/// They land in tune rather than on the nearest whole period, and the string follows pitch
+/// modulation as it plays.
+#[derive(Clone, Default)]
+pub struct PluckString {
+    /// Empty when the pool was built without a sample rate, which leaves the string silent.
+    buffer: Box<[Sample]>,
+    /// Where the next sample is written.
+    position: usize,
+    /// Damping lowpass state.
+    lowpass: Sample,
+    /// The fractional-delay allpass's last input and output.
+    allpass_in: Sample,
+    allpass_out: Sample,
+    noise: u32,
+}
+
+impl PluckString {
+    /// Allocates a delay line long enough for [`MIN_FREQUENCY`] at `sample_rate`.
+    pub fn new(sample_rate: f32) -> Self {
+        let len = (sample_rate / MIN_FREQUENCY).ceil() as usize;
+        Self { buffer: vec![0.0; len].into_boxed_slice(), noise: 0x9e37_79b9, ..Self::default() }
+    }
+
+    /// Excites the string with a fresh burst of noise. The whole line takes it, so whatever
+    /// period the note starts at, the tap reads back a period of the burst.
+    pub fn pluck(&mut self) {
+        self.position = 0;
+        self.lowpass = 0.0;
+        (self.allpass_in, self.allpass_out) = (0.0, 0.0);
+        for sample in self.buffer.iter_mut() {
+            *sample = Sample::from(white_noise(&mut self.noise));
+        }
+    }
+
+    /// Empties the delay line, leaving the string silent until it's plucked again.
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.lowpass = 0.0;
+        (self.allpass_in, self.allpass_out) = (0.0, 0.0);
+    }
+
+    /// Next output sample, with the string `period` samples long: the sample rate over the
+    /// note's frequency, as modulated right now. `tone` runs from dark (0.0), where the
+    /// string dulls quickly, to bright (1.0).
+    pub fn next(&mut self, tone: f32, period: f32) -> Sample {
+        let len = self.buffer.len();
+        if len < 2 {
+            return 0.0;
+        }
+        // The allpass takes between half a sample and one and a half, where its delay is
+        // flattest across the spectrum.
+        let period = period.clamp(1.5, (len - 1) as f32);
+        let whole = (period - 0.5) as usize;
+        let fraction = period - whole as f32;
+        let allpass = Sample::from((1.0 - fraction) / (1.0 + fraction));
+        let tapped = self.buffer[(self.position + len - whole) % len];
+        let out = flush_denormal(allpass * (tapped - self.allpass_out) + self.allpass_in);
+        (self.allpass_in, self.allpass_out) = (tapped, out);
+
+        let coefficient = Sample::from(0.05 + 0.95 * tone.clamp(0.0, 1.0));
+        self.lowpass = flush_denormal(self.lowpass + (out - self.lowpass) * coefficient);
+        self.buffer[self.position] = self.lowpass * FEEDBACK;
+        self.position = (self.position + 1) % len;
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn energy(string: &mut PluckString, samples: usize, tone: f32) -> Sample {
+        (0..samples).map(|_| string.next(tone, 10.0).powi(2)).sum()
+    }
+
+    #[test]
+    fn string_repeats_at_the_note_period_and_decays() {
+        let mut string = PluckString::new(1000.0);
+        string.pluck();
+        let first: Vec<Sample> = (0..10).map(|_| string.next(1.0, 10.0)).collect();
+        let second: Vec<Sample> = (0..10).map(|_| string.next(1.0, 10.0)).collect();
+
+        // At full brightness the lowpass is transparent, so each period is a quieter copy.
+        for (a, b) in first.iter().zip(&second) {
+            assert!((b - a * FEEDBACK).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn darker_tone_damps_faster() {
+        let (mut bright, mut dark) = (PluckString::new(1000.0), PluckString::new(1000.0));
+        bright.pluck();
+        dark.pluck();
+        energy(&mut bright, 200, 1.0);
+        energy(&mut dark, 200, 0.0);
+
+        assert!(energy(&mut dark, 100, 0.0) < energy(&mut bright, 100, 1.0));
+    }
+
+    #[test]
+    fn fractional_periods_tune_between_whole_samples() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        // A 12.3-sample period, which a whole-sample string would play at 4 kHz.
+        const FREQUENCY: f32 = 3900.0;
+        let mut string = PluckString::new(SAMPLE_RATE);
+        string.pluck();
+        let period = SAMPLE_RATE / FREQUENCY;
+        let output: Vec<Sample> = (0..4800).map(|_| string.next(1.0, period)).collect();
+
+        // The fundamental is the loudest frequency between 3.85 and 4.05 kHz, taken every
+        // 10 Hz once the burst has settled into a tone.
+        let settled = &output[1000..];
+        let level = |frequency: f32| {
+            let w = std::f32::consts::TAU * frequency / SAMPLE_RATE;
+            let (re, im) = settled.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &s)| {
+                let (sin, cos) = (w * n as f32).sin_cos();
+                (re + s * Sample::from(cos), im + s * Sample::from(sin))
+            });
+            re * re + im * im
+        };
+        let candidates = (3850..=4050).step_by(10).map(|f| f as f32);
+        let loudest = candidates.max_by(|&a, &b| level(a).total_cmp(&level(b))).unwrap();
+        assert!((loudest - FREQUENCY).abs() <= 20.0, "{loudest} Hz");
+    }
+}
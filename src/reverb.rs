@@ -0,0 +1,330 @@
+use crate::denormals::flush_denormal;
+use crate::params::PARAM_REVERB_PREDELAY_ID;
+use crate::sample::Sample;
+use crate::smoother::Smoother;
+
+/// Comb and allpass delays at [`TUNING_RATE`], Freeverb's: mutually prime, so the combs'
+/// echoes don't pile up on the same samples and ring.
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+const TUNING_RATE: f32 = 44_100.0; // Hz
+/// How much longer each of the right channel's delays is than the left's, at
+/// [`TUNING_RATE`]. The two networks then drift out of step, so the tail has width.
+const STEREO_SPREAD: usize = 23;
+
+/// Keeps eight combs summed in parallel around the level of the input.
+const INPUT_GAIN: Sample = 0.015;
+const ALLPASS_FEEDBACK: Sample = 0.5;
+/// Comb feedback at no decay, and how much more full decay adds. Short of 1.0, so the tail
+/// always dies away.
+const MIN_FEEDBACK: f32 = 0.7;
+const FEEDBACK_RANGE: f32 = 0.28;
+/// Share of each echo the combs' lowpass holds back at full damping.
+const MAX_DAMPING: f32 = 0.4;
+/// How long the input takes to fade out as the reverb freezes, and back in after, so a
+/// note caught mid-way doesn't click.
+const FREEZE_FADE: f32 = 0.01; // seconds
+
+/// Longest predelay, which the predelay line is sized for along with the early taps.
+pub const MAX_PREDELAY: f32 = 0.2; // seconds
+/// A cave's first echoes, off the nearest walls: taps on the predelay line, in seconds
+/// past the predelay and with their gains, for the left side then the right. Spaced
+/// irregularly and differently per side, so they don't flutter or sit in the middle.
+const EARLY_TAPS: [[(f32, Sample); 4]; 2] = [
+    [(0.0071, 0.42), (0.0197, 0.31), (0.0313, 0.24), (0.0461, 0.17)],
+    [(0.0089, 0.40), (0.0233, 0.29), (0.0347, 0.22), (0.0529, 0.15)],
+];
+const MAX_EARLY_TAP: f32 = 0.0529; // seconds
+
+/// The reverb's params, read once a block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReverbSettings {
+    /// Wet level added to the dry signal, 0.0 to 1.0.
+    pub mix: f32,
+    /// Tail length, 0.0 (a small chamber) to 1.0 (a cavern).
+    pub decay: f32,
+    /// How quickly the highs die away in the tail, 0.0 to 1.0.
+    pub damping: f32,
+    /// Seconds before the first reflections, up to [`MAX_PREDELAY`]. The tail follows
+    /// the last of them.
+    pub predelay: f32,
+    /// Holds the tail as it is, indefinitely, and lets nothing more in.
+    pub freeze: bool,
+}
+
+/// A feedback comb with a lowpass in the loop, so each echo comes back darker.
+struct Comb {
+    buffer: Vec<Sample>,
+    position: usize,
+    filtered: Sample,
+}
+
+impl Comb {
+    fn process(&mut self, input: Sample, feedback: Sample, damping: Sample) -> Sample {
+        let out = self.buffer[self.position];
+        self.filtered = flush_denormal(out + (self.filtered - out) * damping);
+        self.buffer[self.position] = input + self.filtered * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+        out
+    }
+}
+
+/// Smears each echo into many without colouring it.
+struct Allpass {
+    buffer: Vec<Sample>,
+    position: usize,
+}
+
+impl Allpass {
+    fn process(&mut self, input: Sample) -> Sample {
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = flush_denormal(input + delayed * ALLPASS_FEEDBACK);
+        self.position = (self.position + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// One side's network: parallel combs into allpasses in series.
+struct ReverbChannel {
+    combs: [Comb; COMB_TUNINGS.len()],
+    allpasses: [Allpass; ALLPASS_TUNINGS.len()],
+}
+
+impl ReverbChannel {
+    fn new(sample_rate: f32, spread: usize) -> Self {
+        let line = |tuning: usize| {
+            let len = ((tuning + spread) as f32 * sample_rate / TUNING_RATE) as usize;
+            vec![0.0; len.max(1)]
+        };
+        let comb = |tuning| Comb { buffer: line(tuning), position: 0, filtered: 0.0 };
+        let allpass = |tuning| Allpass { buffer: line(tuning), position: 0 };
+        Self { combs: COMB_TUNINGS.map(comb), allpasses: ALLPASS_TUNINGS.map(allpass) }
+    }
+
+    fn process(&mut self, input: Sample, feedback: Sample, damping: Sample) -> Sample {
+        let mut out = 0.0;
+        for comb in &mut self.combs {
+            out += comb.process(input, feedback, damping);
+        }
+        for allpass in &mut self.allpasses {
+            out = allpass.process(out);
+        }
+        out
+    }
+}
+
+/// The input, held for the predelay and the early reflections.
+struct PredelayLine {
+    buffer: Vec<Sample>,
+    write: usize,
+}
+
+impl PredelayLine {
+    fn push(&mut self, input: Sample) {
+        self.write = (self.write + 1) % self.buffer.len();
+        self.buffer[self.write] = input;
+    }
+
+    /// The input from `delay` samples ago, between samples read off the two either side,
+    /// so a moving delay glides rather than steps.
+    fn tap(&self, delay: f32) -> Sample {
+        let len = self.buffer.len();
+        let read = self.write as f32 + len as f32 - delay.clamp(0.0, (len - 2) as f32);
+        let (index, frac) = (read as usize, read.fract());
+        let a = self.buffer[index % len];
+        let b = self.buffer[(index + 1) % len];
+        a + (b - a) * Sample::from(frac)
+    }
+}
+
+/// The cave's reverb: early reflections, then the diffuse tail from a Schroeder-Moorer
+/// network per side (Freeverb's), the right one's delays a little longer than the left's
+/// so the two sides decorrelate.
+pub struct Reverb {
+    predelay_line: PredelayLine,
+    /// In seconds, glided so moving it doesn't click.
+    predelay: Smoother,
+    channels: [ReverbChannel; 2],
+    /// Gain on the input, faded down to nothing while frozen.
+    input_level: Sample,
+    sample_rate: f32, // Hz
+}
+
+impl Reverb {
+    /// Delay lines are sized for `sample_rate` here, so processing never allocates.
+    /// `predelay` is where the predelay starts, in seconds.
+    pub fn new(sample_rate: f32, predelay: f32) -> Self {
+        let line_len = ((MAX_PREDELAY + MAX_EARLY_TAP) * sample_rate) as usize + 2;
+        Self {
+            predelay_line: PredelayLine { buffer: vec![0.0; line_len], write: 0 },
+            predelay: Smoother::new(PARAM_REVERB_PREDELAY_ID, predelay),
+            channels: [0, STEREO_SPREAD].map(|spread| ReverbChannel::new(sample_rate, spread)),
+            input_level: 1.0,
+            sample_rate,
+        }
+    }
+
+    /// Forgets the tail.
+    pub fn clear(&mut self) {
+        self.predelay_line.buffer.fill(0.0);
+        self.predelay.snap();
+        for channel in &mut self.channels {
+            for comb in &mut channel.combs {
+                comb.buffer.fill(0.0);
+                comb.filtered = 0.0;
+            }
+            for allpass in &mut channel.allpasses {
+                allpass.buffer.fill(0.0);
+            }
+        }
+    }
+
+    /// The wet left and right signals for the next `input` sample, already scaled by the
+    /// mix.
+    ///
+    /// Frozen, the combs feed back at unity with no damping, so each is lossless and the
+    /// tail circulates unchanged. It can only build up from what's still let in, so the
+    /// input fades out first, and only what was already in the predelay line joins it.
+    pub fn process(&mut self, input: Sample, settings: &ReverbSettings) -> (Sample, Sample) {
+        let (feedback, damping) = if settings.freeze {
+            (1.0, 0.0)
+        } else {
+            let feedback = MIN_FEEDBACK + FEEDBACK_RANGE * settings.decay.clamp(0.0, 1.0);
+            (Sample::from(feedback), Sample::from(MAX_DAMPING * settings.damping.clamp(0.0, 1.0)))
+        };
+        let mix = Sample::from(settings.mix);
+
+        let fade_step = Sample::from(1.0 / (FREEZE_FADE * self.sample_rate));
+        self.input_level = if settings.freeze {
+            (self.input_level - fade_step).max(0.0)
+        } else {
+            (self.input_level + fade_step).min(1.0)
+        };
+        self.predelay_line.push(input * self.input_level);
+        self.predelay.set_target(settings.predelay.clamp(0.0, MAX_PREDELAY), self.sample_rate);
+        let predelay = self.predelay.next_value();
+        let early = EARLY_TAPS.map(|taps| {
+            let tap = |(time, gain): (f32, Sample)| {
+                self.predelay_line.tap((predelay + time) * self.sample_rate) * gain
+            };
+            taps.into_iter().map(tap).sum::<Sample>()
+        });
+
+        let input = self.predelay_line.tap(predelay * self.sample_rate) * INPUT_GAIN;
+        let [left, right] = &mut self.channels;
+        let left = early[0] + left.process(input, feedback, damping);
+        let right = early[1] + right.process(input, feedback, damping);
+        (left * mix, right * mix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::white_noise;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+    const SETTINGS: ReverbSettings =
+        ReverbSettings { mix: 1.0, decay: 0.5, damping: 0.5, predelay: 0.0, freeze: false };
+
+    /// The reverb's answer to a click, left and right.
+    fn impulse_response(samples: usize, settings: ReverbSettings) -> (Vec<Sample>, Vec<Sample>) {
+        let mut reverb = Reverb::new(SAMPLE_RATE, settings.predelay);
+        let input = |n: usize| if n == 0 { 1.0 } else { 0.0 };
+        (0..samples).map(|n| reverb.process(input(n), &settings)).unzip()
+    }
+
+    fn energy(buffer: &[Sample]) -> Sample {
+        buffer.iter().map(|s| s * s).sum()
+    }
+
+    #[test]
+    fn the_tail_is_stereo() {
+        let (left, right) = impulse_response(48_000, SETTINGS);
+        let (left, right) = (&left[4800..], &right[4800..]);
+        let cross: Sample = left.iter().zip(right).map(|(l, r)| l * r).sum();
+        let correlation = cross / (energy(left) * energy(right)).sqrt();
+        assert!(energy(left) > 0.0 && energy(right) > 0.0);
+        assert!(correlation.abs() < 0.5, "left and right correlate by {correlation}");
+    }
+
+    #[test]
+    fn the_tail_dies_away() {
+        let (left, _) = impulse_response(96_000, SETTINGS);
+        let (early, late) = (&left[..24_000], &left[72_000..]);
+        assert!(energy(late) < energy(early) * 1e-3);
+    }
+
+    #[test]
+    fn reflections_arrive_after_the_predelay() {
+        let predelay = 0.05;
+        let (left, right) = impulse_response(9600, ReverbSettings { predelay, ..SETTINGS });
+        for (side, taps) in [left, right].iter().zip(EARLY_TAPS) {
+            let first = side.iter().position(|&s| s != 0.0).unwrap();
+            let expected = ((predelay + taps[0].0) * SAMPLE_RATE).round() as usize;
+            assert!(first.abs_diff(expected) <= 1, "{first}, expected {expected}");
+        }
+    }
+
+    #[test]
+    fn moving_the_predelay_glides() {
+        let mut reverb = Reverb::new(SAMPLE_RATE, 0.0);
+        let tone = |n: usize| Sample::from((n as f32 * 0.05).sin());
+        let mut settings = SETTINGS;
+        let left: Vec<Sample> = (0..9600)
+            .map(|n| {
+                settings.predelay = if n < 4800 { 0.0 } else { MAX_PREDELAY };
+                reverb.process(tone(n), &settings).0
+            })
+            .collect();
+
+        let max_step = |buffer: &[Sample]| {
+            buffer.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, Sample::max)
+        };
+        let (steady, moving) = (max_step(&left[2400..4800]), max_step(&left[4800..]));
+        assert!(moving < 2.0 * steady, "stepped by {moving}, steady {steady}");
+    }
+
+    /// A second of noise into the reverb, then `frozen` seconds frozen with the noise ten
+    /// times louder once the input has faded, then `after` seconds thawed in silence: the
+    /// left output of it all.
+    fn freeze_run(frozen: f32, after: f32) -> Vec<Sample> {
+        let mut reverb = Reverb::new(SAMPLE_RATE, 0.0);
+        let mut state = 1;
+        let (freeze_at, thaw_at) = (SAMPLE_RATE, SAMPLE_RATE * (1.0 + frozen));
+        let faded = freeze_at + FREEZE_FADE * SAMPLE_RATE;
+        let end = thaw_at + SAMPLE_RATE * after;
+        (0..end as usize)
+            .map(|n| {
+                let n = n as f32;
+                let freeze = (freeze_at..thaw_at).contains(&n);
+                let level = if n < faded { 1.0 } else if freeze { 10.0 } else { 0.0 };
+                let input = white_noise(&mut state) * level;
+                reverb.process(Sample::from(input), &ReverbSettings { freeze, ..SETTINGS }).0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_frozen_tail_holds_without_building_up() {
+        let left = freeze_run(4.0, 0.0);
+        let second = SAMPLE_RATE as usize;
+        // Once the predelay line has emptied into the tail, nothing more gets in: the held
+        // tail stays about as loud as it was before, not as loud as the noise now is.
+        let before = &left[second / 2..second];
+        let held = &left[2 * second..3 * second];
+        let later = &left[4 * second..];
+        let caught = energy(held) / energy(before);
+        assert!((0.25..4.0).contains(&caught), "froze at {caught} times the energy");
+        let ratio = energy(later) / energy(held);
+        assert!((0.8..1.25).contains(&ratio), "the held tail's energy changed by {ratio}");
+    }
+
+    #[test]
+    fn thawing_lets_the_tail_die_away() {
+        let left = freeze_run(1.0, 2.0);
+        let second = SAMPLE_RATE as usize;
+        let (thawed, late) = (&left[2 * second..2 * second + 4800], &left[3 * second + 24_000..]);
+        assert!(energy(late) < energy(thawed) * 1e-3);
+    }
+}
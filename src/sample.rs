@@ -0,0 +1,27 @@
+/// What the signal path runs in, from the oscillators to the mix: f32, or f64 with the
+/// `f64-dsp` feature for feedback paths and long voice sums that need the precision.
+/// Control values (envelopes, LFOs, params) stay f32 either way, and the output buffers
+/// are whatever the host asked for.
+#[cfg(not(feature = "f64-dsp"))]
+pub type Sample = f32;
+#[cfg(feature = "f64-dsp")]
+pub type Sample = f64;
+
+/// Conversion from the signal path's [`Sample`] to an output buffer's sample type.
+pub trait FromSample {
+    fn from_sample(sample: Sample) -> Self;
+}
+
+impl FromSample for f32 {
+    #[allow(clippy::unnecessary_cast)] // a no-op unless `f64-dsp` is on
+    fn from_sample(sample: Sample) -> Self {
+        sample as f32
+    }
+}
+
+impl FromSample for f64 {
+    #[allow(clippy::useless_conversion)] // a no-op when `f64-dsp` is on
+    fn from_sample(sample: Sample) -> Self {
+        sample.into()
+    }
+}
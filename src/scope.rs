@@ -0,0 +1,117 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use atomic_float::AtomicF32;
+
+use crate::sample::{FromSample, Sample};
+
+/// Samples the scope keeps: enough to find a trigger and still fill the display.
+pub const SCOPE_LEN: usize = 4096;
+
+/// Recent output for the editor's oscilloscope and spectrum analyser. The audio thread
+/// writes each block in with relaxed stores and the editor copies out whatever is there,
+/// so a read can straddle a block boundary; the displays don't mind, and neither side ever
+/// waits.
+pub struct ScopeBuffer {
+    samples: [AtomicF32; SCOPE_LEN],
+    /// Total samples ever written.
+    written: AtomicUsize,
+    /// Set while the scope is on screen. The audio thread skips the writes otherwise.
+    watching: AtomicBool,
+    /// Of the samples, for the spectrum's frequency axis.
+    sample_rate: AtomicF32,
+}
+
+impl Default for ScopeBuffer {
+    fn default() -> Self {
+        Self {
+            samples: std::array::from_fn(|_| AtomicF32::new(0.0)),
+            written: AtomicUsize::new(0),
+            watching: AtomicBool::new(false),
+            sample_rate: AtomicF32::new(48_000.0),
+        }
+    }
+}
+
+impl ScopeBuffer {
+    /// Audio thread only.
+    pub fn write(&self, block: &[Sample]) {
+        if !self.watching.load(Ordering::Relaxed) {
+            return;
+        }
+        let start = self.written.load(Ordering::Relaxed);
+        for (i, &sample) in block.iter().enumerate() {
+            self.samples[(start + i) % SCOPE_LEN].store(f32::from_sample(sample), Ordering::Relaxed);
+        }
+        self.written.store(start + block.len(), Ordering::Release);
+    }
+
+    /// Audio thread, or while it isn't running. Zeroes the ring; `written` carries on, so
+    /// the editor sees fresh samples.
+    pub fn clear(&self) {
+        for sample in &self.samples {
+            sample.store(0.0, Ordering::Relaxed);
+        }
+        self.written.fetch_add(SCOPE_LEN, Ordering::Release);
+    }
+
+    /// Total samples ever written, which only moves while the scope is watched.
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    pub fn set_watching(&self, watching: bool) {
+        self.watching.store(watching, Ordering::Relaxed);
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    /// Copies out the most recent `out.len()` samples (at most [`SCOPE_LEN`]), oldest first.
+    pub fn read(&self, out: &mut [f32]) {
+        let end = self.written.load(Ordering::Acquire);
+        let start = end.wrapping_sub(out.len().min(SCOPE_LEN));
+        for (i, sample) in out.iter_mut().take(SCOPE_LEN).enumerate() {
+            *sample = self.samples[start.wrapping_add(i) % SCOPE_LEN].load(Ordering::Relaxed);
+        }
+    }
+}
+
+/// Where to start drawing `window` samples of `samples` so periodic waveforms hold still:
+/// the last rising zero crossing that leaves a full window after it, or the latest window
+/// if there's none.
+pub fn trigger(samples: &[f32], window: usize) -> usize {
+    let latest = samples.len().saturating_sub(window);
+    (1..=latest)
+        .rev()
+        .find(|&i| samples[i - 1] < 0.0 && samples[i] >= 0.0)
+        .unwrap_or(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_returns_the_latest_samples_in_order() {
+        let scope = ScopeBuffer::default();
+        scope.set_watching(true);
+        let block: Vec<Sample> = (0..SCOPE_LEN + 10).map(|i| i as Sample).collect();
+        scope.write(&block);
+
+        let mut out = [0.0; 4];
+        scope.read(&mut out);
+        assert_eq!(out, [4102.0, 4103.0, 4104.0, 4105.0]);
+    }
+
+    #[test]
+    fn trigger_finds_the_last_rising_zero_crossing_with_room_after_it() {
+        let samples = [-1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0];
+        assert_eq!(trigger(&samples, 3), 3);
+        assert_eq!(trigger(&[0.5; 8], 3), 5);
+    }
+}
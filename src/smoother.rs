@@ -0,0 +1,100 @@
+use crate::params::param_desc;
+
+/// Glides a param's value to each new target in a straight line over the param's own
+/// smoothing time, so automation and knob moves don't step audibly. Params that don't
+/// smooth, the stepped ones among them, jump.
+#[derive(Debug, Clone, Copy)]
+pub struct Smoother {
+    value: f32,
+    target: f32,
+    /// Moved per sample until `target` is reached.
+    step: f32,
+    /// Seconds a glide takes, from the param's [`ParamDesc`](crate::params::ParamDesc).
+    time: f32,
+}
+
+impl Smoother {
+    /// A smoother for param `id`, starting at `value`.
+    pub fn new(id: u32, value: f32) -> Self {
+        let time = param_desc(id).map_or(0.0, |desc| desc.smoothing);
+        Self { value, target: value, step: 0.0, time }
+    }
+
+    /// Heads for `target`, arriving one smoothing time from now.
+    pub fn set_target(&mut self, target: f32, sample_rate: f32) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        let samples = self.time * sample_rate;
+        if samples < 1.0 {
+            self.snap();
+        } else {
+            self.step = (target - self.value) / samples;
+        }
+    }
+
+    /// Jumps straight to the target, as after a reset.
+    pub fn snap(&mut self) {
+        self.value = self.target;
+        self.step = 0.0;
+    }
+
+    /// The value for the next sample.
+    pub fn next_value(&mut self) -> f32 {
+        self.skip(1)
+    }
+
+    /// Moves on by `frames` samples at once, for values only read once a block, and
+    /// returns where that leaves it.
+    pub fn skip(&mut self, frames: usize) -> f32 {
+        if self.step != 0.0 {
+            self.value += self.step * frames as f32;
+            let arrived = (self.step > 0.0 && self.value >= self.target)
+                || (self.step < 0.0 && self.value <= self.target);
+            if arrived {
+                self.snap();
+            }
+        }
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{
+        PARAMS, PARAM_CUTOFF_ID, PARAM_GAIN_ID, PARAM_REVERB_PREDELAY_ID, PARAM_WAVEFORM_ID,
+    };
+
+    #[test]
+    fn glides_over_the_params_own_time() {
+        let sample_rate = 48_000.0;
+        let mut smoother = Smoother::new(PARAM_CUTOFF_ID, 1000.0);
+        smoother.set_target(2000.0, sample_rate);
+        let samples = (param_desc(PARAM_CUTOFF_ID).unwrap().smoothing * sample_rate) as usize;
+
+        let halfway = smoother.skip(samples / 2);
+        assert!((halfway - 1500.0).abs() < 1.0, "{halfway}");
+        assert_eq!(smoother.skip(samples), 2000.0);
+        assert_eq!(smoother.next_value(), 2000.0);
+    }
+
+    #[test]
+    fn stepped_params_jump() {
+        for desc in PARAMS.iter().filter(|desc| desc.is_stepped()) {
+            assert_eq!(desc.smoothing, 0.0, "{}", desc.name);
+        }
+        let mut smoother = Smoother::new(PARAM_WAVEFORM_ID, 0.0);
+        smoother.set_target(2.0, 48_000.0);
+        assert_eq!(smoother.next_value(), 2.0);
+    }
+
+    #[test]
+    fn only_params_read_through_a_smoother_declare_a_time() {
+        let smoothed = [PARAM_GAIN_ID, PARAM_CUTOFF_ID, PARAM_REVERB_PREDELAY_ID];
+        for desc in PARAMS {
+            assert_eq!(desc.smoothing > 0.0, smoothed.contains(&desc.id), "{}", desc.name);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+/// Keyboard split/layer modes, indexed by the split mode param.
+pub const SPLIT_MODE_NAMES: &[&str] = &["Off", "Split", "Layer"];
+
+const SPLIT_MODE_SPLIT: usize = 1;
+const SPLIT_MODE_LAYER: usize = 2;
+
+/// The keyboard zones a key sounds in, as semitone transpositions.
+///
+/// With the split off a key sounds once, untransposed. Split mode plays keys below the
+/// split point in the lower zone and the rest in the upper zone; layer mode plays every key
+/// in both zones at once.
+pub fn zone_transpositions(
+    mode: usize,
+    split_point: u8,
+    lower_octave: i32,
+    upper_octave: i32,
+    key: u8,
+) -> [Option<i32>; 2] {
+    let lower = Some(lower_octave * 12);
+    let upper = Some(upper_octave * 12);
+
+    match mode {
+        SPLIT_MODE_SPLIT if key < split_point => [lower, None],
+        SPLIT_MODE_SPLIT => [upper, None],
+        SPLIT_MODE_LAYER => [lower, upper],
+        _ => [Some(0), None],
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(not(debug_assertions))]
+use std::marker::PhantomData;
+
+#[cfg(debug_assertions)]
+use clack_extensions::thread_check::HostThreadCheck;
+use clack_plugin::host::HostSharedHandle;
+
+/// Debug-build assertions that an entry point runs on the thread CLAP promises it.
+///
+/// Hosts without the thread-check extension (and the tests, which have no host at all) get
+/// no checks. In release builds this is an empty struct and every check compiles out.
+#[derive(Clone, Copy, Default)]
+pub struct ThreadCheck<'a> {
+    #[cfg(debug_assertions)]
+    host: Option<(HostSharedHandle<'a>, HostThreadCheck)>,
+    #[cfg(not(debug_assertions))]
+    host: PhantomData<HostSharedHandle<'a>>,
+}
+
+impl<'a> ThreadCheck<'a> {
+    #[cfg(debug_assertions)]
+    pub fn new(host: HostSharedHandle<'a>) -> Self {
+        Self { host: host.get_extension::<HostThreadCheck>().map(|ext| (host, ext)) }
+    }
+
+    #[cfg(not(debug_assertions))]
+    pub fn new(_host: HostSharedHandle<'a>) -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    pub fn main_thread(&self, entry_point: &str) {
+        #[cfg(debug_assertions)]
+        if let Some((host, ext)) = &self.host {
+            assert_ne!(ext.is_main_thread(host), Some(false), "{entry_point} called off the main thread");
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = entry_point;
+    }
+
+    #[inline(always)]
+    pub fn audio_thread(&self, entry_point: &str) {
+        #[cfg(debug_assertions)]
+        if let Some((host, ext)) = &self.host {
+            assert_ne!(ext.is_audio_thread(host), Some(false), "{entry_point} called off the audio thread");
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = entry_point;
+    }
+}
@@ -0,0 +1,113 @@
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::denormals::FlushDenormals;
+use crate::sample::Sample;
+use crate::voice::{RenderParams, Voice};
+
+/// Tasks voice rendering is split into when the host lends us its thread pool.
+pub const RENDER_TASKS: usize = 4;
+
+/// Below this many sounding voices, waking the pool costs more than it saves.
+pub const PARALLEL_MIN_VOICES: usize = 8;
+
+/// Buffers each task renders into: the mix and side signal, then the same without the
+/// combs for the FX mix's dry signal.
+pub const TASK_SIGNALS: usize = 4;
+
+/// One block of voice rendering, as seen by the pool's worker threads.
+struct RenderJob {
+    voices: *mut Voice,
+    voice_count: usize,
+    /// `RENDER_TASKS` buffers for each of the [`TASK_SIGNALS`] in turn, `stride` samples
+    /// apart.
+    buffers: *mut Sample,
+    stride: usize,
+    frames: usize,
+    /// Whether to render the dry signals too.
+    dry: bool,
+    render: RenderParams,
+}
+
+/// Lends the audio thread's voices to the host's `exec` calls for one `request_exec`.
+///
+/// The host runs every task before `request_exec` returns, so the job can live on the
+/// audio thread's stack; it is only published while that call is in flight.
+#[derive(Default)]
+pub struct VoiceTasks {
+    job: AtomicPtr<RenderJob>,
+}
+
+impl VoiceTasks {
+    /// Renders `voices` into per-task slices of `task_buffers` by having `exec` run
+    /// [`RENDER_TASKS`] tasks. The first quarter of `task_buffers` takes each task's mix,
+    /// the second its side signal, and with `dry` the last two take the same without the
+    /// combs. Returns whatever `exec` did, i.e. whether the tasks ran.
+    pub fn run(
+        &self,
+        voices: &mut [Voice],
+        task_buffers: &mut [Sample],
+        frames: usize,
+        dry: bool,
+        render: RenderParams,
+        exec: impl FnOnce(u32) -> bool,
+    ) -> bool {
+        let stride = task_buffers.len() / (TASK_SIGNALS * RENDER_TASKS);
+        // The engine splits blocks to fit, so this can't happen; if it does, render what
+        // fits rather than take the host down from the audio thread.
+        debug_assert!(frames <= stride, "block larger than the task buffers allocated at activate");
+        let frames = frames.min(stride);
+
+        let mut job = RenderJob {
+            voices: voices.as_mut_ptr(),
+            voice_count: voices.len(),
+            buffers: task_buffers.as_mut_ptr(),
+            stride,
+            frames,
+            dry,
+            render,
+        };
+        self.job.store(&mut job, Ordering::Release);
+        let ran = exec(RENDER_TASKS as u32);
+        self.job.store(ptr::null_mut(), Ordering::Release);
+        ran
+    }
+
+    /// Renders task `index`'s contiguous share of the voices into its own buffers.
+    pub fn exec(&self, index: u32) {
+        let index = index as usize;
+        // SAFETY: the pointer is either null or points at the job `run` keeps alive until
+        // the host has finished every task.
+        let Some(job) = (unsafe { self.job.load(Ordering::Acquire).as_ref() }) else { return };
+        if index >= RENDER_TASKS {
+            return;
+        }
+
+        let per_task = job.voice_count.div_ceil(RENDER_TASKS);
+        let start = (index * per_task).min(job.voice_count);
+        let end = (start + per_task).min(job.voice_count);
+
+        // SAFETY: each task index owns a disjoint voice range and buffers, and the audio
+        // thread doesn't touch any of them until `request_exec` returns.
+        let voices = unsafe { slice::from_raw_parts_mut(job.voices.add(start), end - start) };
+        let [buffer, side, dry_mix, dry_side] = std::array::from_fn(|signal| unsafe {
+            let at = (signal * RENDER_TASKS + index) * job.stride;
+            slice::from_raw_parts_mut(job.buffers.add(at), job.frames)
+        });
+
+        // The host's worker is ours for the task, like the audio thread for the block.
+        let _flush = FlushDenormals::new();
+        let mut dry = job.dry.then_some((dry_mix, dry_side));
+        buffer.fill(0.0);
+        side.fill(0.0);
+        if let Some((dry_mix, dry_side)) = &mut dry {
+            dry_mix.fill(0.0);
+            dry_side.fill(0.0);
+        }
+        for voice in voices.iter_mut().filter(|v| v.is_active()) {
+            let dry = dry.as_mut().map(|(mix, side)| (&mut **mix, &mut **side));
+            voice.render_add(buffer, side, dry, &job.render);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::sync::Mutex;
+
+use clack_extensions::track_info::TrackInfo as HostTrackInfoData;
+
+/// Owned copy of what the host told us about the track we live on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackInfo {
+    pub name: Option<String>,
+    pub color: Option<[u8; 3]>,
+    pub channel_count: Option<u32>,
+}
+
+impl TrackInfo {
+    pub fn from_host(info: &HostTrackInfoData) -> Self {
+        Self {
+            name: info.name.map(|n| String::from_utf8_lossy(n).into_owned()),
+            color: info.color.map(|c| [c.red, c.green, c.blue]),
+            channel_count: info.audio_channel_count,
+        }
+    }
+
+    pub fn is_mono(&self) -> bool {
+        self.channel_count == Some(1)
+    }
+}
+
+/// Track info as published to the GUI thread. Only the main thread writes it.
+pub type SharedTrackInfo = Mutex<Option<TrackInfo>>;
@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+use atomic_float::AtomicF64;
+use clack_plugin::events::event_types::{TransportEvent, TransportFlags};
+
+use crate::auto_pan::HostTime;
+
+/// What the host's transport last said, kept across blocks. Hosts send it with every block,
+/// now and then, or never; whatever they left out keeps its last value, or the default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transport {
+    /// BPM, once the host has reported one.
+    pub tempo: Option<f64>,
+    /// Beats per bar and the note value of a beat, 4/4 until the host says otherwise.
+    pub time_signature: (u16, u16),
+    /// Song position in quarter-note beats, once the host has reported one.
+    pub beat: Option<f64>,
+    pub playing: bool,
+    pub recording: bool,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self { tempo: None, time_signature: (4, 4), beat: None, playing: false, recording: false }
+    }
+}
+
+impl Transport {
+    /// Takes in a transport event, from the block's start or from among its events.
+    pub fn update(&mut self, event: &TransportEvent) {
+        let signature = (event.time_signature_numerator, event.time_signature_denominator);
+        self.apply(event.flags, event.tempo, event.song_pos_beats.to_float(), signature);
+    }
+
+    fn apply(&mut self, flags: TransportFlags, tempo: f64, beat: f64, signature: (u16, u16)) {
+        if flags.contains(TransportFlags::HAS_TEMPO) {
+            self.tempo = Some(tempo);
+        }
+        if flags.contains(TransportFlags::HAS_BEATS_TIMELINE) {
+            self.beat = Some(beat);
+        }
+        let signature_valid = signature.0 > 0 && signature.1 > 0;
+        if flags.contains(TransportFlags::HAS_TIME_SIGNATURE) && signature_valid {
+            self.time_signature = signature;
+        }
+        self.playing = flags.contains(TransportFlags::IS_PLAYING);
+        self.recording = flags.contains(TransportFlags::IS_RECORDING);
+    }
+
+    /// Moves the song position on by a block of `frames` while playing, for hosts that don't
+    /// send the transport with every block. The next event from the host corrects it.
+    pub fn advance(&mut self, frames: u32, sample_rate: f32) {
+        if let (true, Some(tempo), Some(beat)) = (self.playing, self.tempo, self.beat.as_mut()) {
+            *beat += tempo / 60.0 * frames as f64 / sample_rate as f64;
+        }
+    }
+
+    /// The tempo and, while playing, the song position, for tempo-synced modulation.
+    pub fn host_time(&self) -> HostTime {
+        HostTime { tempo: self.tempo, beat: self.beat.filter(|_| self.playing) }
+    }
+
+    /// Quarter-note beats in a bar.
+    fn bar_beats(&self) -> f64 {
+        let (numerator, denominator) = self.time_signature;
+        numerator as f64 * 4.0 / denominator as f64
+    }
+
+    /// The song position as a bar and a beat within it, both counted from 1.
+    pub fn bar_and_beat(&self) -> Option<(i64, u32)> {
+        let beat = self.beat?;
+        let bar_beats = self.bar_beats();
+        let beat_len = 4.0 / self.time_signature.1 as f64;
+        let bar = (beat / bar_beats).floor() as i64 + 1;
+        let in_bar = (beat.rem_euclid(bar_beats) / beat_len).floor() as u32 + 1;
+        Some((bar, in_bar))
+    }
+}
+
+/// The audio thread's [`Transport`], for the editor to show. Published once a block with
+/// relaxed stores; a read can mix two blocks' values, which a display doesn't mind.
+pub struct SharedTransport {
+    /// BPM, or NaN before the host has reported one. Likewise the song position.
+    tempo: AtomicF64,
+    beat: AtomicF64,
+    /// Numerator in the high half, denominator in the low.
+    time_signature: AtomicU32,
+    /// [`PLAYING`] and [`RECORDING`].
+    state: AtomicU8,
+}
+
+const PLAYING: u8 = 1;
+const RECORDING: u8 = 2;
+
+impl Default for SharedTransport {
+    fn default() -> Self {
+        let shared = Self {
+            tempo: AtomicF64::new(f64::NAN),
+            beat: AtomicF64::new(f64::NAN),
+            time_signature: AtomicU32::new(0),
+            state: AtomicU8::new(0),
+        };
+        shared.publish(&Transport::default());
+        shared
+    }
+}
+
+impl SharedTransport {
+    /// Audio thread only.
+    pub fn publish(&self, transport: &Transport) {
+        self.tempo.store(transport.tempo.unwrap_or(f64::NAN), Ordering::Relaxed);
+        self.beat.store(transport.beat.unwrap_or(f64::NAN), Ordering::Relaxed);
+        let (numerator, denominator) = transport.time_signature;
+        let signature = (numerator as u32) << 16 | denominator as u32;
+        self.time_signature.store(signature, Ordering::Relaxed);
+        let state = if transport.playing { PLAYING } else { 0 }
+            | if transport.recording { RECORDING } else { 0 };
+        self.state.store(state, Ordering::Relaxed);
+    }
+
+    pub fn load(&self) -> Transport {
+        let present = |value: f64| (!value.is_nan()).then_some(value);
+        let signature = self.time_signature.load(Ordering::Relaxed);
+        let state = self.state.load(Ordering::Relaxed);
+        Transport {
+            tempo: present(self.tempo.load(Ordering::Relaxed)),
+            time_signature: ((signature >> 16) as u16, signature as u16),
+            beat: present(self.beat.load(Ordering::Relaxed)),
+            playing: state & PLAYING != 0,
+            recording: state & RECORDING != 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reported(playing: bool) -> TransportFlags {
+        let reported = TransportFlags::HAS_TEMPO
+            | TransportFlags::HAS_BEATS_TIMELINE
+            | TransportFlags::HAS_TIME_SIGNATURE;
+        if playing { reported | TransportFlags::IS_PLAYING } else { reported }
+    }
+
+    #[test]
+    fn keeps_the_last_report_and_defaults_before_one() {
+        let mut transport = Transport::default();
+        assert_eq!(transport.host_time(), HostTime::default());
+        assert_eq!(transport.bar_and_beat(), None);
+
+        transport.apply(reported(true), 120.0, 13.5, (3, 4));
+        assert_eq!(transport.host_time(), HostTime { tempo: Some(120.0), beat: Some(13.5) });
+        assert_eq!(transport.bar_and_beat(), Some((5, 2)));
+
+        // Stopped, with only the flags: the tempo and position stand, the position unused.
+        transport.apply(TransportFlags::empty(), 0.0, 0.0, (0, 0));
+        assert_eq!(transport.host_time(), HostTime { tempo: Some(120.0), beat: None });
+        assert_eq!(transport.time_signature, (3, 4));
+    }
+
+    #[test]
+    fn advances_between_reports_while_playing() {
+        let mut transport = Transport::default();
+        transport.apply(reported(true), 120.0, 4.0, (4, 4));
+        transport.advance(24_000, 48_000.0);
+        assert_eq!(transport.beat, Some(5.0));
+
+        transport.apply(reported(false), 120.0, 4.0, (4, 4));
+        transport.advance(24_000, 48_000.0);
+        assert_eq!(transport.beat, Some(4.0));
+    }
+
+    #[test]
+    fn the_editor_sees_what_was_published() {
+        let shared = SharedTransport::default();
+        assert_eq!(shared.load(), Transport::default());
+
+        let mut transport = Transport::default();
+        transport.apply(reported(true) | TransportFlags::IS_RECORDING, 96.0, 7.25, (6, 8));
+        shared.publish(&transport);
+        assert_eq!(shared.load(), transport);
+    }
+}
@@ -0,0 +1,148 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// Set in `middle` while the middle slot holds a value the reader hasn't taken.
+const FRESH: u8 = 0b100;
+const INDEX: u8 = 0b011;
+
+/// Wait-free handoff of a value from one writer thread to one reader thread. Of three
+/// slots the writer owns one, the reader another, and the third sits in the middle. Each
+/// side swaps its slot with the middle one, so neither ever waits on the other and the
+/// reader always gets the newest whole value.
+///
+/// The two ends are separate handles, neither of them `Clone`, and each needs `&mut` to
+/// move a slot: there's only ever one writer and one reader, whichever threads they're
+/// sent to.
+pub fn triple_buffer<T: Copy>(initial: T) -> (Writer<T>, Reader<T>) {
+    let slots = Arc::new(Slots {
+        slots: [UnsafeCell::new(initial), UnsafeCell::new(initial), UnsafeCell::new(initial)],
+        middle: AtomicU8::new(1),
+    });
+    let writer = Writer { slots: slots.clone(), back: 0, working: initial };
+    (writer, Reader { slots, front: 2 })
+}
+
+/// What the two ends share.
+struct Slots<T> {
+    slots: [UnsafeCell<T>; 3],
+    /// Index of the middle slot, plus [`FRESH`] until the reader takes it.
+    middle: AtomicU8,
+}
+
+impl<T> Slots<T> {
+    fn has_unread(&self) -> bool {
+        self.middle.load(Ordering::Acquire) & FRESH != 0
+    }
+}
+
+/// The writing end of a [`triple_buffer`].
+pub struct Writer<T> {
+    slots: Arc<Slots<T>>,
+    /// Index of the slot this end owns.
+    back: u8,
+    /// The value built up between publishes.
+    working: T,
+}
+
+/// The reading end of a [`triple_buffer`].
+pub struct Reader<T> {
+    slots: Arc<Slots<T>>,
+    /// Index of the slot this end owns.
+    front: u8,
+}
+
+// SAFETY: each end only touches the slot it owns, which it holds by index, and ownership
+// passes through `middle` with acquire/release ordering. There's one of each end, and both
+// need `&mut` to touch a slot, so sending either to another thread can't race.
+unsafe impl<T: Send> Send for Writer<T> {}
+unsafe impl<T: Send> Send for Reader<T> {}
+
+impl<T: Copy> Writer<T> {
+    /// Hands `f` the working copy, and whether the reader has taken the last value
+    /// published, for writers that fold values together until it has. The copy is
+    /// published unless `f` returns false.
+    pub fn publish_with(&mut self, f: impl FnOnce(&mut T, bool) -> bool) {
+        let taken = !self.slots.has_unread();
+        if !f(&mut self.working, taken) {
+            return;
+        }
+        // SAFETY: the back slot belongs to this end until it's swapped into the middle.
+        unsafe { *self.slots.slots[self.back as usize].get() = self.working };
+        self.back = self.slots.middle.swap(self.back | FRESH, Ordering::AcqRel) & INDEX;
+    }
+}
+
+impl<T: Copy> Reader<T> {
+    /// The newest value published since the last call, if there is one.
+    pub fn read(&mut self) -> Option<T> {
+        // Only the reader clears FRESH, so once set it stays set until the swap below.
+        if !self.has_unread() {
+            return None;
+        }
+        self.front = self.slots.middle.swap(self.front, Ordering::AcqRel) & INDEX;
+        // SAFETY: the swap handed this end the middle slot, which the writer has finished
+        // with.
+        Some(unsafe { *self.slots.slots[self.front as usize].get() })
+    }
+
+    /// Whether a value has been published that hasn't been read yet.
+    pub fn has_unread(&self) -> bool {
+        self.slots.has_unread()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reader_gets_the_newest_value_once() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert_eq!(reader.read(), None);
+
+        writer.publish_with(|value, taken| {
+            assert!(taken);
+            *value = 1;
+            true
+        });
+        writer.publish_with(|value, taken| {
+            assert!(!taken, "the first value is still waiting");
+            *value += 1;
+            true
+        });
+        assert!(reader.has_unread());
+        assert_eq!(reader.read(), Some(2));
+        assert_eq!(reader.read(), None);
+
+        writer.publish_with(|_, _| false);
+        assert_eq!(reader.read(), None);
+    }
+
+    #[test]
+    fn values_cross_threads_whole_and_in_order() {
+        const WRITES: u64 = 100_000;
+        let (mut writer, mut reader) = triple_buffer([0u64; 16]);
+
+        let writing = std::thread::spawn(move || {
+            for n in 1..=WRITES {
+                writer.publish_with(|value, _| {
+                    *value = [n; 16];
+                    true
+                });
+            }
+        });
+
+        let mut last = 0;
+        while last < WRITES {
+            let Some(value) = reader.read() else {
+                std::hint::spin_loop();
+                continue;
+            };
+            assert!(value.iter().all(|&n| n == value[0]), "torn read: {value:?}");
+            assert!(value[0] > last, "went back from {last} to {}", value[0]);
+            last = value[0];
+        }
+        writing.join().unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Mutex;
+
+use crate::meter::{level_meter, BlockLevels, MeterReader, MeterWriter};
+use crate::sample::Sample;
+use crate::scope::ScopeBuffer;
+
+/// Audio-thread data for the editor's displays, handed over without waiting: a ring of
+/// recent output for the scope and spectrum, and the meter's levels through a triple
+/// buffer. The audio thread writes each block and never waits; the editor reads each
+/// frame, or not at all while it's closed, and the writes just overwrite. Everything is
+/// allocated here, up front, so nothing allocates once the plugin is running.
+pub struct VisChannel {
+    pub scope: ScopeBuffer,
+    /// Locked by the editor and the main thread's timer, never by the audio thread.
+    pub meter: Mutex<MeterReader>,
+    /// The meter's writing end while no processor holds it. A processor takes it when it's
+    /// built at activate and hands it back at deactivate, so the audio thread publishes
+    /// through its own and only the main thread ever locks this.
+    idle_meter: Mutex<Option<MeterWriter>>,
+}
+
+impl Default for VisChannel {
+    fn default() -> Self {
+        let (writer, reader) = level_meter();
+        Self {
+            scope: ScopeBuffer::default(),
+            meter: Mutex::new(reader),
+            idle_meter: Mutex::new(Some(writer)),
+        }
+    }
+}
+
+impl VisChannel {
+    /// Main thread, at activate. `None` if another processor already has it.
+    pub fn take_meter(&self) -> Option<MeterWriter> {
+        self.idle_meter.lock().ok()?.take()
+    }
+
+    /// Main thread, at deactivate.
+    pub fn return_meter(&self, meter: MeterWriter) {
+        if let Ok(mut idle) = self.idle_meter.lock() {
+            *idle = Some(meter);
+        }
+    }
+
+    /// Audio thread only: a block of the mono mix, and the levels of what went out, through
+    /// the processor's `meter` if it has one.
+    pub fn write(&self, meter: Option<&mut MeterWriter>, mix: &[Sample], levels: &BlockLevels) {
+        self.scope.write(mix);
+        if let Some(meter) = meter {
+            meter.update(levels);
+        }
+    }
+
+    /// Blanks the scope and the meter, so the editor doesn't sit on the last sound once
+    /// audio stops. From the audio thread with the processor's `meter`.
+    pub fn clear(&self, meter: Option<&mut MeterWriter>) {
+        self.scope.clear();
+        if let Some(meter) = meter {
+            meter.clear();
+        }
+    }
+
+    /// [`clear`](Self::clear) from the main thread, standing in for the audio thread as
+    /// writer while no processor is running: between deactivate and the next activate.
+    pub fn clear_idle(&self) {
+        if let Ok(mut idle) = self.idle_meter.lock() {
+            self.clear(idle.as_mut());
+        }
+    }
+}
@@ -0,0 +1,985 @@
+use crate::comb::CombFilter;
+use crate::envelope::{Envelope, EnvelopeSettings};
+use crate::filter::{FilterCoefficients, LowpassFilter};
+use crate::noise::ColoredNoise;
+use crate::pitch::{note_freq, semitones_to_ratio};
+use crate::pluck::PluckString;
+use crate::sample::Sample;
+
+/// Number of voices the pool is allocated with; the max voices param can lower the limit.
+pub const MAX_VOICES: usize = 32;
+
+/// Spare voices beyond [`MAX_VOICES`], where stolen notes fade out while the notes that
+/// stole them start.
+const STEAL_SLOTS: usize = 4;
+
+/// How long a stolen voice takes to fade out, and the note stealing it to fade in.
+pub const STEAL_FADE: f32 = 0.003; // seconds
+
+/// Most copies of the square wave a unison note plays.
+pub const MAX_UNISON: usize = 8;
+
+/// Oscillator types, indexed by the waveform param.
+pub const WAVEFORM_NAMES: &[&str] = &["Square", "Pluck", "Noise"];
+pub const WAVEFORM_PLUCK: usize = 1;
+pub const WAVEFORM_NOISE: usize = 2;
+/// What the block renderer plays for the square wave: alone, or as unison copies.
+#[cfg(any(feature = "simd-voices", test))]
+const WAVEFORM_SQUARE: usize = 0;
+#[cfg(any(feature = "simd-voices", test))]
+const WAVEFORM_UNISON: usize = WAVEFORM_NAMES.len();
+
+/// Per-voice output level before the master gain, so a full chord doesn't clip.
+const VOICE_LEVEL: f32 = 0.1;
+
+/// Samples each stage of the block renderer runs over at a time: its buffers live on the
+/// stack.
+#[cfg(any(feature = "simd-voices", test))]
+const RENDER_BLOCK: usize = 64;
+
+/// Semitones either side of middle C that a full key-to-pan amount spreads hard left and
+/// right.
+const KEY_PAN_SPAN: f32 = 48.0;
+const KEY_PAN_CENTER: u8 = 60;
+
+/// Smallest tuning change, in semitones, worth telling the host about.
+const TUNING_REPORT_STEP: f32 = 0.05;
+
+/// Pan position for `note`, from -1.0 (left) to 1.0 (right). A positive `amount` puts low
+/// notes on the left and high ones on the right, a negative one the other way round.
+pub fn key_pan(note: u8, amount: f32) -> f32 {
+    let offset = (note as f32 - KEY_PAN_CENTER as f32) / KEY_PAN_SPAN;
+    (offset * amount).clamp(-1.0, 1.0)
+}
+
+/// Per-note settings, sampled from the note and the params when a voice starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VoiceSettings {
+    /// 0.0 to 1.0.
+    pub velocity: f32,
+    pub waveform: usize,
+    /// Pitch envelope depth in semitones. Positive sweeps down onto the note, negative up.
+    pub pitch_env_amount: f32,
+    /// Decay time of the pitch envelope, in seconds.
+    pub pitch_env_decay: f32,
+    pub amp_env: EnvelopeSettings,
+    /// Key-to-pan amount, -1.0 to 1.0; see [`key_pan`].
+    pub key_to_pan: f32,
+    /// Copies of the square wave, up to [`MAX_UNISON`]. 0 or 1 plays just the one.
+    pub unison: usize,
+    /// Semitones the outermost copies sit either side of the note.
+    pub unison_detune: f32,
+}
+
+/// Per-block values every voice renders with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderParams {
+    pub sample_rate: f32,
+    /// Block-rate amp modulation, as a gain factor. The master gain comes later, on the mix.
+    pub amp: f32,
+    /// Block-rate pitch modulation, as a frequency multiplier.
+    pub pitch_ratio: f32,
+    /// Comb filter wet/dry, 0.0 bypasses it.
+    pub comb_mix: f32,
+    pub comb_feedback: f32,
+    /// Brightness of the plucked string's damping, 0.0 to 1.0.
+    pub pluck_tone: f32,
+    /// Spectral tilt of the noise oscillator, red (-1.0) to blue (1.0).
+    pub noise_color: f32,
+    pub cutoff: f32, // Hz
+    pub resonance: f32,
+    /// How far a full-velocity note opens the cutoff, in semitones.
+    pub vel_to_cutoff: f32,
+}
+
+/// Where voices add themselves without their combs, mix then side signal: the dry signal
+/// the FX mix crossfades the effects with.
+pub type DryBuffers<'a> = (&'a mut [Sample], &'a mut [Sample]);
+
+#[derive(Clone, Default)]
+pub struct Voice {
+    /// Key the host played; note-offs are matched against this.
+    key: u8,
+    /// Sounding, including a release tail after the key went up.
+    active: bool,
+    /// The key is still down.
+    held: bool,
+    /// Taken over by a newer note, and fading out to make way for it.
+    stolen: bool,
+    /// Gain of the steal crossfade, 0.0 to 1.0, and how far it moves each sample.
+    fade: f32,
+    fade_step: f32,
+    /// Which oscillator plays, indexing [`WAVEFORM_NAMES`].
+    waveform: usize,
+    phase: f32,     // 0.0 to 1.0
+    frequency: f32, // Hz
+    /// Start order, used to pick the oldest voice when stealing.
+    age: u64,
+    velocity: f32, // 0.0 to 1.0
+    /// -1.0 (left) to 1.0 (right), from the note when it started.
+    pan: f32,
+    /// Square-wave copies sounding, each with its own phase, tuning (as a frequency
+    /// multiplier) and pan, key pan included. 1 plays off `phase` alone.
+    unison: usize,
+    unison_phases: [f32; MAX_UNISON],
+    unison_ratios: [f32; MAX_UNISON],
+    unison_pans: [Sample; MAX_UNISON],
+    /// Filters the copies' side signal as `filter` does the mix.
+    side_filter: LowpassFilter,
+    amp_env: Envelope,
+    pitch_env: Envelope,
+    pitch_env_amount: f32, // semitones
+    /// Tuning last reported to the host, in semitones off the note. `None` until reported.
+    reported_tuning: Option<f32>,
+    /// Tuned to `frequency`, so the resonance follows the note.
+    comb: CombFilter,
+    filter: LowpassFilter,
+    /// Filters the voice as it would sound without its comb, for the FX mix's dry signal.
+    /// Kept level with `filter` while nothing asks for it, so it picks up without a click.
+    dry_filter: LowpassFilter,
+    string: PluckString,
+    noise: ColoredNoise,
+}
+
+impl Voice {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Adds this voice's output to `buffer`, and its output scaled by its pan to `side`,
+    /// going idle once its amp envelope ends. With `dry`, adds the same again with the comb
+    /// left out to that pair of buffers.
+    pub fn render_add(
+        &mut self,
+        buffer: &mut [Sample],
+        side: &mut [Sample],
+        dry: Option<DryBuffers>,
+        render: &RenderParams,
+    ) {
+        let dry_on = dry.is_some();
+        #[cfg(feature = "simd-voices")]
+        self.render_add_blocks(buffer, side, dry, render);
+        #[cfg(not(feature = "simd-voices"))]
+        self.render_add_scalar(buffer, side, dry, render);
+        if !dry_on {
+            self.dry_filter.clone_from(&self.filter);
+        }
+
+        if self.amp_env.is_idle() || (self.stolen && self.fade == 0.0) {
+            self.active = false;
+        }
+    }
+
+    /// One sample at a time, every stage in turn: the reference the block renderer is
+    /// held to.
+    #[cfg(any(not(feature = "simd-voices"), test))]
+    fn render_add_scalar(
+        &mut self,
+        buffer: &mut [Sample],
+        side: &mut [Sample],
+        mut dry: Option<DryBuffers>,
+        render: &RenderParams,
+    ) {
+        let RenderParams {
+            sample_rate,
+            amp,
+            pitch_ratio,
+            comb_mix,
+            comb_feedback,
+            pluck_tone,
+            noise_color,
+            cutoff,
+            resonance,
+            vel_to_cutoff,
+        } = *render;
+        let phase_step = self.frequency * pitch_ratio / sample_rate;
+        let comb_frequency = self.frequency * pitch_ratio;
+        // Per voice, so harder-hit notes come out brighter. `None` while fully open.
+        let cutoff = cutoff * semitones_to_ratio(vel_to_cutoff * self.velocity);
+        let filter = FilterCoefficients::for_params(cutoff, resonance, sample_rate);
+        let pan = Sample::from(self.pan);
+
+        for (i, (sample, side)) in buffer.iter_mut().zip(side.iter_mut()).enumerate() {
+            let pitch_env = self.pitch_env.next(sample_rate);
+            let step = if pitch_env == 0.0 {
+                phase_step
+            } else {
+                phase_step * semitones_to_ratio(pitch_env * self.pitch_env_amount)
+            };
+            self.phase += step;
+            if self.phase > 1.0 { self.phase -= 1.0; }
+            // Unison copies bring their own side signal rather than the voice's pan.
+            let (raw, spread): (Sample, _) = match self.waveform {
+                // The string is as long as the modulated period, pitch envelope and all.
+                WAVEFORM_PLUCK => (self.string.next(pluck_tone, 1.0 / step), None),
+                WAVEFORM_NOISE => (self.noise.next(noise_color), None),
+                _ if self.unison > 1 => {
+                    let (mid, spread) = self.unison_next(step);
+                    (mid, Some(spread))
+                }
+                _ if self.phase < 0.5 => (1.0, None),
+                _ => (-1.0, None),
+            };
+            let dry_raw = raw;
+            // Before the amp envelope, which shapes the resonance along with the tone.
+            let raw = self.comb.process(raw, comb_frequency, sample_rate, comb_feedback, comb_mix);
+            let raw = match &filter {
+                Some(coefficients) => self.filter.process(raw, coefficients),
+                None => raw,
+            };
+            let level = self.amp_env.next(sample_rate) * amp * VOICE_LEVEL * self.fade;
+            self.fade = (self.fade + self.fade_step).clamp(0.0, 1.0);
+            let out = raw * Sample::from(level);
+            *sample += out;
+            let spread = match (spread, &filter) {
+                // The spread goes round the comb, whose resonance stays in the middle.
+                (Some(spread), Some(coefficients)) => {
+                    Some(self.side_filter.process(spread, coefficients) * Sample::from(level))
+                }
+                (Some(spread), None) => Some(spread * Sample::from(level)),
+                (None, _) => None,
+            };
+            *side += spread.unwrap_or(out * pan);
+            if let Some((dry_mix, dry_side)) = &mut dry {
+                let dry_raw = match &filter {
+                    Some(coefficients) => self.dry_filter.process(dry_raw, coefficients),
+                    None => dry_raw,
+                };
+                let dry_out = dry_raw * Sample::from(level);
+                dry_mix[i] += dry_out;
+                dry_side[i] += spread.unwrap_or(dry_out * pan);
+            }
+        }
+    }
+
+    /// [`render_add_scalar`](Self::render_add_scalar) a stage at a time over blocks of
+    /// [`RENDER_BLOCK`]. Each stage is its own loop with its branches hoisted out, so the
+    /// ones that carry nothing from sample to sample (the square wave, the level and the
+    /// mix) can vectorize. The rest (the phase, the pluck and noise, the comb, the filter
+    /// and the envelopes) carry state from one sample to the next and stay serial, and the
+    /// voice's state is laid out as before, one voice at a time. Every stage takes the
+    /// same inputs in the same order as in the scalar loop, so the output is the same.
+    #[cfg(any(feature = "simd-voices", test))]
+    fn render_add_blocks(
+        &mut self,
+        buffer: &mut [Sample],
+        side: &mut [Sample],
+        mut dry: Option<DryBuffers>,
+        render: &RenderParams,
+    ) {
+        let RenderParams {
+            sample_rate,
+            amp,
+            pitch_ratio,
+            comb_mix,
+            comb_feedback,
+            pluck_tone,
+            noise_color,
+            cutoff,
+            resonance,
+            vel_to_cutoff,
+        } = *render;
+        let phase_step = self.frequency * pitch_ratio / sample_rate;
+        let comb_frequency = self.frequency * pitch_ratio;
+        let cutoff = cutoff * semitones_to_ratio(vel_to_cutoff * self.velocity);
+        let filter = FilterCoefficients::for_params(cutoff, resonance, sample_rate);
+        let pan = Sample::from(self.pan);
+        let oscillator = match self.waveform {
+            WAVEFORM_PLUCK | WAVEFORM_NOISE => self.waveform,
+            _ if self.unison > 1 => WAVEFORM_UNISON,
+            _ => WAVEFORM_SQUARE,
+        };
+
+        let blocks = buffer.chunks_mut(RENDER_BLOCK).zip(side.chunks_mut(RENDER_BLOCK));
+        for (block, (buffer, side)) in blocks.enumerate() {
+            let frames = buffer.len();
+            let mut steps = [0.0; RENDER_BLOCK];
+            let mut phases = [0.0; RENDER_BLOCK];
+            let mut raw: [Sample; RENDER_BLOCK] = [0.0; RENDER_BLOCK];
+            let mut dry_raw: [Sample; RENDER_BLOCK] = [0.0; RENDER_BLOCK];
+            let mut spread: [Sample; RENDER_BLOCK] = [0.0; RENDER_BLOCK];
+            let mut levels = [0.0; RENDER_BLOCK];
+            let (steps, phases) = (&mut steps[..frames], &mut phases[..frames]);
+            let (raw, dry_raw) = (&mut raw[..frames], &mut dry_raw[..frames]);
+            let spread = &mut spread[..frames];
+            let levels = &mut levels[..frames];
+
+            // A settled pitch envelope leaves every step the same.
+            if self.pitch_env.is_idle() && self.pitch_env.level() == 0.0 {
+                steps.fill(phase_step);
+            } else {
+                for step in steps.iter_mut() {
+                    let pitch_env = self.pitch_env.next(sample_rate);
+                    *step = if pitch_env == 0.0 {
+                        phase_step
+                    } else {
+                        phase_step * semitones_to_ratio(pitch_env * self.pitch_env_amount)
+                    };
+                }
+            }
+            // The voice's own phase runs whatever the waveform, as in the scalar loop.
+            for (phase, &step) in phases.iter_mut().zip(steps.iter()) {
+                self.phase += step;
+                self.phase -= if self.phase > 1.0 { 1.0 } else { 0.0 };
+                *phase = self.phase;
+            }
+
+            match oscillator {
+                WAVEFORM_PLUCK => {
+                    for (raw, &step) in raw.iter_mut().zip(&*steps) {
+                        *raw = self.string.next(pluck_tone, 1.0 / step);
+                    }
+                }
+                WAVEFORM_NOISE => raw.fill_with(|| self.noise.next(noise_color)),
+                WAVEFORM_UNISON => {
+                    for (i, &step) in steps.iter().enumerate() {
+                        (raw[i], spread[i]) = self.unison_next(step);
+                    }
+                }
+                _ => {
+                    for (raw, &phase) in raw.iter_mut().zip(&*phases) {
+                        *raw = if phase < 0.5 { 1.0 } else { -1.0 };
+                    }
+                }
+            }
+
+            if dry.is_some() {
+                dry_raw.copy_from_slice(raw);
+            }
+            // A zero mix leaves the comb alone, as `CombFilter::process` would.
+            if comb_mix > 0.0 {
+                let comb = &mut self.comb;
+                for raw in raw.iter_mut() {
+                    *raw = comb.process(*raw, comb_frequency, sample_rate, comb_feedback, comb_mix);
+                }
+            }
+            if let Some(coefficients) = &filter {
+                raw.iter_mut().for_each(|raw| *raw = self.filter.process(*raw, coefficients));
+                if oscillator == WAVEFORM_UNISON {
+                    for spread in spread.iter_mut() {
+                        *spread = self.side_filter.process(*spread, coefficients);
+                    }
+                }
+                if dry.is_some() {
+                    for raw in dry_raw.iter_mut() {
+                        *raw = self.dry_filter.process(*raw, coefficients);
+                    }
+                }
+            }
+
+            for level in levels.iter_mut() {
+                *level = self.amp_env.next(sample_rate) * amp * VOICE_LEVEL * self.fade;
+                self.fade = (self.fade + self.fade_step).clamp(0.0, 1.0);
+            }
+
+            for (i, (sample, side)) in buffer.iter_mut().zip(side.iter_mut()).enumerate() {
+                let level = Sample::from(levels[i]);
+                let out = raw[i] * level;
+                *sample += out;
+                *side += if oscillator == WAVEFORM_UNISON { spread[i] * level } else { out * pan };
+            }
+            if let Some((dry_mix, dry_side)) = &mut dry {
+                let at = block * RENDER_BLOCK;
+                let outputs = dry_mix[at..].iter_mut().zip(&mut dry_side[at..]);
+                for (i, (sample, side)) in outputs.take(frames).enumerate() {
+                    let level = Sample::from(levels[i]);
+                    let out = dry_raw[i] * level;
+                    *sample += out;
+                    // The spread skips the comb, so it's the same dry as wet.
+                    let unison = oscillator == WAVEFORM_UNISON;
+                    *side += if unison { spread[i] * level } else { out * pan };
+                }
+            }
+        }
+    }
+
+    /// The unison copies' next square-wave samples summed, and summed by pan for the side
+    /// signal. Each copy is at 1/sqrt(n): detuned, they drift apart and add up in power, so
+    /// any number of copies comes out about as loud as one.
+    fn unison_next(&mut self, step: f32) -> (Sample, Sample) {
+        let gain = 1.0 / (self.unison as Sample).sqrt();
+        let copies = self.unison_phases.iter_mut().zip(&self.unison_ratios).zip(&self.unison_pans);
+        let (mut mid, mut spread) = (0.0, 0.0);
+        for ((phase, ratio), pan) in copies.take(self.unison) {
+            *phase += step * ratio;
+            if *phase > 1.0 { *phase -= 1.0; }
+            let square = if *phase < 0.5 { gain } else { -gain };
+            mid += square;
+            spread += square * pan;
+        }
+        (mid, spread)
+    }
+}
+
+pub struct VoicePool {
+    voices: [Voice; MAX_VOICES + STEAL_SLOTS],
+    /// Voices allowed to sound at once, at most [`MAX_VOICES`].
+    limit: usize,
+    next_age: u64,
+    sample_rate: f32,
+}
+
+impl VoicePool {
+    /// Allocates every voice's delay lines for `sample_rate`, so call it off the audio
+    /// thread.
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            voices: std::array::from_fn(|index| Voice {
+                comb: CombFilter::new(sample_rate),
+                string: PluckString::new(sample_rate),
+                noise: ColoredNoise::for_voice(index),
+                ..Voice::default()
+            }),
+            limit: MAX_VOICES,
+            next_age: 0,
+            sample_rate,
+        }
+    }
+
+    /// Changes how many voices may sound at once and returns whether it changed. Lowering
+    /// it releases the oldest held voices over the new limit, so they fade out rather than
+    /// cut off; their release tails still count until they end.
+    pub fn set_limit(&mut self, limit: usize) -> bool {
+        let limit = limit.clamp(1, MAX_VOICES);
+        if limit == self.limit {
+            return false;
+        }
+        self.limit = limit;
+
+        while self.held_voices() > limit {
+            let oldest = self
+                .voices
+                .iter_mut()
+                .filter(|v| v.active && v.held)
+                .min_by_key(|v| v.age);
+            if let Some(voice) = oldest {
+                voice.held = false;
+                voice.amp_env.release();
+            }
+        }
+        true
+    }
+
+    /// Starts `note` on a free voice, stealing the oldest one if the limit is reached, and
+    /// returns whether it had to steal. `key` is the key that triggered it, which can
+    /// differ from `note` for chord tones.
+    ///
+    /// A stolen voice fades out over [`STEAL_FADE`] in a spare slot while the new note
+    /// fades in, rather than cutting off with a click. Only when every spare slot is still
+    /// fading does the new note take over the stolen voice outright.
+    pub fn note_on(&mut self, key: u8, note: u8, settings: VoiceSettings) -> bool {
+        let stealing = self.active_count() >= self.limit;
+        let index = if stealing {
+            let oldest = self.oldest_voice();
+            let free = self.voices.iter().position(|v| !v.active);
+            if free.is_some() {
+                let voice = &mut self.voices[oldest];
+                voice.held = false;
+                voice.stolen = true;
+                voice.fade_step = -1.0 / (STEAL_FADE * self.sample_rate);
+            }
+            free.unwrap_or(oldest)
+        } else {
+            self.voices.iter().position(|v| !v.active).unwrap_or_else(|| self.oldest_voice())
+        };
+
+        // Reset in place: the voice keeps its comb's delay line.
+        let voice = &mut self.voices[index];
+        voice.key = key;
+        voice.active = true;
+        voice.held = true;
+        voice.stolen = false;
+        (voice.fade, voice.fade_step) = if stealing {
+            (0.0, 1.0 / (STEAL_FADE * self.sample_rate))
+        } else {
+            (1.0, 0.0)
+        };
+        voice.phase = 0.0;
+        voice.frequency = note_freq(note);
+        voice.age = self.next_age;
+        voice.velocity = settings.velocity;
+        voice.pan = key_pan(note, settings.key_to_pan);
+        voice.unison = match settings.waveform {
+            WAVEFORM_PLUCK | WAVEFORM_NOISE => 1,
+            _ => settings.unison.clamp(1, MAX_UNISON),
+        };
+        let copies = voice.unison_phases.iter_mut().zip(&mut voice.unison_ratios);
+        for (i, ((phase, ratio), pan)) in copies.zip(&mut voice.unison_pans).enumerate() {
+            // Evenly across the detune and the stereo field, the first copy lowest and
+            // furthest left.
+            let position = if voice.unison > 1 {
+                2.0 * i as f32 / (voice.unison - 1) as f32 - 1.0
+            } else {
+                0.0
+            };
+            *phase = 0.0;
+            *ratio = semitones_to_ratio(position * settings.unison_detune);
+            *pan = Sample::from((voice.pan + position).clamp(-1.0, 1.0));
+        }
+        voice.amp_env = Envelope::default();
+        voice.pitch_env = Envelope::default();
+        voice.pitch_env_amount = settings.pitch_env_amount;
+        voice.reported_tuning = None;
+        voice.comb.clear();
+        voice.filter.clear();
+        voice.dry_filter.clear();
+        voice.side_filter.clear();
+        voice.waveform = settings.waveform;
+        match voice.waveform {
+            WAVEFORM_PLUCK => voice.string.pluck(),
+            WAVEFORM_NOISE => voice.noise.clear(),
+            _ => {}
+        }
+        voice.amp_env.trigger(settings.amp_env);
+        if settings.pitch_env_amount != 0.0 {
+            // Instant attack, then a sweep back to the played pitch.
+            voice.pitch_env.trigger(EnvelopeSettings::gate(0.0, 0.0, settings.pitch_env_decay));
+        }
+        self.next_age += 1;
+        stealing
+    }
+
+    /// Releases every voice `key` holds; they keep sounding until their envelope ends.
+    pub fn note_off(&mut self, key: u8) {
+        for voice in self.voices.iter_mut().filter(|v| v.held && v.key == key) {
+            voice.held = false;
+            voice.amp_env.release();
+        }
+    }
+
+    /// Releases every held voice, as if every key went up.
+    pub fn release_all(&mut self) {
+        for voice in self.voices.iter_mut().filter(|v| v.held) {
+            voice.held = false;
+            voice.amp_env.release();
+        }
+    }
+
+    /// Fades every voice out over [`STEAL_FADE`], the way a stolen one goes: quick enough to
+    /// stop everything at once, slow enough not to click. Held keys are let go.
+    pub fn fade_out_all(&mut self) {
+        let step = -1.0 / (STEAL_FADE * self.sample_rate);
+        for voice in self.voices.iter_mut().filter(|v| v.active) {
+            voice.held = false;
+            voice.stolen = true;
+            voice.fade_step = step;
+        }
+    }
+
+    /// Cuts every voice dead, with no release tail or steal fade, and empties its combs,
+    /// filter and string, so nothing rings on. Allocates nothing.
+    pub fn reset(&mut self) {
+        for voice in &mut self.voices {
+            voice.active = false;
+            voice.held = false;
+            voice.stolen = false;
+            voice.amp_env = Envelope::default();
+            voice.pitch_env = Envelope::default();
+            voice.reported_tuning = None;
+            voice.comb.clear();
+            voice.filter.clear();
+            voice.dry_filter.clear();
+            voice.side_filter.clear();
+            voice.string.clear();
+            voice.noise.clear();
+        }
+    }
+
+    /// Voices still sounding, release tails included. Stolen ones fading out don't count.
+    pub fn active_count(&self) -> usize {
+        self.voices.iter().filter(|v| v.active && !v.stolen).count()
+    }
+
+    /// Silences every voice's comb, so one switched back on doesn't ring with an old tail.
+    pub fn clear_combs(&mut self) {
+        for voice in &mut self.voices {
+            voice.comb.clear();
+        }
+    }
+
+    pub fn voices_mut(&mut self) -> &mut [Voice] {
+        &mut self.voices
+    }
+
+    /// Keys still held down, once per voice they hold.
+    pub fn held_keys(&self) -> impl Iterator<Item = u8> + '_ {
+        self.voices.iter().filter(|v| v.active && v.held).map(|v| v.key)
+    }
+
+    /// Voices whose key is still down. Release tails don't count.
+    #[cfg(test)]
+    pub fn held_count(&self) -> usize {
+        self.held_voices()
+    }
+
+    /// Hands `f` each sounding key whose tuning, in semitones off its note, has moved since
+    /// it was last reported: the pitch envelope's current offset plus `pitch_mod`, the
+    /// block's modulation. Once per key, however many voices it plays; they share a tuning.
+    pub fn tuning_changes(&mut self, pitch_mod: f32, mut f: impl FnMut(u8, f32)) {
+        let mut reported = [false; 128];
+        for voice in self.voices.iter_mut().filter(|v| v.active) {
+            let tuning = voice.pitch_env.level() * voice.pitch_env_amount + pitch_mod;
+            let moved = match voice.reported_tuning {
+                Some(last) => (tuning - last).abs() >= TUNING_REPORT_STEP,
+                None => tuning != 0.0,
+            };
+            if !moved {
+                continue;
+            }
+            voice.reported_tuning = Some(tuning);
+            if let Some(reported) = reported.get_mut(voice.key as usize) {
+                if !std::mem::replace(reported, true) {
+                    f(voice.key, tuning);
+                }
+            }
+        }
+    }
+
+    /// Whether any sounding voice is off centre, so the side signal has anything in it.
+    pub fn any_panned(&self) -> bool {
+        self.voices.iter().any(|v| v.active && (v.pan != 0.0 || v.unison > 1))
+    }
+
+    fn held_voices(&self) -> usize {
+        self.voices.iter().filter(|v| v.active && v.held).count()
+    }
+
+    /// Mixes every active voice into `buffer` and their panned side signal into `side`
+    /// (see [`Voice::render_add`]), overwriting whatever was in both.
+    pub fn render(&mut self, buffer: &mut [Sample], side: &mut [Sample], render: &RenderParams) {
+        self.render_with_dry(buffer, side, None, render);
+    }
+
+    /// [`render`](Self::render), and with `dry` the voices without their combs too.
+    pub fn render_with_dry(
+        &mut self,
+        buffer: &mut [Sample],
+        side: &mut [Sample],
+        mut dry: Option<DryBuffers>,
+        render: &RenderParams,
+    ) {
+        buffer.fill(0.0);
+        side.fill(0.0);
+        if let Some((dry_mix, dry_side)) = &mut dry {
+            dry_mix.fill(0.0);
+            dry_side.fill(0.0);
+        }
+        for voice in self.voices.iter_mut().filter(|v| v.active) {
+            let dry = dry.as_mut().map(|(mix, side)| (&mut **mix, &mut **side));
+            voice.render_add(buffer, side, dry, render);
+        }
+    }
+
+    fn oldest_voice(&self) -> usize {
+        self.voices
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.active && !v.stolen)
+            .min_by_key(|(_, v)| v.age)
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::Stage;
+    use crate::filter::MAX_CUTOFF;
+
+    #[test]
+    fn full_pool_steals_the_oldest_voice() {
+        let mut pool = VoicePool::new(48000.0);
+        for key in 0..MAX_VOICES as u8 {
+            assert!(!pool.note_on(key, key, VoiceSettings::default()));
+        }
+        assert_eq!(pool.held_count(), MAX_VOICES);
+
+        assert!(pool.note_on(100, 100, VoiceSettings::default()));
+        assert_eq!(pool.held_count(), MAX_VOICES);
+
+        // Key 0 was stolen, so releasing it leaves the pool full.
+        pool.note_off(0);
+        assert_eq!(pool.held_count(), MAX_VOICES);
+        pool.note_off(100);
+        assert_eq!(pool.held_count(), MAX_VOICES - 1);
+    }
+
+    #[test]
+    fn release_all_lets_go_of_every_key() {
+        let mut pool = VoicePool::new(48000.0);
+        for key in [36, 60, 60, 84] {
+            pool.note_on(key, key, VoiceSettings::default());
+        }
+        pool.release_all();
+        assert_eq!(pool.held_count(), 0);
+        // Released, not cut: the tails still sound.
+        assert_eq!(pool.active_count(), 4);
+    }
+
+    #[test]
+    fn stealing_crossfades_instead_of_clicking() {
+        let mut pool = VoicePool::new(48000.0);
+        pool.set_limit(1);
+        let amp_env = EnvelopeSettings { sustain: 1.0, ..EnvelopeSettings::default() };
+        let settings = VoiceSettings { velocity: 1.0, amp_env, ..VoiceSettings::default() };
+        // A low cutoff rounds the square off, so any jump left is the steal's.
+        let render = RenderParams {
+            sample_rate: 48000.0,
+            amp: 1.0,
+            pitch_ratio: 1.0,
+            comb_mix: 0.0,
+            comb_feedback: 0.0,
+            pluck_tone: 0.0,
+            noise_color: 0.0,
+            cutoff: 200.0,
+            resonance: 0.0,
+            vel_to_cutoff: 0.0,
+        };
+        let max_step = |buffer: &[Sample]| {
+            buffer.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, Sample::max)
+        };
+        let (mut buffer, mut side) = (vec![0.0; 4800], vec![0.0; 4800]);
+
+        pool.note_on(45, 45, settings);
+        pool.render(&mut buffer, &mut side, &render);
+        let steady = max_step(&buffer[2400..]);
+        let before = buffer[4799];
+
+        assert!(pool.note_on(57, 57, settings));
+        assert_eq!(pool.active_count(), 1);
+        pool.render(&mut buffer, &mut side, &render);
+        let across = max_step(&[before, buffer[0]]).max(max_step(&buffer[..480]));
+        assert!(across <= 2.0 * steady, "steal jumped {across}, steady {steady}");
+    }
+
+    #[test]
+    fn block_render_matches_scalar() {
+        let settings = |waveform, unison| VoiceSettings {
+            velocity: 0.8,
+            waveform,
+            pitch_env_amount: 12.0,
+            pitch_env_decay: 0.01,
+            amp_env: EnvelopeSettings::adsr(0.001, 0.01, 0.5, 0.01),
+            key_to_pan: 0.5,
+            unison,
+            unison_detune: 0.3,
+        };
+        let render = RenderParams {
+            sample_rate: 48000.0,
+            amp: 1.0,
+            pitch_ratio: 1.0,
+            comb_mix: 0.5,
+            comb_feedback: 0.8,
+            pluck_tone: 0.5,
+            noise_color: 0.3,
+            cutoff: 2000.0,
+            resonance: 0.5,
+            vel_to_cutoff: 12.0,
+        };
+
+        for (waveform, unison) in [(0, 1), (0, 4), (WAVEFORM_PLUCK, 1), (WAVEFORM_NOISE, 1)] {
+            let mut pool = VoicePool::new(48000.0);
+            pool.note_on(45, 45, settings(waveform, unison));
+            let mut scalar = pool.voices[0].clone();
+            let mut blocks = pool.voices[0].clone();
+            // Not a whole number of blocks, and on into the release.
+            for _ in 0..2 {
+                let mut expected = [(); 4].map(|_| vec![0.0; 1000]);
+                let mut rendered = [(); 4].map(|_| vec![0.0; 1000]);
+                let [buffer, side, dry_mix, dry_side] = &mut expected;
+                scalar.render_add_scalar(buffer, side, Some((dry_mix, dry_side)), &render);
+                let [buffer, side, dry_mix, dry_side] = &mut rendered;
+                blocks.render_add_blocks(buffer, side, Some((dry_mix, dry_side)), &render);
+                let pairs = expected.iter().flatten().zip(rendered.iter().flatten());
+                for (expected, sample) in pairs {
+                    assert!((expected - sample).abs() <= 1e-6, "waveform {waveform}");
+                }
+                scalar.amp_env.release();
+                blocks.amp_env.release();
+            }
+        }
+    }
+
+    #[test]
+    fn each_voice_runs_its_own_envelope() {
+        let sample_rate = 48000.0;
+        let mut pool = VoicePool::new(sample_rate);
+        let amp_env = EnvelopeSettings::adsr(0.1, 0.0, 1.0, 0.5);
+        let settings = VoiceSettings { velocity: 1.0, amp_env, ..VoiceSettings::default() };
+        let render = RenderParams {
+            sample_rate,
+            amp: 1.0,
+            pitch_ratio: 1.0,
+            comb_mix: 0.0,
+            comb_feedback: 0.0,
+            pluck_tone: 0.0,
+            noise_color: 0.0,
+            cutoff: MAX_CUTOFF,
+            resonance: 0.0,
+            vel_to_cutoff: 0.0,
+        };
+        let (mut buffer, mut side) = (vec![0.0; 4800], vec![0.0; 4800]);
+        let amp_env = |pool: &VoicePool, key| {
+            let voice = pool.voices.iter().find(|v| v.active && v.key == key).unwrap();
+            (voice.amp_env.stage(), voice.amp_env.level())
+        };
+
+        // The first note through its attack and let go just as the second starts.
+        pool.note_on(60, 60, settings);
+        pool.render(&mut buffer, &mut side, &render);
+        pool.note_off(60);
+        pool.note_on(64, 64, settings);
+        pool.render(&mut buffer[..480], &mut side[..480], &render);
+
+        let (stage, level) = amp_env(&pool, 60);
+        assert_eq!(stage, Stage::Release);
+        assert!((level - 0.98).abs() < 1e-3, "{level}");
+        let (stage, level) = amp_env(&pool, 64);
+        assert_eq!(stage, Stage::Attack);
+        assert!((level - 0.1).abs() < 1e-3, "{level}");
+    }
+
+    #[test]
+    fn lowering_the_limit_releases_the_oldest_voices() {
+        let mut pool = VoicePool::new(48000.0);
+        for key in 0..4 {
+            pool.note_on(key, key, VoiceSettings::default());
+        }
+
+        assert!(pool.set_limit(2));
+        assert!(!pool.set_limit(2));
+        assert_eq!(pool.held_count(), 2);
+        // Keys 0 and 1 were released, so letting go of them changes nothing.
+        pool.note_off(0);
+        pool.note_off(1);
+        assert_eq!(pool.held_count(), 2);
+
+        // The released voices are still in their tails, which count against the limit.
+        assert!(pool.note_on(10, 10, VoiceSettings::default()));
+        assert_eq!(pool.active_count(), 4);
+    }
+
+    #[test]
+    fn velocity_opens_the_filter_per_voice() {
+        // Energy of the sample-to-sample differences, which weighs the highs.
+        let brightness = |velocity: f32, vel_to_cutoff: f32| -> Sample {
+            let mut pool = VoicePool::new(48000.0);
+            let amp_env = EnvelopeSettings { sustain: 1.0, ..EnvelopeSettings::default() };
+            pool.note_on(60, 60, VoiceSettings { velocity, amp_env, ..VoiceSettings::default() });
+            let render = RenderParams {
+                sample_rate: 48000.0,
+                amp: 1.0,
+                pitch_ratio: 1.0,
+                comb_mix: 0.0,
+                comb_feedback: 0.0,
+                pluck_tone: 0.0,
+                noise_color: 0.0,
+                cutoff: 300.0,
+                resonance: 0.0,
+                vel_to_cutoff,
+            };
+            let (mut buffer, mut side) = (vec![0.0; 4800], vec![0.0; 4800]);
+            pool.render(&mut buffer, &mut side, &render);
+            buffer.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum()
+        };
+
+        assert!(brightness(1.0, 36.0) > 2.0 * brightness(0.2, 36.0));
+        assert_eq!(brightness(1.0, 0.0), brightness(0.2, 0.0));
+    }
+
+    #[test]
+    fn key_to_pan_spreads_notes_across_the_stereo_field() {
+        assert_eq!(key_pan(60, 1.0), 0.0);
+        assert!(key_pan(36, 1.0) < 0.0 && key_pan(84, 1.0) > 0.0);
+        assert_eq!(key_pan(84, -1.0), -key_pan(84, 1.0));
+        assert_eq!(key_pan(0, 1.0), -1.0);
+        assert_eq!(key_pan(84, 0.0), 0.0);
+    }
+
+    #[test]
+    fn tuning_is_reported_once_per_key_as_it_moves() {
+        let mut pool = VoicePool::new(48000.0);
+        // A chord tone on the same key shares its tuning.
+        pool.note_on(60, 60, VoiceSettings::default());
+        pool.note_on(60, 64, VoiceSettings::default());
+        let changes = |pool: &mut VoicePool, pitch_mod: f32| {
+            let mut changes = Vec::new();
+            pool.tuning_changes(pitch_mod, |key, tuning| changes.push((key, tuning)));
+            changes
+        };
+
+        assert_eq!(changes(&mut pool, 0.0), []);
+        assert_eq!(changes(&mut pool, 2.0), [(60, 2.0)]);
+        assert_eq!(changes(&mut pool, 2.0), []);
+        assert_eq!(changes(&mut pool, 2.01), []);
+        assert_eq!(changes(&mut pool, 1.0), [(60, 1.0)]);
+    }
+
+    #[test]
+    fn side_signal_follows_the_voice_pan() {
+        let mut pool = VoicePool::new(48000.0);
+        let amp_env = EnvelopeSettings { sustain: 1.0, ..EnvelopeSettings::default() };
+        let settings = VoiceSettings { amp_env, key_to_pan: 1.0, ..VoiceSettings::default() };
+        pool.note_on(84, 84, settings);
+        assert!(pool.any_panned());
+
+        let render = RenderParams {
+            sample_rate: 48000.0,
+            amp: 1.0,
+            pitch_ratio: 1.0,
+            comb_mix: 0.0,
+            comb_feedback: 0.0,
+            pluck_tone: 0.0,
+            noise_color: 0.0,
+            cutoff: 20000.0,
+            resonance: 0.0,
+            vel_to_cutoff: 0.0,
+        };
+        let (mut buffer, mut side) = (vec![0.0; 256], vec![0.0; 256]);
+        pool.render(&mut buffer, &mut side, &render);
+        let pan = Sample::from(key_pan(84, 1.0));
+        for (mix, side) in buffer.iter().zip(&side) {
+            assert!((side - mix * pan).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn unison_spreads_at_the_loudness_of_one_copy() {
+        let render = RenderParams {
+            sample_rate: 48000.0,
+            amp: 1.0,
+            pitch_ratio: 1.0,
+            comb_mix: 0.0,
+            comb_feedback: 0.0,
+            pluck_tone: 0.0,
+            noise_color: 0.0,
+            cutoff: 20000.0,
+            resonance: 0.0,
+            vel_to_cutoff: 0.0,
+        };
+        let amp_env = EnvelopeSettings { sustain: 1.0, ..EnvelopeSettings::default() };
+        let play = |unison| {
+            let mut pool = VoicePool::new(48000.0);
+            let settings =
+                VoiceSettings { amp_env, unison, unison_detune: 0.3, ..VoiceSettings::default() };
+            pool.note_on(60, 60, settings);
+            // A second, for the copies' phases to drift through each other.
+            let (mut buffer, mut side) = (vec![0.0; 48000], vec![0.0; 48000]);
+            pool.render(&mut buffer, &mut side, &render);
+            (pool.any_panned(), buffer, side)
+        };
+        let peak = |buffer: &[Sample]| buffer.iter().fold(0.0, |peak: Sample, s| peak.max(s.abs()));
+        let power = |buffer: &[Sample]| buffer.iter().map(|s| s * s).sum::<Sample>();
+
+        let (panned, single, side) = play(1);
+        assert!(!panned);
+        assert_eq!(peak(&side), 0.0);
+
+        let (panned, unison, side) = play(MAX_UNISON);
+        assert!(panned);
+        let db = 10.0 * (power(&unison) / power(&single)).log10();
+        assert!(db.abs() < 1.0, "{db} dB louder than one copy");
+        assert!(peak(&side) > 0.0);
+        assert_ne!(unison, single);
+    }
+}